@@ -1,3 +1,4 @@
+use crate::models::{ChecksInfo, PullRequestInfo};
 use anyhow::Result;
 use rusqlite::{params, Connection};
 use sha1::{Digest, Sha1};
@@ -5,18 +6,32 @@ use std::path::{Path, PathBuf};
 use std::sync::{Mutex, OnceLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct CachedWorktree {
     pub pr_number: Option<i64>,
     pub pr_state: Option<String>,
     pub pr_base: Option<String>,
     pub pr_url: Option<String>,
+    pub pr_draft: bool,
+    pub pr_review_decision: Option<String>,
+    pub pr_title: Option<String>,
+    pub pr_author: Option<String>,
+    pub pr_created_at: Option<i64>,
+    pub pr_merged_at: Option<i64>,
+    pub pr_unresolved_threads: Option<i64>,
     pub checks_passed: Option<i64>,
     pub checks_total: Option<i64>,
     pub checks_state: Option<String>,
     pub additions: i64,
     pub deletions: i64,
     pub dirty: bool,
+    pub pr_updated_at: Option<i64>,
+    pub changes_updated_at: Option<i64>,
+    pub pull_push_updated_at: Option<i64>,
+    pub pull: Option<i64>,
+    pub push: Option<i64>,
+    /// Free-form memo set with `m` in the TUI.
+    pub note: Option<String>,
 }
 
 fn db_lock() -> &'static Mutex<()> {
@@ -24,26 +39,47 @@ fn db_lock() -> &'static Mutex<()> {
     LOCK.get_or_init(|| Mutex::new(()))
 }
 
-fn now_ts() -> i64 {
+pub fn now_ts() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_secs() as i64)
         .unwrap_or(0)
 }
 
+/// `~/.cache/gw`. Test builds use a directory under the temp dir instead, so
+/// they never touch the user's cache.
 fn get_cache_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("cannot resolve home directory"))?;
-    let dir = home.join(".cache").join("gw");
+    let dir = if cfg!(test) {
+        std::env::temp_dir().join(format!("gw-test-cache-{}", std::process::id()))
+    } else {
+        dirs::home_dir()
+            .ok_or_else(|| anyhow::anyhow!("cannot resolve home directory"))?
+            .join(".cache")
+            .join("gw")
+    };
     std::fs::create_dir_all(&dir)?;
     Ok(dir)
 }
 
-fn get_db_path(repo_root: &Path) -> Result<PathBuf> {
+fn repo_id(repo_root: &Path) -> String {
     let mut hasher = Sha1::new();
     hasher.update(repo_root.to_string_lossy().as_bytes());
     let digest = hasher.finalize();
-    let repo_id = format!("{digest:x}");
-    Ok(get_cache_dir()?.join(format!("{repo_id}.sqlite")))
+    format!("{digest:x}")
+}
+
+fn get_db_path(repo_root: &Path) -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join(format!("{}.sqlite", repo_id(repo_root))))
+}
+
+/// Log written by `gw --log-file`, shared by all repos.
+pub fn get_log_path() -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join("gw.log"))
+}
+
+/// Default socket for `gw serve`, next to the repo's cache database.
+pub fn get_socket_path(repo_root: &Path) -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join(format!("{}.sock", repo_id(repo_root))))
 }
 
 fn ensure_schema(conn: &Connection) -> Result<()> {
@@ -69,8 +105,56 @@ fn ensure_schema(conn: &Connection) -> Result<()> {
           push INTEGER,
           pullpush_validated_at INTEGER
         );
+        CREATE TABLE IF NOT EXISTS branch_paths (
+          branch TEXT PRIMARY KEY,
+          path TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS meta (
+          key TEXT PRIMARY KEY,
+          value TEXT NOT NULL,
+          updated_at INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS hook_runs (
+          path TEXT PRIMARY KEY,
+          ran_at INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS landed_checks (
+          branch TEXT PRIMARY KEY,
+          head TEXT NOT NULL,
+          base_head TEXT NOT NULL,
+          landed INTEGER NOT NULL
+        );
         "#,
     )?;
+    add_missing_columns(
+        conn,
+        "worktree_cache",
+        &[
+            ("pr_draft", "INTEGER"),
+            ("pr_review_decision", "TEXT"),
+            ("pr_title", "TEXT"),
+            ("pr_author", "TEXT"),
+            ("pr_created_at", "INTEGER"),
+            ("pr_unresolved_threads", "INTEGER"),
+            ("pr_merged_at", "INTEGER"),
+            ("note", "TEXT"),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Columns added after a table was first created; caches from older versions
+/// get them on open.
+fn add_missing_columns(conn: &Connection, table: &str, columns: &[(&str, &str)]) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let existing: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (name, kind) in columns {
+        if !existing.iter().any(|column| column == name) {
+            conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {name} {kind}"))?;
+        }
+    }
     Ok(())
 }
 
@@ -97,7 +181,11 @@ impl CacheDB {
             SELECT
               pr_number, pr_state, pr_base, pr_url,
               checks_passed, checks_total, checks_state,
-              additions, deletions, dirty
+              additions, deletions, dirty,
+              pr_draft, pr_review_decision,
+              pr_title, pr_author, pr_created_at, pr_unresolved_threads,
+              pr_updated_at, changes_updated_at, pullpush_validated_at,
+              pull, push, pr_merged_at, note
             FROM worktree_cache
             WHERE branch = ?
             "#,
@@ -115,6 +203,19 @@ impl CacheDB {
                 additions: row.get::<_, Option<i64>>(7)?.unwrap_or(0),
                 deletions: row.get::<_, Option<i64>>(8)?.unwrap_or(0),
                 dirty: row.get::<_, Option<i64>>(9)?.unwrap_or(0) != 0,
+                pr_draft: row.get::<_, Option<i64>>(10)?.unwrap_or(0) != 0,
+                pr_review_decision: row.get(11)?,
+                pr_title: row.get(12)?,
+                pr_author: row.get(13)?,
+                pr_created_at: row.get(14)?,
+                pr_unresolved_threads: row.get(15)?,
+                pr_updated_at: row.get(16)?,
+                changes_updated_at: row.get(17)?,
+                pull_push_updated_at: row.get(18)?,
+                pull: row.get(19)?,
+                push: row.get(20)?,
+                pr_merged_at: row.get(21)?,
+                note: row.get(22)?,
             })
         });
 
@@ -201,54 +302,271 @@ impl CacheDB {
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
     pub fn upsert_pr_and_checks(
         &self,
         cache_key: &str,
         path: &Path,
-        pr_number: Option<i64>,
-        pr_state: Option<&str>,
-        pr_base: Option<&str>,
-        pr_url: Option<&str>,
-        checks_passed: Option<i64>,
-        checks_total: Option<i64>,
-        checks_state: Option<&str>,
+        pr: Option<&PullRequestInfo>,
+        checks: Option<&ChecksInfo>,
     ) -> Result<()> {
         let _guard = db_lock().lock().expect("cache lock poisoned");
         let now = now_ts();
         self.conn.execute(
             r#"
             INSERT INTO worktree_cache (
-              branch, path, pr_number, pr_state, pr_base, pr_url,
-              pr_updated_at, checks_passed, checks_total, checks_state, checks_updated_at
+              branch, path, pr_number, pr_state, pr_base, pr_url, pr_draft, pr_review_decision,
+              pr_title, pr_author, pr_created_at, pr_unresolved_threads,
+              pr_updated_at, checks_passed, checks_total, checks_state, checks_updated_at,
+              pr_merged_at
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             ON CONFLICT(branch) DO UPDATE SET
               path = excluded.path,
               pr_number = excluded.pr_number,
               pr_state = excluded.pr_state,
               pr_base = excluded.pr_base,
               pr_url = excluded.pr_url,
+              pr_draft = excluded.pr_draft,
+              pr_review_decision = excluded.pr_review_decision,
+              pr_title = excluded.pr_title,
+              pr_author = excluded.pr_author,
+              pr_created_at = excluded.pr_created_at,
+              pr_unresolved_threads = excluded.pr_unresolved_threads,
               pr_updated_at = excluded.pr_updated_at,
               checks_passed = excluded.checks_passed,
               checks_total = excluded.checks_total,
               checks_state = excluded.checks_state,
-              checks_updated_at = excluded.checks_updated_at
+              checks_updated_at = excluded.checks_updated_at,
+              pr_merged_at = excluded.pr_merged_at
             "#,
             params![
                 cache_key,
                 path.to_string_lossy().to_string(),
-                pr_number,
-                pr_state,
-                pr_base,
-                pr_url,
+                pr.map(|pr| pr.number),
+                pr.map(|pr| pr.state.as_str()),
+                pr.and_then(|pr| pr.base.as_deref()),
+                pr.and_then(|pr| pr.url.as_deref()),
+                pr.map(|pr| if pr.is_draft { 1 } else { 0 }),
+                pr.and_then(|pr| pr.review_decision.as_deref()),
+                pr.and_then(|pr| pr.title.as_deref()),
+                pr.and_then(|pr| pr.author.as_deref()),
+                pr.and_then(|pr| pr.created_ts),
+                pr.and_then(|pr| pr.unresolved_threads),
                 now,
-                checks_passed,
-                checks_total,
-                checks_state,
+                checks.map(|c| c.passed),
+                checks.map(|c| c.total),
+                checks.and_then(|c| c.state.as_deref()),
                 now,
+                pr.and_then(|pr| pr.merged_ts),
             ],
         )?;
         Ok(())
     }
+
+    /// Updates just the draft flag after the PR was changed from gw.
+    pub fn set_pr_draft(&self, cache_key: &str, draft: bool) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        self.conn.execute(
+            "UPDATE worktree_cache SET pr_draft = ? WHERE branch = ?",
+            params![if draft { 1 } else { 0 }, cache_key],
+        )?;
+        Ok(())
+    }
+
+    /// Sets the memo of the worktree cached under `cache_key`, or removes it
+    /// when `note` is `None`.
+    pub fn set_note(&self, cache_key: &str, note: Option<&str>) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        self.conn.execute(
+            "UPDATE worktree_cache SET note = ? WHERE branch = ?",
+            params![note, cache_key],
+        )?;
+        Ok(())
+    }
+
+    /// Copies the memo cached under `old_key` to `new_key`, for a renamed
+    /// branch; the path is corrected by the next [`Self::upsert_path`].
+    pub fn copy_note(&self, old_key: &str, new_key: &str) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        self.conn.execute(
+            r#"
+            INSERT INTO worktree_cache (branch, path, note)
+            SELECT ?2, path, note FROM worktree_cache
+            WHERE branch = ?1 AND note IS NOT NULL
+            ON CONFLICT(branch) DO UPDATE SET note = excluded.note
+            "#,
+            params![old_key, new_key],
+        )?;
+        Ok(())
+    }
+
+    /// Repo-relative worktree path recorded for a branch whose name could not
+    /// be used verbatim as a directory name.
+    pub fn get_branch_path(&self, branch: &str) -> Result<Option<String>> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        let row = self.conn.query_row(
+            "SELECT path FROM branch_paths WHERE branch = ?",
+            params![branch],
+            |row| row.get(0),
+        );
+        drop(_guard);
+
+        match row {
+            Ok(path) => Ok(Some(path)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn get_path_branch(&self, path: &str) -> Result<Option<String>> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        let row = self.conn.query_row(
+            "SELECT branch FROM branch_paths WHERE path = ?",
+            params![path],
+            |row| row.get(0),
+        );
+        drop(_guard);
+
+        match row {
+            Ok(branch) => Ok(Some(branch)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn set_branch_path(&self, branch: &str, path: &str) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        self.conn.execute(
+            r#"
+            INSERT INTO branch_paths (branch, path)
+            VALUES (?, ?)
+            ON CONFLICT(branch) DO UPDATE SET path = excluded.path
+            "#,
+            params![branch, path],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_branch_path(&self, branch: &str) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        self.conn
+            .execute("DELETE FROM branch_paths WHERE branch = ?", params![branch])?;
+        Ok(())
+    }
+
+    /// Notes that the post-creation hooks just ran in the worktree at `path`.
+    pub fn record_hook_run(&self, path: &Path) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        self.conn.execute(
+            r#"
+            INSERT INTO hook_runs (path, ran_at)
+            VALUES (?, ?)
+            ON CONFLICT(path) DO UPDATE SET ran_at = excluded.ran_at
+            "#,
+            params![path.to_string_lossy().to_string(), now_ts()],
+        )?;
+        Ok(())
+    }
+
+    /// When the post-creation hooks last ran in the worktree at `path`.
+    pub fn get_hook_run(&self, path: &Path) -> Result<Option<i64>> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        let row = self.conn.query_row(
+            "SELECT ran_at FROM hook_runs WHERE path = ?",
+            params![path.to_string_lossy().to_string()],
+            |row| row.get(0),
+        );
+        drop(_guard);
+
+        match row {
+            Ok(ran_at) => Ok(Some(ran_at)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Remembers whether `branch` at `head` had landed on the default branch
+    /// at `base_head`, which can't change until one of them moves.
+    pub fn record_landed_check(
+        &self,
+        branch: &str,
+        head: &str,
+        base_head: &str,
+        landed: bool,
+    ) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        self.conn.execute(
+            r#"
+            INSERT INTO landed_checks (branch, head, base_head, landed)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(branch) DO UPDATE SET
+              head = excluded.head,
+              base_head = excluded.base_head,
+              landed = excluded.landed
+            "#,
+            params![branch, head, base_head, landed],
+        )?;
+        Ok(())
+    }
+
+    /// The result recorded by `record_landed_check` for exactly these commits.
+    pub fn get_landed_check(
+        &self,
+        branch: &str,
+        head: &str,
+        base_head: &str,
+    ) -> Result<Option<bool>> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        let row = self.conn.query_row(
+            "SELECT landed FROM landed_checks WHERE branch = ? AND head = ? AND base_head = ?",
+            params![branch, head, base_head],
+            |row| row.get(0),
+        );
+        drop(_guard);
+
+        match row {
+            Ok(landed) => Ok(Some(landed)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Repo-wide value that is not tied to a branch, such as the last forge
+    /// authentication problem.
+    pub fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        let row = self.conn.query_row(
+            "SELECT value FROM meta WHERE key = ?",
+            params![key],
+            |row| row.get(0),
+        );
+        drop(_guard);
+
+        match row {
+            Ok(value) => Ok(Some(value)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Stores `value` under `key`, or removes the key when `value` is `None`.
+    pub fn set_meta(&self, key: &str, value: Option<&str>) -> Result<()> {
+        let _guard = db_lock().lock().expect("cache lock poisoned");
+        match value {
+            Some(value) => self.conn.execute(
+                r#"
+                INSERT INTO meta (key, value, updated_at)
+                VALUES (?, ?, ?)
+                ON CONFLICT(key) DO UPDATE SET
+                  value = excluded.value,
+                  updated_at = excluded.updated_at
+                "#,
+                params![key, value, now_ts()],
+            )?,
+            None => self
+                .conn
+                .execute("DELETE FROM meta WHERE key = ?", params![key])?,
+        };
+        Ok(())
+    }
 }
@@ -1,25 +1,402 @@
-use crate::models::HealthReport;
-use crate::{git_ops, hooks, services, tui};
+use crate::errors::GwError;
+use crate::hooks::PostCreationHook;
+use crate::models::{
+    HealthReport, PullStrategy, RepairFix, RepairPlan, RepoLayout, TableExport, WorktreeFilter,
+    WorktreeInfo,
+};
+use crate::{
+    cache_db, forge, git_ops, hooks, logging, manifest, policies, repo_lock, review, server,
+    services, settings, tags, trash, tui,
+};
 use anyhow::{anyhow, Context, Result};
-use clap::{Args, Parser, Subcommand};
-use std::collections::{HashMap, HashSet};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::collections::HashSet;
 use std::fs;
 use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 #[derive(Debug, Parser)]
 #[command(name = "gw", version, about = "Interactive git worktree manager")]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+    /// Log every git/gh/glab invocation with its duration and exit status
+    /// (to stderr, or to the log file while the TUI is open)
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+    /// Append the --verbose log to gw.log in gw's cache directory
+    #[arg(long, global = true)]
+    pub log_file: bool,
+    /// Only list worktrees that are dirty, have a PR or are behind the
+    /// default branch (repeat to combine)
+    #[arg(long, value_enum, value_name = "KIND")]
+    pub filter: Vec<FilterKind>,
+    /// Only list worktrees whose branch matches GLOB (`*` matches `/` too)
+    #[arg(long, value_name = "GLOB")]
+    pub branch_glob: Option<String>,
+    /// Print BRANCH's worktree path (for the shell function to `cd` into)
+    /// instead of opening the TUI, e.g. with a branch picked from `gw list --fzf`
+    #[arg(long, value_name = "BRANCH")]
+    pub select: Option<String>,
+    /// End the selected path with a NUL byte instead of a newline
+    #[arg(long)]
+    pub print0: bool,
+    /// Write the selected path to file descriptor N instead of stdout
+    #[arg(long, value_name = "N")]
+    pub out_fd: Option<u32>,
+    /// Run COMMAND in the selected worktree (with `sh -c`) instead of printing
+    /// its path
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["print0", "out_fd"])]
+    pub exec: Option<String>,
+    /// Skip the startup check for worktree/branch inconsistencies
+    #[arg(long)]
+    pub no_doctor: bool,
+    /// Print the git, forge and hook commands that would change something
+    /// (`would run: ...`) instead of running them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// An array of objects (one object for `gw status --checks` and
+    /// `gw doctor`) with a stable schema
+    Json,
+}
+
+/// `gw list`'s formats: the ones of other commands plus the table's columns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    #[default]
+    Text,
+    /// An array of objects with a stable schema
+    Json,
+    /// The TUI's columns as a Markdown table, PR numbers linked
+    Markdown,
+    /// The TUI's columns as CSV with a header row
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FilterKind {
+    Dirty,
+    HasPr,
+    Behind,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    Init,
+    /// Convert the repo to the gw layout: one worktree per local branch
+    Init(InitArgs),
+    /// Print the shell functions that `cd` into the worktree gw prints
     #[command(name = "shell-init")]
     ShellInit,
+    /// Manage the commands run after a worktree is created
     Hooks(HooksArgs),
+    /// Check the gw layout and repair worktree/branch inconsistencies
+    Doctor(DoctorArgs),
+    /// List or restore deleted worktrees (from the trash or `gw doctor` backups)
+    Restore(RestoreArgs),
+    /// Create a branch with its worktree and print the worktree path
+    New(NewArgs),
+    /// List worktrees with the age of their last commit
+    List(ListArgs),
+    /// Delete worktrees whose PR was merged, or apply the cleanupPolicies setting
+    Clean(CleanArgs),
+    /// Check out a pull request in a detached `review/<number>` worktree and
+    /// print its path; lists review worktrees when NUMBER is omitted
+    Review(ReviewArgs),
+    /// Print a manifest of the worktrees (branches, bases, upstreams) for `gw apply`
+    Export(ExportArgs),
+    /// Create the worktrees of a manifest from `gw export` that don't exist yet
+    Apply(ApplyArgs),
+    /// Open a branch's worktree in the configured editor
+    Open(OpenArgs),
+    /// Open a worktree, or all of them as a workspace, in VS Code
+    Code(CodeArgs),
+    /// Pull a worktree's branch from its upstream
+    Pull(PullArgs),
+    /// Print a one-line summary of the current worktree from the cache, for shell prompts
+    Prompt(PromptArgs),
+    /// Print each worktree's PR and checks, or gate on one branch's checks
+    Status(StatusArgs),
+    /// Open pull requests (merge requests on GitLab)
+    Pr(PrArgs),
+    /// Run a preset from the presets setting in a worktree
+    Run(RunArgs),
+    /// Answer JSON-RPC requests on a unix socket, for editor integrations
+    Serve(ServeArgs),
+    /// Write man pages and a markdown CLI reference generated from these
+    /// definitions, for packagers
+    #[command(name = "gen-docs", hide = true)]
+    GenDocs(GenDocsArgs),
+    /// Check out the files of a worktree created with `gw new --fast`; started
+    /// in the background by gw itself
+    #[command(name = "finish-checkout", hide = true)]
+    FinishCheckout(FinishCheckoutArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct FinishCheckoutArgs {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct GenDocsArgs {
+    /// Directory for `man/gw*.1` and `cli.md` (created if missing)
+    pub out_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Socket to listen on (default: <repo id>.sock in gw's cache directory)
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct PromptArgs {
+    /// Print the fields as a JSON object instead
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct StatusArgs {
+    /// Refresh and print the check rollup of BRANCH; exits non-zero unless all checks passed
+    #[arg(long, value_name = "BRANCH")]
+    pub checks: Option<String>,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct NewArgs {
+    /// Branch to create (several with --batch); named after the issue when
+    /// omitted with --issue
+    #[arg(
+        value_name = "BRANCH",
+        required_unless_present_any = ["issue", "batch", "tag"]
+    )]
+    pub branches: Vec<String>,
+    /// Create a worktree for each BRANCH in turn, or for each line of stdin
+    /// when none are given
+    #[arg(long, conflicts_with = "issue")]
+    pub batch: bool,
+    /// Forge issue to create the branch for; its title fills the issueBranchTemplate setting
+    #[arg(long, value_name = "NUMBER")]
+    pub issue: Option<u64>,
+    /// Branch to start from instead of the (freshly pulled) default branch
+    #[arg(long, value_name = "BRANCH")]
+    pub from: Option<String>,
+    /// Move the current worktree's uncommitted changes, untracked files
+    /// included, into the new worktree
+    #[arg(long, conflicts_with = "batch")]
+    pub carry: bool,
+    /// Check out release TAG on a detached HEAD in `tags/<TAG>` instead, to
+    /// compare against a shipped version
+    #[arg(
+        long,
+        value_name = "TAG",
+        conflicts_with_all = ["branches", "batch", "issue", "from", "carry"]
+    )]
+    pub tag: Option<String>,
+    /// Print the path as soon as the worktree is registered and check out its
+    /// files (then run the hooks) in the background; the default with the
+    /// fastCreate setting
+    #[arg(long, conflicts_with_all = ["carry", "tag"])]
+    pub fast: bool,
+    /// Ask origin whether it has the branch instead of going by the
+    /// remote-tracking branches of the last fetch
+    #[arg(long, conflicts_with = "tag")]
+    pub check_remote: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    /// Only list branches without a commit for longer than AGE (e.g. `30d`,
+    /// `6w`; default: the staleAfterDays setting)
+    #[arg(long, value_name = "AGE", num_args = 0..=1, value_parser = parse_age_days)]
+    pub stale: Option<Option<u64>>,
+    /// Print tab-separated, colored lines for `fzf --ansi`: branch, path, last
+    /// commit, pull/push, PR and changes
+    #[arg(long, conflicts_with = "format")]
+    pub fzf: bool,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+    /// Copy the output to the clipboard instead of printing it
+    #[arg(long, conflicts_with = "fzf")]
+    pub clipboard: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CleanArgs {
+    /// Apply the cleanupPolicies from .gw/settings.json instead
+    #[arg(long)]
+    pub policies: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Write the manifest to PATH instead of stdout
+    #[arg(long, short, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ApplyArgs {
+    /// Manifest written by `gw export`
+    pub manifest: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ReviewArgs {
+    /// Pull request (merge request on GitLab) to review
+    pub number: Option<u64>,
+    /// Delete the review worktree of NUMBER instead, changes included
+    #[arg(long, requires = "number")]
+    pub remove: bool,
+    /// Delete the review worktrees of closed and merged pull requests
+    #[arg(long, conflicts_with = "number")]
+    pub prune: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct OpenArgs {
+    /// Branch whose worktree to open
+    pub branch: String,
+    /// Editor command to use instead of the `editor` setting, $VISUAL or $EDITOR
+    #[arg(long, value_name = "COMMAND")]
+    pub editor: Option<String>,
+    /// Wait for the editor to exit (needed for terminal editors like nvim)
+    #[arg(long)]
+    pub wait: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CodeArgs {
+    /// Branch whose worktree to open (default: the current directory)
+    #[arg(conflicts_with = "workspace")]
+    pub branch: Option<String>,
+    /// Write a <repo>.code-workspace with every worktree as a folder and open it
+    #[arg(long)]
+    pub workspace: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PullArgs {
+    /// Branch whose worktree to pull (default: the current worktree)
+    pub branch: Option<String>,
+    /// Rebase local commits onto the upstream
+    #[arg(long, conflicts_with_all = ["ff_only", "merge"])]
+    pub rebase: bool,
+    /// Only fast-forward; fail if the branches have diverged
+    #[arg(long, conflicts_with = "merge")]
+    pub ff_only: bool,
+    /// Merge the upstream into the branch
+    #[arg(long)]
+    pub merge: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Name of the preset
+    pub preset: String,
+    /// Branch whose worktree to run it in (default: the current worktree)
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    /// Trashed branch (or trash entry id), backup id or deleted branch to
+    /// restore; lists what can be restored when omitted
+    pub id: Option<String>,
+    /// Branch to restore the worktree on, instead of the one recorded in the backup
+    #[arg(long, requires = "id")]
+    pub branch: Option<String>,
+    /// Empty the trash instead of restoring
+    #[arg(long, conflicts_with = "id")]
+    pub purge: bool,
+    /// List recently deleted branches with their tips, from gw's deletion log
+    /// and the worktrees' HEAD reflogs
+    #[arg(long, conflicts_with_all = ["id", "purge"])]
+    pub list: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// Keep the current checkout at the repo root and put the other worktrees in a separate directory
+    #[arg(long)]
+    pub hub: bool,
+    /// Directory for hub worktrees, relative to the repo root (default: ../<repo>.worktrees)
+    #[arg(long, value_name = "DIR", requires = "hub")]
+    pub worktrees_dir: Option<String>,
+    /// Keep the bare repo in DIR (e.g. ~/repos/<repo>.git) instead of .git at
+    /// the repo root, which keeps the worktrees and a .git file pointing at it
+    #[arg(long, value_name = "DIR", conflicts_with = "hub")]
+    pub git_dir: Option<PathBuf>,
+    /// Keep untracked and ignored paths matching GLOB (e.g. `.env*`) at the
+    /// repo root instead of deleting them; repeatable
+    #[arg(long, value_name = "GLOB", conflicts_with = "hub")]
+    pub preserve: Vec<String>,
+    /// Move untracked and ignored files at the repo root into the default
+    /// branch's worktree instead of deleting them
+    #[arg(long, conflicts_with = "hub")]
+    pub move_untracked: bool,
+    /// Set up the layout in the new directory DIR from a copy of the repo,
+    /// leaving the current clone untouched
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["hub", "git_dir", "preserve", "move_untracked"]
+    )]
+    pub into: Option<PathBuf>,
+    /// Only create worktrees for branches matching GLOB (repeatable); the
+    /// others stay plain branches, recorded in noWorktreeBranches
+    #[arg(long = "branches", value_name = "GLOB")]
+    pub branch_globs: Vec<String>,
+    /// Only create worktrees for the N branches with the most recent commits
+    #[arg(long, value_name = "N")]
+    pub recent: Option<usize>,
+    /// Pick the branches that get worktrees from a list
+    #[arg(long)]
+    pub pick: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    /// Recreate the branch of each orphaned worktree at its HEAD instead of deleting it
+    #[arg(long)]
+    pub adopt: bool,
+    /// Apply fixes without asking for confirmation
+    #[arg(long, short)]
+    pub yes: bool,
+    /// With `json`, print the health report instead of repairing anything
+    #[arg(long, value_enum, default_value_t, conflicts_with_all = ["adopt", "yes"])]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct PrArgs {
+    #[command(subcommand)]
+    pub command: PrSubcommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PrSubcommands {
+    /// Push a branch and open a draft PR for it, titled from the
+    /// prTitleTemplate setting
+    Create {
+        /// Branch to open the PR for (default: the current worktree's)
+        #[arg(conflicts_with = "missing")]
+        branch: Option<String>,
+        /// Do it for every branch ahead of the default branch without a PR
+        #[arg(long)]
+        missing: bool,
+    },
 }
 
 #[derive(Debug, Args)]
@@ -30,283 +407,2285 @@ pub struct HooksArgs {
 
 #[derive(Debug, Subcommand)]
 pub enum HooksSubcommands {
-    Add { command: String },
+    /// Add a post-creation hook to .gw/settings.json
+    Add {
+        /// Shell command to run in each new worktree
+        #[arg(required_unless_present_any = ["direnv", "compose"])]
+        command: Option<String>,
+        /// Add the built-in hook that runs `direnv allow` in new worktrees with an .envrc
+        #[arg(long, conflicts_with_all = ["command", "compose"])]
+        direnv: bool,
+        /// Add the built-in hook that sets COMPOSE_PROJECT_NAME={repo}-{branch} in new worktrees' .env
+        #[arg(long, conflicts_with = "command")]
+        compose: bool,
+        /// Run the command at the repo root after `gw init` instead of in each new worktree
+        #[arg(long, requires = "command")]
+        post_init: bool,
+    },
+    /// Run the post-creation hooks again in the current worktree
     Rerun,
 }
 
 pub fn run() -> Result<()> {
     let cli = Cli::parse();
+    init_logging(&cli)?;
+    git_ops::set_dry_run(cli.dry_run);
+    // A broken settings file must not stop every subcommand (`shell-init`,
+    // `doctor`, ...); the ones that need other settings still report it.
+    if let Ok(repo_root) = git_ops::get_repo_root() {
+        match settings::load_git_timeouts(&repo_root) {
+            Ok((remote_secs, local_secs)) => git_ops::set_timeouts(remote_secs, local_secs),
+            Err(err) => eprintln!("gw: warning: using the default git timeouts: {err}"),
+        }
+        match settings::load_time_format(&repo_root) {
+            Ok(format) => tui::set_time_format(format),
+            Err(err) => eprintln!("gw: warning: using the default time format: {err}"),
+        }
+    }
+
+    match cli.command {
+        Some(Commands::Init(args)) => init_repo(&args),
+        Some(Commands::ShellInit) => shell_init(),
+        Some(Commands::Hooks(hooks_args)) => match hooks_args.command {
+            HooksSubcommands::Add {
+                command,
+                direnv,
+                compose,
+                post_init,
+            } => match (command, direnv, compose) {
+                (Some(command), _, _) => add_hook(&command, post_init),
+                (None, true, _) => add_builtin_hook(PostCreationHook::Direnv, "direnv"),
+                (None, _, true) => {
+                    add_builtin_hook(PostCreationHook::ComposeProject, "compose-project")
+                }
+                (None, false, false) => {
+                    unreachable!("clap requires a command, --direnv or --compose")
+                }
+            },
+            HooksSubcommands::Rerun => rerun_hooks(),
+        },
+        Some(Commands::Doctor(args)) => doctor(&args),
+        Some(Commands::Restore(args)) => restore(&args),
+        Some(Commands::New(args)) => new_worktree(&args),
+        Some(Commands::List(args)) => list(&args),
+        Some(Commands::Clean(args)) => clean(&args),
+        Some(Commands::Review(args)) => review(&args),
+        Some(Commands::Export(args)) => export(&args),
+        Some(Commands::Apply(args)) => apply(&args),
+        Some(Commands::Open(args)) => open(&args),
+        Some(Commands::Code(args)) => code(&args),
+        Some(Commands::Pull(args)) => pull(&args),
+        Some(Commands::Prompt(args)) => prompt(&args),
+        Some(Commands::Status(args)) => status(&args),
+        Some(Commands::Pr(pr_args)) => match pr_args.command {
+            PrSubcommands::Create { branch, missing } => create_prs(branch.as_deref(), missing),
+        },
+        Some(Commands::Run(args)) => run_preset(&args),
+        Some(Commands::Serve(args)) => serve(&args),
+        Some(Commands::GenDocs(args)) => gen_docs(&args),
+        Some(Commands::FinishCheckout(args)) => finish_checkout(&args),
+        None => {
+            let output = selection_output(&cli)?;
+            match &cli.select {
+                Some(branch) => select(branch, output),
+                None => run_default(startup_filter(&cli), output, !cli.no_doctor),
+            }
+        }
+    }
+}
+
+fn init_logging(cli: &Cli) -> Result<()> {
+    if !cli.verbose && !cli.log_file {
+        return Ok(());
+    }
+    // The TUI draws on stderr, so its log can only go to the file.
+    let tui = cli.command.is_none() && io::stderr().is_terminal();
+    let target = if cli.log_file || tui {
+        logging::LogTarget::File(cache_db::get_log_path()?)
+    } else {
+        logging::LogTarget::Stderr
+    };
+    logging::init(target)
+}
+
+fn startup_filter(cli: &Cli) -> WorktreeFilter {
+    let mut filter = WorktreeFilter {
+        branch_glob: cli.branch_glob.clone(),
+        ..WorktreeFilter::default()
+    };
+    for kind in &cli.filter {
+        match kind {
+            FilterKind::Dirty => filter.dirty = true,
+            FilterKind::HasPr => filter.has_pr = true,
+            FilterKind::Behind => filter.behind = true,
+        }
+    }
+    filter
+}
+
+/// Where the worktree picked in the TUI or with `--select` goes.
+enum SelectionOutput {
+    Write { out: Box<dyn Write>, print0: bool },
+    Exec(String),
+}
+
+/// Opens `--out-fd` up front so a bad descriptor fails before the TUI starts.
+fn selection_output(cli: &Cli) -> Result<SelectionOutput> {
+    if let Some(command) = &cli.exec {
+        return Ok(SelectionOutput::Exec(command.clone()));
+    }
+    let out: Box<dyn Write> = match cli.out_fd {
+        Some(fd) => Box::new(open_fd(fd)?),
+        None => Box::new(io::stdout()),
+    };
+    Ok(SelectionOutput::Write {
+        out,
+        print0: cli.print0,
+    })
+}
+
+#[cfg(unix)]
+fn open_fd(fd: u32) -> Result<fs::File> {
+    fs::OpenOptions::new()
+        .append(true)
+        .open(format!("/dev/fd/{fd}"))
+        .map_err(|err| anyhow!("gw: cannot write to file descriptor {fd}: {err}"))
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: u32) -> Result<fs::File> {
+    Err(anyhow!("gw: --out-fd is not supported on this platform"))
+}
+
+fn write_selection(output: SelectionOutput, path: &Path) -> Result<()> {
+    match output {
+        SelectionOutput::Write { mut out, print0 } => {
+            let terminator = if print0 { '\0' } else { '\n' };
+            write!(out, "{}{terminator}", path.display())?;
+            out.flush()?;
+            Ok(())
+        }
+        SelectionOutput::Exec(command) => services::exec_in(&command, path),
+    }
+}
+
+fn run_default(filter: WorktreeFilter, output: SelectionOutput, doctor: bool) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw" })?;
+    let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+
+    // A dry run only warns, since repairs are not previewed.
+    if doctor && interactive && !git_ops::dry_run() {
+        let health = check_health(&repo_root)?;
+        if health.has_issues()
+            && !handle_health_issues(&repo_root, &health, RepairPlan::default(), true, true)?
+        {
+            return Ok(());
+        }
+    } else if doctor {
+        // Listing works on an inconsistent setup too, so scripts only get a
+        // warning rather than nothing.
+        match check_health(&repo_root) {
+            Ok(health) if health.has_issues() => eprintln!(
+                "gw: warning: detected worktree/branch inconsistencies; run `gw doctor` to repair them"
+            ),
+            Ok(_) => {}
+            Err(err) => eprintln!("gw: warning: cannot check the worktree setup: {err}"),
+        }
+    }
+
+    let default_branch = git_ops::get_default_branch(&repo_root);
+    let items = services::load_worktrees(&repo_root)?;
+
+    if !interactive {
+        for item in items
+            .iter()
+            .filter(|item| services::matches_filter(item, &filter))
+        {
+            println!("{}", item.path.display());
+        }
+        return Ok(());
+    }
+
+    let forge = forge::detect_forge(&repo_root);
+    let availability = forge.availability();
+    let forge_available = availability.is_ok();
+    let warning = availability
+        .err()
+        .or_else(|| services::cached_forge_warning(&repo_root));
+
+    let selected = tui::run_tui(
+        repo_root.clone(),
+        items,
+        default_branch,
+        warning,
+        forge_available,
+        filter,
+    )?;
+    if let Some(path) = selected {
+        write_selection(output, &path)?;
+    }
+
+    Ok(())
+}
+
+fn check_health(repo_root: &Path) -> Result<HealthReport> {
+    // Pruning would drop the admin entries of moved worktrees before they can be
+    // repaired, so leave them to the doctor in that case.
+    let worktrees_dir = services::worktrees_dir(repo_root);
+    if git_ops::find_broken_worktree_links(repo_root, &worktrees_dir)?.is_empty() {
+        git_ops::prune_worktrees(repo_root);
+    }
+    services::health_check(repo_root)
+}
+
+fn doctor(args: &DoctorArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw doctor",
+    })?;
+    let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+
+    let health = check_health(&repo_root)?;
+    let speedups = services::missing_status_speedups(&repo_root);
+    if args.format == OutputFormat::Json {
+        return print_health_json(&repo_root, &health, &speedups);
+    }
+    if !health.has_issues() {
+        println!("gw doctor: no issues found");
+        return offer_status_speedups(&repo_root, &speedups, args.yes, interactive);
+    }
+
+    if !args.yes && !interactive {
+        return Err(anyhow!(
+            "gw doctor: rerun in an interactive terminal or pass --yes to apply fixes"
+        ));
+    }
+
+    let plan = if args.adopt {
+        RepairPlan::adopt_all(&health)
+    } else {
+        RepairPlan::default()
+    };
+    if handle_health_issues(
+        &repo_root,
+        &health,
+        plan,
+        !args.adopt && !args.yes,
+        !args.yes,
+    )? {
+        offer_status_speedups(&repo_root, &speedups, args.yes, interactive)?;
+    }
+    Ok(())
+}
+
+/// Suggests the git settings that speed up `git status` in every worktree,
+/// which is most of a refresh's time in big repos.
+fn offer_status_speedups(
+    repo_root: &Path,
+    speedups: &[(&'static str, &'static str)],
+    yes: bool,
+    interactive: bool,
+) -> Result<()> {
+    if speedups.is_empty() {
+        return Ok(());
+    }
+    let settings = speedups
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    eprintln!();
+    eprintln!("- git status could be faster with: {settings}");
+    if !yes {
+        if !interactive {
+            eprintln!("  run `gw doctor --yes` to enable them");
+            return Ok(());
+        }
+        if !confirm("Enable them for every worktree?")? {
+            return Ok(());
+        }
+    }
+    services::enable_status_speedups(repo_root, speedups)?;
+    eprintln!("gw: enabled {settings}");
+    Ok(())
+}
+
+/// `gw doctor --format json`: the report as one object, for scripts that
+/// collect the health of many repos.
+fn print_health_json(
+    repo_root: &Path,
+    health: &HealthReport,
+    speedups: &[(&'static str, &'static str)],
+) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct HealthJson<'a> {
+        repo_root: &'a Path,
+        healthy: bool,
+        recoverable: bool,
+        #[serde(flatten)]
+        report: &'a HealthReport,
+        missing_status_speedups: Vec<&'static str>,
+    }
+    let json = HealthJson {
+        repo_root,
+        healthy: !health.has_issues(),
+        recoverable: health.is_recoverable(),
+        report: health,
+        missing_status_speedups: speedups.iter().map(|(key, _)| *key).collect(),
+    };
+    println!("{}", serde_json::to_string(&json)?);
+    Ok(())
+}
+
+fn handle_health_issues(
+    repo_root: &Path,
+    health: &HealthReport,
+    mut plan: RepairPlan,
+    ask_selection: bool,
+    ask_confirm: bool,
+) -> Result<bool> {
+    eprintln!(
+        "Detected issue with gw setup in {}",
+        services::tilde_path(repo_root)
+    );
+    eprintln!();
+
+    if !health.orphaned_worktrees.is_empty() {
+        eprintln!(
+            "- worktrees without branches: {}",
+            health.orphaned_worktrees.len()
+        );
+        for orphan in &health.orphaned_worktrees {
+            match orphan.adopt_branch.as_deref() {
+                Some(branch) => eprintln!(
+                    "  - {} (can be adopted as branch {branch})",
+                    services::display_path(repo_root, &orphan.path)
+                ),
+                None => eprintln!("  - {}", services::display_path(repo_root, &orphan.path)),
+            }
+        }
+    }
+
+    if !health.broken_worktrees.is_empty() {
+        eprintln!(
+            "- worktrees with broken git links to repair: {}",
+            health.broken_worktrees.len()
+        );
+        for link in &health.broken_worktrees {
+            eprintln!("  - {}", services::display_path(repo_root, &link.path));
+        }
+    }
+
+    if !health.path_issues.is_empty() {
+        eprintln!(
+            "- worktrees with misplaced paths: {}",
+            health.path_issues.len()
+        );
+        for issue in &health.path_issues {
+            eprintln!(
+                "  - {} ({})",
+                services::display_path(repo_root, &issue.path),
+                issue.detail
+            );
+        }
+    }
+
+    if !health.missing_worktrees.is_empty() {
+        eprintln!(
+            "- branches without worktrees to create: {}",
+            health.missing_worktrees.len()
+        );
+        for branch in &health.missing_worktrees {
+            eprintln!(
+                "  - {branch} -> {}",
+                services::display_path(
+                    repo_root,
+                    &services::worktree_path_for_branch(repo_root, branch)
+                )
+            );
+        }
+    }
+
+    if !health.unrecoverable_reasons.is_empty() {
+        eprintln!("- unrecoverable issues:");
+        for reason in &health.unrecoverable_reasons {
+            eprintln!("  - {reason}");
+        }
+        return Err(anyhow!(
+            "gw: setup is not recoverable automatically; run `gw init` first"
+        ));
+    }
+
+    if ask_selection {
+        if !select_repair_fixes(repo_root, health, &mut plan)? {
+            eprintln!("gw: cancelled");
+            return Ok(false);
+        }
+    } else {
+        eprintln!();
+        print_repair_checklist(repo_root, health, &plan);
+    }
+
+    if git_ops::dry_run() {
+        eprintln!();
+        eprintln!("gw: dry run; nothing repaired");
+        return Ok(false);
+    }
+
+    if ask_confirm {
+        eprintln!();
+        if !confirm("Apply these fixes now?")? {
+            eprintln!("gw: cancelled");
+            return Ok(false);
+        }
+    }
+
+    let _lock = repo_lock::acquire(repo_root)?;
+    let backups = services::doctor_repo(repo_root, health, &plan)?;
+    eprintln!("gw: setup repaired");
+    for backup in &backups {
+        eprintln!(
+            "gw: backed up {} before deleting it; run `gw restore {}` to recover it",
+            services::display_path(repo_root, &backup.path),
+            backup.id
+        );
+    }
+    Ok(true)
+}
+
+fn status(args: &StatusArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw status",
+    })?;
+
+    let Some(branch) = args.checks.as_deref() else {
+        let items = services::load_worktrees(&repo_root)?;
+        if args.format == OutputFormat::Json {
+            print!("{}", format_json(&items)?);
+            return Ok(());
+        }
+        for item in items {
+            println!("{}", format_status_line(&item));
+        }
+        return Ok(());
+    };
+
+    let (item, stale_reason) = services::refresh_worktree_forge(&repo_root, branch)
+        .map_err(|err| anyhow!("gw status: {err}"))?;
+    if let Some(reason) = stale_reason {
+        eprintln!("gw status: {reason}; showing cached checks");
+    }
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string(&services::summarize(&item))?);
+    } else {
+        println!("{}", format_status_line(&item));
+    }
+
+    match item.checks_state.as_deref() {
+        Some("fail") => Err(anyhow!("gw status: checks failed for {branch}")),
+        Some("pend") => Err(anyhow!("gw status: checks still running for {branch}")),
+        _ => Ok(()),
+    }
+}
+
+/// `branch  #12 OPEN  checks ok 3/3`, with `-` for a missing PR or checks.
+fn format_status_line(item: &WorktreeInfo) -> String {
+    let pr = match (item.pr_number, item.pr_state.as_deref()) {
+        (Some(number), Some(state)) if item.pr_draft && state == "OPEN" => {
+            format!("#{number} DRAFT")
+        }
+        (Some(number), Some(state)) => format!("#{number} {state}"),
+        _ => "-".to_string(),
+    };
+    let checks = match (
+        item.checks_state.as_deref(),
+        item.checks_passed,
+        item.checks_total,
+    ) {
+        (Some(state), Some(passed), Some(total)) => format!("checks {state} {passed}/{total}"),
+        _ => "checks -".to_string(),
+    };
+    format!("{}  {pr}  {checks}", item.branch)
+}
+
+/// Reads only `.git` files and the cache, so it is cheap enough to run on
+/// every prompt. Prints nothing outside a worktree.
+fn prompt(args: &PromptArgs) -> Result<()> {
+    let Some(location) = git_ops::locate_worktree(&std::env::current_dir()?) else {
+        return Ok(());
+    };
+    let cache_key = services::make_cache_key(
+        location.branch.as_deref().unwrap_or_default(),
+        location.head.as_deref().unwrap_or_default(),
+    );
+    let cached = cache_db::CacheDB::open(&location.repo_root)?
+        .get_cached_worktree(&cache_key)?
+        .unwrap_or_default();
+    let branch = match (&location.branch, &location.head) {
+        (Some(branch), _) => branch.clone(),
+        (None, Some(head)) => head.chars().take(7).collect(),
+        (None, None) => String::new(),
+    };
+
+    if args.json {
+        let summary = serde_json::json!({
+            "branch": branch,
+            "detached": location.branch.is_none(),
+            "dirty": cached.dirty,
+            "pull": cached.pull,
+            "push": cached.push,
+            "prNumber": cached.pr_number,
+            "prState": cached.pr_state,
+            "prDraft": cached.pr_draft,
+            "checksState": cached.checks_state,
+            "checksPassed": cached.checks_passed,
+            "checksTotal": cached.checks_total,
+        });
+        println!("{summary}");
+    } else {
+        println!("{}", format_prompt(&branch, &cached));
+    }
+    Ok(())
+}
+
+/// `feat* ↓1 ↑2 #12 ✓3/3`: `*` when dirty, counts only when non-zero, the
+/// PR with its state unless open, and checks as ✓ (passed), ✗ (failed) or
+/// … (pending).
+fn format_prompt(branch: &str, cached: &cache_db::CachedWorktree) -> String {
+    let mut line = branch.to_string();
+    if cached.dirty {
+        line.push('*');
+    }
+    if let Some(pull) = cached.pull.filter(|count| *count > 0) {
+        line.push_str(&format!(" ↓{pull}"));
+    }
+    if let Some(push) = cached.push.filter(|count| *count > 0) {
+        line.push_str(&format!(" ↑{push}"));
+    }
+    if let Some(number) = cached.pr_number {
+        line.push_str(&format!(" #{number}"));
+        match cached.pr_state.as_deref() {
+            Some("MERGED") => line.push_str(" merged"),
+            Some("CLOSED") => line.push_str(" closed"),
+            _ if cached.pr_draft => line.push_str(" draft"),
+            _ => {}
+        }
+    }
+    if let (Some(state), Some(passed), Some(total)) = (
+        cached.checks_state.as_deref(),
+        cached.checks_passed,
+        cached.checks_total,
+    ) {
+        let symbol = match state {
+            "fail" => '✗',
+            "pend" => '…',
+            _ => '✓',
+        };
+        line.push_str(&format!(" {symbol}{passed}/{total}"));
+    }
+    line
+}
+
+fn serve(args: &ServeArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw serve",
+    })?;
+    let socket = match &args.socket {
+        Some(socket) => socket.clone(),
+        None => cache_db::get_socket_path(&repo_root)?,
+    };
+    server::serve(&repo_root, &socket)
+}
+
+fn restore(args: &RestoreArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw restore",
+    })?;
+
+    if args.purge {
+        let _lock = repo_lock::acquire(&repo_root)?;
+        let purged = trash::purge_all(&repo_root)?;
+        println!("gw restore: purged {purged} trashed worktree(s)");
+        return Ok(());
+    }
+    if args.list {
+        let deleted = trash::list_deleted_branches(&repo_root)?;
+        if deleted.is_empty() {
+            println!("gw restore: no recently deleted branches");
+        }
+        for branch in &deleted {
+            let trashed = if branch.trash_id.is_some() {
+                "  (in the trash)"
+            } else {
+                ""
+            };
+            println!(
+                "{}  {}  deleted {}{trashed}",
+                branch.branch,
+                &branch.head[..branch.head.len().min(7)],
+                tui::format_time(branch.deleted_ts)
+            );
+        }
+        return Ok(());
+    }
+
+    let Some(id) = args.id.as_deref() else {
+        let trashed = trash::list_trash(&repo_root)?;
+        let backups = services::list_backups(&repo_root)?;
+        if trashed.is_empty() && backups.is_empty() {
+            println!("gw restore: nothing to restore");
+            return Ok(());
+        }
+        for entry in &trashed {
+            println!(
+                "{}  deleted {}  {}  (trash {})",
+                entry.branch,
+                tui::format_time(entry.deleted_ts),
+                entry.path.display(),
+                entry.id
+            );
+        }
+        for backup in &backups {
+            let branch = backup.branch.as_deref().unwrap_or("-");
+            println!(
+                "{}  {}  {}  (branch {branch})",
+                backup.id,
+                backup.created,
+                backup.path.display()
+            );
+        }
+        return Ok(());
+    };
+
+    let _lock = repo_lock::acquire(&repo_root)?;
+    let has_backup = services::list_backups(&repo_root)?
+        .iter()
+        .any(|backup| backup.id == id || backup.branch.as_deref() == Some(id));
+    let (branch, path) = match trash::find_trashed(&repo_root, id)? {
+        Some(entry) => trash::restore_trashed(&repo_root, &entry, args.branch.as_deref())?,
+        None if has_backup => services::restore_backup(&repo_root, id, args.branch.as_deref())?,
+        None => {
+            let deleted = trash::find_deleted_branch(&repo_root, id)?.ok_or_else(|| {
+                anyhow!("gw restore: no trashed worktree, backup or deleted branch named {id}")
+            })?;
+            trash::restore_deleted_branch(&repo_root, &deleted, args.branch.as_deref())?
+        }
+    };
+    println!("gw restore: restored branch {branch} at {}", path.display());
+    Ok(())
+}
+
+fn new_worktree(args: &NewArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw new" })?;
+    if let Some(tag) = &args.tag {
+        let _lock = repo_lock::acquire(&repo_root)?;
+        let path = tags::open_tag(&repo_root, tag.trim())
+            .map_err(|err| anyhow!("gw new: cannot check out tag {tag}: {err}"))?;
+        println!("{}", path.display());
+        return Ok(());
+    }
+    if args.batch {
+        return new_worktrees(&repo_root, args);
+    }
+    if args.branches.len() > 1 {
+        return Err(anyhow!("gw new: pass --batch to create several worktrees"));
+    }
+
+    let branch = match (args.branches.first(), args.issue) {
+        (Some(branch), _) => branch.trim().to_string(),
+        (None, Some(number)) => {
+            let forge = forge::detect_forge(&repo_root);
+            let title = forge
+                .issue_title(&repo_root, number)
+                .map_err(|err| match err {
+                    forge::ForgeError::Auth(message) => GwError::GhUnavailable {
+                        detail: format!("gw new: {message}"),
+                    }
+                    .into(),
+                    forge::ForgeError::Failed => {
+                        anyhow!("gw new: could not fetch issue #{number}")
+                    }
+                })?;
+            let template = settings::load_issue_branch_template(&repo_root)?;
+            services::issue_branch_name(&template, number, &title)
+        }
+        (None, None) => unreachable!("clap requires a branch or --issue"),
+    };
+    let path = services::new_worktree_target(&repo_root, &branch)
+        .with_context(|| format!("gw new: cannot create {branch}"))?;
+
+    // Like `n` in the TUI: start from the pulled default branch unless told
+    // otherwise.
+    let (base, pull_before) = match &args.from {
+        Some(base) => (base.clone(), None),
+        None => services::default_base(&repo_root)?,
+    };
+
+    let source = if args.carry {
+        let cwd = std::env::current_dir()?;
+        Some(PathBuf::from(
+            git_ops::run(&["rev-parse", "--show-toplevel"], Some(&cwd))
+                .context("gw new: --carry needs to run inside a worktree")?,
+        ))
+    } else {
+        None
+    };
+
+    // The carried changes need the files in place.
+    let fast = source.is_none() && (args.fast || settings::load_fast_create(&repo_root)?);
+
+    let _lock = repo_lock::acquire(&repo_root)?;
+    eprintln!("gw new: creating {branch} from {base}");
+    let reminder = match &source {
+        Some(source) => services::create_worktree_with_changes(
+            &repo_root,
+            source,
+            &path,
+            &branch,
+            &base,
+            pull_before.as_deref(),
+            args.check_remote,
+        )?,
+        None if fast => {
+            services::create_worktree_fast(
+                &repo_root,
+                &path,
+                &branch,
+                &base,
+                pull_before.as_deref(),
+                args.check_remote,
+            )?;
+            eprintln!(
+                "gw new: checking out the files in the background; the TUI shows the progress"
+            );
+            None
+        }
+        None => services::create_worktree(
+            &repo_root,
+            &path,
+            &branch,
+            &base,
+            pull_before.as_deref(),
+            args.check_remote,
+        )?,
+    };
+    if let Some(reminder) = reminder {
+        eprintln!("gw new: note: {reminder}");
+    }
+    if let Some(number) = args.issue {
+        git_ops::set_branch_issue(&repo_root, &branch, number)?;
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// `gw new --batch`: the branches from the command line, or one per line of
+/// stdin (blank lines and `#` comments skipped), created in order.
+fn new_worktrees(repo_root: &Path, args: &NewArgs) -> Result<()> {
+    let branches: Vec<String> = if args.branches.is_empty() {
+        io::stdin()
+            .lines()
+            .collect::<io::Result<Vec<_>>>()?
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    } else {
+        args.branches
+            .iter()
+            .map(|branch| branch.trim().to_string())
+            .collect()
+    };
+    if branches.is_empty() {
+        return Err(anyhow!("gw new: no branches given"));
+    }
+    let targets = services::new_worktree_targets(repo_root, &branches)?;
+    let (base, pull_before) = match &args.from {
+        Some(base) => (base.clone(), None),
+        None => services::default_base(repo_root)?,
+    };
+
+    let fast = args.fast || settings::load_fast_create(repo_root)?;
+
+    let _lock = repo_lock::acquire(repo_root)?;
+    let total = targets.len();
+    let reminders = services::create_worktrees(
+        repo_root,
+        &targets,
+        &base,
+        pull_before.as_deref(),
+        fast,
+        args.check_remote,
+        &mut |index, branch| {
+            eprintln!(
+                "gw new: [{}/{total}] creating {branch} from {base}",
+                index + 1
+            );
+        },
+    )?;
+    for (_, path) in &targets {
+        println!("{}", path.display());
+    }
+    for reminder in reminders {
+        eprintln!("gw new: note: {reminder}");
+    }
+    Ok(())
+}
+
+/// `30d`, `6w` or a plain number of days.
+fn parse_age_days(value: &str) -> Result<u64, String> {
+    let (number, multiplier) = match value.strip_suffix('d') {
+        Some(number) => (number, 1),
+        None => match value.strip_suffix('w') {
+            Some(number) => (number, 7),
+            None => (value, 1),
+        },
+    };
+    number
+        .parse::<u64>()
+        .map(|number| number * multiplier)
+        .map_err(|_| format!("expected an age like `30d` or `6w`, got `{value}`"))
+}
+
+fn list(args: &ListArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw list" })?;
+    let stale_after_days = match args.stale {
+        None => None,
+        Some(Some(days)) => Some(days),
+        Some(None) => Some(settings::load_stale_after_days(&repo_root)?),
+    };
+
+    let default_branch = git_ops::get_default_branch(&repo_root);
+    let highlight_after_days = match stale_after_days {
+        Some(days) => days,
+        None => settings::load_stale_after_days(&repo_root)?,
+    };
+
+    let mut items = services::load_worktrees(&repo_root)?;
+    items.retain(|item| stale_after_days.is_none_or(|days| services::is_stale(item, days)));
+    if args.fzf {
+        for item in items {
+            println!(
+                "{}",
+                format_fzf_line(&item, &default_branch, highlight_after_days)
+            );
+        }
+        return Ok(());
+    }
+
+    let text = match args.format {
+        ListFormat::Json => format_json(&items)?,
+        ListFormat::Markdown => tui::export_table(&items, &default_branch, TableExport::Markdown),
+        ListFormat::Csv => tui::export_table(&items, &default_branch, TableExport::Csv),
+        ListFormat::Text => items
+            .iter()
+            .map(|item| {
+                format!(
+                    "{}  {}  {}\n",
+                    item.branch,
+                    tui::format_time(item.last_commit_ts),
+                    item.path.display()
+                )
+            })
+            .collect(),
+    };
+    if args.clipboard {
+        tui::copy_to_clipboard(&text).map_err(|err| anyhow!("gw list: cannot copy: {err}"))?;
+        eprintln!("gw list: copied to the clipboard");
+    } else {
+        print!("{text}");
+    }
+    Ok(())
+}
+
+fn clean(args: &CleanArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw clean",
+    })?;
+    let default_branch = git_ops::get_default_branch(&repo_root);
+    let mut items = services::load_worktrees(&repo_root)?;
+    // Decide on current PR states; the cache may predate a merge.
+    let forge = forge::detect_forge(&repo_root);
+    if forge.availability().is_ok() {
+        if let Err(err) = services::refresh_forge(&repo_root, forge.as_ref(), &mut items) {
+            eprintln!(
+                "gw clean: using cached {} data: {err}",
+                forge.request_label()
+            );
+        }
+    }
+
+    if !args.policies {
+        let merged: Vec<(String, PathBuf, String)> =
+            services::merged_cleanup_candidates(&items, &repo_root, &default_branch)
+                .into_iter()
+                .map(|item| {
+                    let ref_name = item.ref_name.clone().unwrap_or_default();
+                    (item.branch.clone(), item.path.clone(), ref_name)
+                })
+                .collect();
+        if merged.is_empty() {
+            println!("gw clean: no merged worktrees");
+            return Ok(());
+        }
+        if git_ops::dry_run() {
+            for (branch, _, _) in &merged {
+                println!("would delete {branch}");
+            }
+            return Ok(());
+        }
+        let _lock = repo_lock::acquire(&repo_root)?;
+        for (branch, path, ref_name) in &merged {
+            trash::trash_worktree(&repo_root, path, ref_name)?;
+            services::forget_worktree_path(&repo_root, ref_name);
+            println!("deleted {branch}");
+        }
+        return Ok(());
+    }
+
+    let policies = settings::load_cleanup_policies(&repo_root)?;
+    if policies.is_empty() {
+        println!("gw clean: no cleanupPolicies in .gw/settings.json");
+        return Ok(());
+    }
+    let matches = policies::evaluate(&repo_root, &default_branch, &items, &policies);
+    if matches.is_empty() {
+        println!("gw clean: no worktrees match the cleanup policies");
+        return Ok(());
+    }
+    for found in &matches {
+        let verb = match (found.deletes(), git_ops::dry_run()) {
+            (true, true) => "would delete",
+            (true, false) => "delete",
+            (false, _) => "warn",
+        };
+        println!("{verb}  {}", policies::describe(found));
+    }
+    if !git_ops::dry_run() && matches.iter().any(|found| found.deletes()) {
+        let _lock = repo_lock::acquire(&repo_root)?;
+        let deleted = policies::apply(&repo_root, &matches)?;
+        println!(
+            "gw clean: deleted {} worktree(s); `gw restore <branch>` brings one back",
+            deleted.len()
+        );
+    }
+    Ok(())
+}
+
+fn format_json(items: &[WorktreeInfo]) -> Result<String> {
+    let summaries: Vec<_> = items.iter().map(services::summarize).collect();
+    Ok(format!("{}\n", serde_json::to_string(&summaries)?))
+}
+
+/// Branch and path stay uncolored so `cut -f1` or `cut -f2` on fzf's
+/// selection yields them as is.
+fn format_fzf_line(item: &WorktreeInfo, default_branch: &str, stale_after_days: u64) -> String {
+    let age_color = if services::is_stale(item, stale_after_days) {
+        "33"
+    } else {
+        "2"
+    };
+    let (pull_push, _) = tui::format_pull_push(item);
+    let (pr, _) = tui::format_pr(item, default_branch);
+    [
+        item.branch.clone(),
+        item.path.display().to_string(),
+        ansi(age_color, &tui::format_time(item.last_commit_ts)),
+        ansi("36", &pull_push),
+        ansi("35", &pr),
+        format!(
+            "{} {}",
+            ansi("32", &format!("+{}", item.additions)),
+            ansi("31", &format!("-{}", item.deletions))
+        ),
+    ]
+    .join("\t")
+}
+
+/// `text` in the given SGR color, or nothing if it is empty.
+fn ansi(code: &str, text: &str) -> String {
+    if text.is_empty() {
+        String::new()
+    } else {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    }
+}
+
+fn select(branch: &str, output: SelectionOutput) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw" })?;
+    let path = find_worktree(&repo_root, branch.trim(), "gw --select")?;
+    write_selection(output, &path)
+}
+
+fn find_worktree(repo_root: &Path, branch: &str, command: &str) -> Result<PathBuf> {
+    services::load_worktrees(repo_root)?
+        .into_iter()
+        .find(|item| item.branch == branch)
+        .map(|item| item.path)
+        .ok_or_else(|| anyhow!("{command}: no worktree for branch {branch}"))
+}
+
+fn export(args: &ExportArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw export",
+    })?;
+    let manifest = manifest::export_manifest(&repo_root)?;
+    let mut text = serde_json::to_string_pretty(&manifest)?;
+    text.push('\n');
+    match &args.output {
+        Some(path) => {
+            fs::write(path, text)
+                .with_context(|| format!("gw export: failed to write {}", path.display()))?;
+            eprintln!(
+                "gw export: wrote {} worktree(s) to {}",
+                manifest.worktrees.len(),
+                path.display()
+            );
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+fn apply(args: &ApplyArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw apply",
+    })?;
+    let manifest = manifest::read_manifest(&args.manifest)?;
+    let missing = manifest::missing_entries(&repo_root, &manifest)?;
+    let existing = manifest.worktrees.len() - missing.len();
+    if missing.is_empty() {
+        eprintln!("gw apply: all {existing} worktree(s) already exist");
+        return Ok(());
+    }
+
+    let _lock = repo_lock::acquire(&repo_root)?;
+    // The manifest's branches may have no worktree yet, so this fetches
+    // every branch whatever fetchWorktreeBranchesOnly says.
+    git_ops::fetch_prune(&repo_root, &settings::load_fetch_options(&repo_root)?);
+    let total = missing.len();
+    let mut notes = Vec::new();
+    for (index, (entry, path)) in missing.iter().enumerate() {
+        eprintln!(
+            "gw apply: [{}/{total}] creating {} from {}",
+            index + 1,
+            entry.branch,
+            entry.base
+        );
+        notes.extend(manifest::apply_entry(&repo_root, entry, path)?);
+    }
+    for note in notes {
+        eprintln!("gw apply: note: {note}");
+    }
+    if existing > 0 {
+        eprintln!("gw apply: skipped {existing} worktree(s) that already exist");
+    }
+    for (_, path) in &missing {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+fn review(args: &ReviewArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw review",
+    })?;
+    let forge = forge::detect_forge(&repo_root);
+
+    if args.prune {
+        forge.availability().map_err(|err| GwError::GhUnavailable {
+            detail: format!("gw review: {err}"),
+        })?;
+        let _lock = repo_lock::acquire(&repo_root)?;
+        let removed = review::prune_closed(&repo_root, forge.as_ref())?;
+        if removed.is_empty() {
+            println!("gw review: no closed reviews");
+        }
+        for number in removed {
+            println!("gw review: removed review #{number}");
+        }
+        return Ok(());
+    }
+
+    let Some(number) = args.number else {
+        for (number, path) in review::list_reviews(&repo_root)? {
+            println!("#{number}  {}", services::display_path(&repo_root, &path));
+        }
+        return Ok(());
+    };
+
+    let _lock = repo_lock::acquire(&repo_root)?;
+    if args.remove {
+        let path = review::list_reviews(&repo_root)?
+            .into_iter()
+            .find(|(review, _)| *review == number)
+            .map(|(_, path)| path)
+            .ok_or_else(|| anyhow!("gw review: no review worktree for #{number}"))?;
+        review::remove_review(&repo_root, number, &path)?;
+        println!("gw review: removed review #{number}");
+        return Ok(());
+    }
+    eprintln!("gw review: fetching {} #{number}", forge.request_label());
+    let path = review::open_review(&repo_root, forge.as_ref(), number)
+        .with_context(|| format!("gw review: cannot check out #{number}"))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn open(args: &OpenArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw open" })?;
+    let path = find_worktree(&repo_root, args.branch.trim(), "gw open")?;
+    let editor = match &args.editor {
+        Some(editor) => editor.clone(),
+        None => settings::load_editor(&repo_root)?.ok_or_else(|| {
+            anyhow!("gw open: no editor configured; set \"editor\" in .gw/settings.json or ~/.config/gw/settings.json, or $EDITOR")
+        })?,
+    };
+    services::open_in_editor(&editor, &path, args.wait).map_err(|err| anyhow!("gw open: {err}"))
+}
+
+fn code(args: &CodeArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw code" })?;
+    let path = if args.workspace {
+        let path =
+            services::write_code_workspace(&repo_root).map_err(|err| anyhow!("gw code: {err}"))?;
+        eprintln!("gw code: wrote {}", path.display());
+        path
+    } else {
+        match &args.branch {
+            Some(branch) => find_worktree(&repo_root, branch.trim(), "gw code")?,
+            None => std::env::current_dir()?,
+        }
+    };
+    services::open_in_editor("code", &path, false).map_err(|err| anyhow!("gw code: {err}"))
+}
+
+fn pull(args: &PullArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw pull" })?;
+    let path = match &args.branch {
+        Some(branch) => find_worktree(&repo_root, branch, "gw pull")?,
+        None => {
+            let cwd = std::env::current_dir()?;
+            PathBuf::from(
+                git_ops::run(&["rev-parse", "--show-toplevel"], Some(&cwd))
+                    .context("gw pull: not inside a worktree; pass a branch")?,
+            )
+        }
+    };
+    // Flags override the pullStrategy setting.
+    let strategy = if args.rebase {
+        Some(PullStrategy::Rebase)
+    } else if args.ff_only {
+        Some(PullStrategy::FfOnly)
+    } else if args.merge {
+        Some(PullStrategy::Merge)
+    } else {
+        settings::load_pull_strategy(&repo_root)?
+    };
+
+    if let Some(branch) = git_ops::worktree_branch(&path) {
+        if let Some(other) = services::checked_out_elsewhere(&repo_root, &branch) {
+            return Err(services::checked_out_elsewhere_error(
+                &repo_root, "gw pull", &branch, &other,
+            ));
+        }
+    }
+
+    let branch = git_ops::worktree_branch(&path).unwrap_or_else(|| path.display().to_string());
+    services::ensure_no_operation_in_progress(&branch, &path)?;
+
+    let _lock = repo_lock::acquire(&repo_root)?;
+    git_ops::pull(&path, strategy)?;
+    println!("gw pull: pulled {}", path.display());
+    Ok(())
+}
+
+fn create_prs(branch: Option<&str>, missing: bool) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw pr create",
+    })?;
+    let default_branch = git_ops::get_default_branch(&repo_root);
+    let forge = forge::detect_forge(&repo_root);
+    forge
+        .availability()
+        .map_err(|reason| anyhow!("gw pr create: {reason}"))?;
+    let label = forge.request_label();
+    let mut items = services::load_worktrees(&repo_root)?;
+    // A request opened since the last refresh must not get a second one.
+    services::refresh_forge(&repo_root, forge.as_ref(), &mut items)
+        .map_err(|err| anyhow!("gw pr create: cannot fetch {label} data: {err}"))?;
+
+    let targets: Vec<&WorktreeInfo> = if missing {
+        services::missing_request_candidates(&items, &default_branch)
+    } else {
+        let branch = match branch {
+            Some(branch) => branch.to_string(),
+            None => {
+                let cwd = std::env::current_dir()?;
+                git_ops::worktree_branch(&cwd)
+                    .ok_or_else(|| anyhow!("gw pr create: not on a branch; pass one"))?
+            }
+        };
+        let item = items
+            .iter()
+            .find(|item| item.branch == branch)
+            .ok_or_else(|| anyhow!("gw pr create: no worktree for branch {branch}"))?;
+        if let Some(number) = item.pr_number {
+            return Err(anyhow!(
+                "gw pr create: {branch} already has {label} #{number}"
+            ));
+        }
+        vec![item]
+    };
+    if targets.is_empty() {
+        println!("gw pr create: every branch ahead of {default_branch} has a {label}");
+        return Ok(());
+    }
+    if git_ops::dry_run() {
+        for item in &targets {
+            println!("would open a {label} for {}", item.branch);
+        }
+        return Ok(());
+    }
+
+    let template = settings::load_pr_title_template(&repo_root)?;
+    let _lock = repo_lock::acquire(&repo_root)?;
+    let mut failed = 0;
+    for item in &targets {
+        eprintln!("gw pr create: pushing {}", item.branch);
+        match services::open_draft_request(
+            &repo_root,
+            forge.as_ref(),
+            item,
+            &default_branch,
+            &template,
+        ) {
+            Ok(url) => println!("{}  {url}", item.branch),
+            Err(err) => {
+                eprintln!("gw pr create: {}: {err}", item.branch);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!(
+            "gw pr create: {failed} of {} {label}s could not be opened",
+            targets.len()
+        ));
+    }
+    Ok(())
+}
+
+fn run_preset(args: &RunArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw run" })?;
+    let presets = settings::load_presets(&repo_root)?;
+    let Some(preset) = presets.iter().find(|preset| preset.name == args.preset) else {
+        let names: Vec<&str> = presets.iter().map(|preset| preset.name.as_str()).collect();
+        return Err(if names.is_empty() {
+            anyhow!("gw run: no presets in settings")
+        } else {
+            anyhow!(
+                "gw run: unknown preset {}; available: {}",
+                args.preset,
+                names.join(", ")
+            )
+        });
+    };
+    let path = match &args.branch {
+        Some(branch) => find_worktree(&repo_root, branch, "gw run")?,
+        None => {
+            let cwd = std::env::current_dir()?;
+            PathBuf::from(
+                git_ops::run(&["rev-parse", "--show-toplevel"], Some(&cwd))
+                    .context("gw run: not inside a worktree; pass a branch")?,
+            )
+        }
+    };
+    let branch = git_ops::worktree_branch(&path)
+        .ok_or_else(|| anyhow!("gw run: {} is not on a branch", path.display()))?;
+    services::ensure_no_operation_in_progress(&branch, &path)?;
+
+    let _lock = repo_lock::acquire(&repo_root)?;
+    let total = preset.steps.len();
+    services::run_preset(&repo_root, &path, &branch, preset, &mut |index, line| {
+        eprintln!("gw run: [{}/{total}] git {line}", index + 1);
+    })?;
+    println!("gw run: {} done on {branch}", preset.name);
+    Ok(())
+}
+
+fn print_repair_checklist(repo_root: &Path, health: &HealthReport, plan: &RepairPlan) {
+    eprintln!("Fixes:");
+    for (idx, fix) in health.fixes().iter().enumerate() {
+        let mark = if plan.is_enabled(fix) { "x" } else { " " };
+        eprintln!(
+            "  [{mark}] {}. {}",
+            idx + 1,
+            services::describe_repair_fix(repo_root, health, plan, fix)
+        );
+    }
+}
+
+/// Lets the user toggle individual fixes before they are applied. Returns
+/// false if the user cancelled.
+fn select_repair_fixes(
+    repo_root: &Path,
+    health: &HealthReport,
+    plan: &mut RepairPlan,
+) -> Result<bool> {
+    let fixes = health.fixes();
+    loop {
+        eprintln!();
+        print_repair_checklist(repo_root, health, plan);
+        let input = prompt_line(
+            "Toggle fixes by number, `a<number>` to adopt/delete an orphan, `i<number>` to never create a worktree, Enter to continue, q to cancel: ",
+        )?;
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(true);
+        }
+        if input.eq_ignore_ascii_case("q") {
+            return Ok(false);
+        }
+
+        for token in input.split(|ch: char| ch.is_whitespace() || ch == ',') {
+            if token.is_empty() {
+                continue;
+            }
+            let (prefix, number) = match token.strip_prefix(['a', 'i']) {
+                Some(rest) => (token.chars().next(), rest),
+                None => (None, token),
+            };
+            let Some(fix) = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|idx| fixes.get(idx))
+            else {
+                eprintln!("gw: unknown selection `{token}`");
+                continue;
+            };
+
+            match (prefix, fix) {
+                (None, _) => plan.toggle(fix),
+                (Some('a'), RepairFix::Orphan(path)) => match health.orphan(path) {
+                    Some(orphan) if orphan.adopt_branch.is_some() => plan.toggle_adopt(orphan),
+                    _ => eprintln!("gw: {} cannot be adopted", path.display()),
+                },
+                (Some('a'), _) => eprintln!("gw: fix {number} is not an orphaned worktree"),
+                (_, RepairFix::CreateWorktree(branch)) => plan.toggle_ignore(branch),
+                _ => eprintln!("gw: fix {number} is not a missing worktree"),
+            }
+        }
+    }
+}
+
+fn init_repo(args: &InitArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo { command: "gw init" })?;
+    // The conversion moves directories itself; its plan is the preview.
+    if git_ops::dry_run() {
+        return Err(anyhow!(
+            "gw init: --dry-run is not supported; the plan is shown before anything changes"
+        ));
+    }
+    let is_bare = git_ops::is_bare_repo(&repo_root)?;
+    let branches = git_ops::list_local_branches(&repo_root)?;
+
+    if branches.is_empty() {
+        return Err(anyhow!("gw init: no local branches found"));
+    }
+
+    let selection = BranchSelection::from_args(args);
+    if let Some(dir) = &args.into {
+        return init_into(&repo_root, &branches, dir, &selection);
+    }
+
+    let hub_configured = matches!(settings::load_layout(&repo_root)?, RepoLayout::Hub { .. });
+    if args.hub || hub_configured {
+        if args.git_dir.is_some() {
+            return Err(anyhow!(
+                "gw init: --git-dir needs the bare layout; the hub layout keeps .git in its checkout"
+            ));
+        }
+        return init_hub(
+            &repo_root,
+            &branches,
+            args.worktrees_dir.as_deref(),
+            &selection,
+        );
+    }
+
+    let git_dir = match &args.git_dir {
+        Some(dir) => Some(split_git_dir_target(&repo_root, dir)?),
+        None => None,
+    };
+
+    let worktree_map = git_ops::worktree_branch_map(&repo_root)?;
+    let repo_abs = git_ops::canonicalize(&repo_root);
+    let linked_paths: Vec<PathBuf> = worktree_map
+        .values()
+        .map(|path| git_ops::canonicalize(path))
+        .filter(|path| *path != repo_abs)
+        .collect();
+
+    if is_bare {
+        let candidates: Vec<String> = branches
+            .iter()
+            .filter(|branch| !worktree_map.contains_key(*branch))
+            .cloned()
+            .collect();
+        let default_branch = git_ops::get_default_branch(&repo_root);
+        let Some((missing, skipped)) =
+            selection.apply(&repo_root, &candidates, &[default_branch])?
+        else {
+            println!("gw init: cancelled");
+            return Ok(());
+        };
+        let conflicts: Vec<String> = missing
+            .iter()
+            .filter_map(|branch| {
+                let target = services::worktree_path_for_branch(&repo_abs, branch);
+                services::worktree_target_conflict(&target, &linked_paths)
+                    .map(|reason| format!("{branch}: {reason}"))
+            })
+            .collect();
+        ensure_no_init_conflicts(&conflicts)?;
+
+        println!(
+            "gw init will initialize worktrees under {}",
+            repo_root.display()
+        );
+        if missing.is_empty() {
+            println!("- no new worktrees to create");
+        } else {
+            println!("- create worktrees for {} local branches", missing.len());
+        }
+        print_skipped_branches(&skipped);
+        print_git_dir_move(&repo_root, git_dir.as_deref());
+
+        if !confirm("Continue?")? {
+            println!("gw init: cancelled");
+            return Ok(());
+        }
+
+        let mut created = Vec::new();
+        create_init_worktrees(&repo_root, &missing, &mut created)?;
+        settings::add_no_worktree_branches(&repo_root, &skipped)?;
+        if let Some(git_dir) = &git_dir {
+            let started = Instant::now();
+            git_ops::relocate_git_dir(&repo_root, git_dir)
+                .map_err(|err| anyhow!("gw init: cannot move the repo: {err}"))?;
+            print_init_step(&format!("moved the repo to {}", git_dir.display()), started);
+        }
+
+        run_post_init_hooks(&repo_root, &created)?;
+        println!("gw init: done");
+        return Ok(());
+    }
+
+    let root_branches: HashSet<String> = worktree_map
+        .iter()
+        .filter_map(|(branch, path)| {
+            let path_abs = git_ops::canonicalize(path);
+            if path_abs == repo_abs {
+                Some(branch.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let candidates: Vec<String> = branches
+        .iter()
+        .filter(|branch| !worktree_map.contains_key(*branch) || root_branches.contains(*branch))
+        .cloned()
+        .collect();
+
+    let worktree_paths: Vec<PathBuf> = git_ops::parse_worktrees(Some(&repo_root))?
+        .into_iter()
+        .map(|wt| wt.path)
+        .collect();
+
+    let keep_entries = git_ops::get_entries_to_preserve(&repo_root, &worktree_paths)?;
+    let default_branch = git_ops::get_default_branch(&repo_root);
+    // The branch checked out at the root loses its checkout, so it always
+    // gets a worktree.
+    let always: Vec<String> = root_branches
+        .iter()
+        .cloned()
+        .chain([default_branch.clone()])
+        .collect();
+    if args.move_untracked {
+        if git_ops::has_uncommitted_changes_excluding(&repo_root, &keep_entries, false)? {
+            return Err(anyhow!("gw init: working tree has uncommitted changes"));
+        }
+    } else if git_ops::has_uncommitted_changes_excluding(&repo_root, &keep_entries, true)? {
+        return Err(anyhow!(
+            "gw init: working tree has uncommitted or untracked changes; commit them, or pass --move-untracked to move untracked files into the default branch's worktree"
+        ));
+    }
+
+    let mut root_entries: Vec<RootEntry> =
+        git_ops::untracked_entries_excluding(&repo_root, &keep_entries)?
+            .into_iter()
+            .map(|path| {
+                let action = if args
+                    .preserve
+                    .iter()
+                    .any(|glob| matches_preserve_glob(glob, &path))
+                {
+                    RootEntryAction::Keep
+                } else if args.move_untracked {
+                    RootEntryAction::Move
+                } else {
+                    RootEntryAction::Delete
+                };
+                RootEntry { path, action }
+            })
+            .collect();
+    let Some((missing, skipped)) = selection.apply(&repo_root, &candidates, &always)? else {
+        println!("gw init: cancelled");
+        return Ok(());
+    };
+    let move_target = if missing.contains(&default_branch) {
+        Some(services::worktree_path_for_branch(
+            &repo_root,
+            &default_branch,
+        ))
+    } else {
+        worktree_map.get(&default_branch).cloned()
+    };
+    if args.move_untracked && move_target.is_none() {
+        return Err(anyhow!(
+            "gw init: --move-untracked needs a worktree for the default branch {default_branch}, which has no local branch"
+        ));
+    }
+
+    // Everything at the root except the preserved entries is staged away before
+    // worktrees are created, so only overlaps with existing worktrees matter here.
+    let conflicts: Vec<String> = missing
+        .iter()
+        .filter_map(|branch| {
+            let target = services::worktree_path_for_branch(&repo_abs, branch);
+            services::worktree_target_overlap(&target, &linked_paths)
+                .map(|reason| format!("{branch}: {reason}"))
+        })
+        .collect();
+    ensure_no_init_conflicts(&conflicts)?;
+
+    println!(
+        "gw init will convert {} into a gw-compliant layout:",
+        repo_root.display()
+    );
+    println!("- delete the current working tree at the repo root");
+    if git_dir.is_none() {
+        println!("- keep only the bare repo in the top-level .git directory");
+    }
+    if skipped.is_empty() {
+        println!("- ensure every local branch has a worktree");
+    }
+
+    if !missing.is_empty() {
+        println!(
+            "- create {} new worktrees under {}/<branch>",
+            missing.len(),
+            repo_root.display()
+        );
+    }
+    print_skipped_branches(&skipped);
+
+    let preserved: Vec<PathBuf> = keep_entries
+        .into_iter()
+        .filter(|entry| entry != Path::new(".git"))
+        .collect();
+    if !preserved.is_empty() {
+        let names: Vec<String> = preserved
+            .iter()
+            .map(|entry| entry.display().to_string())
+            .collect();
+        println!("- preserve existing worktree paths: {}", names.join(", "));
+    }
+    print_git_dir_move(&repo_root, git_dir.as_deref());
+
+    if !root_entries.is_empty() && !review_root_entries(&mut root_entries, move_target.as_deref())?
+    {
+        println!("gw init: cancelled");
+        return Ok(());
+    }
+    if !confirm("Continue?")? {
+        println!("gw init: cancelled");
+        return Ok(());
+    }
+
+    let mut keep_entries = preserved_with_git(preserved);
+    let mut moves = Vec::new();
+    for entry in root_entries {
+        match entry.action {
+            RootEntryAction::Keep => {
+                keep_entries.insert(entry.path);
+            }
+            RootEntryAction::Move => moves.push(entry.path),
+            RootEntryAction::Delete => {}
+        }
+    }
+    let relocate = move_target
+        .as_deref()
+        .filter(|_| !moves.is_empty())
+        .map(|target| (target, moves.as_slice()));
+    let created = convert_repo_with_rollback(&repo_root, &keep_entries, &missing, relocate)?;
+    settings::add_no_worktree_branches(&repo_root, &skipped)?;
+    if let Some(git_dir) = &git_dir {
+        // The conversion itself is done; a failure here leaves the usual layout.
+        let started = Instant::now();
+        git_ops::relocate_git_dir(&repo_root, git_dir).map_err(|err| {
+            anyhow!("gw init: converted, but cannot move the repo (it stays in .git): {err}")
+        })?;
+        print_init_step(&format!("moved the repo to {}", git_dir.display()), started);
+    }
+
+    run_post_init_hooks(&repo_root, &created)?;
+    println!("gw init: done");
+    Ok(())
+}
+
+/// Where `gw init --git-dir` moves the repo, as an absolute path. Refuses
+/// when the repo is already kept outside the root or `dir` is taken.
+fn split_git_dir_target(repo_root: &Path, dir: &Path) -> Result<PathBuf> {
+    if !repo_root.join(".git").is_dir() {
+        return Err(anyhow!(
+            "gw init: the repo is already kept outside {}",
+            repo_root.display()
+        ));
+    }
+    let target = absolute_lexical(dir)?;
+    if target.exists() {
+        return Err(anyhow!("gw init: {} already exists", target.display()));
+    }
+    if target.starts_with(repo_root) {
+        return Err(anyhow!(
+            "gw init: --git-dir must be outside {}",
+            repo_root.display()
+        ));
+    }
+    Ok(target)
+}
+
+/// `dir` as an absolute path with `..` resolved lexically, since the
+/// directory may not exist yet.
+fn absolute_lexical(dir: &Path) -> Result<PathBuf> {
+    let mut target = PathBuf::new();
+    for component in std::path::absolute(dir)?.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                target.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => target.push(other),
+        }
+    }
+    Ok(target)
+}
+
+/// Sets up the bare layout in `dir` from a bare copy of the repo: every local
+/// branch gets a worktree there, and the repo at `repo_root` is only read.
+fn init_into(
+    repo_root: &Path,
+    branches: &[String],
+    dir: &Path,
+    selection: &BranchSelection,
+) -> Result<()> {
+    let target = absolute_lexical(dir)?;
+    let repo_abs = git_ops::canonicalize(repo_root);
+    if target.starts_with(&repo_abs) || repo_abs.starts_with(&target) {
+        return Err(anyhow!(
+            "gw init: --into must be outside {}",
+            repo_root.display()
+        ));
+    }
+    let existed = target.exists();
+    if existed
+        && fs::read_dir(&target)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(true)
+    {
+        return Err(anyhow!(
+            "gw init: {} already exists and is not empty",
+            target.display()
+        ));
+    }
+
+    let default_branch = git_ops::get_default_branch(repo_root);
+    let Some((selected, skipped)) = selection.apply(repo_root, branches, &[default_branch])? else {
+        println!("gw init: cancelled");
+        return Ok(());
+    };
+
+    println!(
+        "gw init will set up a gw-compliant layout in {}, leaving {} as it is:",
+        target.display(),
+        repo_root.display()
+    );
+    println!(
+        "- copy the repo to {} as a bare repo, with its remotes and upstreams",
+        target.join(".git").display()
+    );
+    println!(
+        "- create worktrees for {} local branches under {}/<branch>",
+        selected.len(),
+        target.display()
+    );
+    print_skipped_branches(&skipped);
+    let dirty = !git_ops::is_bare_repo(repo_root)?
+        && git_ops::has_uncommitted_changes_excluding(repo_root, &[], true)?;
+    if dirty {
+        println!(
+            "- uncommitted changes and untracked files in {} are not copied",
+            repo_root.display()
+        );
+    }
+
+    if !confirm("Continue?")? {
+        println!("gw init: cancelled");
+        return Ok(());
+    }
+
+    let mut created = Vec::new();
+    let result = (|| -> Result<()> {
+        let started = Instant::now();
+        eprintln!("gw init: copying the repo");
+        git_ops::clone_bare_copy(repo_root, &target.join(".git"))
+            .map_err(|err| anyhow!("gw init: cannot copy the repo: {err}"))?;
+        print_init_step("copied the repo", started);
+        create_init_worktrees(&target, &selected, &mut created)?;
+        settings::add_no_worktree_branches(&target, &skipped)
+    })();
+    if let Err(err) = result {
+        let cleanup = fs::remove_dir_all(&target).and_then(|()| {
+            if existed {
+                fs::create_dir(&target)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(cleanup_err) = cleanup {
+            return Err(anyhow!(
+                "{err}\ngw init: failed to remove {}: {cleanup_err}",
+                target.display()
+            ));
+        }
+        return Err(err);
+    }
+
+    run_post_init_hooks(&target, &created)?;
+    println!("gw init: done; the new layout is in {}", target.display());
+    Ok(())
+}
+
+fn print_git_dir_move(repo_root: &Path, git_dir: Option<&Path>) {
+    if let Some(git_dir) = git_dir {
+        println!(
+            "- move the bare repo to {}, leaving a .git file in {} that points at it",
+            git_dir.display(),
+            repo_root.display()
+        );
+    }
+}
+
+/// Sets up the hub layout: the checkout at the repo root stays as it is, the
+/// layout is recorded in `.gw/settings.json`, existing worktrees are moved into
+/// the worktrees directory, and every other branch gets a worktree there.
+fn init_hub(
+    repo_root: &Path,
+    branches: &[String],
+    worktrees_dir: Option<&str>,
+    selection: &BranchSelection,
+) -> Result<()> {
+    if git_ops::is_bare_repo(repo_root)? {
+        return Err(anyhow!(
+            "gw init --hub: repository is bare; the hub layout keeps a checkout at the repo root"
+        ));
+    }
 
-    match cli.command {
-        Some(Commands::Init) => init_repo(),
-        Some(Commands::ShellInit) => shell_init(),
-        Some(Commands::Hooks(hooks_args)) => match hooks_args.command {
-            HooksSubcommands::Add { command } => add_hook(&command),
-            HooksSubcommands::Rerun => rerun_hooks(),
-        },
-        None => run_default(),
+    let settings_file = settings::settings_path(repo_root);
+    let previous_settings = fs::read(&settings_file).ok();
+    let dir = match (worktrees_dir, settings::load_layout(repo_root)?) {
+        (Some(dir), _) => Some(dir.to_string()),
+        (None, RepoLayout::Hub { .. }) => None,
+        (None, RepoLayout::Bare) => Some(settings::default_worktrees_dir(repo_root)),
+    };
+    if let Some(dir) = &dir {
+        settings::save_hub_layout(repo_root, dir)?;
     }
-}
 
-fn run_default() -> Result<()> {
-    let repo_root = git_ops::get_repo_root().context("gw: not inside a git repository")?;
-    let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+    let result = init_hub_with_layout(repo_root, branches, selection);
+    if !matches!(result, Ok(true)) {
+        let restored = match &previous_settings {
+            Some(bytes) => fs::write(&settings_file, bytes),
+            None => fs::remove_file(&settings_file).map(|()| {
+                if let Some(dir) = settings_file.parent() {
+                    let _ = fs::remove_dir(dir);
+                }
+            }),
+        };
+        if let Err(err) = restored {
+            eprintln!(
+                "gw init: warning: failed to restore {}: {err}",
+                settings_file.display()
+            );
+        }
+    }
+    result.map(|_| ())
+}
 
-    git_ops::prune_worktrees(&repo_root);
-    let health = services::health_check(&repo_root)?;
-    if health.has_issues() {
-        if !interactive {
+/// Returns false if the user cancelled.
+fn init_hub_with_layout(
+    repo_root: &Path,
+    branches: &[String],
+    selection: &BranchSelection,
+) -> Result<bool> {
+    let base = services::worktrees_dir(repo_root);
+    let base_abs = git_ops::canonicalize(&base);
+    let repo_abs = git_ops::canonicalize(repo_root);
+
+    let mut in_place = Vec::new();
+    let mut moves = Vec::new();
+    let mut mapped = HashSet::new();
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    for wt in worktrees.iter().cloned() {
+        let path_abs = git_ops::canonicalize(&wt.path);
+        mapped.insert(wt.branch.clone());
+        if path_abs == repo_abs {
+            continue;
+        }
+        if path_abs.starts_with(&base_abs) {
+            in_place.push(path_abs);
+        } else if branches.contains(&wt.branch) {
+            moves.push((wt.branch.clone(), wt.path));
+        } else {
             return Err(anyhow!(
-                "gw: detected worktree/branch inconsistencies; rerun in an interactive terminal to repair them, or run `gw init`"
+                "gw init --hub: worktree {} has no branch; remove it or check out a branch first",
+                wt.path.display()
             ));
         }
-        if !handle_health_issues(&repo_root, &health)? {
-            return Ok(());
-        }
     }
+    let candidates: Vec<String> = branches
+        .iter()
+        .filter(|branch| !mapped.contains(*branch))
+        .cloned()
+        .collect();
+    let default_branch = git_ops::get_default_branch(repo_root);
+    let Some((missing, skipped)) = selection.apply(repo_root, &candidates, &[default_branch])?
+    else {
+        println!("gw init: cancelled");
+        return Ok(false);
+    };
 
-    let default_branch = git_ops::get_default_branch(&repo_root);
-    let items = services::load_worktrees(&repo_root)?;
+    let targets: Vec<(&String, PathBuf)> = moves
+        .iter()
+        .map(|(branch, _)| branch)
+        .chain(missing.iter())
+        .map(|branch| {
+            (
+                branch,
+                services::new_worktree_path(repo_root, branch, &worktrees),
+            )
+        })
+        .collect();
+    let conflicts: Vec<String> = targets
+        .iter()
+        .filter_map(|(branch, target)| {
+            services::worktree_target_conflict(target, &in_place)
+                .map(|reason| format!("{branch}: {reason}"))
+        })
+        .collect();
+    ensure_no_init_conflicts(&conflicts)?;
 
-    if !interactive {
-        for item in &items {
-            println!("{}", item.path.display());
-        }
-        return Ok(());
+    println!(
+        "gw init will set up a hub layout for {}:",
+        repo_root.display()
+    );
+    println!("- keep the current checkout at the repo root");
+    println!(
+        "- record the layout in .gw/settings.json, with worktrees under {}",
+        base.display()
+    );
+    if !moves.is_empty() {
+        println!("- move {} existing worktrees there", moves.len());
     }
-
-    let gh_available = command_available("gh");
-    let warning =
-        (!gh_available).then(|| "gh not found: install/configure gh for PR data".to_string());
-
-    let selected = tui::run_tui(
-        repo_root.clone(),
-        items,
-        default_branch,
-        warning,
-        gh_available,
-    )?;
-    if let Some(path) = selected {
-        tui::write_selected_path(&path)?;
+    if missing.is_empty() {
+        println!("- no new worktrees to create");
+    } else {
+        println!("- create worktrees for {} local branches", missing.len());
     }
+    print_skipped_branches(&skipped);
 
-    Ok(())
-}
-
-fn handle_health_issues(repo_root: &Path, health: &HealthReport) -> Result<bool> {
-    eprintln!("Detected issue with gw setup in {}", repo_root.display());
-    eprintln!();
-
-    if !health.orphaned_worktrees.is_empty() {
-        eprintln!(
-            "- worktrees without branches to delete: {}",
-            health.orphaned_worktrees.len()
-        );
-        for path in &health.orphaned_worktrees {
-            eprintln!("  - {}", path.display());
-        }
+    if !confirm("Continue?")? {
+        println!("gw init: cancelled");
+        return Ok(false);
     }
 
-    if !health.missing_worktrees.is_empty() {
-        eprintln!(
-            "- branches without worktrees to create: {}",
-            health.missing_worktrees.len()
-        );
-        for branch in &health.missing_worktrees {
-            eprintln!("  - {branch} -> {}", repo_root.join(branch).display());
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut created: Vec<PathBuf> = Vec::new();
+    let apply = (|| -> Result<()> {
+        let started = Instant::now();
+        for (index, ((branch, src), (_, target))) in moves.iter().zip(&targets).enumerate() {
+            eprintln!(
+                "gw init: [{}/{}] moving worktree for {branch}",
+                index + 1,
+                moves.len()
+            );
+            services::move_worktree(repo_root, src, target)
+                .with_context(|| format!("gw init: failed to move worktree for {branch}"))?;
+            services::record_worktree_path(repo_root, branch, target);
+            moved.push((src.clone(), target.clone()));
         }
-    }
+        if !moves.is_empty() {
+            print_init_step(&format!("moved {} worktrees", moves.len()), started);
+        }
+        create_init_worktrees(repo_root, &missing, &mut created)?;
+        settings::add_no_worktree_branches(repo_root, &skipped)
+    })();
 
-    if !health.unrecoverable_reasons.is_empty() {
-        eprintln!("- unrecoverable issues:");
-        for reason in &health.unrecoverable_reasons {
-            eprintln!("  - {reason}");
+    if let Err(err) = apply {
+        let mut rollback_errors = Vec::new();
+        for path in created.iter().rev() {
+            if let Err(err) = services::remove_worktree(repo_root, path) {
+                rollback_errors.push(format!(
+                    "- failed to remove created worktree {}: {err}",
+                    path.display()
+                ));
+            }
+        }
+        for (src, target) in moved.iter().rev() {
+            if let Err(err) = services::move_worktree(repo_root, target, src) {
+                rollback_errors.push(format!(
+                    "- failed to move {} back to {}: {err}",
+                    target.display(),
+                    src.display()
+                ));
+            }
+        }
+        if rollback_errors.is_empty() {
+            return Err(err);
         }
         return Err(anyhow!(
-            "gw: setup is not recoverable automatically; run `gw init` first"
+            "{err}\ngw init: rollback encountered errors:\n{}",
+            rollback_errors.join("\n")
         ));
     }
 
-    eprintln!();
-    if !confirm("Apply these fixes now?")? {
-        eprintln!("gw: cancelled");
-        return Ok(false);
-    }
-
-    services::doctor_repo(repo_root, health)?;
-    eprintln!("gw: setup repaired");
+    run_post_init_hooks(repo_root, &created)?;
+    println!("gw init: done");
     Ok(true)
 }
 
-fn command_available(cmd: &str) -> bool {
-    std::process::Command::new(cmd)
-        .arg("--version")
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
+/// An untracked or ignored path at the repo root, which `gw init` would
+/// otherwise delete along with the checkout.
+struct RootEntry {
+    path: PathBuf,
+    action: RootEntryAction,
 }
 
-fn init_repo() -> Result<()> {
-    let repo_root = git_ops::get_repo_root().context("gw init: not inside a git repository")?;
-    let is_bare = git_ops::is_bare_repo(&repo_root)?;
-    let branches = git_ops::list_local_branches(&repo_root)?;
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RootEntryAction {
+    Delete,
+    /// Into the default branch's worktree (`--move-untracked`).
+    Move,
+    /// Left at the repo root (`--preserve`).
+    Keep,
+}
 
-    if branches.is_empty() {
-        return Err(anyhow!("gw init: no local branches found"));
+/// A `--preserve` glob matches the whole repo-relative path or, if it has no
+/// `/`, just the file name, so `.env*` also keeps `config/.env.local`.
+fn matches_preserve_glob(glob: &str, path: &Path) -> bool {
+    let glob = glob.trim_end_matches('/');
+    if services::glob_match(glob, &git_ops::pathspec(path)) {
+        return true;
     }
+    !glob.contains('/')
+        && path
+            .file_name()
+            .is_some_and(|name| services::glob_match(glob, &name.to_string_lossy()))
+}
 
-    let worktree_map = git_ops::worktree_branch_map(&repo_root)?;
+/// Lists the untracked and ignored paths at the repo root with what `gw init`
+/// will do to each, and lets the user keep some at the root instead. Returns
+/// false if the user cancelled.
+fn review_root_entries(entries: &mut [RootEntry], move_target: Option<&Path>) -> Result<bool> {
+    loop {
+        eprintln!();
+        eprintln!("Untracked and ignored paths at the repo root:");
+        for (idx, entry) in entries.iter().enumerate() {
+            let action = match (entry.action, move_target) {
+                (RootEntryAction::Delete, _) => "delete".to_string(),
+                (RootEntryAction::Move, Some(target)) => format!("move to {}", target.display()),
+                (RootEntryAction::Move, None) => "move".to_string(),
+                (RootEntryAction::Keep, _) => "keep at the repo root".to_string(),
+            };
+            eprintln!("  {}. {} ({action})", idx + 1, entry.path.display());
+        }
+        let input = prompt_line(
+            "Toggle keeping paths at the repo root by number, Enter to continue, q to cancel: ",
+        )?;
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(true);
+        }
+        if input.eq_ignore_ascii_case("q") {
+            return Ok(false);
+        }
 
-    let get_conflicting_paths = |branches_to_add: &[String], map: &HashMap<String, PathBuf>| {
-        let mut conflicts = Vec::new();
-        for branch in branches_to_add {
-            let target = repo_root.join(branch);
-            if target.exists() && !map.contains_key(branch) {
-                conflicts.push(branch.clone());
+        for token in input.split(|ch: char| ch.is_whitespace() || ch == ',') {
+            if token.is_empty() {
+                continue;
             }
+            let Some(entry) = token
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| number.checked_sub(1))
+                .and_then(|idx| entries.get_mut(idx))
+            else {
+                eprintln!("gw init: no path numbered {token}");
+                continue;
+            };
+            entry.action = match entry.action {
+                RootEntryAction::Keep if move_target.is_some() => RootEntryAction::Move,
+                RootEntryAction::Keep => RootEntryAction::Delete,
+                _ => RootEntryAction::Keep,
+            };
         }
-        conflicts
-    };
+    }
+}
 
-    if is_bare {
-        let missing: Vec<String> = branches
-            .iter()
-            .filter(|branch| !worktree_map.contains_key(*branch))
-            .cloned()
-            .collect();
-        let conflicts = get_conflicting_paths(&missing, &worktree_map);
+/// How `gw init` picks the branches that get a worktree: `--branches`,
+/// `--recent` and `--pick`. Without any of them, every branch gets one.
+struct BranchSelection<'a> {
+    globs: &'a [String],
+    recent: Option<usize>,
+    pick: bool,
+}
 
-        if !conflicts.is_empty() {
-            return Err(anyhow!(
-                "gw init: cannot create worktrees; paths already exist: {}",
-                conflicts.join(", ")
-            ));
+impl<'a> BranchSelection<'a> {
+    fn from_args(args: &'a InitArgs) -> Self {
+        Self {
+            globs: &args.branch_globs,
+            recent: args.recent,
+            pick: args.pick,
         }
+    }
 
-        println!(
-            "gw init will initialize worktrees under {}",
-            repo_root.display()
-        );
-        if missing.is_empty() {
-            println!("- no new worktrees to create");
-        } else {
-            println!("- create worktrees for {} local branches", missing.len());
-        }
+    /// Splits `candidates` into the branches that get a worktree and those
+    /// left without one. Branches in `always` (the default branch, and the
+    /// branch checked out at the root) are never left out, and branches
+    /// already in `noWorktreeBranches` stay left out. Returns `None` if the
+    /// user cancelled the pick list.
+    fn apply(
+        &self,
+        repo_root: &Path,
+        candidates: &[String],
+        always: &[String],
+    ) -> Result<Option<(Vec<String>, Vec<String>)>> {
+        let recorded = settings::load_no_worktree_branches(repo_root)?;
+        let (mut skipped, candidates): (Vec<String>, Vec<String>) =
+            candidates.iter().cloned().partition(|branch| {
+                services::is_no_worktree_branch(&recorded, branch) && !always.contains(branch)
+            });
+
+        let filtered = !self.globs.is_empty() || self.recent.is_some();
+        let recent: Vec<&String> = match self.recent {
+            Some(count) => {
+                let times = git_ops::list_branch_commit_times(repo_root)?;
+                let mut by_time: Vec<&String> = candidates.iter().collect();
+                by_time.sort_by_key(|branch| {
+                    std::cmp::Reverse(times.get(*branch).copied().unwrap_or(0))
+                });
+                by_time.truncate(count);
+                by_time
+            }
+            None => Vec::new(),
+        };
+        let mut selected: Vec<bool> = candidates
+            .iter()
+            .map(|branch| {
+                !filtered
+                    || always.contains(branch)
+                    || recent.contains(&branch)
+                    || self
+                        .globs
+                        .iter()
+                        .any(|glob| services::glob_match(glob, branch))
+            })
+            .collect();
 
-        if !confirm("Continue?")? {
-            println!("gw init: cancelled");
-            return Ok(());
+        if self.pick && !pick_init_branches(&candidates, always, &mut selected)? {
+            return Ok(None);
         }
 
-        for branch in &missing {
-            let target = repo_root.join(branch);
-            git_ops::worktree_add(&repo_root, &target, branch, None)?;
+        let mut chosen = Vec::new();
+        for (branch, selected) in candidates.into_iter().zip(selected) {
+            if selected {
+                chosen.push(branch);
+            } else {
+                skipped.push(branch);
+            }
         }
-
-        println!("gw init: done");
-        return Ok(());
+        skipped.sort();
+        Ok(Some((chosen, skipped)))
     }
+}
 
-    if git_ops::has_uncommitted_changes(&repo_root)? {
-        return Err(anyhow!(
-            "gw init: working tree has uncommitted or untracked changes"
-        ));
-    }
+/// Lets the user toggle which branches get a worktree. Returns false if the
+/// user cancelled.
+fn pick_init_branches(
+    branches: &[String],
+    always: &[String],
+    selected: &mut [bool],
+) -> Result<bool> {
+    loop {
+        eprintln!();
+        eprintln!("Branches to create worktrees for:");
+        for (idx, branch) in branches.iter().enumerate() {
+            let mark = if selected[idx] { "x" } else { " " };
+            eprintln!("  [{mark}] {}. {branch}", idx + 1);
+        }
+        let input = prompt_line(
+            "Toggle branches by number, `a` for all, `n` for none, Enter to continue, q to cancel: ",
+        )?;
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(true);
+        }
+        if input.eq_ignore_ascii_case("q") {
+            return Ok(false);
+        }
 
-    let repo_abs = repo_root
-        .canonicalize()
-        .unwrap_or_else(|_| repo_root.clone());
-    let root_branches: HashSet<String> = worktree_map
-        .iter()
-        .filter_map(|(branch, path)| {
-            let path_abs = path.canonicalize().unwrap_or_else(|_| path.clone());
-            if path_abs == repo_abs {
-                Some(branch.clone())
-            } else {
-                None
+        for token in input.split(|ch: char| ch.is_whitespace() || ch == ',') {
+            match token {
+                "" => {}
+                "a" => selected.fill(true),
+                "n" => {
+                    for (idx, branch) in branches.iter().enumerate() {
+                        selected[idx] = always.contains(branch);
+                    }
+                }
+                _ => {
+                    let Some(idx) = token
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|number| number.checked_sub(1))
+                        .filter(|idx| *idx < branches.len())
+                    else {
+                        eprintln!("gw init: no branch numbered {token}");
+                        continue;
+                    };
+                    if always.contains(&branches[idx]) {
+                        eprintln!("gw init: {} always gets a worktree", branches[idx]);
+                        continue;
+                    }
+                    selected[idx] = !selected[idx];
+                }
             }
-        })
-        .collect();
-
-    let missing: Vec<String> = branches
-        .iter()
-        .filter(|branch| !worktree_map.contains_key(*branch) || root_branches.contains(*branch))
-        .cloned()
-        .collect();
-
-    let worktree_paths: Vec<PathBuf> = git_ops::parse_worktrees(Some(&repo_root))?
-        .into_iter()
-        .map(|wt| wt.path)
-        .collect();
-
-    let keep_entries = git_ops::get_entries_to_preserve(&repo_root, &worktree_paths)?;
-    let conflicts = get_conflicting_paths(&missing, &worktree_map);
-
-    if !conflicts.is_empty() {
-        return Err(anyhow!(
-            "gw init: cannot create worktrees; paths already exist: {}",
-            conflicts.join(", ")
-        ));
+        }
     }
+}
 
-    println!(
-        "gw init will convert {} into a gw-compliant layout:",
-        repo_root.display()
-    );
-    println!("- delete the current working tree at the repo root");
-    println!("- keep only the bare repo in the top-level .git directory");
-    println!("- ensure every local branch has a worktree");
-
-    if !missing.is_empty() {
+fn print_skipped_branches(skipped: &[String]) {
+    if !skipped.is_empty() {
         println!(
-            "- create {} new worktrees under {}/<branch>",
-            missing.len(),
-            repo_root.display()
+            "- leave {} branches without worktrees, recorded in noWorktreeBranches in .gw/settings.json",
+            skipped.len()
         );
     }
+}
 
-    let preserved: Vec<String> = keep_entries
-        .into_iter()
-        .filter(|entry| entry != ".git")
+/// Creates a worktree for each branch at its usual path under `repo_root`,
+/// several at a time, printing each one as it is done. Each created path is
+/// added to `created`, so a failure can be rolled back.
+fn create_init_worktrees(
+    repo_root: &Path,
+    branches: &[impl AsRef<str>],
+    created: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let started = Instant::now();
+    let total = branches.len();
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let targets: Vec<(String, PathBuf)> = branches
+        .iter()
+        .map(|branch| {
+            let branch = branch.as_ref();
+            (
+                branch.to_string(),
+                services::new_worktree_path(repo_root, branch, &worktrees),
+            )
+        })
         .collect();
-    if !preserved.is_empty() {
-        println!(
-            "- preserve existing worktree paths: {}",
-            preserved.join(", ")
-        );
+    if total > 0 {
+        eprintln!("gw init: creating {total} worktrees");
     }
-
-    if !confirm("Continue?")? {
-        println!("gw init: cancelled");
-        return Ok(());
+    let mut done = 0;
+    services::add_worktrees(repo_root, &targets, created, &mut |branch| {
+        done += 1;
+        eprintln!("gw init: [{done}/{total}] created worktree for {branch}");
+    })
+    .map_err(|err| anyhow!("gw init: {err}"))?;
+    if total > 0 {
+        print_init_step(&format!("created {total} worktrees"), started);
     }
+    Ok(())
+}
 
-    let keep_entries = preserved_with_git(preserved);
-    convert_repo_with_rollback(&repo_root, &keep_entries, &missing)?;
-
-    println!("gw init: done");
+/// Runs the `PostInit` hooks once the layout is in place. A failing hook
+/// fails `gw init`, but leaves the converted layout as it is.
+fn run_post_init_hooks(repo_root: &Path, created: &[PathBuf]) -> Result<()> {
+    let started = Instant::now();
+    let ran = hooks::run_post_init_hooks(repo_root, created).inspect_err(|_| {
+        eprintln!("gw init: the layout is ready, but a post-init hook failed");
+    })?;
+    if ran > 0 {
+        print_init_step(&format!("ran {ran} post-init hooks"), started);
+    }
     Ok(())
 }
 
-fn preserved_with_git(mut keep: Vec<String>) -> HashSet<String> {
-    keep.push(".git".to_string());
-    keep.push(".gw".to_string());
+/// Reports a finished step of `gw init` with how long it took.
+fn print_init_step(message: &str, started: Instant) {
+    eprintln!(
+        "gw init: {message} in {:.1}s",
+        started.elapsed().as_secs_f64()
+    );
+}
+
+fn preserved_with_git(mut keep: Vec<PathBuf>) -> HashSet<PathBuf> {
+    keep.push(PathBuf::from(".git"));
+    keep.push(PathBuf::from(".gw"));
     keep.into_iter().collect()
 }
 
+fn ensure_no_init_conflicts(conflicts: &[String]) -> Result<()> {
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "gw init: cannot create worktrees:\n- {}",
+        conflicts.join("\n- ")
+    ))
+}
+
 #[derive(Debug)]
 struct StagedEntry {
     original: PathBuf,
     backup: PathBuf,
 }
 
+/// `relocate` names a worktree and the staged paths to move into it once the
+/// conversion succeeded; everything else staged is deleted. Returns the
+/// worktrees it created.
 fn convert_repo_with_rollback(
     repo_root: &Path,
-    keep_entries: &HashSet<String>,
+    keep_entries: &HashSet<PathBuf>,
     missing_branches: &[String],
-) -> Result<()> {
+    relocate: Option<(&Path, &[PathBuf])>,
+) -> Result<Vec<PathBuf>> {
     let backup_dir = create_backup_dir(repo_root)?;
     let mut tx = InitConversionTx {
         repo_root: repo_root.to_path_buf(),
@@ -318,35 +2697,56 @@ fn convert_repo_with_rollback(
 
     let mut stage_keep = keep_entries.clone();
     if let Some(name) = tx.backup_dir.file_name() {
-        stage_keep.insert(name.to_string_lossy().to_string());
+        stage_keep.insert(PathBuf::from(name));
     }
-    preflight_worktree_targets(repo_root, missing_branches)?;
 
     let convert_result = (|| -> Result<()> {
+        let started = Instant::now();
+        eprintln!("gw init: moving the checkout at the repo root aside");
         tx.staged_entries = stage_repo_root(repo_root, &stage_keep, &tx.backup_dir)?;
+        print_init_step(
+            &format!("moved {} entries aside", tx.staged_entries.len()),
+            started,
+        );
+        preflight_worktree_targets(repo_root, missing_branches)?;
         git_ops::set_bare(repo_root)?;
         tx.bare_changed = true;
 
-        for branch in missing_branches {
-            let target = repo_root.join(branch);
-            git_ops::worktree_add(repo_root, &target, branch, None)
-                .with_context(|| format!("gw init: failed to create worktree for {branch}"))?;
-            tx.created_worktrees.push(target);
-        }
-
+        create_init_worktrees(repo_root, missing_branches, &mut tx.created_worktrees)?;
         postcheck_worktrees(repo_root, missing_branches)?;
         Ok(())
     })();
 
     match convert_result {
         Ok(()) => {
+            if let Some((target, paths)) = relocate {
+                let started = Instant::now();
+                let unmoved = relocate_from_backup(&tx.backup_dir, target, paths);
+                print_init_step(
+                    &format!(
+                        "moved {} untracked paths into {}",
+                        paths.len() - unmoved.len(),
+                        target.display()
+                    ),
+                    started,
+                );
+                if !unmoved.is_empty() {
+                    eprintln!(
+                        "gw init: warning: conversion succeeded, but some paths could not be moved into {}; they are still in {}:\n{}",
+                        target.display(),
+                        tx.backup_dir.display(),
+                        unmoved.join("\n")
+                    );
+                    return Ok(tx.created_worktrees);
+                }
+            }
             if let Err(err) = fs::remove_dir_all(&tx.backup_dir) {
                 eprintln!(
                     "gw init: warning: conversion succeeded, but failed to remove backup {}: {err}",
                     tx.backup_dir.display()
                 );
             }
-            Ok(())
+            Ok(tx.created_worktrees)
         }
         Err(err) => {
             let rollback_errors = rollback_conversion(&tx);
@@ -372,8 +2772,8 @@ struct InitConversionTx {
 
 fn preflight_worktree_targets(repo_root: &Path, missing_branches: &[String]) -> Result<()> {
     for branch in missing_branches {
-        let target = repo_root.join(branch);
-        if target.exists() {
+        let target = services::worktree_path_for_branch(repo_root, branch);
+        if !git_ops::is_path_available(&target) {
             return Err(anyhow!(
                 "gw init: cannot create worktree for {branch}; target path already exists: {}",
                 target.display()
@@ -409,22 +2809,53 @@ fn create_backup_dir(repo_root: &Path) -> Result<PathBuf> {
     ))
 }
 
+/// Moves everything under `repo_root` except `keep_entries` into `backup_dir`.
+/// Directories that contain a preserved path (e.g. `feat` for a `feat/login`
+/// worktree) are descended into rather than staged wholesale.
 fn stage_repo_root(
     repo_root: &Path,
-    keep_entries: &HashSet<String>,
+    keep_entries: &HashSet<PathBuf>,
     backup_dir: &Path,
 ) -> Result<Vec<StagedEntry>> {
     let mut staged = Vec::new();
-    for entry in fs::read_dir(repo_root)
-        .with_context(|| format!("gw init: failed to list {}", repo_root.display()))?
+    stage_dir(
+        repo_root,
+        Path::new(""),
+        keep_entries,
+        backup_dir,
+        &mut staged,
+    )?;
+    Ok(staged)
+}
+
+fn stage_dir(
+    repo_root: &Path,
+    rel_dir: &Path,
+    keep_entries: &HashSet<PathBuf>,
+    backup_dir: &Path,
+    staged: &mut Vec<StagedEntry>,
+) -> Result<()> {
+    let dir = repo_root.join(rel_dir);
+    for entry in
+        fs::read_dir(&dir).with_context(|| format!("gw init: failed to list {}", dir.display()))?
     {
         let entry = entry?;
-        let name = entry.file_name().to_string_lossy().to_string();
-        if keep_entries.contains(&name) {
+        let rel = rel_dir.join(entry.file_name());
+        if keep_entries.contains(&rel) {
             continue;
         }
+
+        let contains_kept = keep_entries.iter().any(|keep| keep.starts_with(&rel));
+        if contains_kept && entry.file_type()?.is_dir() {
+            stage_dir(repo_root, &rel, keep_entries, backup_dir, staged)?;
+            continue;
+        }
+
         let source = entry.path();
-        let backup = backup_dir.join(&name);
+        let backup = backup_dir.join(&rel);
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
         fs::rename(&source, &backup).with_context(|| {
             format!(
                 "gw init: failed to stage {} into backup {}",
@@ -437,7 +2868,29 @@ fn stage_repo_root(
             backup,
         });
     }
-    Ok(staged)
+    Ok(())
+}
+
+/// Moves staged `paths` from `backup_dir` into `target`, never over an
+/// existing file. Returns a line for each path that stayed behind.
+fn relocate_from_backup(backup_dir: &Path, target: &Path, paths: &[PathBuf]) -> Vec<String> {
+    let mut unmoved = Vec::new();
+    for rel in paths {
+        let source = backup_dir.join(rel);
+        let dest = target.join(rel);
+        let moved = if dest.symlink_metadata().is_ok() {
+            Err(anyhow!("{} already exists", dest.display()))
+        } else {
+            dest.parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::rename(&source, &dest))
+                .map_err(anyhow::Error::from)
+        };
+        if let Err(err) = moved {
+            unmoved.push(format!("- {}: {err}", rel.display()));
+        }
+    }
+    unmoved
 }
 
 fn postcheck_worktrees(repo_root: &Path, missing_branches: &[String]) -> Result<()> {
@@ -456,7 +2909,7 @@ fn rollback_conversion(tx: &InitConversionTx) -> Vec<String> {
     let mut errors = Vec::new();
 
     for path in tx.created_worktrees.iter().rev() {
-        if let Err(err) = git_ops::worktree_remove(&tx.repo_root, path) {
+        if let Err(err) = services::remove_worktree(&tx.repo_root, path) {
             errors.push(format!(
                 "- failed to remove created worktree {}: {err}",
                 path.display()
@@ -491,11 +2944,15 @@ fn rollback_conversion(tx: &InitConversionTx) -> Vec<String> {
 }
 
 fn shell_init() -> Result<()> {
+    // Output that isn't a directory (`gw list`, `gw status`, ...) is passed
+    // through.
     let bash_zsh = r#"gw() {
   local dest
   dest="$(command gw "$@" </dev/tty)" || return $?
-  if [ -n "$dest" ]; then
+  if [ -d "$dest" ]; then
     cd "$dest" || return $?
+  elif [ -n "$dest" ]; then
+    printf '%s\n' "$dest"
   fi
 }
 "#;
@@ -506,34 +2963,114 @@ fn shell_init() -> Result<()> {
   if test $gw_status -ne 0
     return $gw_status
   end
-  if test -n "$dest"
+  if test -d "$dest"
     cd "$dest"
+  else if test -n "$dest"
+    printf '%s\n' "$dest"
   end
 end
 "#;
 
-    println!("# bash/zsh\n{bash_zsh}\n# fish\n{fish}");
+    // `gw list` and `gw status` become tables, e.g. `gw list | where dirty`.
+    let nushell = r#"export def --env --wrapped gw [...args: string] {
+  let subcommand = ($args | get 0? | default "")
+  let formatted = ($args | any {|arg| $arg in ["--format" "--fzf"] or ($arg | str starts-with "--format=") })
+  if $subcommand in ["list" "status"] and not $formatted {
+    return (^gw ...$args --format json | from json)
+  }
+  let dest = (^gw ...$args | str trim)
+  if ($dest | is-not-empty) and (($dest | path type) == "dir") {
+    cd $dest
+  } else if ($dest | is-not-empty) {
+    $dest
+  }
+}
+"#;
+
+    // Calls gw.exe explicitly so the function doesn't call itself.
+    let powershell = r#"function gw {
+  $exe = Get-Command gw -CommandType Application | Select-Object -First 1
+  $dest = (& $exe @args | Out-String).Trim()
+  if ($LASTEXITCODE -ne 0) { return }
+  if ($dest -and (Test-Path -LiteralPath $dest -PathType Container)) {
+    Set-Location -LiteralPath $dest
+  } elseif ($dest) {
+    $dest
+  }
+}
+"#;
+
+    // cmd has no functions; a doskey macro runs gw.exe and checks each
+    // output line.
+    let cmd = r#"doskey gw=for /f "usebackq delims=" %i in (`gw.exe $*`) do @if exist "%i\*" (cd /d "%i") else (echo(%i)
+"#;
+
+    println!(
+        "# bash/zsh\n{bash_zsh}\n# fish\n{fish}\n# nushell (save as a module, e.g. gw.nu, and `use gw.nu *`)\n{nushell}\n# PowerShell (add to $PROFILE)\n{powershell}\n# cmd (run per session, or set it as the AutoRun value of HKCU\\Software\\Microsoft\\Command Processor)\n{cmd}"
+    );
+    Ok(())
+}
+
+fn gen_docs(args: &GenDocsArgs) -> Result<()> {
+    let man_dir = args.out_dir.join("man");
+    fs::create_dir_all(&man_dir)
+        .with_context(|| format!("gw gen-docs: cannot create {}", man_dir.display()))?;
+    // One page per visible subcommand, named `gw-<subcommand>.1`.
+    clap_mangen::generate_to(Cli::command(), &man_dir)
+        .with_context(|| format!("gw gen-docs: cannot write to {}", man_dir.display()))?;
+
+    let reference = args.out_dir.join("cli.md");
+    fs::write(&reference, clap_markdown::help_markdown::<Cli>())
+        .with_context(|| format!("gw gen-docs: cannot write {}", reference.display()))?;
+    println!(
+        "gw gen-docs: wrote {} and {}",
+        man_dir.display(),
+        reference.display()
+    );
     Ok(())
 }
 
-fn add_hook(command: &str) -> Result<()> {
-    let repo_root =
-        git_ops::get_repo_root().context("gw hooks add: not inside a git repository")?;
-    hooks::add_post_worktree_creation_hook(&repo_root, command)?;
+fn finish_checkout(args: &FinishCheckoutArgs) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw finish-checkout",
+    })?;
+    services::finish_background_checkout(&repo_root, &args.path)
+}
+
+fn add_hook(command: &str, post_init: bool) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw hooks add",
+    })?;
+    if post_init {
+        hooks::add_post_init_hook(&repo_root, command)?;
+    } else {
+        hooks::add_post_worktree_creation_hook(&repo_root, command)?;
+    }
     println!("gw hooks add: hook added");
     Ok(())
 }
 
+fn add_builtin_hook(hook: PostCreationHook, name: &str) -> Result<()> {
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw hooks add",
+    })?;
+    if hooks::add_builtin_hook(&repo_root, &hook)? {
+        println!("gw hooks add: {name} hook added");
+    } else {
+        println!("gw hooks add: {name} hook already configured");
+    }
+    Ok(())
+}
+
 fn rerun_hooks() -> Result<()> {
-    let repo_root =
-        git_ops::get_repo_root().context("gw hooks rerun: not inside a git repository")?;
+    let repo_root = git_ops::get_repo_root().context(GwError::NotARepo {
+        command: "gw hooks rerun",
+    })?;
 
     let cwd = std::env::current_dir()?;
     let worktree_root_raw = git_ops::run(&["rev-parse", "--show-toplevel"], Some(&cwd))
         .context("gw hooks rerun: not inside a git worktree")?;
-    let worktree_root = PathBuf::from(worktree_root_raw)
-        .canonicalize()
-        .unwrap_or_else(|_| PathBuf::from("."));
+    let worktree_root = git_ops::canonicalize(Path::new(&worktree_root_raw));
 
     hooks::run_post_worktree_creation_hooks(&repo_root, Some(&worktree_root))?;
     println!(
@@ -543,6 +3080,15 @@ fn rerun_hooks() -> Result<()> {
     Ok(())
 }
 
+fn prompt_line(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+
+    let mut buf = String::new();
+    io::stdin().read_line(&mut buf)?;
+    Ok(buf)
+}
+
 fn confirm(prompt: &str) -> Result<bool> {
     eprint!("{prompt} [y/N]: ");
     io::stderr().flush()?;
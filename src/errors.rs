@@ -0,0 +1,198 @@
+use crate::models::InProgressOp;
+use std::fmt;
+
+/// Failures with a known cause and remedy. They travel inside
+/// `anyhow::Error` like any other error; `main` maps them to exit codes and
+/// the TUI to a suggested next step.
+#[derive(Debug)]
+pub enum GwError {
+    /// `command` was run outside a git repository.
+    NotARepo {
+        command: &'static str,
+    },
+    /// An operation needs a clean worktree, e.g. a pull that would overwrite
+    /// local changes.
+    DirtyWorktree {
+        detail: String,
+    },
+    BranchExists {
+        branch: String,
+    },
+    /// A remote could not be reached or refused access.
+    RemoteUnavailable {
+        detail: String,
+    },
+    /// The forge CLI is missing or not logged in.
+    GhUnavailable {
+        detail: String,
+    },
+    HookFailed {
+        command: String,
+        detail: String,
+    },
+    /// The remote wants a password, token or SSH passphrase that gw can't
+    /// ask for.
+    CredentialsRequired {
+        detail: String,
+    },
+    /// A pull found local and upstream commits and no way to reconcile them.
+    Diverged {
+        detail: String,
+    },
+    /// Another process holds the repo lock.
+    RepoLocked {
+        pid: Option<u32>,
+    },
+    /// A rebase, merge, cherry-pick or bisect is unfinished in the worktree.
+    OperationInProgress {
+        branch: String,
+        operation: InProgressOp,
+    },
+    /// The remote refused a push; `detail` is the line of git's output that
+    /// says why.
+    PushRejected {
+        reason: PushRejection,
+        detail: String,
+    },
+    /// A git command ran past its time limit and was killed.
+    TimedOut {
+        detail: String,
+    },
+    /// The global `commandPolicy` setting doesn't let gw run a hook or
+    /// preset command.
+    CommandDenied {
+        command: String,
+    },
+}
+
+/// Why a push was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushRejection {
+    /// Branch protection on the forge forbids pushing to the branch.
+    Protected,
+    /// The upstream has commits the local branch lacks.
+    NeedsPull,
+    /// The local branch rewrote commits that were already pushed, e.g. with
+    /// a rebase or an amend.
+    NeedsForce,
+}
+
+impl GwError {
+    /// Process exit code; 1 stays the code for all other errors and 2 is
+    /// clap's code for usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GwError::NotARepo { .. } => 3,
+            GwError::DirtyWorktree { .. } => 4,
+            GwError::BranchExists { .. } => 5,
+            GwError::RemoteUnavailable { .. } => 6,
+            GwError::GhUnavailable { .. } => 7,
+            GwError::HookFailed { .. } => 8,
+            GwError::RepoLocked { .. } => 9,
+            GwError::CredentialsRequired { .. } => 10,
+            GwError::Diverged { .. } => 11,
+            GwError::OperationInProgress { .. } => 12,
+            GwError::PushRejected { .. } => 13,
+            GwError::TimedOut { .. } => 14,
+            GwError::CommandDenied { .. } => 15,
+        }
+    }
+
+    /// What the user can do about it, phrased for the TUI status line.
+    pub fn suggestion(&self) -> &'static str {
+        match self {
+            GwError::NotARepo { .. } => "run gw from inside a git repository",
+            GwError::DirtyWorktree { .. } => "commit or stash the changes, then retry",
+            GwError::BranchExists { .. } => "pick another name or open the existing worktree",
+            GwError::RemoteUnavailable { .. } => {
+                "check the network and `git remote -v`, then press r"
+            }
+            GwError::GhUnavailable { .. } => "install gh and run `gh auth login`",
+            GwError::HookFailed { .. } => {
+                "fix the hook in .gw/settings.json, then run `gw hooks rerun` in the worktree"
+            }
+            GwError::RepoLocked { .. } => "wait for the other gw to finish, then retry",
+            GwError::Diverged { .. } => {
+                "pull with `gw pull --rebase` or `--merge`, or set \"pullStrategy\" in .gw/settings.json"
+            }
+            GwError::CredentialsRequired { .. } => {
+                "credentials required: run the git command in the worktree yourself, or set up ssh-agent or a credential helper"
+            }
+            GwError::OperationInProgress { .. } => {
+                "finish or abort it in the worktree, then retry"
+            }
+            GwError::PushRejected { reason, .. } => match reason {
+                PushRejection::Protected => {
+                    "push the commits to a new branch and open a PR for them instead"
+                }
+                PushRejection::NeedsPull => "press p to pull, then push again",
+                PushRejection::NeedsForce => "press u, then f to force-push with lease",
+            },
+            GwError::TimedOut { .. } => {
+                "check the network and `git remote -v`, or raise gitTimeoutSeconds in .gw/settings.json"
+            }
+            GwError::CommandDenied { .. } => {
+                "allow it under commandPolicy in ~/.config/gw/settings.json, or drop it from the repo's settings"
+            }
+        }
+    }
+}
+
+impl fmt::Display for GwError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GwError::NotARepo { command } => write!(f, "{command}: not inside a git repository"),
+            GwError::DirtyWorktree { detail } => write!(f, "{detail}"),
+            GwError::BranchExists { branch } => {
+                write!(f, "branch `{branch}` already exists locally")
+            }
+            GwError::RemoteUnavailable { detail } => write!(f, "{detail}"),
+            GwError::TimedOut { detail } => write!(f, "{detail}"),
+            GwError::CommandDenied { command } => {
+                write!(f, "`{command}` is not allowed by the commandPolicy setting")
+            }
+            GwError::CredentialsRequired { detail } => write!(f, "{detail}"),
+            GwError::Diverged { detail } => write!(f, "{detail}"),
+            GwError::GhUnavailable { detail } => write!(f, "{detail}"),
+            GwError::HookFailed { command, detail } => {
+                write!(f, "hook failed: `{command}`: {detail}")
+            }
+            GwError::RepoLocked { pid: Some(pid) } => {
+                write!(
+                    f,
+                    "another gw instance (pid {pid}) is operating on this repository"
+                )
+            }
+            GwError::RepoLocked { pid: None } => {
+                write!(f, "another gw instance is operating on this repository")
+            }
+            GwError::OperationInProgress { branch, operation } => write!(
+                f,
+                "{branch} has a {} in progress ({})",
+                operation.name(),
+                operation.way_out()
+            ),
+            GwError::PushRejected { reason, detail } => match reason {
+                PushRejection::Protected => {
+                    write!(f, "push rejected, the branch is protected: {detail}")
+                }
+                PushRejection::NeedsPull => write!(
+                    f,
+                    "push rejected, the upstream has commits that are not in the branch: {detail}"
+                ),
+                PushRejection::NeedsForce => write!(
+                    f,
+                    "push rejected, the branch rewrote commits that were already pushed: {detail}"
+                ),
+            },
+        }
+    }
+}
+
+impl std::error::Error for GwError {}
+
+/// The `GwError` behind `err`, whether it was returned directly or wrapped
+/// in context.
+pub fn classify(err: &anyhow::Error) -> Option<&GwError> {
+    err.downcast_ref::<GwError>()
+}
@@ -0,0 +1,641 @@
+use crate::gh_ops::GitHubClient;
+use crate::gitea_ops::GiteaClient;
+use crate::models::{BranchHead, ChecksInfo, PullRequestInfo};
+use crate::{gh_ops, git_ops, glab_ops, logging, settings};
+use anyhow::anyhow;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, RwLock};
+
+/// Latest pull/merge request and its CI status, keyed by head branch.
+pub type BranchRequests = HashMap<String, (PullRequestInfo, ChecksInfo)>;
+
+/// Why a forge query returned nothing.
+#[derive(Debug, Clone)]
+pub enum ForgeError {
+    /// Credentials are missing, rejected or lack scopes; holds the warning to
+    /// show, including the command that fixes it.
+    Auth(String),
+    /// Anything else: the tool is missing, the network is down, the response
+    /// could not be parsed.
+    Failed,
+}
+
+/// A code hosting service that gw asks for pull/merge request and CI data.
+pub trait Forge: Send + Sync {
+    /// What the forge calls a pull request, e.g. "PR" or "MR".
+    fn request_label(&self) -> &'static str;
+    /// Whether the backend can be queried at all; otherwise the reason why not.
+    fn availability(&self) -> Result<(), String>;
+    fn requests_for_branches(
+        &self,
+        repo_root: &Path,
+        branches: &[String],
+    ) -> Result<BranchRequests, ForgeError>;
+    /// Title of issue `number` in the repository.
+    fn issue_title(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError>;
+    /// State of request `number`: `OPEN`, `CLOSED` or `MERGED`.
+    fn request_state(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError>;
+    /// The ref on origin holding the head commit of request `number`.
+    fn request_head_ref(&self, number: u64) -> String {
+        format!("refs/pull/{number}/head")
+    }
+    /// Problem with the installed tooling that degrades but does not prevent
+    /// forge access, e.g. an outdated CLI.
+    fn capability_warning(&self, _repo_root: &Path) -> Option<String> {
+        None
+    }
+    /// Converts request `number` to a draft, or marks it ready for review.
+    fn set_draft(&self, _repo_root: &Path, _number: i64, _draft: bool) -> anyhow::Result<()> {
+        Err(anyhow!(
+            "changing the draft state of a {} is not supported for this forge",
+            self.request_label()
+        ))
+    }
+    /// Opens a draft request from the pushed `branch` into `base` and returns
+    /// its URL.
+    fn create_draft(
+        &self,
+        _repo_root: &Path,
+        _branch: &str,
+        _base: &str,
+        _title: &str,
+    ) -> anyhow::Result<String> {
+        Err(anyhow!(
+            "opening a {} is not supported for this forge",
+            self.request_label()
+        ))
+    }
+}
+
+/// GitHub through its GraphQL API directly when a token is available, falling
+/// back to the `gh` CLI when there is none or a native request fails.
+pub struct GitHub {
+    pub native: Option<GitHubClient>,
+    /// GitHub Enterprise host to send gh to; `None` leaves it to gh.
+    pub host: Option<String>,
+    /// `owner/name` of the base repository, passed to gh along with `host`.
+    pub owner_repo: Option<String>,
+}
+
+impl Forge for GitHub {
+    fn request_label(&self) -> &'static str {
+        "PR"
+    }
+
+    fn availability(&self) -> Result<(), String> {
+        if self.native.is_some() {
+            return Ok(());
+        }
+        cli_availability("gh", self.request_label())
+            .map_err(|err| format!("{err} (or set GH_TOKEN)"))
+    }
+
+    fn requests_for_branches(
+        &self,
+        repo_root: &Path,
+        branches: &[String],
+    ) -> Result<BranchRequests, ForgeError> {
+        let heads = branch_heads(repo_root, branches);
+        let native_err = match self
+            .native
+            .as_ref()
+            .map(|client| client.get_prs_for_branches(&heads))
+        {
+            Some(Ok(prs)) => return Ok(prs),
+            Some(Err(err)) => Some(err),
+            None => None,
+        };
+        // A native auth error is only worth reporting if gh has nothing
+        // more specific to say (e.g. because it is not installed).
+        gh_ops::get_prs_for_branches(
+            repo_root,
+            self.host.as_deref(),
+            self.owner_repo.as_deref(),
+            &heads,
+        )
+        .map_err(|err| match (err, native_err) {
+            (ForgeError::Failed, Some(native_err)) => native_err,
+            (err, _) => err,
+        })
+    }
+
+    fn capability_warning(&self, repo_root: &Path) -> Option<String> {
+        gh_ops::capability_warning(repo_root)
+    }
+
+    fn set_draft(&self, repo_root: &Path, number: i64, draft: bool) -> anyhow::Result<()> {
+        gh_ops::set_pr_draft(
+            repo_root,
+            self.host.as_deref(),
+            self.owner_repo.as_deref(),
+            number,
+            draft,
+        )
+    }
+
+    fn create_draft(
+        &self,
+        repo_root: &Path,
+        branch: &str,
+        base: &str,
+        title: &str,
+    ) -> anyhow::Result<String> {
+        gh_ops::create_draft_pr(
+            repo_root,
+            self.host.as_deref(),
+            self.owner_repo.as_deref(),
+            branch,
+            base,
+            title,
+        )
+    }
+
+    fn issue_title(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        let native_err = match self
+            .native
+            .as_ref()
+            .map(|client| client.get_issue_title(number))
+        {
+            Some(Ok(title)) => return Ok(title),
+            Some(Err(err)) => Some(err),
+            None => None,
+        };
+        gh_ops::get_issue_title(
+            repo_root,
+            self.host.as_deref(),
+            self.owner_repo.as_deref(),
+            number,
+        )
+        .map_err(|err| match (err, native_err) {
+            (ForgeError::Failed, Some(native_err)) => native_err,
+            (err, _) => err,
+        })
+    }
+
+    fn request_state(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        let native_err = match self
+            .native
+            .as_ref()
+            .map(|client| client.get_pr_state(number))
+        {
+            Some(Ok(state)) => return Ok(state),
+            Some(Err(err)) => Some(err),
+            None => None,
+        };
+        gh_ops::get_pr_state(
+            repo_root,
+            self.host.as_deref(),
+            self.owner_repo.as_deref(),
+            number,
+        )
+        .map_err(|err| match (err, native_err) {
+            (ForgeError::Failed, Some(native_err)) => native_err,
+            (err, _) => err,
+        })
+    }
+}
+
+/// Pairs each branch with the remote branch it is pushed as and the owner of
+/// the repository behind that remote. Branches without a push target or
+/// upstream are assumed to be pushed to `origin` under the same name.
+fn branch_heads(repo_root: &Path, branches: &[String]) -> Vec<BranchHead> {
+    let targets = git_ops::list_branch_push_targets(repo_root);
+    let mut owners: HashMap<String, Option<String>> = HashMap::new();
+    branches
+        .iter()
+        .map(|branch| {
+            let (remote, head_ref) = targets
+                .get(branch)
+                .cloned()
+                .unwrap_or_else(|| ("origin".to_string(), branch.clone()));
+            let head_owner = owners
+                .entry(remote)
+                .or_insert_with_key(|remote| {
+                    let url = git_ops::get_remote_url(repo_root, remote)?;
+                    let (_, path) = parse_remote_url(&url)?;
+                    path.split('/').next().map(str::to_string)
+                })
+                .clone();
+            BranchHead {
+                branch: branch.clone(),
+                head_ref,
+                head_owner,
+            }
+        })
+        .collect()
+}
+
+/// GitLab through the `glab` CLI. `project_path` is the project's full path
+/// (`group/subgroup/project`) taken from the remote URL.
+pub struct GitLab {
+    pub project_path: Option<String>,
+}
+
+impl Forge for GitLab {
+    fn request_label(&self) -> &'static str {
+        "MR"
+    }
+
+    fn availability(&self) -> Result<(), String> {
+        cli_availability("glab", self.request_label())?;
+        if self.project_path.is_none() {
+            return Err("cannot determine the GitLab project from the origin remote".to_string());
+        }
+        Ok(())
+    }
+
+    fn requests_for_branches(
+        &self,
+        repo_root: &Path,
+        branches: &[String],
+    ) -> Result<BranchRequests, ForgeError> {
+        let project_path = self.project_path.as_deref().ok_or(ForgeError::Failed)?;
+        glab_ops::get_mrs_for_branches(repo_root, project_path, branches).ok_or(ForgeError::Failed)
+    }
+
+    fn issue_title(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        let project_path = self.project_path.as_deref().ok_or(ForgeError::Failed)?;
+        glab_ops::get_issue_title(repo_root, project_path, number).ok_or(ForgeError::Failed)
+    }
+
+    fn request_state(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        let project_path = self.project_path.as_deref().ok_or(ForgeError::Failed)?;
+        glab_ops::get_mr_state(repo_root, project_path, number).ok_or(ForgeError::Failed)
+    }
+
+    fn request_head_ref(&self, number: u64) -> String {
+        format!("refs/merge-requests/{number}/head")
+    }
+
+    fn set_draft(&self, repo_root: &Path, number: i64, draft: bool) -> anyhow::Result<()> {
+        glab_ops::set_mr_draft(repo_root, number, draft)
+    }
+
+    fn create_draft(
+        &self,
+        repo_root: &Path,
+        branch: &str,
+        base: &str,
+        title: &str,
+    ) -> anyhow::Result<String> {
+        glab_ops::create_draft_mr(repo_root, branch, base, title)
+    }
+}
+
+/// Gitea or Forgejo through the REST API. `client` holds the reason instead
+/// when the repository or the API token could not be determined.
+pub struct Gitea {
+    pub client: Result<GiteaClient, String>,
+}
+
+impl Forge for Gitea {
+    fn request_label(&self) -> &'static str {
+        "PR"
+    }
+
+    fn availability(&self) -> Result<(), String> {
+        self.client.as_ref().map(|_| ()).map_err(String::clone)
+    }
+
+    fn requests_for_branches(
+        &self,
+        _repo_root: &Path,
+        branches: &[String],
+    ) -> Result<BranchRequests, ForgeError> {
+        self.client
+            .as_ref()
+            .ok()
+            .and_then(|client| client.get_prs_for_branches(branches))
+            .ok_or(ForgeError::Failed)
+    }
+
+    fn issue_title(&self, _repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        self.client
+            .as_ref()
+            .ok()
+            .and_then(|client| client.get_issue_title(number))
+            .ok_or(ForgeError::Failed)
+    }
+
+    fn request_state(&self, _repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        self.client
+            .as_ref()
+            .ok()
+            .and_then(|client| client.get_pr_state(number))
+            .ok_or(ForgeError::Failed)
+    }
+}
+
+/// Makes the forge for a repo root in place of the detected one.
+pub type ForgeFactory = Arc<dyn Fn(&Path) -> Box<dyn Forge> + Send + Sync>;
+
+static FORGE_FACTORY: RwLock<Option<ForgeFactory>> = RwLock::new(None);
+
+/// Makes [`detect_forge`] hand out the forges `factory` makes, e.g. fakes
+/// for a headless harness, or detect them again with `None`.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn set_forge_factory(factory: Option<ForgeFactory>) {
+    *FORGE_FACTORY.write().expect("forge lock poisoned") = factory;
+}
+
+/// Picks the forge from the `forge` setting, or from the origin remote's host
+/// (anything with "gitlab" in it is GitLab; "gitea", "forgejo" or "codeberg"
+/// is Gitea). Defaults to GitHub. A factory installed with
+/// [`set_forge_factory`] takes precedence.
+pub fn detect_forge(repo_root: &Path) -> Box<dyn Forge> {
+    let factory = FORGE_FACTORY.read().expect("forge lock poisoned").clone();
+    let forge = match factory {
+        Some(make) => make(repo_root),
+        None => detect_backend(repo_root),
+    };
+    if git_ops::dry_run() {
+        Box::new(DryRun(forge))
+    } else {
+        forge
+    }
+}
+
+fn detect_backend(repo_root: &Path) -> Box<dyn Forge> {
+    let remote =
+        git_ops::get_remote_url(repo_root, "origin").and_then(|url| parse_remote_url(&url));
+    let settings = settings::load_settings(repo_root).unwrap_or(Value::Null);
+    let setting = |key: &str| {
+        settings
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    let kind = match setting("forge") {
+        Some(kind) => kind.to_ascii_lowercase(),
+        None => match &remote {
+            Some((host, _)) => {
+                let host = host.to_ascii_lowercase();
+                if host.contains("gitlab") {
+                    "gitlab".to_string()
+                } else if ["gitea", "forgejo", "codeberg"]
+                    .iter()
+                    .any(|name| host.contains(name))
+                {
+                    "gitea".to_string()
+                } else {
+                    "github".to_string()
+                }
+            }
+            None => "github".to_string(),
+        },
+    };
+
+    match kind.as_str() {
+        "gitlab" => Box::new(GitLab {
+            project_path: remote.map(|(_, path)| path),
+        }),
+        "gitea" | "forgejo" => {
+            let token = setting("giteaToken")
+                .or_else(|| std::env::var("GITEA_TOKEN").ok())
+                .filter(|token| !token.trim().is_empty());
+            Box::new(Gitea {
+                client: gitea_client(remote, setting("giteaUrl"), token),
+            })
+        }
+        _ => {
+            // `"githubClient": "gh"` keeps every request going through the CLI.
+            // PRs live in the base repository, which is `upstream` in fork
+            // setups where `origin` is the fork.
+            let base = git_ops::get_remote_url(repo_root, "upstream")
+                .and_then(|url| parse_remote_url(&url))
+                .or(remote);
+            let (remote_host, owner_repo) = base.unzip();
+            // `githubHost` overrides the remote's host, which may be an SSH
+            // alias. github.com and aliases are otherwise left to gh.
+            let host = setting("githubHost").or_else(|| {
+                remote_host.filter(|host| {
+                    host.contains('.') && !host.eq_ignore_ascii_case(gh_ops::GITHUB_COM)
+                })
+            });
+            let native = if setting("githubClient").as_deref() == Some("gh") {
+                None
+            } else {
+                owner_repo.as_deref().and_then(|owner_repo| {
+                    let api_host = host.as_deref().unwrap_or(gh_ops::GITHUB_COM);
+                    let token = gh_ops::resolve_token(repo_root, api_host)?;
+                    GitHubClient::new(api_host, owner_repo, &token)
+                })
+            };
+            Box::new(GitHub {
+                native,
+                owner_repo: owner_repo.filter(|_| host.is_some()),
+                host,
+            })
+        }
+    }
+}
+
+/// A forge during a dry run: queries go through, changes are reported with
+/// [`logging::would_run`] instead of made.
+struct DryRun(Box<dyn Forge>);
+
+impl Forge for DryRun {
+    fn request_label(&self) -> &'static str {
+        self.0.request_label()
+    }
+
+    fn availability(&self) -> Result<(), String> {
+        self.0.availability()
+    }
+
+    fn requests_for_branches(
+        &self,
+        repo_root: &Path,
+        branches: &[String],
+    ) -> Result<BranchRequests, ForgeError> {
+        self.0.requests_for_branches(repo_root, branches)
+    }
+
+    fn issue_title(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        self.0.issue_title(repo_root, number)
+    }
+
+    fn request_state(&self, repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+        self.0.request_state(repo_root, number)
+    }
+
+    fn request_head_ref(&self, number: u64) -> String {
+        self.0.request_head_ref(number)
+    }
+
+    fn capability_warning(&self, repo_root: &Path) -> Option<String> {
+        self.0.capability_warning(repo_root)
+    }
+
+    fn set_draft(&self, _repo_root: &Path, number: i64, draft: bool) -> anyhow::Result<()> {
+        let state = if draft { "a draft" } else { "ready for review" };
+        logging::would_run(&format!(
+            "mark {} #{number} as {state}",
+            self.request_label()
+        ));
+        Ok(())
+    }
+
+    fn create_draft(
+        &self,
+        _repo_root: &Path,
+        branch: &str,
+        base: &str,
+        title: &str,
+    ) -> anyhow::Result<String> {
+        logging::would_run(&format!(
+            "open a draft {} from {branch} into {base} titled \"{title}\"",
+            self.request_label()
+        ));
+        Ok(format!("(no {} opened in a dry run)", self.request_label()))
+    }
+}
+
+/// The API is served from `giteaUrl` if set, otherwise from `https://<host>`
+/// of the origin remote (which is wrong for SSH remotes on another port or
+/// host name, hence the setting).
+fn gitea_client(
+    remote: Option<(String, String)>,
+    base_url: Option<String>,
+    token: Option<String>,
+) -> Result<GiteaClient, String> {
+    let (host, owner_repo) =
+        remote.ok_or("cannot determine the Gitea repository from the origin remote")?;
+    let token = token.ok_or(
+        "no Gitea token: set GITEA_TOKEN or \"giteaToken\" in .gw/settings.json for PR data",
+    )?;
+    let base_url = base_url.unwrap_or_else(|| format!("https://{host}"));
+    Ok(GiteaClient::new(&base_url, &owner_repo, &token))
+}
+
+/// Splits a remote URL into `(host, repo path)`, handling
+/// `https://host/group/repo.git`, `ssh://git@host:22/group/repo.git` and
+/// scp-like `git@host:group/repo.git`.
+pub fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let (host, path) = if let Some((_, rest)) = url.split_once("://") {
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next()?;
+        let host = host.split(':').next()?;
+        (host, path)
+    } else {
+        let (authority, path) = url.split_once(':')?;
+        let host = authority.rsplit('@').next()?;
+        (host, path)
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), path.to_string()))
+}
+
+fn cli_availability(cmd: &str, label: &str) -> Result<(), String> {
+    let installed = logging::output(Command::new(cmd).arg("--version"))
+        .is_ok_and(|output| output.status.success());
+    if installed {
+        Ok(())
+    } else {
+        Err(format!(
+            "{cmd} not found: install/configure {cmd} for {label} data"
+        ))
+    }
+}
+
+/// Unix timestamp of an RFC 3339 date as the forge APIs return them, e.g.
+/// `2024-05-01T12:30:00Z` or `2024-05-01T14:30:00.123+02:00`.
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+    let (date, time) = value.split_once('T')?;
+    let mut date_parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+
+    let offset_at = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+    let (clock, zone) = time.split_at(offset_at);
+    let clock = clock.split('.').next()?;
+    let mut clock_parts = clock.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (
+        clock_parts.next()??,
+        clock_parts.next()??,
+        clock_parts.next()??,
+    );
+
+    let offset = match zone.chars().next() {
+        None | Some('Z') | Some('z') => 0,
+        Some(sign) => {
+            let (hours, minutes) = zone[1..].split_once(':')?;
+            let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+            if sign == '-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+    };
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar.
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake;
+
+    impl Forge for Fake {
+        fn request_label(&self) -> &'static str {
+            "FAKE"
+        }
+
+        fn availability(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn requests_for_branches(
+            &self,
+            _repo_root: &Path,
+            _branches: &[String],
+        ) -> Result<BranchRequests, ForgeError> {
+            Ok(BranchRequests::default())
+        }
+
+        fn issue_title(&self, _repo_root: &Path, number: u64) -> Result<String, ForgeError> {
+            Ok(format!("Issue {number}"))
+        }
+
+        fn request_state(&self, _repo_root: &Path, _number: u64) -> Result<String, ForgeError> {
+            Ok("OPEN".to_string())
+        }
+    }
+
+    #[test]
+    fn detect_forge_hands_out_the_installed_factory() {
+        let _fakes = git_ops::lock_fakes();
+        set_forge_factory(Some(Arc::new(|_: &Path| Box::new(Fake) as Box<dyn Forge>)));
+        let forge = detect_forge(Path::new("/nonexistent"));
+
+        assert_eq!(forge.request_label(), "FAKE");
+        assert_eq!(forge.issue_title(Path::new("/"), 4).unwrap(), "Issue 4");
+    }
+}
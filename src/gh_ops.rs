@@ -1,105 +1,804 @@
-use crate::models::{ChecksInfo, PullRequestInfo};
+use crate::cache_db::CacheDB;
+use crate::errors::GwError;
+use crate::forge::{parse_timestamp, ForgeError};
+use crate::logging;
+use crate::models::{BranchHead, ChecksInfo, PullRequestInfo};
 use serde::Deserialize;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Branches per GraphQL request; each adds an aliased `pullRequests` field.
+const GRAPHQL_BATCH_SIZE: usize = 50;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// PRs fetched per head branch name, so one from the right fork can be picked
+/// when several repositories have a branch of that name.
+const PRS_PER_HEAD: usize = 5;
+
+const PR_FIELDS: &str = "number state isDraft baseRefName url mergedAt reviewDecision \
+    title author { login } createdAt reviewThreads(first: 100) { nodes { isResolved } } \
+    headRepositoryOwner { login } \
+    commits(last: 1) { nodes { commit { statusCheckRollup { contexts(first: 100) { nodes { \
+    __typename ... on CheckRun { status conclusion } ... on StatusContext { state } } } } } } }";
+
+#[derive(Debug, Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
 
 #[derive(Debug, Deserialize)]
-struct PrListItem {
+#[serde(rename_all = "camelCase")]
+struct PrNode {
     number: i64,
     state: Option<String>,
-    #[serde(rename = "baseRefName")]
+    #[serde(default)]
+    is_draft: bool,
     base_ref_name: Option<String>,
-    #[serde(rename = "mergedAt")]
-    merged_at: Option<String>,
     url: Option<String>,
+    merged_at: Option<String>,
+    review_decision: Option<String>,
+    title: Option<String>,
+    author: Option<Owner>,
+    created_at: Option<String>,
+    review_threads: Option<Nodes<ReviewThread>>,
+    head_repository_owner: Option<Owner>,
+    commits: Option<Nodes<CommitNode>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewThread {
+    is_resolved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Owner {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitNode {
+    commit: CommitRollup,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitRollup {
+    status_check_rollup: Option<Rollup>,
 }
 
 #[derive(Debug, Deserialize)]
-struct ChecksView {
-    #[serde(rename = "statusCheckRollup")]
-    status_check_rollup: Option<Vec<Value>>,
+struct Rollup {
+    contexts: Nodes<Value>,
 }
 
-fn run_gh(args: &[&str], repo_root: &Path) -> Option<String> {
-    let output = Command::new("gh")
-        .args(args)
-        .current_dir(repo_root)
-        .output()
-        .ok()?;
+pub const GITHUB_COM: &str = "github.com";
+
+/// gh exits with this code when it has no credentials at all.
+const GH_EXIT_AUTH_REQUIRED: i32 = 4;
+
+const NOT_AUTHENTICATED: &str = "gh is not authenticated: run `gh auth login` for PR data";
+const MISSING_SCOPES: &str =
+    "GitHub token lacks required scopes: run `gh auth refresh -s repo,read:org` for PR data";
+
+/// Cache meta key holding `<gh version> <capability names>` from the last probe.
+const GH_CAPABILITIES_KEY: &str = "gh_capabilities";
+
+/// Optional gh flags gw uses that older gh releases lack.
+#[derive(Debug, Clone, Copy)]
+pub struct GhCapabilities {
+    /// `--json`/`--jq` output on `gh issue view`.
+    pub json_output: bool,
+    /// `gh pr ready --undo`, to convert a PR back to a draft.
+    pub pr_ready_undo: bool,
+}
+
+impl GhCapabilities {
+    fn probe() -> Self {
+        Self {
+            json_output: help_mentions(&["issue", "view"], "--json"),
+            pr_ready_undo: help_mentions(&["pr", "ready"], "--undo"),
+        }
+    }
+
+    fn encode(&self) -> String {
+        let mut names = Vec::new();
+        if self.json_output {
+            names.push("json");
+        }
+        if self.pr_ready_undo {
+            names.push("undo");
+        }
+        names.join(",")
+    }
+
+    fn decode(names: &str) -> Self {
+        let names: Vec<&str> = names.split(',').collect();
+        Self {
+            json_output: names.contains(&"json"),
+            pr_ready_undo: names.contains(&"undo"),
+        }
+    }
+
+    fn missing(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if !self.json_output {
+            missing.push("`--json` output");
+        }
+        if !self.pr_ready_undo {
+            missing.push("`gh pr ready --undo`");
+        }
+        missing
+    }
+}
+
+/// Whether `gh <command> --help` documents `flag`.
+fn help_mentions(command: &[&str], flag: &str) -> bool {
+    logging::output(Command::new("gh").args(command).arg("--help"))
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(flag))
+}
+
+/// Version of the installed gh, e.g. `2.40.1`.
+fn gh_version() -> Option<String> {
+    let output = logging::output(Command::new("gh").arg("--version")).ok()?;
     if !output.status.success() {
         return None;
     }
-    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
-}
-
-pub fn get_pr_info(repo_root: &Path, branch: &str) -> Option<PullRequestInfo> {
-    let stdout = run_gh(
-        &[
-            "pr",
-            "list",
-            "--state",
-            "all",
-            "--head",
-            branch,
-            "--json",
-            "number,state,baseRefName,mergedAt,url",
-            "--limit",
-            "1",
-        ],
-        repo_root,
-    )?;
-
-    let list: Vec<PrListItem> = serde_json::from_str(&stdout).ok()?;
-    let first = list.into_iter().next()?;
-
-    let state = if first.merged_at.is_some() {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // "gh version 2.40.1 (2023-12-13)"
+    stdout
+        .lines()
+        .next()?
+        .split_whitespace()
+        .nth(2)
+        .map(str::to_string)
+}
+
+/// The gh version and its capabilities, probed once per process. The probe
+/// result is cached per gh version, so the `--help` calls only run again
+/// after gh is upgraded. `None` if gh is not installed.
+fn capabilities(repo_root: &Path) -> Option<(String, GhCapabilities)> {
+    static CAPABILITIES: OnceLock<Option<(String, GhCapabilities)>> = OnceLock::new();
+    CAPABILITIES
+        .get_or_init(|| {
+            let version = gh_version()?;
+            let db = CacheDB::open(repo_root).ok();
+            let cached = db
+                .as_ref()
+                .and_then(|db| db.get_meta(GH_CAPABILITIES_KEY).ok().flatten());
+            if let Some((cached_version, names)) =
+                cached.as_deref().and_then(|value| value.split_once(' '))
+            {
+                if cached_version == version {
+                    return Some((version, GhCapabilities::decode(names)));
+                }
+            }
+
+            let probed = GhCapabilities::probe();
+            if let Some(db) = db {
+                let value = format!("{version} {}", probed.encode());
+                let _ = db.set_meta(GH_CAPABILITIES_KEY, Some(&value));
+            }
+            Some((version, probed))
+        })
+        .clone()
+}
+
+/// Capabilities to go by; assumes everything is supported when gh's version
+/// cannot be determined, so the real error surfaces from the command itself.
+fn capabilities_or_all(repo_root: &Path) -> GhCapabilities {
+    capabilities(repo_root)
+        .map(|(_, capabilities)| capabilities)
+        .unwrap_or(GhCapabilities {
+            json_output: true,
+            pr_ready_undo: true,
+        })
+}
+
+/// Warning naming the flags the installed gh lacks, if any.
+pub fn capability_warning(repo_root: &Path) -> Option<String> {
+    let (version, capabilities) = capabilities(repo_root)?;
+    let missing = capabilities.missing();
+    if missing.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "gh {version} lacks {}; using slower fallbacks (update gh)",
+        missing.join(" and ")
+    ))
+}
+
+/// `owner=`/`repo=` GraphQL variables: explicit when `owner_repo` is known,
+/// otherwise gh's placeholders for the current repository.
+fn repo_variables(owner_repo: Option<&str>) -> (String, String) {
+    match owner_repo.and_then(|path| path.split_once('/')) {
+        Some((owner, repo)) => (format!("owner={owner}"), format!("repo={repo}")),
+        None => ("owner={owner}".to_string(), "repo={repo}".to_string()),
+    }
+}
+
+/// Runs gh against `host` (via `GH_HOST`) when one is given, otherwise
+/// against whatever host gh picks from its config and the remotes.
+fn run_gh(args: &[&str], repo_root: &Path, host: Option<&str>) -> Result<String, ForgeError> {
+    let mut command = Command::new("gh");
+    command.args(args).current_dir(repo_root);
+    if let Some(host) = host {
+        command.env("GH_HOST", host);
+    }
+    let output = logging::output(&mut command).map_err(|_| ForgeError::Failed)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(auth_problem(output.status.code(), &stderr)
+            .map(|message| ForgeError::Auth(message.to_string()))
+            .unwrap_or(ForgeError::Failed));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Remediation message if a failed gh call was an authentication problem
+/// rather than e.g. a network error.
+fn auth_problem(exit_code: Option<i32>, stderr: &str) -> Option<&'static str> {
+    let stderr = stderr.to_ascii_lowercase();
+    if stderr.contains("insufficient_scopes")
+        || stderr.contains("required scopes")
+        || stderr.contains("missing required scope")
+    {
+        return Some(MISSING_SCOPES);
+    }
+    let unauthenticated = exit_code == Some(GH_EXIT_AUTH_REQUIRED)
+        || stderr.contains("gh auth login")
+        || stderr.contains("http 401")
+        || stderr.contains("bad credentials");
+    unauthenticated.then_some(NOT_AUTHENTICATED)
+}
+
+/// Auth error for GraphQL-level errors in an otherwise successful response.
+fn graphql_auth_error(response: &Value) -> Option<ForgeError> {
+    let errors = response.get("errors")?.as_array()?;
+    errors
+        .iter()
+        .any(|error| error.get("type").and_then(Value::as_str) == Some("INSUFFICIENT_SCOPES"))
+        .then(|| ForgeError::Auth(MISSING_SCOPES.to_string()))
+}
+
+/// Latest PR (any state) from each of `heads` and its check rollup, keyed by
+/// local branch. Fetched with one `gh api graphql` call per batch of
+/// branches. On `host` (e.g. a GitHub Enterprise instance), the repository
+/// has to be given as `owner_repo`, since gh cannot match it to a remote
+/// there; without a host gh resolves both itself.
+pub fn get_prs_for_branches(
+    repo_root: &Path,
+    host: Option<&str>,
+    owner_repo: Option<&str>,
+    heads: &[BranchHead],
+) -> Result<HashMap<String, (PullRequestInfo, ChecksInfo)>, ForgeError> {
+    let (owner, repo) = repo_variables(owner_repo);
+    let mut prs = HashMap::new();
+    for chunk in heads.chunks(GRAPHQL_BATCH_SIZE) {
+        let mut args = vec![
+            "api".to_string(),
+            "graphql".to_string(),
+            "-f".to_string(),
+            format!("query={}", batch_query(chunk.len())),
+            "-F".to_string(),
+            owner.clone(),
+            "-F".to_string(),
+            repo.clone(),
+        ];
+        if let Some(host) = host {
+            args.push("--hostname".to_string());
+            args.push(host.to_string());
+        }
+        for (idx, head) in chunk.iter().enumerate() {
+            args.push("-f".to_string());
+            args.push(format!("h{idx}={}", head.head_ref));
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+        let stdout = run_gh(&args, repo_root, host)?;
+        let parsed: Value = serde_json::from_str(&stdout).map_err(|_| ForgeError::Failed)?;
+        collect_batch(chunk, &parsed, &mut prs)?;
+    }
+    Ok(prs)
+}
+
+/// Title of issue `number`, through `gh issue view`, or `gh api` for gh
+/// releases without `--json`. `host` and `owner_repo` work as in
+/// [`get_prs_for_branches`].
+pub fn get_issue_title(
+    repo_root: &Path,
+    host: Option<&str>,
+    owner_repo: Option<&str>,
+    number: u64,
+) -> Result<String, ForgeError> {
+    if !capabilities_or_all(repo_root).json_output {
+        let path = match host.zip(owner_repo) {
+            Some((_, path)) => format!("repos/{path}/issues/{number}"),
+            None => format!("repos/{{owner}}/{{repo}}/issues/{number}"),
+        };
+        let mut args = vec!["api", &path];
+        if let Some(host) = host {
+            args.push("--hostname");
+            args.push(host);
+        }
+        let issue: Value = serde_json::from_str(&run_gh(&args, repo_root, host)?)
+            .map_err(|_| ForgeError::Failed)?;
+        return issue
+            .get("title")
+            .and_then(Value::as_str)
+            .filter(|title| !title.is_empty())
+            .map(str::to_string)
+            .ok_or(ForgeError::Failed);
+    }
+
+    let number = number.to_string();
+    let mut args = vec![
+        "issue", "view", &number, "--json", "title", "--jq", ".title",
+    ];
+    let repo_arg = host
+        .zip(owner_repo)
+        .map(|(host, path)| format!("{host}/{path}"));
+    if let Some(repo_arg) = &repo_arg {
+        args.push("--repo");
+        args.push(repo_arg);
+    }
+    let title = run_gh(&args, repo_root, host)?;
+    if title.is_empty() {
+        return Err(ForgeError::Failed);
+    }
+    Ok(title)
+}
+
+/// `OPEN`, `CLOSED` or `MERGED` for PR `number`, through `gh pr view`, or
+/// `gh api` for gh releases without `--json`.
+pub fn get_pr_state(
+    repo_root: &Path,
+    host: Option<&str>,
+    owner_repo: Option<&str>,
+    number: u64,
+) -> Result<String, ForgeError> {
+    if !capabilities_or_all(repo_root).json_output {
+        let path = match host.zip(owner_repo) {
+            Some((_, path)) => format!("repos/{path}/pulls/{number}"),
+            None => format!("repos/{{owner}}/{{repo}}/pulls/{number}"),
+        };
+        let mut args = vec!["api", &path];
+        if let Some(host) = host {
+            args.push("--hostname");
+            args.push(host);
+        }
+        let pr: Value = serde_json::from_str(&run_gh(&args, repo_root, host)?)
+            .map_err(|_| ForgeError::Failed)?;
+        let merged = pr.get("merged").and_then(Value::as_bool).unwrap_or(false);
+        return match pr.get("state").and_then(Value::as_str) {
+            _ if merged => Ok("MERGED".to_string()),
+            Some("closed") => Ok("CLOSED".to_string()),
+            Some(_) => Ok("OPEN".to_string()),
+            None => Err(ForgeError::Failed),
+        };
+    }
+
+    let number = number.to_string();
+    let mut args = vec!["pr", "view", &number, "--json", "state", "--jq", ".state"];
+    let repo_arg = host
+        .zip(owner_repo)
+        .map(|(host, path)| format!("{host}/{path}"));
+    if let Some(repo_arg) = &repo_arg {
+        args.push("--repo");
+        args.push(repo_arg);
+    }
+    let state = run_gh(&args, repo_root, host)?;
+    if state.is_empty() {
+        return Err(ForgeError::Failed);
+    }
+    Ok(state)
+}
+
+/// Opens a draft PR from `branch` into `base` with `gh pr create` and returns
+/// its URL. The branch must already be pushed.
+pub fn create_draft_pr(
+    repo_root: &Path,
+    host: Option<&str>,
+    owner_repo: Option<&str>,
+    branch: &str,
+    base: &str,
+    title: &str,
+) -> anyhow::Result<String> {
+    let mut args = vec![
+        "pr", "create", "--draft", "--head", branch, "--base", base, "--title", title, "--body", "",
+    ];
+    let repo_arg = host
+        .zip(owner_repo)
+        .map(|(host, path)| format!("{host}/{path}"));
+    if let Some(repo_arg) = &repo_arg {
+        args.push("--repo");
+        args.push(repo_arg);
+    }
+
+    let mut command = Command::new("gh");
+    command.args(&args).current_dir(repo_root);
+    if let Some(host) = host {
+        command.env("GH_HOST", host);
+    }
+    let output = logging::output(&mut command).map_err(|err| GwError::GhUnavailable {
+        detail: format!("failed to run gh: {err}"),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(message) = auth_problem(output.status.code(), &stderr) {
+            return Err(GwError::GhUnavailable {
+                detail: format!("gh pr create: {message}"),
+            }
+            .into());
+        }
+        return Err(anyhow::anyhow!("gh pr create: {}", stderr.trim()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.trim().lines().last().unwrap_or_default().to_string())
+}
+
+/// Marks PR `number` ready for review (`gh pr ready`) or converts it back to
+/// a draft (`gh pr ready --undo`, or a GraphQL mutation on gh releases
+/// without `--undo`).
+pub fn set_pr_draft(
+    repo_root: &Path,
+    host: Option<&str>,
+    owner_repo: Option<&str>,
+    number: i64,
+    draft: bool,
+) -> anyhow::Result<()> {
+    if draft && !capabilities_or_all(repo_root).pr_ready_undo {
+        return convert_to_draft_via_graphql(repo_root, host, owner_repo, number).map_err(|err| {
+            match err {
+                ForgeError::Auth(message) => GwError::GhUnavailable {
+                    detail: format!("gh api graphql: {message}"),
+                }
+                .into(),
+                ForgeError::Failed => anyhow::anyhow!("gh api graphql failed"),
+            }
+        });
+    }
+
+    let number = number.to_string();
+    let mut args = vec!["pr", "ready", &number];
+    if draft {
+        args.push("--undo");
+    }
+    let repo_arg = host
+        .zip(owner_repo)
+        .map(|(host, path)| format!("{host}/{path}"));
+    if let Some(repo_arg) = &repo_arg {
+        args.push("--repo");
+        args.push(repo_arg);
+    }
+
+    let mut command = Command::new("gh");
+    command.args(&args).current_dir(repo_root);
+    if let Some(host) = host {
+        command.env("GH_HOST", host);
+    }
+    let output = logging::output(&mut command).map_err(|err| GwError::GhUnavailable {
+        detail: format!("failed to run gh: {err}"),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(message) = auth_problem(output.status.code(), &stderr) {
+            return Err(GwError::GhUnavailable {
+                detail: format!("gh pr ready: {message}"),
+            }
+            .into());
+        }
+        return Err(anyhow::anyhow!("gh pr ready: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+fn convert_to_draft_via_graphql(
+    repo_root: &Path,
+    host: Option<&str>,
+    owner_repo: Option<&str>,
+    number: i64,
+) -> Result<(), ForgeError> {
+    let (owner, repo) = repo_variables(owner_repo);
+    let number = format!("number={number}");
+    let mut args = vec![
+        "api",
+        "graphql",
+        "-f",
+        "query=query($owner: String!, $repo: String!, $number: Int!) { \
+         repository(owner: $owner, name: $repo) { pullRequest(number: $number) { id } } }",
+        "-F",
+        &owner,
+        "-F",
+        &repo,
+        "-F",
+        &number,
+    ];
+    if let Some(host) = host {
+        args.push("--hostname");
+        args.push(host);
+    }
+    let response: Value =
+        serde_json::from_str(&run_gh(&args, repo_root, host)?).map_err(|_| ForgeError::Failed)?;
+    let id = response
+        .pointer("/data/repository/pullRequest/id")
+        .and_then(Value::as_str)
+        .ok_or(ForgeError::Failed)?;
+
+    let id = format!("id={id}");
+    let mut args = vec![
+        "api",
+        "graphql",
+        "-f",
+        "query=mutation($id: ID!) { \
+         convertPullRequestToDraft(input: { pullRequestId: $id }) { clientMutationId } }",
+        "-f",
+        &id,
+    ];
+    if let Some(host) = host {
+        args.push("--hostname");
+        args.push(host);
+    }
+    run_gh(&args, repo_root, host).map(|_| ())
+}
+
+/// Token for the native client, looked up the way gh does: `GH_TOKEN` or
+/// `GITHUB_TOKEN` for github.com, `GH_ENTERPRISE_TOKEN` or
+/// `GITHUB_ENTERPRISE_TOKEN` for other hosts, then whatever `gh auth token`
+/// prints for `host` if gh is installed and logged in there.
+pub fn resolve_token(repo_root: &Path, host: &str) -> Option<String> {
+    let vars = if host.eq_ignore_ascii_case(GITHUB_COM) {
+        ["GH_TOKEN", "GITHUB_TOKEN"]
+    } else {
+        ["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"]
+    };
+    vars.iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .chain(
+            run_gh(
+                &["auth", "token", "--hostname", host],
+                repo_root,
+                Some(host),
+            )
+            .ok(),
+        )
+        .find(|token| !token.trim().is_empty())
+}
+
+/// GitHub's GraphQL API over HTTP, for when `gh` is missing or slow to spawn.
+/// The agent keeps connections alive across batches.
+pub struct GitHubClient {
+    agent: ureq::Agent,
+    endpoint: String,
+    owner: String,
+    repo: String,
+    token: String,
+}
+
+impl GitHubClient {
+    /// `owner_repo` is `owner/name` as taken from the remote URL.
+    pub fn new(host: &str, owner_repo: &str, token: &str) -> Option<Self> {
+        let (owner, repo) = owner_repo.split_once('/')?;
+        let endpoint = if host.eq_ignore_ascii_case(GITHUB_COM) {
+            "https://api.github.com/graphql".to_string()
+        } else {
+            format!("https://{host}/api/graphql")
+        };
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .build()
+            .into();
+        Some(Self {
+            agent,
+            endpoint,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            token: token.to_string(),
+        })
+    }
+
+    /// Same result as the `gh`-backed [`get_prs_for_branches`].
+    pub fn get_prs_for_branches(
+        &self,
+        heads: &[BranchHead],
+    ) -> Result<HashMap<String, (PullRequestInfo, ChecksInfo)>, ForgeError> {
+        let mut prs = HashMap::new();
+        for chunk in heads.chunks(GRAPHQL_BATCH_SIZE) {
+            let mut variables = serde_json::Map::new();
+            variables.insert("owner".to_string(), Value::from(self.owner.as_str()));
+            variables.insert("repo".to_string(), Value::from(self.repo.as_str()));
+            for (idx, head) in chunk.iter().enumerate() {
+                variables.insert(format!("h{idx}"), Value::from(head.head_ref.as_str()));
+            }
+            let parsed = self.graphql(&batch_query(chunk.len()), variables)?;
+            collect_batch(chunk, &parsed, &mut prs)?;
+        }
+        Ok(prs)
+    }
+
+    /// Same result as the `gh`-backed [`get_issue_title`].
+    pub fn get_issue_title(&self, number: u64) -> Result<String, ForgeError> {
+        let mut variables = serde_json::Map::new();
+        variables.insert("owner".to_string(), Value::from(self.owner.as_str()));
+        variables.insert("repo".to_string(), Value::from(self.repo.as_str()));
+        variables.insert("number".to_string(), Value::from(number));
+        let parsed = self.graphql(
+            "query($owner: String!, $repo: String!, $number: Int!) { \
+             repository(owner: $owner, name: $repo) { issue(number: $number) { title } } }",
+            variables,
+        )?;
+        if let Some(err) = graphql_auth_error(&parsed) {
+            return Err(err);
+        }
+        parsed
+            .pointer("/data/repository/issue/title")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or(ForgeError::Failed)
+    }
+
+    pub fn get_pr_state(&self, number: u64) -> Result<String, ForgeError> {
+        let mut variables = serde_json::Map::new();
+        variables.insert("owner".to_string(), Value::from(self.owner.as_str()));
+        variables.insert("repo".to_string(), Value::from(self.repo.as_str()));
+        variables.insert("number".to_string(), Value::from(number));
+        let parsed = self.graphql(
+            "query($owner: String!, $repo: String!, $number: Int!) { \
+             repository(owner: $owner, name: $repo) { pullRequest(number: $number) { state } } }",
+            variables,
+        )?;
+        if let Some(err) = graphql_auth_error(&parsed) {
+            return Err(err);
+        }
+        parsed
+            .pointer("/data/repository/pullRequest/state")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or(ForgeError::Failed)
+    }
+
+    fn graphql(
+        &self,
+        query: &str,
+        variables: serde_json::Map<String, Value>,
+    ) -> Result<Value, ForgeError> {
+        let body = serde_json::json!({
+            "query": query,
+            "variables": variables,
+        });
+        let mut response = self
+            .agent
+            .post(&self.endpoint)
+            .header("Authorization", format!("bearer {}", self.token))
+            .send_json(&body)
+            .map_err(|err| match err {
+                ureq::Error::StatusCode(401) => ForgeError::Auth(
+                    "GitHub rejected the API token: check GH_TOKEN/GITHUB_TOKEN or run `gh auth login`"
+                        .to_string(),
+                ),
+                _ => ForgeError::Failed,
+            })?;
+        response
+            .body_mut()
+            .read_json()
+            .map_err(|_| ForgeError::Failed)
+    }
+}
+
+/// GraphQL query with an aliased `pullRequests` field per branch, taking
+/// `$owner`, `$repo` and `$h0..$hN` (the branch names) as variables.
+fn batch_query(branch_count: usize) -> String {
+    let mut variables = String::new();
+    let mut fields = String::new();
+    for idx in 0..branch_count {
+        variables.push_str(&format!(", $h{idx}: String!"));
+        fields.push_str(&format!(
+            " b{idx}: pullRequests(headRefName: $h{idx}, first: {PRS_PER_HEAD}, states: [OPEN, CLOSED, MERGED], \
+             orderBy: {{field: CREATED_AT, direction: DESC}}) {{ nodes {{ {PR_FIELDS} }} }}"
+        ));
+    }
+    format!(
+        "query($owner: String!, $repo: String!{variables}) {{ repository(owner: $owner, name: $repo) {{{fields} }} }}"
+    )
+}
+
+/// Adds the PRs from one batch response to `prs`, taking the newest PR whose
+/// head repository belongs to the head's owner (any PR if the owner is
+/// unknown). Fails if the response has no repository data.
+fn collect_batch(
+    chunk: &[BranchHead],
+    response: &Value,
+    prs: &mut HashMap<String, (PullRequestInfo, ChecksInfo)>,
+) -> Result<(), ForgeError> {
+    if let Some(err) = graphql_auth_error(response) {
+        return Err(err);
+    }
+    let repository = response
+        .get("data")
+        .and_then(|data| data.get("repository"))
+        .filter(|repository| !repository.is_null())
+        .ok_or(ForgeError::Failed)?;
+    for (idx, head) in chunk.iter().enumerate() {
+        let Some(node) = repository.get(format!("b{idx}")).cloned() else {
+            continue;
+        };
+        let Ok(connection) = serde_json::from_value::<Nodes<PrNode>>(node) else {
+            continue;
+        };
+        let pr = connection.nodes.into_iter().find(|pr| {
+            match (&head.head_owner, &pr.head_repository_owner) {
+                (Some(owner), Some(pr_owner)) => owner.eq_ignore_ascii_case(&pr_owner.login),
+                _ => true,
+            }
+        });
+        if let Some(pr) = pr {
+            prs.insert(head.branch.clone(), pr_from_node(pr));
+        }
+    }
+    Ok(())
+}
+
+fn pr_from_node(node: PrNode) -> (PullRequestInfo, ChecksInfo) {
+    let state = if node.merged_at.is_some() {
         "MERGED".to_string()
     } else {
-        first.state.unwrap_or_else(|| "OPEN".to_string())
+        node.state.unwrap_or_else(|| "OPEN".to_string())
     };
 
-    Some(PullRequestInfo {
-        number: first.number,
+    let contexts = node
+        .commits
+        .and_then(|commits| commits.nodes.into_iter().next())
+        .and_then(|commit| commit.commit.status_check_rollup)
+        .map(|rollup| rollup.contexts.nodes)
+        .unwrap_or_default();
+    let (conclusions, states): (Vec<Option<String>>, Vec<Option<String>>) =
+        contexts.iter().map(check_context_outcome).unzip();
+
+    let pr = PullRequestInfo {
+        number: node.number,
         state,
-        base: first.base_ref_name,
-        url: first.url,
-    })
-}
-
-pub fn get_checks_info(repo_root: &Path, pr_number: i64) -> Option<ChecksInfo> {
-    let stdout = run_gh(
-        &[
-            "pr",
-            "view",
-            &pr_number.to_string(),
-            "--json",
-            "statusCheckRollup",
-        ],
-        repo_root,
-    )?;
-
-    let parsed: ChecksView = serde_json::from_str(&stdout).ok()?;
-    let rollup = parsed.status_check_rollup.unwrap_or_default();
-
-    let conclusions: Vec<Option<String>> = rollup
-        .iter()
-        .map(|item| {
-            item.get("conclusion")
-                .and_then(Value::as_str)
-                .map(ToOwned::to_owned)
-        })
-        .collect();
-    let states: Vec<Option<String>> = rollup
-        .iter()
-        .map(|item| {
-            item.get("state")
-                .and_then(Value::as_str)
-                .map(ToOwned::to_owned)
-        })
-        .collect();
+        base: node.base_ref_name,
+        url: node.url,
+        is_draft: node.is_draft,
+        review_decision: node.review_decision,
+        title: node.title,
+        author: node.author.map(|author| author.login),
+        created_ts: node.created_at.as_deref().and_then(parse_timestamp),
+        merged_ts: node.merged_at.as_deref().and_then(parse_timestamp),
+        unresolved_threads: node.review_threads.map(|threads| {
+            threads
+                .nodes
+                .iter()
+                .filter(|thread| !thread.is_resolved)
+                .count() as i64
+        }),
+    };
+    (pr, classify_checks(&conclusions, &states))
+}
 
-    Some(classify_checks(&conclusions, &states))
+/// `(conclusion, state)` of a rollup context in the shape `classify_checks`
+/// expects. Commit statuses are mapped onto check run semantics.
+fn check_context_outcome(context: &Value) -> (Option<String>, Option<String>) {
+    let field = |key: &str| {
+        context
+            .get(key)
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned)
+    };
+    if context.get("__typename").and_then(Value::as_str) == Some("StatusContext") {
+        return match field("state").as_deref() {
+            Some("SUCCESS") => (Some("SUCCESS".to_string()), Some("COMPLETED".to_string())),
+            Some("FAILURE") | Some("ERROR") => {
+                (Some("FAILURE".to_string()), Some("COMPLETED".to_string()))
+            }
+            _ => (None, Some("PENDING".to_string())),
+        };
+    }
+    (field("conclusion"), field("status"))
 }
 
 pub fn classify_checks(conclusions: &[Option<String>], states: &[Option<String>]) -> ChecksInfo {
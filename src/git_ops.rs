@@ -1,61 +1,621 @@
 #![allow(dead_code)]
 
-use crate::models::{AheadBehind, DiffStat, ParsedWorktree};
+use crate::errors::{self, GwError, PushRejection};
+use crate::logging;
+use crate::models::{
+    AheadBehind, BrokenWorktreeLink, CommitSummary, DiffStat, FetchOptions, InProgressOp,
+    ParsedWorktree, PullStrategy, UnpushedCommits, WorktreeLocation,
+};
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
-use std::ffi::OsStr;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Time limit in seconds for git commands that talk to a remote; 0 for none.
+static REMOTE_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_REMOTE_TIMEOUT_SECS);
+/// Time limit in seconds for all other git commands; 0 for none.
+static LOCAL_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+pub const DEFAULT_REMOTE_TIMEOUT_SECS: u64 = 60;
+
+/// Sets the time limits of git commands for the rest of the process, from
+/// the `gitTimeoutSeconds` and `localGitTimeoutSeconds` settings.
+pub fn set_timeouts(remote_secs: u64, local_secs: u64) {
+    REMOTE_TIMEOUT_SECS.store(remote_secs, Ordering::Relaxed);
+    LOCAL_TIMEOUT_SECS.store(local_secs, Ordering::Relaxed);
+}
+
+fn timeout(secs: &AtomicU64) -> Option<Duration> {
+    match secs.load(Ordering::Relaxed) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// Where the git commands of this module go. [`GitBinary`] runs `git`;
+/// another implementation installed with [`set_git_ops`] can fake or
+/// intercept them, e.g. for a headless harness or a preview of what would
+/// run.
+///
+/// The seam is at the level of git's argv on purpose: `services` and `tui`
+/// keep calling this module's free functions, which all end up here, so a
+/// fake answers the commands those functions run (`worktree list
+/// --porcelain`, `branch -m`, ...) with git's own output formats. Forge
+/// access has its own seam, [`crate::forge::Forge`].
+pub trait GitOps: Send + Sync {
+    /// Runs git with `args` and returns its output, or `None` when it ran
+    /// past `limit` and was killed.
+    fn output(
+        &self,
+        args: &[&str],
+        cwd: Option<&Path>,
+        envs: &[(&str, &OsStr)],
+        limit: Option<Duration>,
+    ) -> io::Result<Option<Output>>;
+
+    /// Runs git with `args`, sending its stderr to `stderr` and dropping its
+    /// stdout. By default the stderr is written once git is done; [`GitBinary`]
+    /// streams it, e.g. for checkout progress.
+    fn run_to(
+        &self,
+        args: &[&str],
+        cwd: Option<&Path>,
+        mut stderr: fs::File,
+    ) -> io::Result<ExitStatus> {
+        let output = self.output(args, cwd, &[], None)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::TimedOut, "git ran past its time limit")
+        })?;
+        stderr.write_all(&output.stderr)?;
+        Ok(output.status)
+    }
+}
+
+/// The `git` executable on `PATH`.
+pub struct GitBinary;
+
+impl GitOps for GitBinary {
+    fn output(
+        &self,
+        args: &[&str],
+        cwd: Option<&Path>,
+        envs: &[(&str, &OsStr)],
+        limit: Option<Duration>,
+    ) -> io::Result<Option<Output>> {
+        let mut cmd = Command::new("git");
+        cmd.args(args);
+        cmd.envs(envs.iter().copied());
+        if let Some(dir) = cwd {
+            cmd.current_dir(dir);
+        }
+        logging::output_within(&mut cmd, limit)
+    }
+
+    fn run_to(
+        &self,
+        args: &[&str],
+        cwd: Option<&Path>,
+        stderr: fs::File,
+    ) -> io::Result<ExitStatus> {
+        let mut cmd = Command::new("git");
+        cmd.args(args).stdout(Stdio::null()).stderr(stderr);
+        if let Some(dir) = cwd {
+            cmd.current_dir(dir);
+        }
+        Ok(logging::output(&mut cmd)?.status)
+    }
+}
+
+static GIT_OPS: RwLock<Option<Arc<dyn GitOps>>> = RwLock::new(None);
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Sends the git commands that follow to `ops`, or back to [`GitBinary`]
+/// with `None`.
+pub fn set_git_ops(ops: Option<Arc<dyn GitOps>>) {
+    *GIT_OPS.write().expect("git ops lock poisoned") = ops;
+}
+
+/// Held by tests that run git or install fakes with [`set_git_ops`] or
+/// [`crate::forge::set_forge_factory`], which apply to the whole process.
+/// The real ones are back once it is dropped.
+#[cfg(test)]
+pub struct Fakes {
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+pub fn lock_fakes() -> Fakes {
+    static FAKES: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    Fakes {
+        _lock: FAKES
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    }
+}
+
+#[cfg(test)]
+impl Drop for Fakes {
+    fn drop(&mut self) {
+        set_git_ops(None);
+        crate::forge::set_forge_factory(None);
+    }
+}
+
+/// Turns the dry run (`--dry-run`, `X` in the TUI) on or off. While it is on,
+/// git commands that change the repo, hooks and forge changes are reported
+/// with [`logging::would_run`] instead of run.
+pub fn set_dry_run(on: bool) {
+    DRY_RUN.store(on, Ordering::Relaxed);
+}
+
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+fn installed_ops() -> Arc<dyn GitOps> {
+    GIT_OPS
+        .read()
+        .expect("git ops lock poisoned")
+        .clone()
+        .unwrap_or_else(|| Arc::new(GitBinary))
+}
+
+fn ops() -> Arc<dyn GitOps> {
+    if dry_run() {
+        Arc::new(DryRun)
+    } else {
+        installed_ops()
+    }
+}
+
+/// Stands in for the installed [`GitOps`] during a dry run: commands that
+/// only read pass through, the others are reported and succeed without
+/// output.
+struct DryRun;
+
+impl GitOps for DryRun {
+    fn output(
+        &self,
+        args: &[&str],
+        cwd: Option<&Path>,
+        envs: &[(&str, &OsStr)],
+        limit: Option<Duration>,
+    ) -> io::Result<Option<Output>> {
+        if !changes_repo(args) {
+            return installed_ops().output(args, cwd, envs, limit);
+        }
+        let location = cwd.map(|dir| format!(" (in {})", dir.display()));
+        logging::would_run(&format!(
+            "git {}{}",
+            fmt_args(args),
+            location.unwrap_or_default()
+        ));
+        Ok(Some(Output {
+            status: ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }))
+    }
+}
+
+/// Whether `git <args>` may change refs, worktrees, files or config, going by
+/// its subcommand and flags. Anything not known to only read counts as a
+/// change.
+fn changes_repo(args: &[&str]) -> bool {
+    let mut args = args.iter().copied();
+    let subcommand = loop {
+        match args.next() {
+            Some("-C" | "-c") => {
+                args.next();
+            }
+            Some(arg) if arg.starts_with('-') => {}
+            Some(arg) => break arg,
+            None => return false,
+        }
+    };
+    let rest: Vec<&str> = args.collect();
+    if writes_files_or_runs_programs(subcommand, &rest) {
+        return true;
+    }
+    let has = |flags: &[&str]| {
+        rest.iter().any(|arg| {
+            flags.iter().any(|flag| {
+                arg == flag
+                    || (flag.starts_with("--")
+                        && arg
+                            .strip_prefix(flag)
+                            .is_some_and(|value| value.starts_with('=')))
+            })
+        })
+    };
+    let positionals = rest.iter().filter(|arg| !arg.starts_with('-')).count();
+    match subcommand {
+        "rev-parse" | "rev-list" | "log" | "show" | "status" | "diff" | "for-each-ref"
+        | "show-ref" | "ls-remote" | "ls-files" | "merge-base" | "cat-file" | "cherry"
+        | "check-ref-format" | "check-ignore" | "describe" | "name-rev" | "var" | "version"
+        | "blame" | "shortlog" | "grep" | "count-objects" => false,
+        // These only write objects, which nothing uses until a ref points
+        // at them.
+        "write-tree" | "commit-tree" | "hash-object" => false,
+        // Fetching into remote-tracking refs (or only FETCH_HEAD) leaves
+        // branches and worktrees alone, so a dry run still sees fresh data.
+        "fetch" => rest
+            .iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .filter_map(|arg| arg.split_once(':'))
+            .any(|(_, dest)| !dest.is_empty() && !dest.starts_with("refs/remotes/")),
+        "config" => !has(&["--get", "--get-all", "--get-regexp", "--list", "-l"]),
+        "branch" => {
+            has(&[
+                "-m",
+                "-M",
+                "-d",
+                "-D",
+                "-c",
+                "-C",
+                "-f",
+                "-u",
+                "--move",
+                "--copy",
+                "--delete",
+                "--force",
+                "--set-upstream-to",
+                "--unset-upstream",
+                "--edit-description",
+            ]) || (positionals > 0
+                && !has(&[
+                    "--list",
+                    "-l",
+                    "--contains",
+                    "--no-contains",
+                    "--merged",
+                    "--no-merged",
+                    "--points-at",
+                ]))
+        }
+        "tag" => positionals > 0 && !has(&["--list", "-l"]),
+        "worktree" => rest.first() != Some(&"list"),
+        "remote" => !matches!(rest.first(), None | Some(&("get-url" | "show" | "-v"))),
+        "stash" => !matches!(rest.first(), Some(&("list" | "show"))),
+        "symbolic-ref" => has(&["-d", "--delete"]) || positionals > 1,
+        "fsmonitor--daemon" => rest.first() != Some(&"status"),
+        _ => true,
+    }
+}
 
 fn fmt_args(args: &[&str]) -> String {
     args.join(" ")
 }
 
 pub fn run(args: &[&str], cwd: Option<&Path>) -> Result<String> {
-    let mut cmd = Command::new("git");
-    cmd.args(args);
-    if let Some(dir) = cwd {
-        cmd.current_dir(dir);
-    }
+    run_with_env(args, cwd, &[])
+}
 
-    let output = cmd.output()?;
+pub fn run_with_env(args: &[&str], cwd: Option<&Path>, envs: &[(&str, &OsStr)]) -> Result<String> {
+    run_within(args, cwd, envs, timeout(&LOCAL_TIMEOUT_SECS))
+}
+
+fn run_within(
+    args: &[&str],
+    cwd: Option<&Path>,
+    envs: &[(&str, &OsStr)],
+    limit: Option<Duration>,
+) -> Result<String> {
+    let Some(output) = ops().output(args, cwd, envs, limit)? else {
+        let secs = limit.map(|limit| limit.as_secs()).unwrap_or_default();
+        return Err(GwError::TimedOut {
+            detail: format!("git {} timed out after {secs}s", fmt_args(args)),
+        }
+        .into());
+    };
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-        return Err(anyhow!("git {}: {}", fmt_args(args), stderr));
+        return Err(classify_failure(args, stderr));
     }
 
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
+/// Runs a git command that talks to a remote. Credential and passphrase
+/// prompts fail instead of waiting on a terminal that the TUI owns or that
+/// isn't there.
+fn run_remote(args: &[&str], cwd: Option<&Path>) -> Result<String> {
+    let mut envs: Vec<(&str, OsString)> = vec![("GIT_TERMINAL_PROMPT", "0".into())];
+    let custom_ssh = std::env::var_os("GIT_SSH_COMMAND").is_some()
+        || std::env::var_os("GIT_SSH").is_some()
+        || try_run(&["config", "--get", "core.sshCommand"], cwd).is_some();
+    if !custom_ssh {
+        envs.push(("GIT_SSH_COMMAND", "ssh -o BatchMode=yes".into()));
+    }
+    let envs: Vec<(&str, &OsStr)> = envs
+        .iter()
+        .map(|(key, value)| (*key, value.as_os_str()))
+        .collect();
+    run_within(args, cwd, &envs, timeout(&REMOTE_TIMEOUT_SECS))
+}
+
+/// Stderr fragments of git failures caused by missing credentials, once
+/// prompting is disabled.
+const CREDENTIAL_FAILURES: &[&str] = &[
+    "terminal prompts disabled",
+    "could not read username",
+    "could not read password",
+    "authentication failed",
+    "permission denied (publickey",
+    "host key verification failed",
+];
+/// Stderr fragments of git failures that come from the remote side.
+const REMOTE_FAILURES: &[&str] = &[
+    "could not read from remote repository",
+    "unable to access",
+    "could not resolve host",
+    "connection timed out",
+    "connection refused",
+    "repository not found",
+];
+/// Stderr fragments of a pull that can't fast-forward and has no strategy
+/// for reconciling.
+const DIVERGED_FAILURES: &[&str] = &[
+    "need to specify how to reconcile divergent branches",
+    "not possible to fast-forward",
+];
+/// Stderr fragments of pushes refused by branch protection (GitHub, GitLab,
+/// Gitea).
+const PROTECTED_FAILURES: &[&str] = &["protected branch", "gh006"];
+/// Stderr fragments of pushes that are not fast-forwards of the remote
+/// branch.
+const NON_FAST_FORWARD_FAILURES: &[&str] = &["(fetch first)", "(non-fast-forward)", "(stale info)"];
+/// Stderr fragments of git refusing to touch uncommitted changes.
+const DIRTY_FAILURES: &[&str] = &[
+    "would be overwritten by",
+    "you have unstaged changes",
+    "your index contains uncommitted changes",
+    "contains modified or untracked files",
+];
+
+/// Error for a failed git command, typed when the cause is recognizable.
+fn classify_failure(args: &[&str], stderr: String) -> anyhow::Error {
+    let detail = format!("git {}: {}", fmt_args(args), stderr);
+    let lower = stderr.to_ascii_lowercase();
+    if CREDENTIAL_FAILURES
+        .iter()
+        .any(|needle| lower.contains(needle))
+    {
+        GwError::CredentialsRequired { detail }.into()
+    } else if REMOTE_FAILURES.iter().any(|needle| lower.contains(needle)) {
+        GwError::RemoteUnavailable { detail }.into()
+    } else if PROTECTED_FAILURES
+        .iter()
+        .any(|needle| lower.contains(needle))
+    {
+        GwError::PushRejected {
+            reason: PushRejection::Protected,
+            detail: rejection_line(&stderr, PROTECTED_FAILURES),
+        }
+        .into()
+    } else if NON_FAST_FORWARD_FAILURES
+        .iter()
+        .any(|needle| lower.contains(needle))
+    {
+        GwError::PushRejected {
+            reason: PushRejection::NeedsPull,
+            detail: rejection_line(&stderr, NON_FAST_FORWARD_FAILURES),
+        }
+        .into()
+    } else if DIVERGED_FAILURES
+        .iter()
+        .any(|needle| lower.contains(needle))
+    {
+        GwError::Diverged { detail }.into()
+    } else if DIRTY_FAILURES.iter().any(|needle| lower.contains(needle)) {
+        GwError::DirtyWorktree { detail }.into()
+    } else {
+        anyhow!(detail)
+    }
+}
+
+/// The first line of `stderr` containing one of `needles`, without git's
+/// `remote:`/`error:` prefixes and column padding.
+fn rejection_line(stderr: &str, needles: &[&str]) -> String {
+    let line = stderr
+        .lines()
+        .find(|line| {
+            let lower = line.to_ascii_lowercase();
+            needles.iter().any(|needle| lower.contains(needle))
+        })
+        .unwrap_or_default()
+        .trim()
+        .trim_start_matches("remote:")
+        .trim_start()
+        .trim_start_matches("error:");
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 pub fn try_run(args: &[&str], cwd: Option<&Path>) -> Option<String> {
     run(args, cwd).ok()
 }
 
+/// Offset of the local time zone from UTC in seconds, as git writes it into
+/// new commits (`git var GIT_COMMITTER_IDENT`, honoring `TZ`); 0 when git
+/// cannot tell.
+pub fn local_utc_offset() -> i64 {
+    let Some(ident) = try_run(&["var", "GIT_COMMITTER_IDENT"], None) else {
+        return 0;
+    };
+    let Some(zone) = ident
+        .rsplit(' ')
+        .next()
+        .filter(|zone| zone.len() == 5 && zone.is_ascii())
+    else {
+        return 0;
+    };
+    let (sign, digits) = zone.split_at(1);
+    let (Ok(hours), Ok(minutes)) = (digits[..2].parse::<i64>(), digits[2..].parse::<i64>()) else {
+        return 0;
+    };
+    let offset = hours * 3600 + minutes * 60;
+    if sign == "-" {
+        -offset
+    } else {
+        offset
+    }
+}
+
+/// The directory gw manages: the parent of the shared `.git` dir, or for a
+/// bare repo kept elsewhere, the worktree root recorded in
+/// [`WORKTREE_ROOT_KEY`].
 pub fn get_repo_root() -> Result<PathBuf> {
     let common_dir_raw = run(&["rev-parse", "--git-common-dir"], None)?;
     let common = PathBuf::from(common_dir_raw);
-    let mut common_abs = if common.is_absolute() {
+    let common_abs = if common.is_absolute() {
         common
     } else {
         std::env::current_dir()?.join(common)
     };
+    Ok(repo_root_for_common_dir(&canonicalize(&common_abs)))
+}
+
+fn repo_root_for_common_dir(common: &Path) -> PathBuf {
+    match common.parent() {
+        Some(parent) if common.file_name() == Some(OsStr::new(".git")) => parent.to_path_buf(),
+        _ => configured_worktree_root(common).unwrap_or_else(|| common.to_path_buf()),
+    }
+}
+
+/// Git config key (in the bare repo) naming the directory that holds the
+/// worktrees when the repo lives elsewhere, e.g. `~/repos/foo.git` with
+/// worktrees under `~/work/foo`. That directory has a `.git` file pointing
+/// at the repo, so git commands run there work as in the repo itself.
+pub const WORKTREE_ROOT_KEY: &str = "gw.worktreeRoot";
+
+/// [`WORKTREE_ROOT_KEY`] read straight from the repo's config file, without
+/// running git.
+fn configured_worktree_root(common_dir: &Path) -> Option<PathBuf> {
+    let config = fs::read_to_string(common_dir.join("config")).ok()?;
+    let mut in_gw_section = false;
+    for line in config.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_gw_section = line.eq_ignore_ascii_case("[gw]");
+            continue;
+        }
+        if !in_gw_section {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key.trim().eq_ignore_ascii_case("worktreeRoot") {
+            let value = value.trim().trim_matches('"');
+            return (!value.is_empty()).then(|| PathBuf::from(value));
+        }
+    }
+    None
+}
 
-    common_abs = common_abs.canonicalize().unwrap_or(common_abs);
+/// Moves the repo at `<worktree_root>/.git` to `dest`, leaving a `.git` file
+/// pointing at it, records the split in [`WORKTREE_ROOT_KEY`] and re-links
+/// the worktrees. `dest` must be on the same filesystem.
+pub fn relocate_git_dir(worktree_root: &Path, dest: &Path) -> Result<()> {
+    let git_dir = worktree_root.join(".git");
+    if !git_dir.is_dir() {
+        return Err(anyhow!("{} is not a git directory", git_dir.display()));
+    }
+    if dest.exists() {
+        return Err(anyhow!("{} already exists", dest.display()));
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&git_dir, dest).map_err(|err| {
+        anyhow!(
+            "cannot move {} to {} (it has to be on the same filesystem): {err}",
+            git_dir.display(),
+            dest.display()
+        )
+    })?;
 
-    if common_abs.file_name() == Some(OsStr::new(".git")) {
-        if let Some(parent) = common_abs.parent() {
-            return Ok(parent.to_path_buf());
+    let link = (|| -> Result<()> {
+        fs::write(&git_dir, format!("gitdir: {}\n", dest.to_string_lossy()))?;
+        let root = worktree_root.to_string_lossy().to_string();
+        run(&["config", WORKTREE_ROOT_KEY, &root], Some(dest))?;
+        // Run from the repo: from the root, git would take the `.git` file
+        // for a linked worktree and "repair" it.
+        run(&["worktree", "repair"], Some(dest))?;
+        Ok(())
+    })();
+    if let Err(err) = link {
+        let _ = fs::remove_file(&git_dir);
+        let _ = run(&["config", "--unset", WORKTREE_ROOT_KEY], Some(dest));
+        if fs::rename(dest, &git_dir).is_ok() {
+            let _ = run(&["worktree", "repair"], Some(&git_dir));
         }
+        return Err(err);
     }
+    Ok(())
+}
+
+/// Finds the worktree containing `dir` by reading `.git`, `commondir` and
+/// `HEAD` directly, without running git, for callers that must stay fast.
+/// `None` outside a worktree.
+pub fn locate_worktree(dir: &Path) -> Option<WorktreeLocation> {
+    let (path, git_dir) = dir.ancestors().find_map(|ancestor| {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            // The bare repo at the root of the gw layout has no worktree.
+            return (!is_bare_config(&dot_git)).then(|| (ancestor.to_path_buf(), dot_git));
+        }
+        let text = fs::read_to_string(&dot_git).ok()?;
+        let git_dir = ancestor.join(text.strip_prefix("gitdir:")?.trim());
+        // The root of a split layout points at the bare repo itself.
+        if is_bare_config(&git_dir) {
+            return None;
+        }
+        Some((ancestor.to_path_buf(), git_dir))
+    })?;
+
+    let common = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(relative) => git_dir.join(relative.trim()),
+        Err(_) => git_dir.clone(),
+    };
+    let repo_root = repo_root_for_common_dir(&canonicalize(&common));
 
-    Ok(common_abs)
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let (branch, head) = match head.strip_prefix("ref: refs/heads/") {
+        Some(branch) => (Some(branch.to_string()), None),
+        None => (None, Some(head.to_string())),
+    };
+    Some(WorktreeLocation {
+        repo_root,
+        path,
+        branch,
+        head,
+    })
+}
+
+/// Whether the git dir's config says `bare = true`.
+fn is_bare_config(git_dir: &Path) -> bool {
+    let config = fs::read_to_string(git_dir.join("config")).unwrap_or_default();
+    config.lines().any(|line| {
+        let line: String = line.split_whitespace().collect();
+        line.eq_ignore_ascii_case("bare=true")
+    })
 }
 
 pub fn is_bare_repo(repo_root: &Path) -> Result<bool> {
     Ok(run(&["rev-parse", "--is-bare-repository"], Some(repo_root))? == "true")
 }
 
+/// The branch `origin/HEAD` points at. Bare clones usually lack that symref,
+/// so the first time it is missing the remote is asked (`ls-remote --symref`)
+/// and the answer persisted in `gw.defaultBranch`, and as `origin/HEAD` when
+/// the remote-tracking branch exists. Without an answer, the first of `main`,
+/// `master` and `trunk` that exists locally, else `main`.
 pub fn get_default_branch(repo_root: &Path) -> String {
     if let Some(reference) = try_run(
         &[
@@ -70,59 +630,348 @@ pub fn get_default_branch(repo_root: &Path) -> String {
             return branch.to_string();
         }
     }
-    "main".to_string()
+    if let Some(branch) = try_run(&["config", "--get", DEFAULT_BRANCH_KEY], Some(repo_root)) {
+        return branch;
+    }
+    if let Some(branch) = remote_default_branch(repo_root) {
+        let _ = run(&["config", DEFAULT_BRANCH_KEY, &branch], Some(repo_root));
+        if remote_tracking_branch_exists(repo_root, "origin", &branch) {
+            let target = format!("refs/remotes/origin/{branch}");
+            let _ = run(
+                &["symbolic-ref", "refs/remotes/origin/HEAD", &target],
+                Some(repo_root),
+            );
+        }
+        return branch;
+    }
+    ["main", "master", "trunk"]
+        .into_iter()
+        .find(|branch| branch_exists(repo_root, branch))
+        .unwrap_or("main")
+        .to_string()
+}
+
+const DEFAULT_BRANCH_KEY: &str = "gw.defaultBranch";
+
+/// The branch HEAD points at on origin, as reported by the remote.
+fn remote_default_branch(repo_root: &Path) -> Option<String> {
+    get_remote_url(repo_root, "origin")?;
+    let output = run_remote(
+        &["ls-remote", "--symref", "origin", "HEAD"],
+        Some(repo_root),
+    )
+    .ok()?;
+    output.lines().find_map(|line| {
+        let target = line.strip_prefix("ref:")?.split_whitespace().next()?;
+        target.strip_prefix("refs/heads/").map(str::to_string)
+    })
 }
 
 pub fn prune_worktrees(repo_root: &Path) {
     let _ = try_run(&["worktree", "prune"], Some(repo_root));
 }
 
+pub fn get_git_common_dir(repo_root: &Path) -> Result<PathBuf> {
+    let raw = PathBuf::from(run(&["rev-parse", "--git-common-dir"], Some(repo_root))?);
+    let abs = if raw.is_absolute() {
+        raw
+    } else {
+        repo_root.join(raw)
+    };
+    Ok(canonicalize(&abs))
+}
+
+/// Re-links moved worktrees. Both sides of each link are rewritten before
+/// `git worktree repair` runs, because git would otherwise follow the stale
+/// pointers and re-link worktrees at the old location, which may still hold a
+/// copy of the repo.
+pub fn worktree_repair(repo_root: &Path, links: &[BrokenWorktreeLink]) -> Result<()> {
+    for link in links {
+        let dot_git = link.path.join(".git");
+        fs::write(
+            link.admin_dir.join("gitdir"),
+            format!("{}\n", dot_git.to_string_lossy()),
+        )?;
+        fs::write(
+            &dot_git,
+            format!("gitdir: {}\n", link.admin_dir.to_string_lossy()),
+        )?;
+    }
+
+    let path_args: Vec<String> = links
+        .iter()
+        .map(|link| link.path.to_string_lossy().to_string())
+        .collect();
+    let mut args = vec!["worktree", "repair"];
+    args.extend(path_args.iter().map(String::as_str));
+    run(&args, Some(repo_root))?;
+    Ok(())
+}
+
+/// Scans `scan_root` for linked worktree directories whose gitdir links are
+/// broken but still repairable, i.e. the repo has an admin entry matching the
+/// worktree's `.git` file. Worktrees still at their registered paths are
+/// skipped without looking inside.
+pub fn find_broken_worktree_links(
+    repo_root: &Path,
+    scan_root: &Path,
+) -> Result<Vec<BrokenWorktreeLink>> {
+    let common_dir = get_git_common_dir(repo_root)?;
+    let admin_root = common_dir.join("worktrees");
+    let registered: HashSet<PathBuf> = list_worktree_admin_entries(repo_root)?
+        .into_iter()
+        .map(|(_, path)| canonicalize(&path))
+        .collect();
+
+    let mut dirs = Vec::new();
+    collect_linked_worktree_dirs(scan_root, 0, &registered, &mut dirs);
+
+    let mut broken = Vec::new();
+    for dir in dirs {
+        let Some(linked_gitdir) = read_gitdir_file(&dir.join(".git")) else {
+            continue;
+        };
+        let Some(admin_id) = linked_gitdir.file_name() else {
+            continue;
+        };
+        let admin_dir = admin_root.join(admin_id);
+        if !admin_dir.is_dir() {
+            continue;
+        }
+
+        let registered = fs::read_to_string(admin_dir.join("gitdir"))
+            .ok()
+            .map(|raw| PathBuf::from(raw.trim()));
+        let forward_ok = same_path(&linked_gitdir, &admin_dir);
+        let backward_ok = registered
+            .as_deref()
+            .is_some_and(|gitdir| same_path(gitdir, &dir.join(".git")));
+        if forward_ok && backward_ok {
+            continue;
+        }
+
+        broken.push(BrokenWorktreeLink {
+            path: dir,
+            admin_dir,
+            registered_path: registered.and_then(|gitdir| gitdir.parent().map(Path::to_path_buf)),
+        });
+    }
+
+    Ok(broken)
+}
+
+/// Linked worktree admin entries under `<common-dir>/worktrees`, as
+/// `(admin dir, registered worktree path)` pairs.
+pub fn list_worktree_admin_entries(repo_root: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let admin_root = get_git_common_dir(repo_root)?.join("worktrees");
+    let Ok(entries) = fs::read_dir(&admin_root) else {
+        return Ok(Vec::new());
+    };
+
+    let mut admin_entries = Vec::new();
+    for entry in entries.flatten() {
+        let admin_dir = entry.path();
+        let Ok(raw) = fs::read_to_string(admin_dir.join("gitdir")) else {
+            continue;
+        };
+        let dot_git = PathBuf::from(raw.trim());
+        if let Some(registered) = dot_git.parent() {
+            admin_entries.push((admin_dir.clone(), registered.to_path_buf()));
+        }
+    }
+    Ok(admin_entries)
+}
+
+/// Admin dir the `.git` file inside `worktree_path` points to.
+pub fn worktree_admin_dir(worktree_path: &Path) -> Option<PathBuf> {
+    let gitdir = read_gitdir_file(&worktree_path.join(".git"))?;
+    Some(canonicalize(&gitdir))
+}
+
+/// The operation left unfinished in the worktree, judging by the state files
+/// git keeps in its git dir.
+pub fn in_progress_operation(worktree_path: &Path) -> Option<InProgressOp> {
+    let git_dir = worktree_admin_dir(worktree_path).or_else(|| {
+        let dir = worktree_path.join(".git");
+        dir.is_dir().then_some(dir)
+    })?;
+    let has = |name: &str| git_dir.join(name).exists();
+    if has("rebase-merge") || has("rebase-apply") {
+        Some(InProgressOp::Rebase)
+    } else if has("MERGE_HEAD") {
+        Some(InProgressOp::Merge)
+    } else if has("CHERRY_PICK_HEAD") {
+        Some(InProgressOp::CherryPick)
+    } else if has("BISECT_LOG") {
+        Some(InProgressOp::Bisect)
+    } else {
+        None
+    }
+}
+
+/// Drops a worktree registration without touching the directory it names.
+pub fn unregister_worktree(admin_dir: &Path) -> Result<()> {
+    fs::remove_dir_all(admin_dir)?;
+    Ok(())
+}
+
+/// Moves a worktree directory that git can't move itself (e.g. one reached
+/// through a symlink) and re-links it at its new location.
+pub fn relocate_worktree(
+    repo_root: &Path,
+    registered_path: &Path,
+    real_path: &Path,
+    dest: &Path,
+    admin_dir: &Path,
+) -> Result<()> {
+    for link in [registered_path, dest] {
+        if fs::symlink_metadata(link).is_ok_and(|meta| meta.is_symlink()) {
+            fs::remove_file(link)?;
+        }
+    }
+    ensure_worktree_parent(dest)?;
+    fs::rename(real_path, dest).map_err(|err| {
+        anyhow!(
+            "failed to move {} to {}: {err}",
+            real_path.display(),
+            dest.display()
+        )
+    })?;
+    worktree_repair(
+        repo_root,
+        &[BrokenWorktreeLink {
+            path: dest.to_path_buf(),
+            admin_dir: admin_dir.to_path_buf(),
+            registered_path: None,
+        }],
+    )
+}
+
+const MAX_WORKTREE_SCAN_DEPTH: usize = 16;
+
+/// Build and dependency directories, which can be huge and never hold a
+/// worktree.
+const SKIPPED_SCAN_DIRS: [&str; 2] = ["node_modules", "target"];
+
+fn collect_linked_worktree_dirs(
+    dir: &Path,
+    depth: usize,
+    registered: &HashSet<PathBuf>,
+    out: &mut Vec<PathBuf>,
+) {
+    if depth > MAX_WORKTREE_SCAN_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || SKIPPED_SCAN_DIRS.contains(&name.as_ref()) {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if !file_type.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        let dot_git = path.join(".git");
+        if dot_git.is_file() {
+            if !registered.contains(&canonicalize(&path)) {
+                out.push(path);
+            }
+        } else if !dot_git.exists() {
+            // A `.git` directory is a separate repo, with nothing of ours inside.
+            collect_linked_worktree_dirs(&path, depth + 1, registered, out);
+        }
+    }
+}
+
+fn read_gitdir_file(path: &Path) -> Option<PathBuf> {
+    let raw = fs::read_to_string(path).ok()?;
+    let gitdir = PathBuf::from(raw.trim().strip_prefix("gitdir:")?.trim());
+    if gitdir.is_absolute() {
+        Some(gitdir)
+    } else {
+        path.parent().map(|parent| parent.join(gitdir))
+    }
+}
+
+/// `Path::canonicalize`, falling back to `path` itself when it cannot be
+/// resolved. On Windows the `\\?\` verbatim prefix is dropped again (unless
+/// the path needs it), since git and most shells do not understand it.
+pub fn canonicalize(path: &Path) -> PathBuf {
+    let Ok(canonical) = path.canonicalize() else {
+        return path.to_path_buf();
+    };
+    #[cfg(windows)]
+    {
+        let text = canonical.to_string_lossy();
+        if let Some(rest) = text.strip_prefix(r"\\?\") {
+            if !rest.starts_with(r"UNC\") && rest.len() < 260 {
+                return PathBuf::from(rest);
+            }
+        }
+    }
+    canonical
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
 pub fn parse_worktrees(repo_root: Option<&Path>) -> Result<Vec<ParsedWorktree>> {
     let output = run(&["worktree", "list", "--porcelain"], repo_root)?;
     let mut worktrees = Vec::new();
 
-    let mut current_path = String::new();
-    let mut current_branch = String::new();
-    let mut current_head = String::new();
+    // Entries are separated by blank lines and start with `worktree <path>`.
+    let mut current: Option<ParsedWorktree> = None;
     let mut current_is_bare = false;
+    let mut finish = |current: Option<ParsedWorktree>, is_bare: bool| {
+        if let Some(wt) = current {
+            if !is_bare && (!wt.branch.is_empty() || !wt.head.is_empty()) {
+                worktrees.push(wt);
+            }
+        }
+    };
 
     for line in output.lines() {
         if let Some(path) = line.strip_prefix("worktree ") {
-            if !current_path.is_empty()
-                && !current_is_bare
-                && (!current_branch.is_empty() || !current_head.is_empty())
-            {
-                worktrees.push(ParsedWorktree {
-                    path: PathBuf::from(&current_path),
-                    branch: current_branch.clone(),
-                    head: current_head.clone(),
-                });
-            }
-            current_path = path.to_string();
-            current_branch.clear();
-            current_head.clear();
+            finish(current.take(), current_is_bare);
+            current = Some(ParsedWorktree {
+                path: PathBuf::from(path),
+                branch: String::new(),
+                head: String::new(),
+                locked: None,
+                prunable: None,
+            });
             current_is_bare = false;
-        } else if let Some(reference) = line.strip_prefix("branch ") {
-            current_branch = reference.trim_start_matches("refs/heads/").to_string();
+            continue;
+        }
+        let Some(wt) = current.as_mut() else {
+            continue;
+        };
+        if let Some(reference) = line.strip_prefix("branch ") {
+            wt.branch = reference.trim_start_matches("refs/heads/").to_string();
         } else if let Some(head) = line.strip_prefix("HEAD ") {
-            current_head = head.to_string();
+            wt.head = head.to_string();
         } else if line.starts_with("detached") {
-            current_branch = "(detached)".to_string();
+            wt.branch = "(detached)".to_string();
         } else if line.starts_with("bare") {
             current_is_bare = true;
+        } else if let Some(reason) = line.strip_prefix("locked") {
+            wt.locked = Some(reason.trim().to_string());
+        } else if let Some(reason) = line.strip_prefix("prunable") {
+            wt.prunable = Some(reason.trim().to_string());
         }
     }
-
-    if !current_path.is_empty()
-        && !current_is_bare
-        && (!current_branch.is_empty() || !current_head.is_empty())
-    {
-        worktrees.push(ParsedWorktree {
-            path: PathBuf::from(current_path),
-            branch: current_branch,
-            head: current_head,
-        });
-    }
+    finish(current, current_is_bare);
 
     Ok(worktrees)
 }
@@ -152,7 +1001,10 @@ pub fn count_ahead_behind(repo_root: &Path, left: &str, right: &str) -> AheadBeh
     AheadBehind { ahead, behind }
 }
 
-pub fn diff_counts(worktree_path: &Path) -> DiffStat {
+/// Line counts and dirtiness of a worktree. Without `include_untracked`,
+/// untracked files are neither looked for nor counted, which skips the
+/// slowest part of `git status` in huge trees.
+pub fn diff_counts(worktree_path: &Path, include_untracked: bool) -> DiffStat {
     if !worktree_path.is_dir() {
         return DiffStat {
             additions: 0,
@@ -161,13 +1013,30 @@ pub fn diff_counts(worktree_path: &Path) -> DiffStat {
         };
     }
 
-    let status = try_run(&["status", "--porcelain"], Some(worktree_path)).unwrap_or_default();
+    let untracked_files = if include_untracked {
+        "--untracked-files=normal"
+    } else {
+        "--untracked-files=no"
+    };
+    let status = try_run(
+        &["status", "--porcelain", untracked_files],
+        Some(worktree_path),
+    )
+    .unwrap_or_default();
     let dirty = !status.trim().is_empty();
 
     let mut additions = 0_i64;
     let mut deletions = 0_i64;
 
-    let numstat = try_run(&["diff", "--numstat"], Some(worktree_path)).unwrap_or_default();
+    // Staged and unstaged changes together, as a commit of everything would
+    // see them. Without a HEAD yet, everything staged is new.
+    let numstat = try_run(&["diff", "HEAD", "--numstat"], Some(worktree_path))
+        .or_else(|| {
+            let staged = try_run(&["diff", "--cached", "--numstat"], Some(worktree_path))?;
+            let unstaged = try_run(&["diff", "--numstat"], Some(worktree_path))?;
+            Some(format!("{staged}\n{unstaged}"))
+        })
+        .unwrap_or_default();
     for line in numstat.lines() {
         let mut parts = line.split('\t');
         let a = parts.next().and_then(|v| v.parse::<i64>().ok());
@@ -178,11 +1047,9 @@ pub fn diff_counts(worktree_path: &Path) -> DiffStat {
         }
     }
 
-    let untracked = status
-        .lines()
-        .filter(|line| line.starts_with("?? "))
-        .count() as i64;
-    additions += untracked;
+    if include_untracked && status.lines().any(|line| line.starts_with("?? ")) {
+        additions += untracked_line_count(worktree_path);
+    }
 
     DiffStat {
         additions,
@@ -191,50 +1058,395 @@ pub fn diff_counts(worktree_path: &Path) -> DiffStat {
     }
 }
 
+/// Untracked files larger than this are left out of the CHANGES count, like
+/// binary files, rather than read in full on every refresh.
+const MAX_UNTRACKED_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Lines in the untracked (not ignored) files of a worktree.
+fn untracked_line_count(worktree_path: &Path) -> i64 {
+    let Some(files) = try_run(
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+        Some(worktree_path),
+    ) else {
+        return 0;
+    };
+
+    files
+        .split('\0')
+        .filter(|file| !file.is_empty())
+        .map(|file| worktree_path.join(file))
+        .filter(|path| {
+            fs::metadata(path)
+                .is_ok_and(|meta| meta.is_file() && meta.len() <= MAX_UNTRACKED_FILE_BYTES)
+        })
+        .filter_map(|path| fs::read(path).ok())
+        .filter(|bytes| !bytes.contains(&0))
+        .map(|bytes| {
+            let newlines = bytes.iter().filter(|&&byte| byte == b'\n').count() as i64;
+            // A last line without a newline still counts, as in `git diff`.
+            newlines + i64::from(bytes.last().is_some_and(|&byte| byte != b'\n'))
+        })
+        .sum()
+}
+
+/// Whether git consults a filesystem monitor (`core.fsmonitor`), which makes
+/// finding untracked files cheap even in huge trees.
+pub fn fsmonitor_enabled(repo_root: &Path) -> bool {
+    try_run(&["config", "--get", "core.fsmonitor"], Some(repo_root))
+        .is_some_and(|value| !value.is_empty() && value != "false")
+}
+
+/// Whether git keeps an untracked cache (`core.untrackedCache`), so `git
+/// status` only rescans directories whose mtime changed.
+pub fn untracked_cache_enabled(repo_root: &Path) -> bool {
+    // `keep` leaves an existing cache in use, so it counts as on.
+    try_run(&["config", "--get", "core.untrackedCache"], Some(repo_root))
+        .is_some_and(|value| !matches!(value.as_str(), "" | "false" | "no" | "off" | "0"))
+}
+
+/// Whether this git ships the built-in filesystem monitor daemon that
+/// `core.fsmonitor=true` starts (macOS and Windows builds only).
+pub fn builtin_fsmonitor_supported() -> bool {
+    try_run(&["version", "--build-options"], None).is_some_and(|options| {
+        options
+            .lines()
+            .any(|line| line.trim() == "feature: fsmonitor--daemon")
+    })
+}
+
+/// Sets `key` in the repo's shared config, which every worktree reads.
+pub fn set_config(repo_root: &Path, key: &str, value: &str) -> Result<()> {
+    run(&["config", key, value], Some(repo_root))?;
+    Ok(())
+}
+
 pub fn get_last_commit_ts(repo_root: &Path, target: &str) -> i64 {
     try_run(&["log", "-1", "--format=%ct", target], Some(repo_root))
         .and_then(|v| v.parse::<i64>().ok())
         .unwrap_or(0)
 }
 
-pub fn get_upstream(repo_root: &Path, ref_name: &str) -> Option<String> {
-    let arg = format!("{ref_name}@{{upstream}}");
-    try_run(&["rev-parse", "--abbrev-ref", &arg], Some(repo_root))
-}
-
-pub fn list_local_branches(repo_root: &Path) -> Result<Vec<String>> {
+/// Commit time of each local branch's tip, as a unix timestamp.
+pub fn list_branch_commit_times(repo_root: &Path) -> Result<HashMap<String, i64>> {
     let out = run(
-        &["for-each-ref", "--format=%(refname:short)", "refs/heads"],
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%09%(committerdate:unix)",
+            "refs/heads",
+        ],
         Some(repo_root),
     )?;
     Ok(out
         .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty())
-        .map(ToOwned::to_owned)
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(branch, ts)| (branch.to_string(), ts.parse().unwrap_or(0)))
         .collect())
 }
 
-pub fn branch_exists(repo_root: &Path, branch: &str) -> bool {
-    let ref_name = format!("refs/heads/{branch}");
-    try_run(&["show-ref", "--verify", &ref_name], Some(repo_root)).is_some()
+/// Branch checked out in the worktree, or `None` if HEAD is detached.
+pub fn worktree_branch(worktree_path: &Path) -> Option<String> {
+    try_run(
+        &["symbolic-ref", "--quiet", "--short", "HEAD"],
+        Some(worktree_path),
+    )
 }
 
-pub fn remote_branch_exists(repo_root: &Path, branch: &str) -> bool {
-    let out = try_run(&["ls-remote", "--heads", "origin", branch], Some(repo_root));
-    out.is_some_and(|v| !v.trim().is_empty())
-}
+/// Commit a worktree is sitting on. Falls back to the worktree's HEAD reflog
+/// when HEAD points at a branch that no longer exists.
+pub fn worktree_head_commit(worktree_path: &Path) -> Option<String> {
+    if let Some(head) = try_run(&["rev-parse", "--verify", "HEAD"], Some(worktree_path)) {
+        return Some(head);
+    }
 
-pub fn is_valid_branch_name(repo_root: &Path, name: &str) -> bool {
-    try_run(&["check-ref-format", "--branch", name], Some(repo_root)).is_some()
+    let log = fs::read_to_string(head_reflog_path(worktree_path)?).ok()?;
+    let last = log.lines().last()?;
+    let new_head = last.split_whitespace().nth(1)?;
+    (new_head.chars().any(|ch| ch != '0')).then(|| new_head.to_string())
 }
 
-pub fn has_unpushed_commits(repo_root: &Path, branch: &str) -> bool {
-    let Some(upstream) = get_upstream(repo_root, branch) else {
-        return true;
+fn head_reflog_path(worktree_path: &Path) -> Option<PathBuf> {
+    let log_path = try_run(
+        &["rev-parse", "--git-path", "logs/HEAD"],
+        Some(worktree_path),
+    )?;
+    let log_path = PathBuf::from(log_path);
+    Some(if log_path.is_absolute() {
+        log_path
+    } else {
+        worktree_path.join(log_path)
+    })
+}
+
+/// Branches the worktree's HEAD reflog switched away from (`checkout: moving
+/// from <branch> to ...`), each with the commit it was on and when, oldest
+/// first. Detached HEADs are left out.
+pub fn head_reflog_departures(worktree_path: &Path) -> Vec<(String, String, i64)> {
+    let Some(log) = head_reflog_path(worktree_path).and_then(|path| fs::read_to_string(path).ok())
+    else {
+        return Vec::new();
+    };
+    log.lines()
+        .filter_map(|line| {
+            // `<old> <new> <name> <email> <time> <tz>\t<message>`
+            let (entry, message) = line.split_once('\t')?;
+            let from = message.strip_prefix("checkout: moving from ")?;
+            let (from, _) = from.rsplit_once(" to ")?;
+            if from.len() >= 7 && from.chars().all(|ch| ch.is_ascii_hexdigit()) {
+                return None;
+            }
+            let old = entry.split_whitespace().next()?;
+            let time = entry.split_whitespace().rev().nth(1)?.parse().ok()?;
+            Some((from.to_string(), old.to_string(), time))
+        })
+        .collect()
+}
+
+/// Whether `sha` names a commit that is still in the object store.
+pub fn commit_exists(repo_root: &Path, sha: &str) -> bool {
+    try_run(
+        &["cat-file", "-e", &format!("{sha}^{{commit}}")],
+        Some(repo_root),
+    )
+    .is_some()
+}
+
+pub fn get_upstream(repo_root: &Path, ref_name: &str) -> Option<String> {
+    let arg = format!("{ref_name}@{{upstream}}");
+    try_run(&["rev-parse", "--abbrev-ref", &arg], Some(repo_root))
+}
+
+pub fn list_local_branches(repo_root: &Path) -> Result<Vec<String>> {
+    let out = run(
+        &["for-each-ref", "--format=%(refname:short)", "refs/heads"],
+        Some(repo_root),
+    )?;
+    Ok(out
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// Where each local branch is pushed, as `branch -> (remote, remote branch)`:
+/// the push destination if git can work one out, otherwise the upstream.
+/// Branches with neither are left out.
+pub fn list_branch_push_targets(repo_root: &Path) -> HashMap<String, (String, String)> {
+    let Some(output) = try_run(
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%09%(push:remotename)%09%(push:remoteref)%09%(upstream:remotename)%09%(upstream:remoteref)",
+            "refs/heads",
+        ],
+        Some(repo_root),
+    ) else {
+        return HashMap::new();
     };
-    let ab = count_ahead_behind(repo_root, branch, &upstream);
-    ab.ahead > 0
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [branch, push_remote, push_ref, upstream_remote, upstream_ref] = fields[..] else {
+                return None;
+            };
+            let (remote, remote_ref) = if !push_remote.is_empty() && !push_ref.is_empty() {
+                (push_remote, push_ref)
+            } else if !upstream_remote.is_empty() && !upstream_ref.is_empty() {
+                (upstream_remote, upstream_ref)
+            } else {
+                return None;
+            };
+            let remote_branch = remote_ref.strip_prefix("refs/heads/").unwrap_or(remote_ref);
+            Some((
+                branch.to_string(),
+                (remote.to_string(), remote_branch.to_string()),
+            ))
+        })
+        .collect()
+}
+
+/// Links `branch` to forge issue `number` in the branch's config section, so
+/// the link follows `git branch -m` and goes away with the branch.
+pub fn set_branch_issue(repo_root: &Path, branch: &str, number: u64) -> Result<()> {
+    let key = format!("branch.{branch}.gwIssue");
+    run(&["config", &key, &number.to_string()], Some(repo_root))?;
+    Ok(())
+}
+
+/// Records the branch `branch` was created from, for `gw export`.
+pub fn set_branch_base(repo_root: &Path, branch: &str, base: &str) -> Result<()> {
+    let key = format!("branch.{branch}.gwBase");
+    run(&["config", &key, base], Some(repo_root))?;
+    Ok(())
+}
+
+/// The base recorded with [`set_branch_base`].
+pub fn get_branch_base(repo_root: &Path, branch: &str) -> Option<String> {
+    let key = format!("branch.{branch}.gwBase");
+    try_run(&["config", "--get", &key], Some(repo_root))
+}
+
+/// Issue numbers recorded with [`set_branch_issue`], keyed by branch.
+pub fn list_branch_issues(repo_root: &Path) -> HashMap<String, u64> {
+    let Some(output) = try_run(
+        &["config", "--get-regexp", r"^branch\..*\.gwissue$"],
+        Some(repo_root),
+    ) else {
+        return HashMap::new();
+    };
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            let branch = key.strip_prefix("branch.")?.strip_suffix(".gwissue")?;
+            Some((branch.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// `branch.<name>.description` of every branch that has one, as set by
+/// `git branch --edit-description`; may span several lines.
+pub fn list_branch_descriptions(repo_root: &Path) -> HashMap<String, String> {
+    let Some(output) = try_run(
+        &["config", "-z", "--get-regexp", r"^branch\..*\.description$"],
+        Some(repo_root),
+    ) else {
+        return HashMap::new();
+    };
+    output
+        .split('\0')
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once('\n')?;
+            let branch = key.strip_prefix("branch.")?.strip_suffix(".description")?;
+            let value = value.trim();
+            (!value.is_empty()).then(|| (branch.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Sets `branch.<branch>.description`, or removes it when `description` is
+/// empty.
+pub fn set_branch_description(repo_root: &Path, branch: &str, description: &str) -> Result<()> {
+    let key = format!("branch.{branch}.description");
+    if description.is_empty() {
+        if try_run(&["config", "--get", &key], Some(repo_root)).is_some() {
+            run(&["config", "--unset", &key], Some(repo_root))?;
+        }
+        return Ok(());
+    }
+    run(&["config", &key, description], Some(repo_root))?;
+    Ok(())
+}
+
+/// Local branches whose upstream was deleted on the remote: `[gone]` once a
+/// fetch has pruned its remote-tracking branch.
+pub fn list_gone_upstreams(repo_root: &Path) -> HashSet<String> {
+    let Some(output) = try_run(
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%09%(upstream:track)",
+            "refs/heads",
+        ],
+        Some(repo_root),
+    ) else {
+        return HashSet::new();
+    };
+    output
+        .lines()
+        .filter_map(|line| {
+            let (branch, track) = line.split_once('\t')?;
+            (track == "[gone]").then(|| branch.to_string())
+        })
+        .collect()
+}
+
+pub fn branch_exists(repo_root: &Path, branch: &str) -> bool {
+    let ref_name = format!("refs/heads/{branch}");
+    try_run(&["show-ref", "--verify", &ref_name], Some(repo_root)).is_some()
+}
+
+pub fn remote_tracking_branch_exists(repo_root: &Path, remote: &str, branch: &str) -> bool {
+    let ref_name = format!("refs/remotes/{remote}/{branch}");
+    try_run(&["show-ref", "--verify", &ref_name], Some(repo_root)).is_some()
+}
+
+pub fn get_remote_url(repo_root: &Path, remote: &str) -> Option<String> {
+    try_run(&["remote", "get-url", remote], Some(repo_root)).filter(|url| !url.is_empty())
+}
+
+pub fn list_remotes(repo_root: &Path) -> Vec<String> {
+    try_run(&["remote"], Some(repo_root))
+        .map(|out| out.lines().map(ToOwned::to_owned).collect())
+        .unwrap_or_default()
+}
+
+/// Copies the repo at `source` into a new bare repo at `dest` with the same
+/// local branches, tags, remotes, remote-tracking branches and upstreams.
+/// `source` is only read from; objects are hardlinked where possible.
+pub fn clone_bare_copy(source: &Path, dest: &Path) -> Result<()> {
+    let source_arg = source.to_string_lossy();
+    let dest_arg = dest.to_string_lossy();
+    run(
+        &["clone", "--bare", "--quiet", &source_arg, &dest_arg],
+        None,
+    )?;
+    // The clone's origin is `source`; the copy talks to its remotes instead.
+    run(&["remote", "remove", "origin"], Some(dest))?;
+    let remotes = list_remotes(source);
+    for remote in &remotes {
+        if let Some(url) = get_remote_url(source, remote) {
+            run(&["remote", "add", remote, &url], Some(dest))?;
+        }
+    }
+    run(
+        &[
+            "fetch",
+            "--quiet",
+            &source_arg,
+            "+refs/remotes/*:refs/remotes/*",
+            "^refs/remotes/*/HEAD",
+        ],
+        Some(dest),
+    )?;
+    for remote in &remotes {
+        let head = format!("refs/remotes/{remote}/HEAD");
+        if let Some(target) = try_run(&["symbolic-ref", &head], Some(source)) {
+            let _ = run(&["symbolic-ref", &head, &target], Some(dest));
+        }
+    }
+    for branch in list_local_branches(source)? {
+        if let Some(upstream) = get_upstream(source, &branch) {
+            // Upstreams on remotes that weren't copied are dropped.
+            let _ = branch_set_upstream(dest, &branch, &upstream);
+        }
+    }
+    Ok(())
+}
+
+pub fn remote_branch_exists(repo_root: &Path, branch: &str) -> bool {
+    let out = run_remote(&["ls-remote", "--heads", "origin", branch], Some(repo_root)).ok();
+    out.is_some_and(|v| !v.trim().is_empty())
+}
+
+pub fn is_valid_branch_name(repo_root: &Path, name: &str) -> bool {
+    try_run(&["check-ref-format", "--branch", name], Some(repo_root)).is_some()
+}
+
+/// Commits of `branch` that exist nowhere else: those ahead of its upstream,
+/// or, for a branch that was never pushed, those not on `base` (the default
+/// branch). A never-pushed branch with nothing of its own loses nothing.
+pub fn unpushed_commits(repo_root: &Path, branch: &str, base: &str) -> UnpushedCommits {
+    match get_upstream(repo_root, branch) {
+        Some(upstream) => match count_ahead_behind(repo_root, branch, &upstream).ahead {
+            0 => UnpushedCommits::None,
+            ahead => UnpushedCommits::Ahead(ahead),
+        },
+        None => match count_ahead_behind(repo_root, branch, base).ahead {
+            0 => UnpushedCommits::None,
+            unique => UnpushedCommits::NeverPushed(unique),
+        },
+    }
 }
 
 pub fn has_uncommitted_changes(repo_root: &Path) -> Result<bool> {
@@ -243,8 +1455,228 @@ pub fn has_uncommitted_changes(repo_root: &Path) -> Result<bool> {
         .is_empty())
 }
 
-pub fn fetch_prune(repo_root: &Path) {
-    let _ = try_run(&["fetch", "--prune"], Some(repo_root));
+/// Commits in `range` (e.g. `main..feature`, or just a branch), newest
+/// first, at most `limit`.
+pub fn log_commits(repo_root: &Path, range: &str, limit: usize) -> Result<Vec<CommitSummary>> {
+    let limit = limit.to_string();
+    let out = run(
+        &["log", "--format=%H%x09%s", "-n", &limit, range, "--"],
+        Some(repo_root),
+    )?;
+    Ok(out
+        .lines()
+        .filter_map(|line| {
+            let (sha, subject) = line.split_once('\t')?;
+            Some(CommitSummary {
+                sha: sha.to_string(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Stash entries made on `branch` (`WIP on <branch>: ...` or
+/// `On <branch>: ...`); the stash is shared by all worktrees.
+pub fn stash_count(worktree_path: &Path, branch: &str) -> usize {
+    let Some(out) = try_run(&["stash", "list", "--format=%gs"], Some(worktree_path)) else {
+        return 0;
+    };
+    let (wip, on) = (format!("WIP on {branch}: "), format!("On {branch}: "));
+    out.lines()
+        .filter(|line| line.starts_with(&wip) || line.starts_with(&on))
+        .count()
+}
+
+/// `git diff --stat` of what `branch` changed since it forked from `base`
+/// (`base...branch`): one line per file, then the summary line.
+pub fn diff_stat(repo_root: &Path, base: &str, branch: &str) -> Result<Vec<String>> {
+    let range = format!("{base}...{branch}");
+    let out = run(&["diff", "--stat=120", &range, "--"], Some(repo_root))?;
+    Ok(out.lines().map(|line| line.trim().to_string()).collect())
+}
+
+/// Cherry-picks `commits` in order onto the worktree's branch, recording
+/// where each came from (`-x`).
+pub fn cherry_pick(worktree_path: &Path, commits: &[String]) -> Result<()> {
+    let mut args = vec!["cherry-pick", "-x"];
+    args.extend(commits.iter().map(String::as_str));
+    run(&args, Some(worktree_path))?;
+    Ok(())
+}
+
+/// Undoes an interrupted cherry-pick, back to the state before it started.
+pub fn cherry_pick_abort(worktree_path: &Path) -> Result<()> {
+    run(&["cherry-pick", "--abort"], Some(worktree_path))?;
+    Ok(())
+}
+
+/// The commit an interrupted cherry-pick stopped at.
+pub fn cherry_pick_head(worktree_path: &Path) -> Option<String> {
+    try_run(
+        &["rev-parse", "--verify", "--quiet", "CHERRY_PICK_HEAD"],
+        Some(worktree_path),
+    )
+}
+
+/// Paths with unresolved merge conflicts.
+pub fn conflicted_files(worktree_path: &Path) -> Vec<String> {
+    try_run(
+        &["diff", "--name-only", "--diff-filter=U"],
+        Some(worktree_path),
+    )
+    .map(|out| out.lines().map(str::to_string).collect())
+    .unwrap_or_default()
+}
+
+/// Stashes the worktree's uncommitted changes, untracked files included.
+pub fn stash_push(worktree_path: &Path, message: &str) -> Result<()> {
+    run(
+        &["stash", "push", "--include-untracked", "--message", message],
+        Some(worktree_path),
+    )?;
+    Ok(())
+}
+
+/// Applies the newest stash to the worktree and drops it. On conflicts the
+/// stash is kept and an error returned.
+pub fn stash_pop(worktree_path: &Path) -> Result<()> {
+    run(&["stash", "pop"], Some(worktree_path))?;
+    Ok(())
+}
+
+/// Like [`has_uncommitted_changes`], but ignores the given repo-relative paths,
+/// e.g. linked worktrees nested inside the main checkout. Untracked files only
+/// count with `include_untracked`.
+pub fn has_uncommitted_changes_excluding(
+    repo_root: &Path,
+    excluded: &[PathBuf],
+    include_untracked: bool,
+) -> Result<bool> {
+    let excludes = exclude_pathspecs(excluded);
+    let untracked = if include_untracked {
+        "--untracked-files=normal"
+    } else {
+        "--untracked-files=no"
+    };
+    let mut args = vec!["status", "--porcelain", untracked, "--", "."];
+    args.extend(excludes.iter().map(String::as_str));
+    Ok(!run(&args, Some(repo_root))?.trim().is_empty())
+}
+
+/// Untracked and ignored paths in the checkout at `repo_root`, outside the
+/// given repo-relative paths. Untracked directories are listed once rather
+/// than file by file, as in `git status`.
+pub fn untracked_entries_excluding(repo_root: &Path, excluded: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let excludes = exclude_pathspecs(excluded);
+    let mut args = vec!["status", "--porcelain", "-z", "--ignored", "--", "."];
+    args.extend(excludes.iter().map(String::as_str));
+    let mut entries: Vec<PathBuf> = run(&args, Some(repo_root))?
+        .split('\0')
+        .filter_map(|line| {
+            line.strip_prefix("?? ")
+                .or_else(|| line.strip_prefix("!! "))
+        })
+        .map(|path| PathBuf::from(path.trim_end_matches('/')))
+        .collect();
+    entries.sort();
+    Ok(entries)
+}
+
+fn exclude_pathspecs(excluded: &[PathBuf]) -> Vec<String> {
+    excluded
+        .iter()
+        .map(|path| format!(":(exclude){}", pathspec(path)))
+        .collect()
+}
+
+/// A repo-relative path as a git pathspec, with `/` separators on every
+/// platform.
+pub fn pathspec(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn fetch_prune(repo_root: &Path, options: &FetchOptions) {
+    let mut args = vec!["fetch".to_string(), "--prune".to_string()];
+    args.extend(fetch_flags(options));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let _ = run_remote(&args, Some(repo_root));
+}
+
+fn fetch_flags(options: &FetchOptions) -> Vec<String> {
+    let mut flags = Vec::new();
+    if !options.tags {
+        flags.push("--no-tags".to_string());
+    }
+    if let Some(jobs) = options.jobs {
+        flags.push(format!("--jobs={jobs}"));
+    }
+    flags
+}
+
+/// Fetches only `branches` from origin into their remote-tracking refs.
+/// Branches origin no longer has are pruned by hand: an explicit refspec
+/// for a missing branch fails the whole fetch, so they are dropped one by
+/// one as git names them.
+pub fn fetch_prune_branches(repo_root: &Path, branches: &[String], options: &FetchOptions) {
+    let mut remaining: Vec<&str> = branches.iter().map(String::as_str).collect();
+    while !remaining.is_empty() {
+        let mut args = vec!["fetch".to_string()];
+        args.extend(fetch_flags(options));
+        args.push("origin".to_string());
+        args.extend(
+            remaining
+                .iter()
+                .map(|branch| format!("+refs/heads/{branch}:refs/remotes/origin/{branch}")),
+        );
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let Err(err) = run_remote(&args, Some(repo_root)) else {
+            return;
+        };
+        let message = err.to_string();
+        let Some(missing) = message
+            .split("couldn't find remote ref ")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+        else {
+            return;
+        };
+        let missing = missing.strip_prefix("refs/heads/").unwrap_or(missing);
+        let before = remaining.len();
+        remaining.retain(|branch| *branch != missing);
+        if remaining.len() == before {
+            return;
+        }
+        let tracking = format!("refs/remotes/origin/{missing}");
+        let _ = run(&["update-ref", "-d", &tracking], Some(repo_root));
+    }
+}
+
+/// The branches on origin that `branches` track, by name on origin.
+pub fn origin_upstream_branches(repo_root: &Path, branches: &[&str]) -> Vec<String> {
+    let Some(output) = try_run(
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%09%(upstream:remotename)%09%(upstream:remoteref)",
+            "refs/heads",
+        ],
+        Some(repo_root),
+    ) else {
+        return Vec::new();
+    };
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let (branch, remote, remote_ref) = (fields.next()?, fields.next()?, fields.next()?);
+            if remote != "origin" || !branches.contains(&branch) {
+                return None;
+            }
+            remote_ref.strip_prefix("refs/heads/").map(str::to_string)
+        })
+        .collect()
 }
 
 pub fn worktree_add(repo_root: &Path, path: &Path, branch: &str, base: Option<&str>) -> Result<()> {
@@ -261,6 +1693,71 @@ pub fn worktree_add(repo_root: &Path, path: &Path, branch: &str, base: Option<&s
     Ok(())
 }
 
+/// Registers a worktree like [`worktree_add`] without checking out any
+/// files, leaving only the `.git` file in the new directory and no index.
+pub fn worktree_add_no_checkout(
+    repo_root: &Path,
+    path: &Path,
+    branch: &str,
+    base: Option<&str>,
+) -> Result<()> {
+    ensure_worktree_parent(path)?;
+    let path_s = path.to_string_lossy().to_string();
+    if let Some(base) = base {
+        run(
+            &[
+                "worktree",
+                "add",
+                "--no-checkout",
+                "-b",
+                branch,
+                &path_s,
+                base,
+            ],
+            Some(repo_root),
+        )?;
+    } else {
+        run(
+            &["worktree", "add", "--no-checkout", &path_s, branch],
+            Some(repo_root),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes HEAD's files and index into a worktree added with `--no-checkout`,
+/// sending git's progress (`Updating files: 45% (...)`) to `progress`.
+pub fn checkout_files(worktree_path: &Path, progress: fs::File) -> Result<()> {
+    let status = ops().run_to(
+        &["checkout", "--progress", "--force"],
+        Some(worktree_path),
+        progress,
+    )?;
+    if !status.success() {
+        return Err(anyhow!(
+            "git checkout failed in {} ({status})",
+            worktree_path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Adds a worktree at `path` with `commit` checked out on a detached HEAD.
+pub fn worktree_add_detached(repo_root: &Path, path: &Path, commit: &str) -> Result<()> {
+    ensure_worktree_parent(path)?;
+    let path_s = path.to_string_lossy().to_string();
+    run(
+        &["worktree", "add", "--detach", &path_s, commit],
+        Some(repo_root),
+    )?;
+    Ok(())
+}
+
+pub fn checkout_detached(worktree_path: &Path, commit: &str) -> Result<()> {
+    run(&["checkout", "--detach", commit], Some(worktree_path))?;
+    Ok(())
+}
+
 pub fn worktree_remove(repo_root: &Path, path: &Path) -> Result<()> {
     let path_s = path.to_string_lossy().to_string();
     run(&["worktree", "remove", "--force", &path_s], Some(repo_root))?;
@@ -275,11 +1772,143 @@ pub fn worktree_move(repo_root: &Path, src: &Path, dest: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Commits the full contents of `worktree_path` (tracked changes and untracked
+/// files, not ignored ones) on top of `parent` and points `ref_name` at it.
+/// Uses a throwaway index so the worktree itself is left untouched.
+pub fn snapshot_worktree(
+    repo_root: &Path,
+    worktree_path: &Path,
+    parent: Option<&str>,
+    message: &str,
+    ref_name: &str,
+) -> Result<String> {
+    let index = std::env::temp_dir().join(format!("gw-snapshot-{}.index", std::process::id()));
+    let envs = [("GIT_INDEX_FILE", index.as_os_str())];
+    let result = (|| -> Result<String> {
+        run_with_env(&["add", "--all", "."], Some(worktree_path), &envs)?;
+        let tree = run_with_env(&["write-tree"], Some(worktree_path), &envs)?;
+        let mut args = vec!["commit-tree", tree.as_str(), "-m", message];
+        if let Some(parent) = parent {
+            args.extend(["-p", parent]);
+        }
+        let commit = run(&args, Some(repo_root))?;
+        run(&["update-ref", ref_name, &commit], Some(repo_root))?;
+        Ok(commit)
+    })();
+    let _ = fs::remove_file(&index);
+    result
+}
+
+/// `(ref name, commit, relative creation date)` for every ref under `prefix`.
+pub fn list_refs(repo_root: &Path, prefix: &str) -> Result<Vec<(String, String, String)>> {
+    let output = run(
+        &[
+            "for-each-ref",
+            "--sort=-creatordate",
+            "--format=%(refname)%09%(objectname)%09%(creatordate:relative)",
+            prefix,
+        ],
+        Some(repo_root),
+    )?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            Some((
+                parts.next()?.to_string(),
+                parts.next()?.to_string(),
+                parts.next()?.to_string(),
+            ))
+        })
+        .collect())
+}
+
+pub fn commit_message(repo_root: &Path, commit: &str) -> Result<String> {
+    run(&["log", "-1", "--format=%B", commit], Some(repo_root))
+}
+
+pub fn commit_parent(repo_root: &Path, commit: &str) -> Option<String> {
+    try_run(
+        &["rev-parse", "--verify", "--quiet", &format!("{commit}^")],
+        Some(repo_root),
+    )
+}
+
+pub fn update_ref(repo_root: &Path, ref_name: &str, commit: &str) -> Result<()> {
+    run(&["update-ref", ref_name, commit], Some(repo_root))?;
+    Ok(())
+}
+
+pub fn delete_ref(repo_root: &Path, ref_name: &str) -> Result<()> {
+    run(&["update-ref", "-d", ref_name], Some(repo_root))?;
+    Ok(())
+}
+
+/// Makes the files in `worktree_path` match `source` without touching the
+/// index, so files that were untracked in the snapshot come back untracked.
+pub fn restore_worktree_files(worktree_path: &Path, source: &str) -> Result<()> {
+    let source_arg = format!("--source={source}");
+    run(
+        &["restore", &source_arg, "--worktree", "--", ":/"],
+        Some(worktree_path),
+    )?;
+    Ok(())
+}
+
+/// Rebuilds the index of `worktree_path` from HEAD, leaving its files alone.
+pub fn reset_index(worktree_path: &Path) -> Result<()> {
+    run(&["reset", "--quiet"], Some(worktree_path))?;
+    Ok(())
+}
+
 pub fn branch_delete(repo_root: &Path, branch: &str) -> Result<()> {
     run(&["branch", "-D", branch], Some(repo_root))?;
     Ok(())
 }
 
+pub fn branch_create(repo_root: &Path, branch: &str, start_point: &str) -> Result<()> {
+    run(&["branch", branch, start_point], Some(repo_root))?;
+    Ok(())
+}
+
+pub fn switch_branch(worktree_path: &Path, branch: &str) -> Result<()> {
+    run(&["switch", branch], Some(worktree_path))?;
+    Ok(())
+}
+
+/// Creates `branch` at the worktree's HEAD and switches to it.
+pub fn switch_create(worktree_path: &Path, branch: &str) -> Result<()> {
+    run(&["switch", "-c", branch], Some(worktree_path))?;
+    Ok(())
+}
+
+/// Closest tag reachable from `commit` (`git describe --tags --abbrev=0`).
+pub fn nearest_tag(repo_root: &Path, commit: &str) -> Option<String> {
+    try_run(
+        &["describe", "--tags", "--abbrev=0", commit],
+        Some(repo_root),
+    )
+}
+
+/// Whether a branch, tag or remote-tracking branch contains `commit`, so
+/// that removing a worktree with it checked out loses no commits.
+pub fn commit_on_any_ref(repo_root: &Path, commit: &str) -> bool {
+    try_run(
+        &[
+            "for-each-ref",
+            "--count=1",
+            "--contains",
+            commit,
+            "--format=%(refname)",
+            "refs/heads",
+            "refs/tags",
+            "refs/remotes",
+        ],
+        Some(repo_root),
+    )
+    .is_some_and(|refs| !refs.is_empty())
+}
+
 pub fn branch_rename(repo_root: &Path, old_name: &str, new_name: &str) -> Result<()> {
     run(&["branch", "-m", old_name, new_name], Some(repo_root))?;
     Ok(())
@@ -295,23 +1924,263 @@ pub fn branch_set_upstream(repo_root: &Path, branch: &str, upstream: &str) -> Re
 
 pub fn fetch_branch(repo_root: &Path, branch: &str) -> Result<()> {
     let spec = format!("{branch}:{branch}");
-    run(&["fetch", "origin", &spec], Some(repo_root))?;
+    run_remote(&["fetch", "origin", &spec], Some(repo_root))?;
     Ok(())
 }
 
-pub fn pull(worktree_path: &Path) -> Result<()> {
-    run(&["pull"], Some(worktree_path))?;
+/// Git subcommands that only read the repository.
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "blame",
+    "cat-file",
+    "cherry",
+    "describe",
+    "diff",
+    "for-each-ref",
+    "grep",
+    "help",
+    "log",
+    "ls-files",
+    "ls-tree",
+    "merge-base",
+    "name-rev",
+    "rev-list",
+    "rev-parse",
+    "shortlog",
+    "show",
+    "show-branch",
+    "show-ref",
+    "status",
+    "version",
+    "whatchanged",
+];
+/// Flags that keep `git branch` and `git tag` listing.
+const LISTING_FLAGS: &[&str] = &[
+    "-a",
+    "--all",
+    "-r",
+    "--remotes",
+    "-v",
+    "-vv",
+    "--verbose",
+    "--merged",
+    "--no-merged",
+    "--show-current",
+];
+
+/// Long options that make otherwise read-only commands write a file or run a
+/// program: `log`/`diff`/`show --output=<file>`, `--ext-diff` drivers and
+/// `grep --open-files-in-pager`.
+const WRITING_OR_RUNNING_FLAGS: &[&str] = &["--output", "--ext-diff", "--open-files-in-pager"];
+
+/// Whether `rest`, the arguments of `git <subcommand>`, ask it to write a
+/// file or run a program. Unique abbreviations of the long options count,
+/// as git accepts them, and so does `grep -O[<pager>]`, also bundled with
+/// other short flags.
+fn writes_files_or_runs_programs(subcommand: &str, rest: &[&str]) -> bool {
+    rest.iter().any(|arg| {
+        if let Some(name) = arg.strip_prefix("--") {
+            let name = name.split('=').next().unwrap_or_default();
+            return !name.is_empty()
+                && WRITING_OR_RUNNING_FLAGS
+                    .iter()
+                    .any(|flag| flag[2..].starts_with(name));
+        }
+        subcommand == "grep"
+            && arg
+                .strip_prefix('-')
+                .is_some_and(|flags| flags.contains('O'))
+    })
+}
+
+/// Whether `git <args>` only reads the repository: a read-only subcommand,
+/// or the listing forms of `branch`, `tag`, `stash`, `remote`, `worktree`,
+/// `config` and `reflog`, without flags that write files or run programs.
+pub fn is_read_only_command(args: &[String]) -> bool {
+    let Some((command, rest)) = args.split_first() else {
+        return true;
+    };
+    let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+    if writes_files_or_runs_programs(command, &rest) {
+        return false;
+    }
+    match command.as_str() {
+        "branch" | "tag" => {
+            rest.iter().any(|arg| matches!(*arg, "-l" | "--list"))
+                || rest.iter().all(|arg| LISTING_FLAGS.contains(arg))
+        }
+        "stash" => matches!(rest.first(), Some(&"list" | &"show")),
+        "remote" => matches!(rest.first(), None | Some(&"-v" | &"show" | &"get-url")),
+        "worktree" => rest.first() == Some(&"list"),
+        "config" => rest.iter().any(|arg| {
+            matches!(
+                *arg,
+                "--get" | "--get-all" | "--get-regexp" | "-l" | "--list"
+            )
+        }),
+        "reflog" => !matches!(rest.first(), Some(&"expire" | &"delete")),
+        command => READ_ONLY_COMMANDS.contains(&command),
+    }
+}
+
+/// Runs a step of a preset in the worktree, with the same guards against
+/// prompts as other commands that may talk to a remote.
+pub fn run_preset_step(worktree_path: &Path, args: &[String]) -> Result<String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    run_remote(&args, Some(worktree_path))
+}
+
+/// Runs `git <args>` as typed by the user in the worktree, without a
+/// terminal: credential prompts and editors fail instead of waiting.
+/// Returns whether it succeeded, and its stdout followed by its stderr.
+pub fn run_user_command(worktree_path: &Path, args: &[String]) -> Result<(bool, String)> {
+    let limit = timeout(&LOCAL_TIMEOUT_SECS);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let envs: [(&str, &OsStr); 3] = [
+        ("GIT_TERMINAL_PROMPT", OsStr::new("0")),
+        ("GIT_EDITOR", OsStr::new("false")),
+        ("GIT_PAGER", OsStr::new("cat")),
+    ];
+    let output = ops()
+        .output(&args, Some(worktree_path), &envs, limit)?
+        .ok_or_else(|| GwError::TimedOut {
+            detail: format!(
+                "git {} timed out after {}s",
+                fmt_args(&args),
+                limit.map(|limit| limit.as_secs()).unwrap_or_default()
+            ),
+        })?;
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), text))
+}
+
+/// The commit `rev` resolves to, if any.
+pub fn resolve_commit(repo_root: &Path, rev: &str) -> Option<String> {
+    let spec = format!("{rev}^{{commit}}");
+    try_run(
+        &["rev-parse", "--verify", "--quiet", &spec],
+        Some(repo_root),
+    )
+}
+
+/// Whether everything `branch` changed since it forked from `base` has
+/// landed on `base` under other commits: each of its commits was rebased or
+/// cherry-picked there, or the whole branch was squashed into one commit.
+/// Compares patch ids, so line numbers may have moved.
+pub fn changes_landed(repo_root: &Path, base: &str, branch: &str) -> bool {
+    let Some(cherry) = try_run(&["cherry", base, branch], Some(repo_root)) else {
+        return false;
+    };
+    if cherry.lines().all(|line| line.starts_with('-')) {
+        return true;
+    }
+
+    // Squash the branch into a throwaway commit on its merge base and look
+    // for an equivalent one on `base`.
+    let Some(merge_base) = try_run(&["merge-base", base, branch], Some(repo_root)) else {
+        return false;
+    };
+    let tree = format!("{branch}^{{tree}}");
+    let identity: &[(&str, &OsStr)] = &[
+        ("GIT_AUTHOR_NAME", OsStr::new("gw")),
+        ("GIT_AUTHOR_EMAIL", OsStr::new("gw@localhost")),
+        ("GIT_COMMITTER_NAME", OsStr::new("gw")),
+        ("GIT_COMMITTER_EMAIL", OsStr::new("gw@localhost")),
+    ];
+    let Ok(squashed) = run_with_env(
+        &["commit-tree", &tree, "-p", &merge_base, "-m", "squash"],
+        Some(repo_root),
+        identity,
+    ) else {
+        return false;
+    };
+    try_run(&["cherry", base, &squashed], Some(repo_root)).is_some_and(|out| out.starts_with('-'))
+}
+
+/// The commit `tag` points to, if the tag exists locally.
+pub fn tag_commit(repo_root: &Path, tag: &str) -> Option<String> {
+    let spec = format!("refs/tags/{tag}^{{commit}}");
+    try_run(
+        &["rev-parse", "--verify", "--quiet", &spec],
+        Some(repo_root),
+    )
+}
+
+/// Fetches `remote_ref` from origin into `local_ref`, overwriting it, e.g. a
+/// pull request's head into a ref of gw's own.
+pub fn fetch_ref(repo_root: &Path, remote_ref: &str, local_ref: &str) -> Result<()> {
+    let spec = format!("+{remote_ref}:{local_ref}");
+    run_remote(&["fetch", "origin", &spec], Some(repo_root))?;
+    Ok(())
+}
+
+pub fn pull(worktree_path: &Path, strategy: Option<PullStrategy>) -> Result<()> {
+    let mut args = vec!["pull"];
+    args.extend(strategy.map(PullStrategy::git_flag));
+    run_remote(&args, Some(worktree_path))?;
     Ok(())
 }
 
 pub fn push(worktree_path: &Path) -> Result<()> {
-    run(&["push"], Some(worktree_path))?;
+    run_push(&["push"], worktree_path)
+}
+
+/// Overwrites the upstream branch, but only if it is still where the last
+/// fetch saw it.
+pub fn push_force_with_lease(worktree_path: &Path) -> Result<()> {
+    run_push(&["push", "--force-with-lease"], worktree_path)
+}
+
+/// Runs a push, telling a non-fast-forward rejection caused by rewritten
+/// local commits (which needs a force-push) from one caused by new upstream
+/// commits (which needs a pull).
+fn run_push(args: &[&str], worktree_path: &Path) -> Result<()> {
+    let Err(err) = run_remote(args, Some(worktree_path)) else {
+        return Ok(());
+    };
+    match errors::classify(&err) {
+        Some(GwError::PushRejected {
+            reason: PushRejection::NeedsPull,
+            detail,
+        }) if detail.contains("(non-fast-forward)") && upstream_rewritten(worktree_path) => {
+            Err(GwError::PushRejected {
+                reason: PushRejection::NeedsForce,
+                detail: detail.clone(),
+            }
+            .into())
+        }
+        _ => Err(err),
+    }
+}
+
+/// Whether the upstream commits missing from HEAD were replaced locally:
+/// the upstream tip was checked out in this worktree before, or every one of
+/// those commits has a patch-equivalent in HEAD.
+fn upstream_rewritten(worktree_path: &Path) -> bool {
+    let Some(upstream) = try_run(&["rev-parse", "@{upstream}"], Some(worktree_path)) else {
+        return false;
+    };
+    let reflog = try_run(
+        &["log", "-g", "-n", "1000", "--format=%H", "HEAD"],
+        Some(worktree_path),
+    )
+    .unwrap_or_default();
+    if reflog.lines().any(|sha| sha == upstream) {
+        return true;
+    }
+    match try_run(&["cherry", "HEAD", &upstream], Some(worktree_path)) {
+        Some(out) => !out.is_empty() && out.lines().all(|line| line.starts_with('-')),
+        None => false,
+    }
+}
+
+pub fn rebase_abort(worktree_path: &Path) -> Result<()> {
+    run(&["rebase", "--abort"], Some(worktree_path))?;
     Ok(())
 }
 
 pub fn push_set_upstream(worktree_path: &Path, branch: &str) -> Result<()> {
-    run(&["push", "-u", "origin", branch], Some(worktree_path))?;
-    Ok(())
+    run_push(&["push", "-u", "origin", branch], worktree_path)
 }
 
 pub fn set_bare(repo_root: &Path) -> Result<()> {
@@ -326,6 +2195,30 @@ pub fn ensure_worktree_parent(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Removes directories left empty between `path` and `base`, such as the
+/// `feat/` prefix directory after deleting or renaming `feat/login`.
+pub fn remove_empty_parents(base: &Path, path: &Path) {
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if dir == base || !dir.starts_with(base) {
+            break;
+        }
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+/// A worktree can be created at `path` if nothing is there yet or if it is an
+/// empty directory (e.g. a leftover branch prefix directory).
+pub fn is_path_available(path: &Path) -> bool {
+    match fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(_) => fs::symlink_metadata(path).is_err(),
+    }
+}
+
 pub fn worktree_branch_map(repo_root: &Path) -> Result<HashMap<String, PathBuf>> {
     let mut mapping = HashMap::new();
     for wt in parse_worktrees(Some(repo_root))? {
@@ -336,30 +2229,30 @@ pub fn worktree_branch_map(repo_root: &Path) -> Result<HashMap<String, PathBuf>>
     Ok(mapping)
 }
 
+/// Returns the repo-root-relative paths that must survive `gw init` staging:
+/// `.git`, `.gw`, and every existing linked worktree. Worktrees of slashed
+/// branches are returned as nested paths (e.g. `feat/login`) so only that
+/// subtree is kept rather than the whole `feat` directory.
 pub fn get_entries_to_preserve(
     repo_root: &Path,
     worktree_paths: &[PathBuf],
-) -> Result<Vec<String>> {
-    let mut keep = vec![".git".to_string(), ".gw".to_string()];
-    let repo_abs = repo_root
-        .canonicalize()
-        .unwrap_or_else(|_| repo_root.to_path_buf());
+) -> Result<Vec<PathBuf>> {
+    let mut keep = vec![PathBuf::from(".git"), PathBuf::from(".gw")];
+    let repo_abs = canonicalize(repo_root);
 
     for path in worktree_paths {
-        let abs_path = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let abs_path = canonicalize(path);
         if abs_path == repo_abs {
             continue;
         }
-        if !abs_path.starts_with(&repo_abs) {
-            continue;
-        }
 
         if let Ok(rel) = abs_path.strip_prefix(&repo_abs) {
-            if let Some(first) = rel.components().next() {
-                let entry = first.as_os_str().to_string_lossy().to_string();
-                if !entry.is_empty() && !keep.contains(&entry) {
-                    keep.push(entry);
-                }
+            if rel.as_os_str().is_empty() {
+                continue;
+            }
+            let rel = rel.to_path_buf();
+            if !keep.contains(&rel) {
+                keep.push(rel);
             }
         }
     }
@@ -367,3 +2260,153 @@ pub fn get_entries_to_preserve(
     keep.sort();
     Ok(keep)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_only(command: &str) -> bool {
+        let args: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+        is_read_only_command(&args)
+    }
+
+    fn changes(command: &str) -> bool {
+        let args: Vec<&str> = command.split_whitespace().collect();
+        changes_repo(&args)
+    }
+
+    #[test]
+    fn reading_commands_are_read_only() {
+        for command in [
+            "log --oneline -5",
+            "diff --stat",
+            "show HEAD",
+            "grep -n foo",
+            "branch",
+            "branch --merged",
+            "stash list",
+            "config --get user.name",
+            "diff --no-ext-diff",
+        ] {
+            assert!(read_only(command), "{command}");
+            assert!(!changes(command), "{command}");
+        }
+    }
+
+    #[test]
+    fn changing_commands_are_not_read_only() {
+        for command in [
+            "commit -am wip",
+            "branch -D old",
+            "stash pop",
+            "config user.name x",
+        ] {
+            assert!(!read_only(command), "{command}");
+            assert!(changes(command), "{command}");
+        }
+    }
+
+    #[test]
+    fn output_files_and_external_programs_are_not_read_only() {
+        for command in [
+            "log --output=/tmp/x",
+            "log --output /tmp/x",
+            "diff --outp=/tmp/x",
+            "show --output=/home/me/.bashrc HEAD",
+            "diff --ext-diff",
+            "show --ext HEAD",
+            "grep -Ovim foo",
+            "grep -O foo",
+            "grep -niO foo",
+            "grep --open-files-in-pager=sh foo",
+            "grep --open foo",
+        ] {
+            assert!(!read_only(command), "{command}");
+            assert!(changes(command), "{command}");
+        }
+    }
+
+    #[test]
+    fn global_options_before_the_subcommand_are_skipped() {
+        assert!(!changes("-C /tmp log --oneline"));
+        assert!(changes("-C /tmp log --output=/tmp/x"));
+    }
+
+    #[test]
+    fn worktree_scan_skips_registered_worktrees_repos_and_build_dirs() {
+        let root = std::env::temp_dir().join(format!("gw-scan-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in [
+            "moved",
+            "registered/inner",
+            "feat/nested",
+            "node_modules/pkg",
+            "target/debug",
+            "vendored/.git",
+            "vendored/sub",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for dir in [
+            "moved",
+            "registered",
+            "registered/inner",
+            "feat/nested",
+            "node_modules/pkg",
+            "target/debug",
+            "vendored/sub",
+        ] {
+            fs::write(root.join(dir).join(".git"), "gitdir: /nowhere\n").unwrap();
+        }
+        let registered = HashSet::from([canonicalize(&root.join("registered"))]);
+
+        let mut found = Vec::new();
+        collect_linked_worktree_dirs(&root, 0, &registered, &mut found);
+        found.sort();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(found, vec![root.join("feat/nested"), root.join("moved")]);
+    }
+
+    struct Recorder {
+        calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl GitOps for Recorder {
+        fn output(
+            &self,
+            args: &[&str],
+            _cwd: Option<&Path>,
+            _envs: &[(&str, &OsStr)],
+            _limit: Option<Duration>,
+        ) -> io::Result<Option<Output>> {
+            self.calls.lock().unwrap().push(args.join(" "));
+            Ok(Some(Output {
+                status: ExitStatus::default(),
+                stdout: Vec::new(),
+                stderr: b"Updating files: 100% (3/3), done.\n".to_vec(),
+            }))
+        }
+    }
+
+    #[test]
+    fn checkout_files_goes_through_the_installed_ops() {
+        let _fakes = lock_fakes();
+        let recorder = Arc::new(Recorder {
+            calls: std::sync::Mutex::new(Vec::new()),
+        });
+        set_git_ops(Some(recorder.clone()));
+        let log = std::env::temp_dir().join(format!("gw-checkout-test-{}", std::process::id()));
+        let result = checkout_files(Path::new("/nonexistent"), fs::File::create(&log).unwrap());
+
+        result.unwrap();
+        assert_eq!(
+            *recorder.calls.lock().unwrap(),
+            ["checkout --progress --force"]
+        );
+        assert!(fs::read_to_string(&log)
+            .unwrap()
+            .contains("Updating files: 100%"));
+        let _ = fs::remove_file(&log);
+    }
+}
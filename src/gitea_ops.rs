@@ -0,0 +1,203 @@
+use crate::forge::parse_timestamp;
+use crate::gh_ops::classify_checks;
+use crate::models::{ChecksInfo, PullRequestInfo};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// PRs per page; Gitea caps `limit` at 50 by default.
+const PAGE_SIZE: usize = 50;
+/// Gitea lists the newest PRs first, so branches whose latest PR is older than
+/// this many pages are treated as having none.
+const MAX_PAGES: usize = 10;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Deserialize)]
+struct PullNode {
+    number: i64,
+    state: Option<String>,
+    #[serde(default)]
+    merged: bool,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    title: String,
+    html_url: Option<String>,
+    user: Option<User>,
+    created_at: Option<String>,
+    merged_at: Option<String>,
+    base: Option<BranchRef>,
+    head: BranchRef,
+}
+
+#[derive(Debug, Deserialize)]
+struct BranchRef {
+    #[serde(rename = "ref")]
+    name: String,
+    sha: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CombinedStatus {
+    #[serde(default)]
+    statuses: Vec<CommitStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitStatus {
+    status: Option<String>,
+}
+
+/// A Gitea/Forgejo repository reached through its REST API.
+pub struct GiteaClient {
+    agent: ureq::Agent,
+    api_base: String,
+    token: String,
+}
+
+impl GiteaClient {
+    /// `base_url` is the web root of the instance, e.g. `https://codeberg.org`.
+    pub fn new(base_url: &str, owner_repo: &str, token: &str) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .build()
+            .into();
+        Self {
+            agent,
+            api_base: format!(
+                "{}/api/v1/repos/{owner_repo}",
+                base_url.trim_end_matches('/')
+            ),
+            token: token.to_string(),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Option<T> {
+        let mut request = self
+            .agent
+            .get(format!("{}{path}", self.api_base))
+            .header("Authorization", format!("token {}", self.token))
+            .header("Accept", "application/json");
+        for (key, value) in query {
+            request = request.query(*key, value);
+        }
+        request.call().ok()?.body_mut().read_json().ok()
+    }
+
+    /// Latest PR (any state) and its commit statuses for each of `branches`,
+    /// keyed by head branch. Returns `None` if listing PRs fails.
+    pub fn get_prs_for_branches(
+        &self,
+        branches: &[String],
+    ) -> Option<HashMap<String, (PullRequestInfo, ChecksInfo)>> {
+        let mut latest: HashMap<String, PullNode> = HashMap::new();
+        for page in 1..=MAX_PAGES {
+            let nodes: Vec<PullNode> = self.get(
+                "/pulls",
+                &[
+                    ("state", "all".to_string()),
+                    ("limit", PAGE_SIZE.to_string()),
+                    ("page", page.to_string()),
+                ],
+            )?;
+            let last_page = nodes.len() < PAGE_SIZE;
+            for node in nodes {
+                if !branches.contains(&node.head.name) {
+                    continue;
+                }
+                let newer = latest
+                    .get(&node.head.name)
+                    .is_none_or(|seen| node.number > seen.number);
+                if newer {
+                    latest.insert(node.head.name.clone(), node);
+                }
+            }
+            if last_page || latest.len() == branches.len() {
+                break;
+            }
+        }
+
+        let prs = latest
+            .into_iter()
+            .map(|(branch, node)| {
+                let checks = node
+                    .head
+                    .sha
+                    .as_deref()
+                    .and_then(|sha| self.get_checks(sha))
+                    .unwrap_or_else(|| classify_checks(&[], &[]));
+                (branch, (pr_from_node(node), checks))
+            })
+            .collect();
+        Some(prs)
+    }
+
+    pub fn get_issue_title(&self, number: u64) -> Option<String> {
+        let issue: Issue = self.get(&format!("/issues/{number}"), &[])?;
+        Some(issue.title)
+    }
+
+    /// `OPEN`, `CLOSED` or `MERGED`, like the other forges report it.
+    pub fn get_pr_state(&self, number: u64) -> Option<String> {
+        let node: PullNode = self.get(&format!("/pulls/{number}"), &[])?;
+        Some(pr_from_node(node).state)
+    }
+
+    fn get_checks(&self, sha: &str) -> Option<ChecksInfo> {
+        let combined: CombinedStatus = self.get(&format!("/commits/{sha}/status"), &[])?;
+        let (conclusions, states): (Vec<Option<String>>, Vec<Option<String>>) =
+            combined.statuses.iter().map(status_outcome).unzip();
+        Some(classify_checks(&conclusions, &states))
+    }
+}
+
+fn pr_from_node(node: PullNode) -> PullRequestInfo {
+    let state = if node.merged {
+        "MERGED"
+    } else if node.state.as_deref() == Some("closed") {
+        "CLOSED"
+    } else {
+        "OPEN"
+    };
+    // Older Gitea versions have no draft flag and mark drafts by title prefix.
+    let marker = node.title.to_ascii_uppercase();
+    let is_draft = node.draft || marker.starts_with("WIP:") || marker.starts_with("[WIP]");
+
+    PullRequestInfo {
+        number: node.number,
+        state: state.to_string(),
+        base: node.base.map(|base| base.name),
+        url: node.html_url,
+        is_draft,
+        review_decision: None,
+        title: Some(node.title).filter(|title| !title.is_empty()),
+        author: node.user.map(|user| user.login),
+        created_ts: node.created_at.as_deref().and_then(parse_timestamp),
+        merged_ts: node.merged_at.as_deref().and_then(parse_timestamp),
+        unresolved_threads: None,
+    }
+}
+
+/// `(conclusion, state)` of a commit status in the check run terms
+/// `classify_checks` uses. Warnings count as neutral.
+fn status_outcome(status: &CommitStatus) -> (Option<String>, Option<String>) {
+    let completed =
+        |conclusion: &str| (Some(conclusion.to_string()), Some("COMPLETED".to_string()));
+    match status.status.as_deref() {
+        Some("success") => completed("SUCCESS"),
+        Some("warning") => completed("NEUTRAL"),
+        Some("failure") | Some("error") => completed("FAILURE"),
+        _ => (None, Some("IN_PROGRESS".to_string())),
+    }
+}
@@ -0,0 +1,276 @@
+use crate::errors::GwError;
+use crate::forge::parse_timestamp;
+use crate::gh_ops::classify_checks;
+use crate::logging;
+use crate::models::{ChecksInfo, PullRequestInfo};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Branches per GraphQL request. Each request asks for up to 100 MRs, which
+/// leaves room for branches with several (older) MRs.
+const GRAPHQL_BATCH_SIZE: usize = 20;
+
+const MR_FIELDS: &str = "iid state draft sourceBranch targetBranch webUrl mergedAt \
+    title author { username } createdAt \
+    discussions(first: 100) { nodes { resolvable resolved } } \
+    headPipeline { jobs(first: 100) { nodes { status allowFailure } } }";
+
+#[derive(Debug, Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MrNode {
+    iid: String,
+    state: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    source_branch: String,
+    target_branch: Option<String>,
+    web_url: Option<String>,
+    merged_at: Option<String>,
+    title: Option<String>,
+    author: Option<Author>,
+    created_at: Option<String>,
+    discussions: Option<Nodes<Discussion>>,
+    head_pipeline: Option<Pipeline>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Discussion {
+    #[serde(default)]
+    resolvable: bool,
+    #[serde(default)]
+    resolved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Pipeline {
+    jobs: Option<Nodes<Job>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Job {
+    status: Option<String>,
+    #[serde(default)]
+    allow_failure: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    data: Option<ResponseData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResponseData {
+    project: Option<Project>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Project {
+    merge_requests: Nodes<MrNode>,
+}
+
+fn run_glab(args: &[&str], repo_root: &Path) -> Option<String> {
+    let output = logging::output(Command::new("glab").args(args).current_dir(repo_root)).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Latest MR (any state) and its head pipeline jobs for each of `branches`,
+/// keyed by source branch. Returns `None` if a query fails.
+pub fn get_mrs_for_branches(
+    repo_root: &Path,
+    project_path: &str,
+    branches: &[String],
+) -> Option<HashMap<String, (PullRequestInfo, ChecksInfo)>> {
+    let mut mrs = HashMap::new();
+    for chunk in branches.chunks(GRAPHQL_BATCH_SIZE) {
+        let literals: Vec<String> = chunk
+            .iter()
+            .map(|branch| serde_json::to_string(branch).unwrap_or_default())
+            .collect();
+        let query = format!(
+            "query {{ project(fullPath: {path}) {{ mergeRequests(sourceBranches: [{branches}], \
+             sort: CREATED_DESC, first: 100) {{ nodes {{ {MR_FIELDS} }} }} }} }}",
+            path = serde_json::to_string(project_path).ok()?,
+            branches = literals.join(", "),
+        );
+
+        let query_arg = format!("query={query}");
+        let stdout = run_glab(&["api", "graphql", "-f", &query_arg], repo_root)?;
+        let response: Response = serde_json::from_str(&stdout).ok()?;
+        let project = response.data?.project?;
+
+        // Newest first, so the first MR seen for a branch wins.
+        for node in project.merge_requests.nodes {
+            if mrs.contains_key(&node.source_branch) {
+                continue;
+            }
+            let branch = node.source_branch.clone();
+            if let Some(mr) = mr_from_node(node) {
+                mrs.insert(branch, mr);
+            }
+        }
+    }
+    Some(mrs)
+}
+
+fn mr_from_node(node: MrNode) -> Option<(PullRequestInfo, ChecksInfo)> {
+    let state = if node.merged_at.is_some() {
+        "MERGED"
+    } else {
+        match node.state.as_deref() {
+            Some("merged") => "MERGED",
+            Some("closed") | Some("locked") => "CLOSED",
+            _ => "OPEN",
+        }
+    };
+
+    let jobs = node
+        .head_pipeline
+        .and_then(|pipeline| pipeline.jobs)
+        .map(|jobs| jobs.nodes)
+        .unwrap_or_default();
+    let (conclusions, states): (Vec<Option<String>>, Vec<Option<String>>) =
+        jobs.iter().map(job_outcome).unzip();
+
+    let mr = PullRequestInfo {
+        number: node.iid.parse().ok()?,
+        state: state.to_string(),
+        base: node.target_branch,
+        url: node.web_url,
+        is_draft: node.draft,
+        review_decision: None,
+        title: node.title,
+        author: node.author.map(|author| author.username),
+        created_ts: node.created_at.as_deref().and_then(parse_timestamp),
+        merged_ts: node.merged_at.as_deref().and_then(parse_timestamp),
+        unresolved_threads: node.discussions.map(|discussions| {
+            discussions
+                .nodes
+                .iter()
+                .filter(|discussion| discussion.resolvable && !discussion.resolved)
+                .count() as i64
+        }),
+    };
+    Some((mr, classify_checks(&conclusions, &states)))
+}
+
+/// `(conclusion, state)` of a CI job in the check run terms `classify_checks`
+/// uses. Manual jobs count as skipped, allowed failures as neutral.
+fn job_outcome(job: &Job) -> (Option<String>, Option<String>) {
+    let completed =
+        |conclusion: &str| (Some(conclusion.to_string()), Some("COMPLETED".to_string()));
+    match job.status.as_deref() {
+        Some("SUCCESS") => completed("SUCCESS"),
+        Some("SKIPPED") | Some("MANUAL") => completed("SKIPPED"),
+        Some("FAILED") if job.allow_failure => completed("NEUTRAL"),
+        Some("FAILED") => completed("FAILURE"),
+        Some("CANCELED") => completed("CANCELLED"),
+        _ => (None, Some("IN_PROGRESS".to_string())),
+    }
+}
+
+/// Title of issue `number` in the project at `project_path`.
+pub fn get_issue_title(repo_root: &Path, project_path: &str, number: u64) -> Option<String> {
+    let endpoint = format!(
+        "projects/{}/issues/{number}",
+        project_path.replace('/', "%2F")
+    );
+    let stdout = run_glab(&["api", &endpoint], repo_root)?;
+    let issue: serde_json::Value = serde_json::from_str(&stdout).ok()?;
+    issue.get("title")?.as_str().map(str::to_string)
+}
+
+/// `OPEN`, `CLOSED` or `MERGED` for MR `iid` (GitLab's `locked` counts as
+/// open).
+pub fn get_mr_state(repo_root: &Path, project_path: &str, iid: u64) -> Option<String> {
+    let endpoint = format!(
+        "projects/{}/merge_requests/{iid}",
+        project_path.replace('/', "%2F")
+    );
+    let stdout = run_glab(&["api", &endpoint], repo_root)?;
+    let mr: serde_json::Value = serde_json::from_str(&stdout).ok()?;
+    let state = match mr.get("state")?.as_str()? {
+        "merged" => "MERGED",
+        "closed" => "CLOSED",
+        _ => "OPEN",
+    };
+    Some(state.to_string())
+}
+
+/// Opens a draft MR from `branch` into `base` with `glab mr create` and
+/// returns its URL. The branch must already be pushed.
+pub fn create_draft_mr(
+    repo_root: &Path,
+    branch: &str,
+    base: &str,
+    title: &str,
+) -> anyhow::Result<String> {
+    let output = logging::output(
+        Command::new("glab")
+            .args([
+                "mr",
+                "create",
+                "--draft",
+                "--source-branch",
+                branch,
+                "--target-branch",
+                base,
+                "--title",
+                title,
+                "--description",
+                "",
+                "--yes",
+            ])
+            .current_dir(repo_root),
+    )
+    .map_err(|err| GwError::GhUnavailable {
+        detail: format!("failed to run glab: {err}"),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("glab mr create: {}", stderr.trim()));
+    }
+    // glab prints progress before the URL.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .map(str::trim)
+        .rfind(|line| line.starts_with("http"))
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// Converts MR `iid` to a draft or marks it ready with `glab mr update`.
+pub fn set_mr_draft(repo_root: &Path, iid: i64, draft: bool) -> anyhow::Result<()> {
+    let flag = if draft { "--draft" } else { "--ready" };
+    let output = logging::output(
+        Command::new("glab")
+            .args(["mr", "update", &iid.to_string(), flag])
+            .current_dir(repo_root),
+    )
+    .map_err(|err| GwError::GhUnavailable {
+        detail: format!("failed to run glab: {err}"),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("glab mr update: {}", stderr.trim()));
+    }
+    Ok(())
+}
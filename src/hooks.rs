@@ -1,46 +1,78 @@
+use crate::cache_db::CacheDB;
+use crate::errors::GwError;
+use crate::settings::{self, load_settings, save_settings};
+use crate::{git_ops, logging, services};
 use anyhow::{anyhow, Context, Result};
 use serde_json::{json, Value};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-fn settings_path(repo_root: &Path) -> std::path::PathBuf {
-    repo_root.join(".gw").join("settings.json")
-}
-
-fn load_settings(repo_root: &Path) -> Result<Value> {
-    let path = settings_path(repo_root);
-    if !path.exists() {
-        return Ok(json!({}));
-    }
+const DIRENV_HOOK_TYPE: &str = "direnv";
+const COMPOSE_HOOK_TYPE: &str = "compose-project";
+const COMPOSE_PROJECT_VAR: &str = "COMPOSE_PROJECT_NAME";
+const POST_CREATION_EVENT: &str = "PostWorktreeCreation";
+const POST_INIT_EVENT: &str = "PostInit";
 
-    let text =
-        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
-    let raw: Value = serde_json::from_str(&text)
-        .with_context(|| format!("invalid JSON in {}", path.display()))?;
-    if !raw.is_object() {
-        return Err(anyhow!("invalid settings format in {}", path.display()));
-    }
-    Ok(raw)
+/// A `PostWorktreeCreation` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostCreationHook {
+    /// `{"type": "command", "command": ...}`: a shell command.
+    Command(String),
+    /// `{"type": "direnv"}`: `direnv allow` if the worktree has an `.envrc`.
+    Direnv,
+    /// `{"type": "compose-project"}`: sets `COMPOSE_PROJECT_NAME` in the
+    /// worktree's `.env` to `{repo}-{branch-slug}`.
+    ComposeProject,
 }
 
-fn save_settings(repo_root: &Path, settings: &Value) -> Result<()> {
-    let path = settings_path(repo_root);
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+pub fn add_post_worktree_creation_hook(repo_root: &Path, command: &str) -> Result<()> {
+    let normalized = command.trim();
+    if normalized.is_empty() {
+        return Err(anyhow!("hook command cannot be empty"));
     }
-    let mut text = serde_json::to_string_pretty(settings)?;
-    text.push('\n');
-    fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
-    Ok(())
+    append_hook_entry(
+        repo_root,
+        POST_CREATION_EVENT,
+        json!({
+            "type": "command",
+            "command": normalized,
+        }),
+    )
 }
 
-pub fn add_post_worktree_creation_hook(repo_root: &Path, command: &str) -> Result<()> {
+/// Adds a `PostInit` command, run at the repo root after `gw init`.
+pub fn add_post_init_hook(repo_root: &Path, command: &str) -> Result<()> {
     let normalized = command.trim();
     if normalized.is_empty() {
         return Err(anyhow!("hook command cannot be empty"));
     }
+    append_hook_entry(
+        repo_root,
+        POST_INIT_EVENT,
+        json!({
+            "type": "command",
+            "command": normalized,
+        }),
+    )
+}
 
+/// Adds a built-in hook (`Direnv` or `ComposeProject`), unless it is
+/// already configured.
+pub fn add_builtin_hook(repo_root: &Path, hook: &PostCreationHook) -> Result<bool> {
+    let hook_type = match hook {
+        PostCreationHook::Direnv => DIRENV_HOOK_TYPE,
+        PostCreationHook::ComposeProject => COMPOSE_HOOK_TYPE,
+        PostCreationHook::Command(_) => return Err(anyhow!("not a built-in hook")),
+    };
+    if get_post_worktree_creation_hooks(repo_root)?.contains(hook) {
+        return Ok(false);
+    }
+    append_hook_entry(repo_root, POST_CREATION_EVENT, json!({ "type": hook_type }))?;
+    Ok(true)
+}
+
+fn append_hook_entry(repo_root: &Path, event: &str, entry: Value) -> Result<()> {
     let mut settings = load_settings(repo_root)?;
     let Some(settings_obj) = settings.as_object_mut() else {
         return Err(anyhow!("invalid settings object"));
@@ -55,90 +87,240 @@ pub fn add_post_worktree_creation_hook(repo_root: &Path, command: &str) -> Resul
         .and_then(Value::as_object_mut)
         .ok_or_else(|| anyhow!("invalid hooks section in settings"))?;
 
-    if !hooks.contains_key("PostWorktreeCreation") {
-        hooks.insert("PostWorktreeCreation".to_string(), json!([]));
+    if !hooks.contains_key(event) {
+        hooks.insert(event.to_string(), json!([]));
     }
 
     let entries = hooks
-        .get_mut("PostWorktreeCreation")
+        .get_mut(event)
         .and_then(Value::as_array_mut)
-        .ok_or_else(|| anyhow!("invalid PostWorktreeCreation section in settings"))?;
+        .ok_or_else(|| anyhow!("invalid {event} section in settings"))?;
 
-    entries.push(json!({
-        "type": "command",
-        "command": normalized,
-    }));
+    entries.push(entry);
 
     save_settings(repo_root, &settings)
 }
 
-pub fn get_post_worktree_creation_commands(repo_root: &Path) -> Result<Vec<String>> {
-    let settings = load_settings(repo_root)?;
+/// The entries of `hooks.<event>` in `settings`.
+fn hook_entries(settings: &Value, event: &str) -> Result<Vec<Value>> {
     let Some(hooks) = settings.get("hooks") else {
         return Ok(Vec::new());
     };
     let Some(hooks_obj) = hooks.as_object() else {
         return Err(anyhow!("invalid hooks section in settings"));
     };
-    let Some(entries) = hooks_obj.get("PostWorktreeCreation") else {
+    let Some(entries) = hooks_obj.get(event) else {
         return Ok(Vec::new());
     };
     let Some(entries) = entries.as_array() else {
-        return Err(anyhow!("invalid PostWorktreeCreation section in settings"));
+        return Err(anyhow!("invalid {event} section in settings"));
     };
+    Ok(entries.clone())
+}
 
-    let mut commands = Vec::new();
-    for entry in entries {
+pub fn get_post_worktree_creation_hooks(repo_root: &Path) -> Result<Vec<PostCreationHook>> {
+    let entries = hook_entries(&load_settings(repo_root)?, POST_CREATION_EVENT)?;
+    let mut hooks = Vec::new();
+    for entry in &entries {
         let Some(obj) = entry.as_object() else {
             continue;
         };
-        let is_command = obj.get("type").and_then(Value::as_str) == Some("command");
-        if !is_command {
-            continue;
-        }
-        if let Some(command) = obj.get("command").and_then(Value::as_str) {
-            let normalized = command.trim();
-            if !normalized.is_empty() {
-                commands.push(normalized.to_string());
+        match obj.get("type").and_then(Value::as_str) {
+            Some("command") => {
+                if let Some(command) = obj.get("command").and_then(Value::as_str) {
+                    let normalized = command.trim();
+                    if !normalized.is_empty() {
+                        hooks.push(PostCreationHook::Command(normalized.to_string()));
+                    }
+                }
             }
+            Some(DIRENV_HOOK_TYPE) => hooks.push(PostCreationHook::Direnv),
+            Some(COMPOSE_HOOK_TYPE) => hooks.push(PostCreationHook::ComposeProject),
+            _ => {}
         }
     }
 
-    Ok(commands)
+    Ok(hooks)
 }
 
 pub fn run_post_worktree_creation_hooks(repo_root: &Path, cwd: Option<&Path>) -> Result<()> {
     let run_cwd = cwd.unwrap_or(repo_root);
-    for command in get_post_worktree_creation_commands(repo_root)? {
-        #[cfg(unix)]
-        let output = Command::new("sh")
+    let hooks = get_post_worktree_creation_hooks(repo_root)?;
+    let ran_any = !hooks.is_empty();
+    for hook in hooks {
+        let command = match hook {
+            PostCreationHook::Command(command) => command,
+            PostCreationHook::Direnv if run_cwd.join(".envrc").exists() => {
+                "direnv allow".to_string()
+            }
+            PostCreationHook::Direnv => continue,
+            PostCreationHook::ComposeProject if git_ops::dry_run() => {
+                logging::would_run(&format!(
+                    "write the compose project name in {}",
+                    run_cwd.display()
+                ));
+                continue;
+            }
+            PostCreationHook::ComposeProject => {
+                write_compose_project(repo_root, run_cwd).map_err(|detail| {
+                    GwError::HookFailed {
+                        command: COMPOSE_HOOK_TYPE.to_string(),
+                        detail,
+                    }
+                })?;
+                continue;
+            }
+        };
+
+        run_hook_command(command, run_cwd, &[])?;
+    }
+
+    // Only for the detail pane; a cache problem doesn't fail the hooks.
+    if ran_any {
+        if let Ok(db) = CacheDB::open(repo_root) {
+            let _ = db.record_hook_run(run_cwd);
+        }
+    }
+    Ok(())
+}
+
+/// `PostInit` commands from the repo's settings, then from the global
+/// settings, so a team can set them up before any repo is converted.
+pub fn get_post_init_hooks(repo_root: &Path) -> Result<Vec<String>> {
+    let mut commands = Vec::new();
+    for settings in [load_settings(repo_root)?, settings::load_global_settings()?] {
+        for entry in hook_entries(&settings, POST_INIT_EVENT)? {
+            if entry.get("type").and_then(Value::as_str) != Some("command") {
+                continue;
+            }
+            if let Some(command) = entry.get("command").and_then(Value::as_str) {
+                let normalized = command.trim();
+                if !normalized.is_empty() {
+                    commands.push(normalized.to_string());
+                }
+            }
+        }
+    }
+    Ok(commands)
+}
+
+/// Runs the `PostInit` hooks at `repo_root` once `gw init` is done, with
+/// `GW_REPO_ROOT` set and the worktrees it created in `GW_WORKTREES`, one
+/// path per line. Returns how many hooks ran.
+pub fn run_post_init_hooks(repo_root: &Path, created: &[PathBuf]) -> Result<usize> {
+    let commands = get_post_init_hooks(repo_root)?;
+    let worktrees = created
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let envs = [
+        ("GW_REPO_ROOT", repo_root.as_os_str().to_os_string()),
+        ("GW_WORKTREES", worktrees.into()),
+    ];
+    for command in &commands {
+        run_hook_command(command.clone(), repo_root, &envs)?;
+    }
+    Ok(commands.len())
+}
+
+fn run_hook_command(
+    command: String,
+    cwd: &Path,
+    envs: &[(&str, std::ffi::OsString)],
+) -> Result<()> {
+    services::check_command_policy(&command)?;
+    if git_ops::dry_run() {
+        logging::would_run(&format!("{command} (hook, in {})", cwd.display()));
+        return Ok(());
+    }
+    #[cfg(unix)]
+    let output = logging::output(
+        Command::new("sh")
             .arg("-c")
             .arg(&command)
-            .current_dir(run_cwd)
-            .output()
-            .with_context(|| format!("failed to run hook `{command}`"))?;
+            .current_dir(cwd)
+            .envs(envs.iter().map(|(key, value)| (key, value))),
+    )
+    .with_context(|| format!("failed to run hook `{command}`"))?;
 
-        #[cfg(windows)]
-        let output = Command::new("cmd")
+    #[cfg(windows)]
+    let output = logging::output(
+        Command::new("cmd")
             .arg("/C")
             .arg(&command)
-            .current_dir(run_cwd)
-            .output()
-            .with_context(|| format!("failed to run hook `{command}`"))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-            let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            let msg = if !stderr.is_empty() {
-                stderr
-            } else if !stdout.is_empty() {
-                stdout
-            } else {
-                "unknown error".to_string()
-            };
-            return Err(anyhow!("hook failed: `{command}`: {msg}"));
+            .current_dir(cwd)
+            .envs(envs.iter().map(|(key, value)| (key, value))),
+    )
+    .with_context(|| format!("failed to run hook `{command}`"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let msg = if !stderr.is_empty() {
+            stderr
+        } else if !stdout.is_empty() {
+            stdout
+        } else {
+            "unknown error".to_string()
+        };
+        return Err(GwError::HookFailed {
+            command,
+            detail: msg,
         }
+        .into());
     }
-
     Ok(())
 }
+
+/// What still needs doing before direnv or devenv work in the new worktree
+/// at `path`, if anything: `.envrc` files without the direnv hook need a
+/// `direnv allow`, and devenv projects without an `.envrc` need `devenv shell`.
+pub fn direnv_reminder(repo_root: &Path, path: &Path) -> Option<String> {
+    if path.join(".envrc").exists() {
+        let allowed = get_post_worktree_creation_hooks(repo_root)
+            .is_ok_and(|hooks| hooks.contains(&PostCreationHook::Direnv));
+        return (!allowed).then(|| {
+            format!(
+                "run `direnv allow` in {} (or `gw hooks add --direnv` to do it for new worktrees)",
+                path.display()
+            )
+        });
+    }
+    path.join("devenv.nix").exists().then(|| {
+        format!(
+            "run `devenv shell` in {} to enter its devenv",
+            path.display()
+        )
+    })
+}
+
+/// Sets `COMPOSE_PROJECT_NAME` in `<worktree>/.env`, replacing an earlier
+/// value and keeping the other lines. A symlinked `.env` is shared with other
+/// worktrees, so it is left alone.
+fn write_compose_project(repo_root: &Path, worktree: &Path) -> Result<(), String> {
+    let branch = git_ops::worktree_branch(worktree)
+        .ok_or_else(|| format!("{} is not on a branch", worktree.display()))?;
+    let name = services::compose_project_name(repo_root, &branch);
+    let env_path = worktree.join(".env");
+    if env_path.is_symlink() {
+        return Err(format!(
+            "{} is a symlink shared with other worktrees; copy it instead of linking",
+            env_path.display()
+        ));
+    }
+
+    let existing = fs::read_to_string(&env_path).unwrap_or_default();
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| {
+            !line
+                .trim_start()
+                .starts_with(&format!("{COMPOSE_PROJECT_VAR}="))
+        })
+        .map(str::to_string)
+        .collect();
+    lines.push(format!("{COMPOSE_PROJECT_VAR}={name}"));
+    fs::write(&env_path, lines.join("\n") + "\n")
+        .map_err(|err| format!("cannot write {}: {err}", env_path.display()))
+}
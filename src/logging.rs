@@ -0,0 +1,203 @@
+use anyhow::Result;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// Where `--verbose` output goes.
+pub enum LogTarget {
+    Stderr,
+    /// Appended to, for runs where stderr is taken by the TUI.
+    File(PathBuf),
+}
+
+/// Installs the global tracing subscriber at debug level.
+pub fn init(target: LogTarget) -> Result<()> {
+    let builder = tracing_subscriber::fmt().with_max_level(Level::DEBUG);
+    match target {
+        LogTarget::Stderr => builder
+            .with_ansi(io::stderr().is_terminal())
+            .with_writer(io::stderr)
+            .init(),
+        LogTarget::File(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .init();
+        }
+    }
+    Ok(())
+}
+
+/// What a dry run skipped, collected for the TUI while it owns the terminal;
+/// `None` prints it on stderr instead.
+static WOULD_RUN: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Reports a command or change that a dry run skipped, as
+/// `would run: <what>`.
+pub fn would_run(what: &str) {
+    tracing::debug!(command = %what, "skipped in dry run");
+    let mut collected = WOULD_RUN
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match collected.as_mut() {
+        Some(lines) => lines.push(format!("would run: {what}")),
+        None => eprintln!("would run: {what}"),
+    }
+}
+
+/// Collects what [`would_run`] reports from now on for [`take_would_run`].
+pub fn collect_would_run() {
+    let mut collected = WOULD_RUN
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    collected.get_or_insert_with(Vec::new);
+}
+
+/// The lines collected since the last call.
+pub fn take_would_run() -> Vec<String> {
+    let mut collected = WOULD_RUN
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    collected.as_mut().map(std::mem::take).unwrap_or_default()
+}
+
+/// `CREATE_NO_WINDOW`: keeps console programs such as `git` and `gh` from
+/// opening a console window when gw itself has none, e.g. under `gw serve`.
+#[cfg(windows)]
+pub const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// `Command::output` that logs the command line, working directory,
+/// duration and exit status. On Windows the child gets no console window;
+/// its output is captured anyway.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let started = Instant::now();
+    let result = command.output();
+    let duration_ms = started.elapsed().as_millis() as u64;
+    match &result {
+        Ok(output) => tracing::debug!(
+            command = %command_line(command),
+            cwd = %working_dir(command),
+            duration_ms,
+            status = %output.status,
+            "ran subprocess"
+        ),
+        Err(err) => tracing::debug!(
+            command = %command_line(command),
+            cwd = %working_dir(command),
+            duration_ms,
+            error = %err,
+            "failed to start subprocess"
+        ),
+    }
+    result
+}
+
+/// `output`, but the child is killed once it runs for longer than `limit`,
+/// and `None` returned. Without a limit it is `output`.
+pub fn output_within(command: &mut Command, limit: Option<Duration>) -> io::Result<Option<Output>> {
+    let Some(limit) = limit else {
+        return output(command).map(Some);
+    };
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let started = Instant::now();
+    let mut child = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            tracing::debug!(
+                command = %command_line(command),
+                cwd = %working_dir(command),
+                error = %err,
+                "failed to start subprocess"
+            );
+            return Err(err);
+        }
+    };
+
+    // Both pipes are drained on their own threads so a full one can't stall
+    // the child; they report back once the child (and anything it started
+    // that holds them) closed them.
+    let (tx, rx) = mpsc::channel();
+    let readers: [(bool, Option<Box<dyn Read + Send>>); 2] = [
+        (true, child.stdout.take().map(|pipe| Box::new(pipe) as _)),
+        (false, child.stderr.take().map(|pipe| Box::new(pipe) as _)),
+    ];
+    for (is_stdout, pipe) in readers {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            let _ = tx.send((is_stdout, buffer));
+        });
+    }
+
+    let deadline = started + limit;
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    for _ in 0..2 {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining) {
+            Ok((true, buffer)) => stdout = buffer,
+            Ok((false, buffer)) => stderr = buffer,
+            Err(_) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                tracing::debug!(
+                    command = %command_line(command),
+                    cwd = %working_dir(command),
+                    duration_ms = started.elapsed().as_millis() as u64,
+                    "killed subprocess after its time limit"
+                );
+                return Ok(None);
+            }
+        }
+    }
+    let status = child.wait()?;
+    tracing::debug!(
+        command = %command_line(command),
+        cwd = %working_dir(command),
+        duration_ms = started.elapsed().as_millis() as u64,
+        status = %status,
+        "ran subprocess"
+    );
+    Ok(Some(Output {
+        status,
+        stdout,
+        stderr,
+    }))
+}
+
+fn working_dir(command: &Command) -> String {
+    command
+        .get_current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|| ".".to_string())
+}
+
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,15 +1,30 @@
 mod cache_db;
 mod cli;
+mod errors;
+mod forge;
 mod gh_ops;
 mod git_ops;
+mod gitea_ops;
+mod glab_ops;
 mod hooks;
+mod logging;
+mod manifest;
 mod models;
+mod policies;
+mod repo_lock;
+mod review;
+mod server;
 mod services;
+mod settings;
+mod tags;
+mod tasks;
+mod trash;
 mod tui;
 
 fn main() {
     if let Err(err) = cli::run() {
         eprintln!("{err}");
-        std::process::exit(1);
+        let code = errors::classify(&err).map_or(1, errors::GwError::exit_code);
+        std::process::exit(code);
     }
 }
@@ -0,0 +1,120 @@
+use crate::models::{ManifestEntry, WorktreeManifest};
+use crate::{git_ops, services};
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// Every branch with a worktree, with the base it was created from (else its
+/// PR's base, else the default branch), its upstream and linked issue.
+pub fn export_manifest(repo_root: &Path) -> Result<WorktreeManifest> {
+    let default_branch = git_ops::get_default_branch(repo_root);
+    let mut items = services::load_worktrees(repo_root)?;
+    items.sort_by(|a, b| a.branch.cmp(&b.branch));
+
+    let worktrees = items
+        .into_iter()
+        .filter_map(|item| {
+            let branch = item.ref_name?;
+            let base = git_ops::get_branch_base(repo_root, &branch)
+                .or(item.pr_base)
+                .unwrap_or_else(|| default_branch.clone());
+            Some(ManifestEntry {
+                upstream: git_ops::get_upstream(repo_root, &branch),
+                issue: item.issue,
+                branch,
+                base,
+            })
+        })
+        .collect();
+    Ok(WorktreeManifest {
+        version: MANIFEST_VERSION,
+        worktrees,
+    })
+}
+
+pub fn read_manifest(path: &Path) -> Result<WorktreeManifest> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let manifest: WorktreeManifest = serde_json::from_str(&text)
+        .with_context(|| format!("invalid manifest {}", path.display()))?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err(anyhow!(
+            "{} is a version {} manifest; this gw reads up to version {MANIFEST_VERSION}",
+            path.display(),
+            manifest.version
+        ));
+    }
+    Ok(manifest)
+}
+
+/// The manifest entries whose branch does not exist here yet, with the
+/// worktree path each will get, ordered so that bases are created before the
+/// branches started from them. All are checked before anything is created.
+pub fn missing_entries(
+    repo_root: &Path,
+    manifest: &WorktreeManifest,
+) -> Result<Vec<(ManifestEntry, PathBuf)>> {
+    let mut pending: Vec<ManifestEntry> = manifest
+        .worktrees
+        .iter()
+        .filter(|entry| !git_ops::branch_exists(repo_root, &entry.branch))
+        .cloned()
+        .collect();
+    let mut missing = Vec::new();
+    while !pending.is_empty() {
+        let (ready, waiting): (Vec<ManifestEntry>, Vec<ManifestEntry>) =
+            pending.iter().cloned().partition(|entry| {
+                !pending
+                    .iter()
+                    .any(|other| other.branch == entry.base && other.branch != entry.branch)
+            });
+        if ready.is_empty() {
+            // A cycle of bases; their order no longer matters.
+            missing.extend(waiting);
+            break;
+        }
+        missing.extend(ready);
+        pending = waiting;
+    }
+
+    let branches: Vec<String> = missing.iter().map(|entry| entry.branch.clone()).collect();
+    let targets = services::new_worktree_targets(repo_root, &branches)?;
+    Ok(missing
+        .into_iter()
+        .zip(targets)
+        .map(|(entry, (_, path))| (entry, path))
+        .collect())
+}
+
+/// Creates the worktree for one manifest entry: from origin if the branch
+/// was pushed, else from its base. Returns notes about what could not be
+/// restored as recorded, e.g. a missing upstream.
+pub fn apply_entry(repo_root: &Path, entry: &ManifestEntry, path: &Path) -> Result<Vec<String>> {
+    let base = if git_ops::branch_exists(repo_root, &entry.base) {
+        entry.base.clone()
+    } else {
+        format!("origin/{}", entry.base)
+    };
+    let mut notes = Vec::new();
+    if let Some(reminder) =
+        services::create_worktree(repo_root, path, &entry.branch, &base, None, false)?
+    {
+        notes.push(reminder);
+    }
+    git_ops::set_branch_base(repo_root, &entry.branch, &entry.base)?;
+
+    if let Some(upstream) = &entry.upstream {
+        let current = git_ops::get_upstream(repo_root, &entry.branch);
+        if current.as_deref() != Some(upstream.as_str())
+            && git_ops::branch_set_upstream(repo_root, &entry.branch, upstream).is_err()
+        {
+            notes.push(format!("{}: upstream {upstream} not found", entry.branch));
+        }
+    }
+    if let Some(number) = entry.issue {
+        git_ops::set_branch_issue(repo_root, &entry.branch, number)?;
+    }
+    Ok(notes)
+}
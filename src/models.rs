@@ -1,8 +1,10 @@
 #![allow(dead_code)]
 
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct WorktreeInfo {
     pub path: PathBuf,
     pub branch: String,
@@ -13,7 +15,11 @@ pub struct WorktreeInfo {
     pub pull: i64,
     pub push: i64,
     pub pull_push_validated: bool,
+    /// When pull/push counts were last computed; `None` if never.
+    pub pull_push_updated_ts: Option<i64>,
     pub has_upstream: bool,
+    /// The branch tracks an upstream that was deleted on the remote.
+    pub upstream_gone: bool,
     pub behind: i64,
     pub ahead: i64,
     pub additions: i64,
@@ -23,18 +29,107 @@ pub struct WorktreeInfo {
     pub pr_state: Option<String>,
     pub pr_base: Option<String>,
     pub pr_url: Option<String>,
+    pub pr_draft: bool,
+    pub pr_review_decision: Option<String>,
+    pub pr_title: Option<String>,
+    pub pr_author: Option<String>,
+    pub pr_created_ts: Option<i64>,
+    pub pr_merged_ts: Option<i64>,
+    pub pr_unresolved_threads: Option<i64>,
     pub pr_validated: bool,
+    /// When PR and checks data was last fetched from the forge.
+    pub pr_updated_ts: Option<i64>,
+    /// Forge issue the branch was created for with `gw new --issue`.
+    pub issue: Option<u64>,
+    /// Pull request checked out by `gw review`.
+    pub review: Option<u64>,
+    /// Release tag checked out by `gw new --tag`.
+    pub tag: Option<String>,
+    /// `branch.<name>.description`, a note that travels with the branch.
+    pub description: Option<String>,
+    /// Memo kept in gw's cache only, set with `m` in the TUI.
+    pub note: Option<String>,
+    /// Closest tag reachable from a detached HEAD that is neither a review
+    /// nor a tag worktree.
+    pub nearest_tag: Option<String>,
+    /// A rebase, merge, cherry-pick or bisect left unfinished in the worktree.
+    pub in_progress: Option<InProgressOp>,
+    /// Files still being checked out after a fast create.
+    pub checkout: Option<BackgroundCheckout>,
+    /// Lock reason from `git worktree lock`, possibly empty.
+    pub locked: Option<String>,
+    pub prunable: Option<String>,
     pub checks_passed: Option<i64>,
     pub checks_total: Option<i64>,
     pub checks_state: Option<String>,
     pub checks_validated: bool,
     pub changes_validated: bool,
+    /// When the diff stats were last computed.
+    pub changes_updated_ts: Option<i64>,
 }
 
 impl WorktreeInfo {
     pub fn is_detached(&self) -> bool {
         self.ref_name.is_none()
     }
+
+    /// Both the branch and its upstream have commits the other lacks, so
+    /// neither a fast-forward pull nor a plain push works.
+    pub fn is_diverged(&self) -> bool {
+        self.has_upstream && self.pull > 0 && self.push > 0
+    }
+}
+
+/// One worktree in `gw list --format json` and `gw status --format json`.
+/// Fields are only ever added, so scripts and nushell pipelines can rely on
+/// them.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorktreeSummary {
+    pub branch: String,
+    pub path: PathBuf,
+    pub head: String,
+    pub detached: bool,
+    pub last_commit_ts: i64,
+    pub age_days: Option<u64>,
+    pub dirty: bool,
+    pub has_upstream: bool,
+    pub upstream_gone: bool,
+    pub pull: i64,
+    pub push: i64,
+    pub behind: i64,
+    pub ahead: i64,
+    pub additions: i64,
+    pub deletions: i64,
+    pub issue: Option<u64>,
+    pub review: Option<u64>,
+    pub tag: Option<String>,
+    pub nearest_tag: Option<String>,
+    pub description: Option<String>,
+    pub note: Option<String>,
+    pub in_progress: Option<InProgressOp>,
+    pub checkout: Option<BackgroundCheckout>,
+    pub locked: bool,
+    pub prunable: bool,
+    pub pr_number: Option<i64>,
+    pub pr_state: Option<String>,
+    pub pr_draft: bool,
+    pub pr_url: Option<String>,
+    pub pr_base: Option<String>,
+    pub checks_state: Option<String>,
+    pub checks_passed: Option<i64>,
+    pub checks_total: Option<i64>,
+}
+
+/// The worktree a directory is in, as found from its `.git` file.
+#[derive(Debug, Clone)]
+pub struct WorktreeLocation {
+    /// Same as `git_ops::get_repo_root` would return.
+    pub repo_root: PathBuf,
+    pub path: PathBuf,
+    /// Checked-out branch, or `None` if HEAD is detached.
+    pub branch: Option<String>,
+    /// HEAD commit when detached.
+    pub head: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -43,6 +138,16 @@ pub struct AheadBehind {
     pub behind: i64,
 }
 
+/// Commits a branch would lose if it were deleted locally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnpushedCommits {
+    None,
+    /// Commits ahead of the branch's upstream.
+    Ahead(i64),
+    /// No upstream; commits not on the default branch.
+    NeverPushed(i64),
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct DiffStat {
     pub additions: i64,
@@ -55,6 +160,288 @@ pub struct ParsedWorktree {
     pub path: PathBuf,
     pub branch: String,
     pub head: String,
+    /// Set by `git worktree lock`, with its reason (possibly empty).
+    pub locked: Option<String>,
+    /// Why `git worktree prune` would drop the entry, e.g. its directory is
+    /// gone.
+    pub prunable: Option<String>,
+}
+
+/// How worktrees are arranged. `Bare` keeps a bare repo at the root with one
+/// worktree per branch beneath it; `Hub` keeps a normal checkout at the root
+/// and puts the other worktrees under `worktrees_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoLayout {
+    Bare,
+    Hub { worktrees_dir: PathBuf },
+}
+
+/// How refreshes fetch from origin, from the `fetchTags`, `fetchJobs` and
+/// `fetchWorktreeBranchesOnly` settings.
+#[derive(Debug, Clone, Copy)]
+pub struct FetchOptions {
+    /// `false` passes `--no-tags`.
+    pub tags: bool,
+    /// `--jobs`, for submodules and multiple remotes.
+    pub jobs: Option<u64>,
+    /// Fetch only the default branch and the upstreams of branches with a
+    /// worktree instead of every branch.
+    pub worktree_branches_only: bool,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            tags: true,
+            jobs: None,
+            worktree_branches_only: false,
+        }
+    }
+}
+
+/// How `gw` pulls, from the `pullStrategy` setting. Without it, plain
+/// `git pull` follows git's own `pull.rebase`/`pull.ff` config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullStrategy {
+    Merge,
+    Rebase,
+    FfOnly,
+}
+
+impl PullStrategy {
+    pub fn git_flag(self) -> &'static str {
+        match self {
+            PullStrategy::Merge => "--no-rebase",
+            PullStrategy::Rebase => "--rebase",
+            PullStrategy::FfOnly => "--ff-only",
+        }
+    }
+}
+
+/// How timestamps are shown, from the `timeFormat` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// `3d ago`, `2mo ago`, `1y ago`.
+    #[default]
+    Relative,
+    /// `2024-05-17`, in the local time zone.
+    Date,
+    /// `2024-05-17 14:03`, in the local time zone.
+    DateTime,
+}
+
+/// What the TUI can send a desktop notification about while its terminal is
+/// unfocused, each enabled in the `notifications` setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationEvent {
+    Pull,
+    Push,
+    /// A worktree creation or restore that ran post-creation hooks.
+    Hooks,
+    /// A PR's pending checks finished.
+    Checks,
+}
+
+impl NotificationEvent {
+    pub const ALL: [NotificationEvent; 4] = [
+        NotificationEvent::Pull,
+        NotificationEvent::Push,
+        NotificationEvent::Hooks,
+        NotificationEvent::Checks,
+    ];
+
+    pub fn setting_key(self) -> &'static str {
+        match self {
+            NotificationEvent::Pull => "pull",
+            NotificationEvent::Push => "push",
+            NotificationEvent::Hooks => "hooks",
+            NotificationEvent::Checks => "checks",
+        }
+    }
+}
+
+/// Text formats the worktree table can be exported in, with `gw list` or
+/// `y`/`Y` in the TUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableExport {
+    Markdown,
+    Csv,
+}
+
+/// A git operation left unfinished in a worktree, e.g. a rebase stopped at a
+/// conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InProgressOp {
+    Rebase,
+    Merge,
+    CherryPick,
+    Bisect,
+}
+
+impl InProgressOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            InProgressOp::Rebase => "rebase",
+            InProgressOp::Merge => "merge",
+            InProgressOp::CherryPick => "cherry-pick",
+            InProgressOp::Bisect => "bisect",
+        }
+    }
+
+    /// Shown next to the branch, worded like git's prompt.
+    pub fn badge(self) -> &'static str {
+        match self {
+            InProgressOp::Rebase => "REBASE",
+            InProgressOp::Merge => "MERGING",
+            InProgressOp::CherryPick => "CHERRY-PICKING",
+            InProgressOp::Bisect => "BISECTING",
+        }
+    }
+
+    /// The commands that finish or abandon it.
+    pub fn way_out(self) -> &'static str {
+        match self {
+            InProgressOp::Rebase => "`git rebase --continue` or `--abort`",
+            InProgressOp::Merge => "`git merge --continue` or `--abort`",
+            InProgressOp::CherryPick => "`git cherry-pick --continue` or `--abort`",
+            InProgressOp::Bisect => "`git bisect reset`",
+        }
+    }
+}
+
+/// The checkout a fast create (`gw new --fast`, `fastCreate`) left running
+/// in the background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BackgroundCheckout {
+    /// How many of the files are written so far, as git reports it.
+    pub percent: u8,
+    /// The checkout or a post-creation hook failed; the log is in the
+    /// worktree's admin dir.
+    pub failed: bool,
+}
+
+impl BackgroundCheckout {
+    /// Shown next to the branch.
+    pub fn badge(self) -> String {
+        if self.failed {
+            "checkout failed".to_string()
+        } else {
+            format!("checkout {}%", self.percent)
+        }
+    }
+}
+
+/// A commit as offered for cherry-picking.
+#[derive(Debug, Clone)]
+pub struct CommitSummary {
+    pub sha: String,
+    pub subject: String,
+}
+
+/// What the TUI's detail pane shows about the selected worktree beyond the
+/// table, gathered in the background.
+#[derive(Debug, Clone, Default)]
+pub struct WorktreeDetails {
+    pub upstream: Option<String>,
+    /// Stash entries made on the worktree's branch.
+    pub stashes: usize,
+    /// Whether the repo has post-creation hooks at all.
+    pub hooks_configured: bool,
+    /// When the post-creation hooks last ran in the worktree.
+    pub hooks_ran_ts: Option<i64>,
+    /// Bytes of all files under the worktree, ignored ones included.
+    pub disk_usage: u64,
+}
+
+/// Two branches side by side, as `v` in the TUI shows them.
+#[derive(Debug, Clone)]
+pub struct BranchComparison {
+    pub branch: String,
+    pub other: String,
+    /// Commits on `branch` but not on `other`, newest first.
+    pub only_branch: Vec<CommitSummary>,
+    /// Commits on `other` but not on `branch`, newest first.
+    pub only_other: Vec<CommitSummary>,
+    /// `git diff --stat` of `branch` since it forked from `other`.
+    pub stat: Vec<String>,
+}
+
+/// A named sequence of git commands from the `presets` setting, run in a
+/// worktree with `gw run <name>` or its `key` in the TUI.
+#[derive(Debug, Clone)]
+pub struct Preset {
+    pub name: String,
+    /// Git command lines, with or without a leading `git`; `{branch}`, `{default}`
+    /// and `{base}` are substituted.
+    pub steps: Vec<String>,
+    pub key: Option<char>,
+}
+
+/// The `commandPolicy` of the global settings: which hook and preset
+/// commands gw may run. `allow` (when set) lists the only permitted ones;
+/// `deny` wins over it.
+#[derive(Debug, Clone, Default)]
+pub struct CommandPolicy {
+    pub allow: Option<Vec<String>>,
+    pub deny: Vec<String>,
+}
+
+/// One entry of the `cleanupPolicies` setting: what to do with worktrees
+/// matching `condition` for more than `days`.
+#[derive(Debug, Clone)]
+pub struct CleanupPolicy {
+    pub condition: PolicyCondition,
+    pub days: u64,
+    pub action: PolicyAction,
+    /// Whether the TUI applies the policy when it starts, not just
+    /// `gw clean --policies`.
+    pub on_startup: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyCondition {
+    /// The branch's PR was merged.
+    Merged,
+    /// The branch has no new commits.
+    Untouched,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyAction {
+    Warn,
+    /// Move the worktree and its branch to the trash.
+    Delete,
+}
+
+/// A worktree a cleanup policy applies to.
+#[derive(Debug, Clone)]
+pub struct PolicyMatch {
+    pub policy: CleanupPolicy,
+    pub branch: String,
+    pub path: PathBuf,
+    pub ref_name: String,
+    /// Days since the PR was merged or the last commit.
+    pub age_days: u64,
+    /// Why a `delete` policy keeps the worktree after all, e.g. uncommitted
+    /// changes; it is only warned about then.
+    pub kept_because: Option<String>,
+}
+
+impl PolicyMatch {
+    pub fn deletes(&self) -> bool {
+        self.policy.action == PolicyAction::Delete && self.kept_because.is_none()
+    }
+}
+
+/// A worktree directory under the repo root whose `.git` file and the repo's
+/// `worktrees/<id>` admin entry no longer point at each other, typically after
+/// the repo or worktree was moved.
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenWorktreeLink {
+    pub path: PathBuf,
+    pub admin_dir: PathBuf,
+    pub registered_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +450,27 @@ pub struct PullRequestInfo {
     pub state: String,
     pub base: Option<String>,
     pub url: Option<String>,
+    pub is_draft: bool,
+    pub review_decision: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Unix timestamp of when the PR was opened.
+    pub created_ts: Option<i64>,
+    /// Unix timestamp of when the PR was merged.
+    pub merged_ts: Option<i64>,
+    /// Review conversations still waiting to be resolved; `None` if the forge
+    /// does not track resolution.
+    pub unresolved_threads: Option<i64>,
+}
+
+/// Where a local branch's PR would come from: the remote branch name it is
+/// pushed as and the owner of the repository it is pushed to, which differs
+/// from the base repository's owner for PRs opened from a fork.
+#[derive(Debug, Clone)]
+pub struct BranchHead {
+    pub branch: String,
+    pub head_ref: String,
+    pub head_owner: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -72,21 +480,346 @@ pub struct ChecksInfo {
     pub state: Option<String>,
 }
 
+/// A worktree with no local branch behind it: detached, or on a branch that
+/// was deleted. `adopt_branch` is the branch it can be re-attached to instead
+/// of being removed, when that is possible.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanedWorktree {
+    pub path: PathBuf,
+    pub head: Option<String>,
+    pub adopt_branch: Option<String>,
+}
+
+/// Snapshot of a worktree's files taken before gw deleted it, stored as a
+/// commit under `refs/gw/backup/<id>` on top of the worktree's HEAD.
 #[derive(Debug, Clone)]
+pub struct WorktreeBackup {
+    pub id: String,
+    pub commit: String,
+    pub created: String,
+    pub path: PathBuf,
+    pub branch: Option<String>,
+}
+
+/// Which worktrees the TUI lists, from `gw --filter` and `--branch-glob`.
+/// A worktree is shown only if it meets every condition that is set.
+#[derive(Debug, Clone, Default)]
+pub struct WorktreeFilter {
+    pub dirty: bool,
+    pub has_pr: bool,
+    pub behind: bool,
+    pub branch_glob: Option<String>,
+}
+
+impl WorktreeFilter {
+    pub fn is_empty(&self) -> bool {
+        !self.dirty && !self.has_pr && !self.behind && self.branch_glob.is_none()
+    }
+
+    /// E.g. `dirty, has-pr, feature/*`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.dirty {
+            parts.push("dirty");
+        }
+        if self.has_pr {
+            parts.push("has-pr");
+        }
+        if self.behind {
+            parts.push("behind");
+        }
+        if let Some(glob) = &self.branch_glob {
+            parts.push(glob);
+        }
+        parts.join(", ")
+    }
+}
+
+/// A worktree deleted from gw, kept as-is (ignored files included) in the
+/// repo's trash until it is restored or purged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedWorktree {
+    pub id: String,
+    pub branch: String,
+    /// Where the worktree lived.
+    pub path: PathBuf,
+    /// Branch tip when it was deleted, also kept alive by `refs/gw/trash/<id>`.
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    pub deleted_ts: i64,
+}
+
+/// A branch deleted recently, found in gw's deletion log or in a worktree's
+/// HEAD reflog, that can be brought back with a worktree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedBranch {
+    pub branch: String,
+    /// Branch tip when it was deleted.
+    pub head: String,
+    /// When gw deleted it or, for branches found in a reflog, when a
+    /// worktree last switched away from it.
+    pub deleted_ts: i64,
+    /// The trash entry still holding its worktree, if any.
+    pub trash_id: Option<String>,
+}
+
+/// The set of worktrees written by `gw export` and recreated by `gw apply`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeManifest {
+    pub version: u32,
+    pub worktrees: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub branch: String,
+    /// Branch it was created from; new branches start there on apply.
+    pub base: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathIssueKind {
+    /// A second registration for a directory that already belongs to another
+    /// worktree entry.
+    Duplicate,
+    /// A worktree living inside another worktree's directory.
+    Nested,
+    /// A worktree path under the repo root that resolves outside of it
+    /// through a symlink.
+    SymlinkEscape,
+}
+
+/// A worktree whose location breaks the layout. `target` is where the fix
+/// moves it; duplicates are unregistered instead.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorktreePathIssue {
+    pub kind: PathIssueKind,
+    pub path: PathBuf,
+    pub real_path: PathBuf,
+    pub admin_dir: Option<PathBuf>,
+    pub target: Option<PathBuf>,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanAction {
+    Delete,
+    Adopt,
+    Skip,
+}
+
+/// One individually selectable fix derived from a `HealthReport`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepairFix {
+    Relink(PathBuf),
+    PathIssue(PathBuf),
+    Orphan(PathBuf),
+    CreateWorktree(String),
+}
+
+/// User choices applied on top of a `HealthReport` by the doctor.
+#[derive(Debug, Clone, Default)]
+pub struct RepairPlan {
+    pub orphan_actions: HashMap<PathBuf, OrphanAction>,
+    pub skipped_relinks: HashSet<PathBuf>,
+    pub skipped_path_fixes: HashSet<PathBuf>,
+    pub deferred_branches: HashSet<String>,
+    /// Branches to leave without a worktree from now on, by adding them to
+    /// `noWorktreeBranches` instead of creating one.
+    pub ignored_branches: HashSet<String>,
+}
+
+impl RepairPlan {
+    pub fn orphan_action(&self, orphan: &OrphanedWorktree) -> OrphanAction {
+        match self.orphan_actions.get(&orphan.path) {
+            Some(OrphanAction::Adopt) if orphan.adopt_branch.is_some() => OrphanAction::Adopt,
+            Some(OrphanAction::Skip) => OrphanAction::Skip,
+            _ => OrphanAction::Delete,
+        }
+    }
+
+    pub fn adopt_all(report: &HealthReport) -> Self {
+        let orphan_actions = report
+            .orphaned_worktrees
+            .iter()
+            .filter(|orphan| orphan.adopt_branch.is_some())
+            .map(|orphan| (orphan.path.clone(), OrphanAction::Adopt))
+            .collect();
+        Self {
+            orphan_actions,
+            ..Self::default()
+        }
+    }
+
+    pub fn deletes_orphan(&self, orphan: &OrphanedWorktree) -> bool {
+        self.orphan_action(orphan) == OrphanAction::Delete
+    }
+
+    pub fn is_enabled(&self, fix: &RepairFix) -> bool {
+        match fix {
+            RepairFix::Relink(path) => !self.skipped_relinks.contains(path),
+            RepairFix::PathIssue(path) => !self.skipped_path_fixes.contains(path),
+            RepairFix::Orphan(path) => self.orphan_actions.get(path) != Some(&OrphanAction::Skip),
+            RepairFix::CreateWorktree(branch) => !self.deferred_branches.contains(branch),
+        }
+    }
+
+    /// Switches a fix between applied and skipped. A skipped orphan comes back
+    /// as a delete.
+    pub fn toggle(&mut self, fix: &RepairFix) {
+        match fix {
+            RepairFix::Relink(path) => {
+                if !self.skipped_relinks.remove(path) {
+                    self.skipped_relinks.insert(path.clone());
+                }
+            }
+            RepairFix::PathIssue(path) => {
+                if !self.skipped_path_fixes.remove(path) {
+                    self.skipped_path_fixes.insert(path.clone());
+                }
+            }
+            RepairFix::Orphan(path) => {
+                let next = if self.orphan_actions.get(path) == Some(&OrphanAction::Skip) {
+                    OrphanAction::Delete
+                } else {
+                    OrphanAction::Skip
+                };
+                self.orphan_actions.insert(path.clone(), next);
+            }
+            RepairFix::CreateWorktree(branch) => {
+                if !self.ignored_branches.remove(branch) && !self.deferred_branches.remove(branch) {
+                    self.deferred_branches.insert(branch.clone());
+                }
+            }
+        }
+    }
+
+    /// Switches a missing worktree between being created and its branch
+    /// being recorded as one that never gets a worktree.
+    pub fn toggle_ignore(&mut self, branch: &str) {
+        if !self.ignored_branches.remove(branch) {
+            self.deferred_branches.remove(branch);
+            self.ignored_branches.insert(branch.to_string());
+        }
+    }
+
+    /// Switches an orphan between being deleted and being adopted.
+    pub fn toggle_adopt(&mut self, orphan: &OrphanedWorktree) {
+        if orphan.adopt_branch.is_none() {
+            return;
+        }
+        let next = if self.orphan_action(orphan) == OrphanAction::Adopt {
+            OrphanAction::Delete
+        } else {
+            OrphanAction::Adopt
+        };
+        self.orphan_actions.insert(orphan.path.clone(), next);
+    }
+}
+
+/// What kind of problem keeps the doctor from repairing the setup, as a
+/// stable code for `gw doctor --format json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnrecoverableCode {
+    /// One directory registered as several worktrees, none of them live.
+    DuplicateRegistration,
+    /// A worktree outside the repo root (or the hub worktrees directory).
+    OutsideRoot,
+    /// A worktree reaching outside the root through a symlink that can't be
+    /// moved back.
+    SymlinkEscape,
+    /// A worktree inside another one with nowhere to move it.
+    NestedWorktree,
+    /// A branch checked out in more than one worktree.
+    BranchInSeveralWorktrees,
+    /// A branch without a worktree whose worktree path is taken.
+    WorktreePathTaken,
+    /// A non-bare repo in the bare layout; needs `gw init`.
+    NotBare,
+    /// A bare repo configured with the hub layout.
+    HubRepoBare,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnrecoverableIssue {
+    pub code: UnrecoverableCode,
+    pub message: String,
+}
+
+impl UnrecoverableIssue {
+    pub fn new(code: UnrecoverableCode, message: String) -> Self {
+        Self { code, message }
+    }
+}
+
+impl std::fmt::Display for UnrecoverableIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct HealthReport {
     pub missing_worktrees: Vec<String>,
-    pub orphaned_worktrees: Vec<PathBuf>,
-    pub unrecoverable_reasons: Vec<String>,
+    pub orphaned_worktrees: Vec<OrphanedWorktree>,
+    pub broken_worktrees: Vec<BrokenWorktreeLink>,
+    pub path_issues: Vec<WorktreePathIssue>,
+    pub unrecoverable_reasons: Vec<UnrecoverableIssue>,
 }
 
 impl HealthReport {
     pub fn has_issues(&self) -> bool {
         !self.missing_worktrees.is_empty()
             || !self.orphaned_worktrees.is_empty()
+            || !self.broken_worktrees.is_empty()
+            || !self.path_issues.is_empty()
             || !self.unrecoverable_reasons.is_empty()
     }
 
     pub fn is_recoverable(&self) -> bool {
         self.unrecoverable_reasons.is_empty()
     }
+
+    pub fn fixes(&self) -> Vec<RepairFix> {
+        let mut fixes = Vec::new();
+        fixes.extend(
+            self.broken_worktrees
+                .iter()
+                .map(|link| RepairFix::Relink(link.path.clone())),
+        );
+        fixes.extend(
+            self.path_issues
+                .iter()
+                .map(|issue| RepairFix::PathIssue(issue.path.clone())),
+        );
+        fixes.extend(
+            self.orphaned_worktrees
+                .iter()
+                .map(|orphan| RepairFix::Orphan(orphan.path.clone())),
+        );
+        fixes.extend(
+            self.missing_worktrees
+                .iter()
+                .map(|branch| RepairFix::CreateWorktree(branch.clone())),
+        );
+        fixes
+    }
+
+    pub fn path_issue(&self, path: &Path) -> Option<&WorktreePathIssue> {
+        self.path_issues.iter().find(|issue| issue.path == path)
+    }
+
+    pub fn orphan(&self, path: &Path) -> Option<&OrphanedWorktree> {
+        self.orphaned_worktrees
+            .iter()
+            .find(|orphan| orphan.path == path)
+    }
 }
@@ -0,0 +1,123 @@
+use crate::cache_db::now_ts;
+use crate::models::{
+    CleanupPolicy, PolicyAction, PolicyCondition, PolicyMatch, UnpushedCommits, WorktreeInfo,
+};
+use crate::{git_ops, services, trash};
+use anyhow::Result;
+use std::path::Path;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// The worktrees `policies` apply to. A worktree several policies match is
+/// listed once, under the first `delete` policy if any. Detached worktrees,
+/// the default branch and the repo root checkout are never matched.
+pub fn evaluate(
+    repo_root: &Path,
+    default_branch: &str,
+    items: &[WorktreeInfo],
+    policies: &[CleanupPolicy],
+) -> Vec<PolicyMatch> {
+    let base = services::ahead_behind_base(repo_root, default_branch)
+        .unwrap_or_else(|_| default_branch.to_string());
+    let mut matches = Vec::new();
+    for item in items {
+        let Some(ref_name) = item.ref_name.as_deref() else {
+            continue;
+        };
+        if item.branch == default_branch || item.path == repo_root {
+            continue;
+        }
+        let applicable: Vec<(&CleanupPolicy, u64)> = policies
+            .iter()
+            .filter_map(|policy| {
+                let age = condition_age_days(item, policy.condition)?;
+                (age > policy.days).then_some((policy, age))
+            })
+            .collect();
+        let Some(&(policy, age_days)) = applicable
+            .iter()
+            .find(|(policy, _)| policy.action == PolicyAction::Delete)
+            .or(applicable.first())
+        else {
+            continue;
+        };
+        let kept_because = if policy.action == PolicyAction::Delete {
+            kept_because(repo_root, item, ref_name, policy.condition, &base)
+        } else {
+            None
+        };
+        matches.push(PolicyMatch {
+            policy: policy.clone(),
+            branch: item.branch.clone(),
+            path: item.path.clone(),
+            ref_name: ref_name.to_string(),
+            age_days,
+            kept_because,
+        });
+    }
+    matches
+}
+
+/// Days the worktree has met `condition`, or `None` if it does not.
+fn condition_age_days(item: &WorktreeInfo, condition: PolicyCondition) -> Option<u64> {
+    match condition {
+        PolicyCondition::Merged => {
+            if item.pr_state.as_deref() != Some("MERGED") {
+                return None;
+            }
+            let merged_ts = item.pr_merged_ts?;
+            Some(((now_ts() - merged_ts).max(0) / SECONDS_PER_DAY) as u64)
+        }
+        PolicyCondition::Untouched => services::branch_age_days(item),
+    }
+}
+
+/// Checked live rather than from the cache, since the worktree is about to
+/// go. A merged branch may still have commits the squashed PR does not, so
+/// only untouched branches are held back by unpushed commits.
+fn kept_because(
+    repo_root: &Path,
+    item: &WorktreeInfo,
+    ref_name: &str,
+    condition: PolicyCondition,
+    base: &str,
+) -> Option<String> {
+    if git_ops::has_uncommitted_changes(&item.path).unwrap_or(true) {
+        return Some("uncommitted changes".to_string());
+    }
+    if condition == PolicyCondition::Untouched {
+        match git_ops::unpushed_commits(repo_root, ref_name, base) {
+            UnpushedCommits::None => {}
+            UnpushedCommits::Ahead(count) | UnpushedCommits::NeverPushed(count) => {
+                return Some(format!("{count} unpushed commit(s)"));
+            }
+        }
+    }
+    None
+}
+
+/// What the policy found, e.g. `feat: PR merged 9 days ago`, plus why the
+/// worktree is kept if it is.
+pub fn describe(found: &PolicyMatch) -> String {
+    let days = found.age_days;
+    let mut text = match found.policy.condition {
+        PolicyCondition::Merged => format!("{}: PR merged {days} days ago", found.branch),
+        PolicyCondition::Untouched => format!("{}: no commits for {days} days", found.branch),
+    };
+    if let Some(reason) = &found.kept_because {
+        text.push_str(&format!(" (kept: {reason})"));
+    }
+    text
+}
+
+/// Moves the worktrees of the matches that delete to the trash. Returns
+/// their branches.
+pub fn apply(repo_root: &Path, matches: &[PolicyMatch]) -> Result<Vec<String>> {
+    let mut deleted = Vec::new();
+    for found in matches.iter().filter(|found| found.deletes()) {
+        trash::trash_worktree(repo_root, &found.path, &found.ref_name)?;
+        services::forget_worktree_path(repo_root, &found.ref_name);
+        deleted.push(found.branch.clone());
+    }
+    Ok(deleted)
+}
@@ -0,0 +1,52 @@
+use crate::errors::GwError;
+use crate::git_ops;
+use anyhow::Result;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+const LOCK_FILE_NAME: &str = "gw.lock";
+
+/// Advisory lock held while worktrees and branches are being changed, so two
+/// `gw` processes (TUI, CLI or `gw serve`) don't operate on the same repo at
+/// once. Released when dropped.
+pub struct RepoLock {
+    file: File,
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Takes the repo's lock (`gw.lock` in the git common dir) without waiting.
+/// Fails with `GwError::RepoLocked` if another process holds it.
+pub fn acquire(repo_root: &Path) -> Result<RepoLock> {
+    let path = git_ops::get_git_common_dir(repo_root)?.join(LOCK_FILE_NAME);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            return Err(GwError::RepoLocked {
+                pid: holder.trim().parse().ok(),
+            }
+            .into());
+        }
+        Err(TryLockError::Error(err)) => return Err(err.into()),
+    }
+
+    // Only informational, for the message other processes show.
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    Ok(RepoLock { file })
+}
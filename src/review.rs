@@ -0,0 +1,84 @@
+use crate::forge::Forge;
+use crate::{git_ops, services};
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const REVIEW_DIR: &str = "review";
+const REVIEW_REF_PREFIX: &str = "refs/gw/review/";
+/// Written to a review worktree's admin dir in the git common dir; holds the
+/// request number. Goes away with the worktree.
+const MARKER_FILE: &str = "gw-review";
+
+/// `review/<number>` in the worktrees directory.
+pub fn review_path(repo_root: &Path, number: u64) -> PathBuf {
+    services::worktrees_dir(repo_root)
+        .join(REVIEW_DIR)
+        .join(number.to_string())
+}
+
+/// The request number if the worktree at `path` was created by `gw review`.
+pub fn review_number(path: &Path) -> Option<u64> {
+    let admin_dir = git_ops::worktree_admin_dir(path)?;
+    fs::read_to_string(admin_dir.join(MARKER_FILE))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Review worktrees by request number.
+pub fn list_reviews(repo_root: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let mut reviews: Vec<(u64, PathBuf)> = git_ops::parse_worktrees(Some(repo_root))?
+        .into_iter()
+        .filter_map(|wt| review_number(&wt.path).map(|number| (number, wt.path)))
+        .collect();
+    reviews.sort();
+    Ok(reviews)
+}
+
+/// Checks out the head of request `number`, fetched from origin, on a
+/// detached HEAD in `review/<number>`. An existing review worktree is moved
+/// to the latest head. Returns its path.
+pub fn open_review(repo_root: &Path, forge: &dyn Forge, number: u64) -> Result<PathBuf> {
+    let local_ref = format!("{REVIEW_REF_PREFIX}{number}");
+    git_ops::fetch_ref(repo_root, &forge.request_head_ref(number), &local_ref)?;
+
+    let path = review_path(repo_root, number);
+    if review_number(&path) == Some(number) {
+        git_ops::checkout_detached(&path, &local_ref)?;
+        return Ok(path);
+    }
+    if !git_ops::is_path_available(&path) {
+        return Err(anyhow!("{} already exists", path.display()));
+    }
+    git_ops::worktree_add_detached(repo_root, &path, &local_ref)?;
+    let admin_dir = git_ops::worktree_admin_dir(&path)
+        .ok_or_else(|| anyhow!("{} is not registered", path.display()))?;
+    fs::write(admin_dir.join(MARKER_FILE), format!("{number}\n"))?;
+    Ok(path)
+}
+
+/// Removes the review worktree of request `number`, changes included.
+pub fn remove_review(repo_root: &Path, number: u64, path: &Path) -> Result<()> {
+    services::remove_worktree(repo_root, path)?;
+    git_ops::delete_ref(repo_root, &format!("{REVIEW_REF_PREFIX}{number}"))
+}
+
+/// Removes the review worktrees whose request was closed or merged, except
+/// ones with uncommitted changes. Requests the forge cannot tell about are
+/// kept. Returns the numbers removed.
+pub fn prune_closed(repo_root: &Path, forge: &dyn Forge) -> Result<Vec<u64>> {
+    let mut removed = Vec::new();
+    for (number, path) in list_reviews(repo_root)? {
+        let Ok(state) = forge.request_state(repo_root, number) else {
+            continue;
+        };
+        if state == "OPEN" || git_ops::has_uncommitted_changes(&path).unwrap_or(true) {
+            continue;
+        }
+        remove_review(repo_root, number, &path)?;
+        removed.push(number);
+    }
+    Ok(removed)
+}
@@ -0,0 +1,334 @@
+use crate::models::WorktreeInfo;
+use crate::{forge, repo_lock, services, trash};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Any error from the operation itself, e.g. a failed git command.
+const OPERATION_FAILED: i64 = -32000;
+
+/// Serves JSON-RPC 2.0 on the unix socket at `socket`, one request or
+/// response per line. Long-running methods send `progress` notifications
+/// (`{"method": "progress", "params": {"id": ..., "message": ...}}`) before
+/// their response. Methods: `list`, `create {branch, from?}`,
+/// `delete {branch, force?}` and `refresh`. Notifications (requests without
+/// an `id`) run without any reply.
+#[cfg(unix)]
+pub fn serve(repo_root: &Path, socket: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(socket) {
+        if !metadata.file_type().is_socket() {
+            return Err(anyhow!(
+                "gw serve: {} exists and is not a socket",
+                socket.display()
+            ));
+        }
+        if UnixStream::connect(socket).is_ok() {
+            return Err(anyhow!(
+                "gw serve: another server is listening on {}",
+                socket.display()
+            ));
+        }
+        // Left behind by a server that was killed.
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)
+        .map_err(|err| anyhow!("gw serve: cannot listen on {}: {err}", socket.display()))?;
+    eprintln!("gw serve: listening on {}", socket.display());
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let repo_root = repo_root.to_path_buf();
+        std::thread::spawn(move || {
+            let Ok(writer) = stream.try_clone() else {
+                return;
+            };
+            handle_connection(&repo_root, BufReader::new(stream), writer);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_repo_root: &Path, _socket: &Path) -> Result<()> {
+    Err(anyhow!(
+        "gw serve: unix sockets are not supported on this platform"
+    ))
+}
+
+fn handle_connection(repo_root: &Path, reader: impl BufRead, mut writer: impl Write) {
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                let response = error_response(&Value::Null, PARSE_ERROR, &err.to_string());
+                if send(&mut writer, &response).is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        // A notification: the client wants no reply, not even progress.
+        let Some(id) = request.get("id").cloned() else {
+            let method = request.get("method").and_then(Value::as_str).unwrap_or("");
+            let params = request.get("params").cloned().unwrap_or(Value::Null);
+            let _ = dispatch(repo_root, method, &params, &mut |_| {});
+            continue;
+        };
+        let method = request.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        let mut progress = |message: &str| {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "progress",
+                "params": { "id": id, "message": message },
+            });
+            let _ = send(&mut writer, &notification);
+        };
+        let response = match dispatch(repo_root, method, &params, &mut progress) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(RpcError { code, message }) => error_response(&id, code, &message),
+        };
+        if send(&mut writer, &response).is_err() {
+            return;
+        }
+    }
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        Self {
+            code: OPERATION_FAILED,
+            message: err.to_string(),
+        }
+    }
+}
+
+fn invalid_params(message: &str) -> RpcError {
+    RpcError {
+        code: INVALID_PARAMS,
+        message: message.to_string(),
+    }
+}
+
+fn dispatch(
+    repo_root: &Path,
+    method: &str,
+    params: &Value,
+    progress: &mut dyn FnMut(&str),
+) -> Result<Value, RpcError> {
+    match method {
+        "list" => Ok(to_value(&services::load_worktrees(repo_root)?)),
+        "create" => {
+            let branch = string_param(params, "branch")?
+                .ok_or_else(|| invalid_params("`branch` is required"))?;
+            let from = string_param(params, "from")?;
+            let path = create(repo_root, &branch, from, progress)?;
+            Ok(json!({ "path": path }))
+        }
+        "delete" => {
+            let branch = string_param(params, "branch")?
+                .ok_or_else(|| invalid_params("`branch` is required"))?;
+            let force = params
+                .get("force")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            delete(repo_root, &branch, force, progress)?;
+            Ok(Value::Null)
+        }
+        "refresh" => Ok(to_value(&refresh(repo_root, progress)?)),
+        _ => Err(RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("unknown method `{method}`"),
+        }),
+    }
+}
+
+fn string_param(params: &Value, name: &str) -> Result<Option<String>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.trim().to_string())),
+        Some(_) => Err(invalid_params(&format!("`{name}` must be a string"))),
+    }
+}
+
+fn to_value(items: &[WorktreeInfo]) -> Value {
+    serde_json::to_value(items).unwrap_or(Value::Null)
+}
+
+/// Same as `gw new <branch> [--from <from>]`.
+fn create(
+    repo_root: &Path,
+    branch: &str,
+    from: Option<String>,
+    progress: &mut dyn FnMut(&str),
+) -> Result<PathBuf> {
+    let path = services::new_worktree_target(repo_root, branch)?;
+    let (base, pull_before) = match from {
+        Some(base) => (base, None),
+        None => services::default_base(repo_root)?,
+    };
+    if pull_before.is_some() {
+        progress(&format!("pulling {base}"));
+    }
+    let _lock = repo_lock::acquire(repo_root)?;
+    progress(&format!("creating {branch} from {base}"));
+    let reminder = services::create_worktree(
+        repo_root,
+        &path,
+        branch,
+        &base,
+        pull_before.as_deref(),
+        false,
+    )?;
+    if let Some(reminder) = reminder {
+        progress(&reminder);
+    }
+    Ok(path)
+}
+
+/// Deletes the worktree and local branch like `D` in the TUI. Worktrees
+/// with uncommitted changes are only deleted with `force`.
+fn delete(
+    repo_root: &Path,
+    branch: &str,
+    force: bool,
+    progress: &mut dyn FnMut(&str),
+) -> Result<()> {
+    let item = services::load_worktrees(repo_root)?
+        .into_iter()
+        .find(|item| item.branch == branch)
+        .ok_or_else(|| anyhow!("no worktree for branch {branch}"))?;
+    let ref_name = item
+        .ref_name
+        .clone()
+        .ok_or_else(|| anyhow!("cannot delete a detached worktree"))?;
+    if item.path == repo_root {
+        return Err(anyhow!("cannot delete the checkout at the repo root"));
+    }
+    if let Some(other) = services::checked_out_elsewhere(repo_root, branch) {
+        return Err(services::checked_out_elsewhere_error(
+            repo_root, "delete", branch, &other,
+        ));
+    }
+    if item.dirty && !force {
+        return Err(anyhow!(
+            "{branch} has uncommitted changes; pass `force` to delete it anyway"
+        ));
+    }
+
+    let _lock = repo_lock::acquire(repo_root)?;
+    progress(&format!("deleting {branch}"));
+    trash::trash_worktree(repo_root, &item.path, &ref_name)?;
+    services::forget_worktree_path(repo_root, &ref_name);
+    Ok(())
+}
+
+/// Everything `r` in the TUI refreshes, reported step by step.
+fn refresh(repo_root: &Path, progress: &mut dyn FnMut(&str)) -> Result<Vec<WorktreeInfo>> {
+    let mut items = services::load_worktrees(repo_root)?;
+    progress("fetching upstream status");
+    services::refresh_pull_push(repo_root, &mut items, &mut |_| {})?;
+    progress("computing changes");
+    services::refresh_changes(repo_root, &mut items, &mut |_| {})?;
+
+    let forge = forge::detect_forge(repo_root);
+    if forge.availability().is_ok() {
+        progress(&format!("fetching {} data", forge.request_label()));
+        services::refresh_forge(repo_root, forge.as_ref(), &mut items)?;
+    }
+    Ok(items)
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn send(writer: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    writeln!(writer, "{message}")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replies(input: &str) -> Vec<Value> {
+        let _fakes = crate::git_ops::lock_fakes();
+        let mut output = Vec::new();
+        handle_connection(Path::new("/nonexistent"), input.as_bytes(), &mut output);
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn requests_get_a_response_with_their_id() {
+        let replies = replies("{\"jsonrpc\": \"2.0\", \"id\": 7, \"method\": \"nope\"}\n");
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["id"], 7);
+        assert_eq!(replies[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn notifications_get_no_response() {
+        let replies = replies(
+            "{\"jsonrpc\": \"2.0\", \"method\": \"nope\"}\n\
+             {\"jsonrpc\": \"2.0\", \"method\": \"list\"}\n",
+        );
+        assert!(replies.is_empty(), "{replies:?}");
+    }
+
+    #[test]
+    fn unparsable_lines_get_a_parse_error() {
+        let replies = replies("{not json\n");
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["id"], Value::Null);
+        assert_eq!(replies[0]["error"]["code"], PARSE_ERROR);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serve_refuses_to_replace_a_file_that_is_not_a_socket() {
+        let dir = std::env::temp_dir().join(format!("gw-serve-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("not-a-socket");
+        std::fs::write(&file, "keep me").unwrap();
+
+        let err = serve(&dir, &file).unwrap_err();
+        assert!(err.to_string().contains("is not a socket"), "{err}");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+
+        let err = serve(&dir, &dir).unwrap_err();
+        assert!(err.to_string().contains("is not a socket"), "{err}");
+        assert!(dir.is_dir());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
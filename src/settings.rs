@@ -0,0 +1,488 @@
+use crate::models::{
+    CleanupPolicy, CommandPolicy, FetchOptions, NotificationEvent, PolicyAction, PolicyCondition,
+    Preset, PullStrategy, RepoLayout, TimeFormat,
+};
+use crate::{git_ops, logging};
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const DEFAULT_ISSUE_BRANCH_TEMPLATE: &str = "{number}-{title}";
+const DEFAULT_PR_TITLE_TEMPLATE: &str = "{subject}";
+const DEFAULT_TRASH_RETENTION_DAYS: u64 = 7;
+pub const DEFAULT_STALE_AFTER_DAYS: u64 = 30;
+
+pub fn settings_path(repo_root: &Path) -> PathBuf {
+    repo_root.join(".gw").join("settings.json")
+}
+
+/// `~/.config/gw/settings.json`, for settings shared by every repo.
+pub fn global_settings_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("cannot resolve home directory"))?;
+    Ok(home.join(".config").join("gw").join("settings.json"))
+}
+
+pub fn load_settings(repo_root: &Path) -> Result<Value> {
+    read_settings_file(&settings_path(repo_root))
+}
+
+pub fn load_global_settings() -> Result<Value> {
+    read_settings_file(&global_settings_path()?)
+}
+
+fn read_settings_file(path: &Path) -> Result<Value> {
+    if !path.exists() {
+        return Ok(json!({}));
+    }
+
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let raw: Value = serde_json::from_str(&text)
+        .with_context(|| format!("invalid JSON in {}", path.display()))?;
+    if !raw.is_object() {
+        return Err(anyhow!("invalid settings format in {}", path.display()));
+    }
+    Ok(raw)
+}
+
+pub fn save_settings(repo_root: &Path, settings: &Value) -> Result<()> {
+    let path = settings_path(repo_root);
+    if git_ops::dry_run() {
+        logging::would_run(&format!("write {}", path.display()));
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut text = serde_json::to_string_pretty(settings)?;
+    text.push('\n');
+    fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Layout selected by the `layout` and `worktreesDir` settings. Without them
+/// the repo uses the bare layout.
+pub fn load_layout(repo_root: &Path) -> Result<RepoLayout> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("layout").and_then(Value::as_str) {
+        None | Some("bare") => Ok(RepoLayout::Bare),
+        Some("hub") => {
+            let dir = match settings.get("worktreesDir") {
+                None => default_worktrees_dir(repo_root),
+                Some(Value::String(dir)) if !dir.trim().is_empty() => dir.trim().to_string(),
+                Some(_) => return Err(anyhow!("invalid worktreesDir in settings")),
+            };
+            Ok(RepoLayout::Hub {
+                worktrees_dir: resolve_dir(repo_root, &dir),
+            })
+        }
+        Some(other) => Err(anyhow!("unknown layout `{other}` in settings")),
+    }
+}
+
+/// Template for branches created with `gw new --issue`; `{number}` and
+/// `{title}` (slugified) are substituted.
+pub fn load_issue_branch_template(repo_root: &Path) -> Result<String> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("issueBranchTemplate") {
+        None => Ok(DEFAULT_ISSUE_BRANCH_TEMPLATE.to_string()),
+        Some(Value::String(template)) if !template.trim().is_empty() => {
+            Ok(template.trim().to_string())
+        }
+        Some(_) => Err(anyhow!("invalid issueBranchTemplate in settings")),
+    }
+}
+
+/// Title of the draft requests opened by `gw pr create`; `{branch}` and
+/// `{subject}` (of the branch's first commit) are substituted.
+pub fn load_pr_title_template(repo_root: &Path) -> Result<String> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("prTitleTemplate") {
+        None => Ok(DEFAULT_PR_TITLE_TEMPLATE.to_string()),
+        Some(Value::String(template)) if !template.trim().is_empty() => {
+            Ok(template.trim().to_string())
+        }
+        Some(_) => Err(anyhow!("invalid prTitleTemplate in settings")),
+    }
+}
+
+/// Days deleted worktrees stay in the trash (`trashRetentionDays`).
+pub fn load_trash_retention_days(repo_root: &Path) -> Result<u64> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("trashRetentionDays") {
+        None => Ok(DEFAULT_TRASH_RETENTION_DAYS),
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| anyhow!("invalid trashRetentionDays in settings")),
+    }
+}
+
+/// Command that opens a worktree in an editor: the repo's `editor` setting,
+/// else the one in the global settings, else `$VISUAL` or `$EDITOR`.
+pub fn load_editor(repo_root: &Path) -> Result<Option<String>> {
+    for settings in [load_settings(repo_root)?, load_global_settings()?] {
+        match settings.get("editor") {
+            None => {}
+            Some(Value::String(editor)) if !editor.trim().is_empty() => {
+                return Ok(Some(editor.trim().to_string()));
+            }
+            Some(_) => return Err(anyhow!("invalid editor in settings")),
+        }
+    }
+    Ok(["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .map(|editor| editor.trim().to_string())
+        .find(|editor| !editor.is_empty()))
+}
+
+/// Days without a commit after which a branch counts as stale
+/// (`staleAfterDays`).
+pub fn load_stale_after_days(repo_root: &Path) -> Result<u64> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("staleAfterDays") {
+        None => Ok(DEFAULT_STALE_AFTER_DAYS),
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| anyhow!("invalid staleAfterDays in settings")),
+    }
+}
+
+/// Whether BEHIND|AHEAD compares against `origin/<default>` (`"remote"`, the
+/// default) or the local default branch (`"local"`), per `aheadBehindBase`.
+pub fn load_compare_with_remote(repo_root: &Path) -> Result<bool> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("aheadBehindBase").and_then(Value::as_str) {
+        None | Some("remote") => Ok(true),
+        Some("local") => Ok(false),
+        Some(other) => Err(anyhow!("unknown aheadBehindBase `{other}` in settings")),
+    }
+}
+
+/// Whether refreshes skip untracked files when checking worktrees for
+/// changes (`fastStatus`), for repos where a full `git status` is slow.
+pub fn load_fast_status(repo_root: &Path) -> Result<bool> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("fastStatus") {
+        None => Ok(false),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| anyhow!("invalid fastStatus in settings")),
+    }
+}
+
+/// Whether new worktrees are created with their files checked out in the
+/// background (`fastCreate`), like `gw new --fast`, for very large repos.
+pub fn load_fast_create(repo_root: &Path) -> Result<bool> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("fastCreate") {
+        None => Ok(false),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| anyhow!("invalid fastCreate in settings")),
+    }
+}
+
+/// `fetchTags` (default true), `fetchJobs` and `fetchWorktreeBranchesOnly`
+/// (default false), for repos where fetching everything is slow.
+pub fn load_fetch_options(repo_root: &Path) -> Result<FetchOptions> {
+    let settings = load_settings(repo_root)?;
+    let flag = |key: &str, default: bool| match settings.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| anyhow!("invalid {key} in settings")),
+    };
+    let jobs = match settings.get("fetchJobs") {
+        None => None,
+        Some(value) => Some(
+            value
+                .as_u64()
+                .filter(|jobs| *jobs > 0)
+                .ok_or_else(|| anyhow!("invalid fetchJobs in settings"))?,
+        ),
+    };
+    Ok(FetchOptions {
+        tags: flag("fetchTags", true)?,
+        jobs,
+        worktree_branches_only: flag("fetchWorktreeBranchesOnly", false)?,
+    })
+}
+
+/// Time limits in seconds of git commands that talk to a remote
+/// (`gitTimeoutSeconds`, default 60) and of all others
+/// (`localGitTimeoutSeconds`, default none); 0 disables a limit.
+pub fn load_git_timeouts(repo_root: &Path) -> Result<(u64, u64)> {
+    let settings = load_settings(repo_root)?;
+    let seconds = |key: &str, default: u64| match settings.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| anyhow!("invalid {key} in settings")),
+    };
+    Ok((
+        seconds("gitTimeoutSeconds", git_ops::DEFAULT_REMOTE_TIMEOUT_SECS)?,
+        seconds("localGitTimeoutSeconds", 0)?,
+    ))
+}
+
+/// Events with desktop notifications, from `notifications`: `true` for all
+/// of them, or e.g. `{"pull": true, "checks": true}`. None by default.
+pub fn load_notifications(repo_root: &Path) -> Result<Vec<NotificationEvent>> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("notifications") {
+        None | Some(Value::Bool(false)) => Ok(Vec::new()),
+        Some(Value::Bool(true)) => Ok(NotificationEvent::ALL.to_vec()),
+        Some(Value::Object(events)) => {
+            let mut enabled = Vec::new();
+            for (key, value) in events {
+                let event = NotificationEvent::ALL
+                    .into_iter()
+                    .find(|event| event.setting_key() == key)
+                    .ok_or_else(|| anyhow!("unknown event `{key}` in notifications setting"))?;
+                match value {
+                    Value::Bool(true) => enabled.push(event),
+                    Value::Bool(false) => {}
+                    _ => return Err(anyhow!("invalid notifications.{key} in settings")),
+                }
+            }
+            Ok(enabled)
+        }
+        Some(_) => Err(anyhow!("invalid notifications in settings")),
+    }
+}
+
+/// Branches whose worktrees always get a full status check, even with
+/// `fastStatus` on (`fullStatusBranches`).
+pub fn load_full_status_branches(repo_root: &Path) -> Result<Vec<String>> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("fullStatusBranches") {
+        None => Ok(Vec::new()),
+        Some(Value::Array(branches)) => branches
+            .iter()
+            .map(|branch| {
+                branch
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("invalid fullStatusBranches in settings"))
+            })
+            .collect(),
+        Some(_) => Err(anyhow!("invalid fullStatusBranches in settings")),
+    }
+}
+
+/// The `pullStrategy` setting: `"merge"`, `"rebase"` or `"ff-only"`.
+pub fn load_pull_strategy(repo_root: &Path) -> Result<Option<PullStrategy>> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("pullStrategy").and_then(Value::as_str) {
+        None => Ok(None),
+        Some("merge") => Ok(Some(PullStrategy::Merge)),
+        Some("rebase") => Ok(Some(PullStrategy::Rebase)),
+        Some("ff-only") => Ok(Some(PullStrategy::FfOnly)),
+        Some(other) => Err(anyhow!("unknown pullStrategy `{other}` in settings")),
+    }
+}
+
+/// The `timeFormat` setting: `"relative"` (the default), `"date"` or
+/// `"datetime"`.
+pub fn load_time_format(repo_root: &Path) -> Result<TimeFormat> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("timeFormat").and_then(Value::as_str) {
+        None | Some("relative") => Ok(TimeFormat::Relative),
+        Some("date") => Ok(TimeFormat::Date),
+        Some("datetime") => Ok(TimeFormat::DateTime),
+        Some(other) => Err(anyhow!("unknown timeFormat `{other}` in settings")),
+    }
+}
+
+/// The `cleanupPolicies` setting, e.g.
+/// `[{"when": "merged", "days": 7, "action": "delete", "onStartup": true}]`.
+/// `action` defaults to `"warn"`, `onStartup` to false.
+pub fn load_cleanup_policies(repo_root: &Path) -> Result<Vec<CleanupPolicy>> {
+    let settings = load_settings(repo_root)?;
+    let policies = match settings.get("cleanupPolicies") {
+        None => return Ok(Vec::new()),
+        Some(Value::Array(policies)) => policies,
+        Some(_) => return Err(anyhow!("invalid cleanupPolicies in settings")),
+    };
+    policies.iter().map(parse_cleanup_policy).collect()
+}
+
+fn parse_cleanup_policy(policy: &Value) -> Result<CleanupPolicy> {
+    let condition = match policy.get("when").and_then(Value::as_str) {
+        Some("merged") => PolicyCondition::Merged,
+        Some("untouched") => PolicyCondition::Untouched,
+        Some(other) => return Err(anyhow!("unknown cleanup policy condition `{other}`")),
+        None => return Err(anyhow!("cleanup policy without `when` in settings")),
+    };
+    let days = policy
+        .get("days")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("cleanup policy without a valid `days` in settings"))?;
+    let action = match policy.get("action").and_then(Value::as_str) {
+        None | Some("warn") => PolicyAction::Warn,
+        Some("delete") => PolicyAction::Delete,
+        Some(other) => return Err(anyhow!("unknown cleanup policy action `{other}`")),
+    };
+    let on_startup = match policy.get("onStartup") {
+        None => false,
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| anyhow!("invalid onStartup in cleanup policy"))?,
+    };
+    Ok(CleanupPolicy {
+        condition,
+        days,
+        action,
+        on_startup,
+    })
+}
+
+/// The `commandPolicy` of the global settings only, so a repo can't loosen
+/// it: `{"allow": ["npm ci", "direnv", "git *"], "deny": ["curl*"]}`.
+pub fn load_command_policy() -> Result<CommandPolicy> {
+    let settings = load_global_settings()?;
+    let Some(policy) = settings.get("commandPolicy") else {
+        return Ok(CommandPolicy::default());
+    };
+    let patterns = |key: &str| -> Result<Option<Vec<String>>> {
+        match policy.get(key) {
+            None => Ok(None),
+            Some(value) => value
+                .as_array()
+                .and_then(|items| {
+                    items
+                        .iter()
+                        .map(|item| item.as_str().map(|pattern| pattern.trim().to_string()))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .map(Some)
+                .ok_or_else(|| anyhow!("invalid commandPolicy.{key} in global settings")),
+        }
+    };
+    if !policy.is_object() {
+        return Err(anyhow!("invalid commandPolicy in global settings"));
+    }
+    Ok(CommandPolicy {
+        allow: patterns("allow")?,
+        deny: patterns("deny")?.unwrap_or_default(),
+    })
+}
+
+/// The `presets` setting: named git command sequences, e.g.
+/// `{"sync": {"steps": ["fetch", "rebase {base}"], "key": "S"}}`.
+pub fn load_presets(repo_root: &Path) -> Result<Vec<Preset>> {
+    let settings = load_settings(repo_root)?;
+    let presets = match settings.get("presets") {
+        None => return Ok(Vec::new()),
+        Some(Value::Object(presets)) => presets,
+        Some(_) => return Err(anyhow!("invalid presets in settings")),
+    };
+    presets
+        .iter()
+        .map(|(name, preset)| parse_preset(name, preset))
+        .collect()
+}
+
+fn parse_preset(name: &str, preset: &Value) -> Result<Preset> {
+    let steps = preset
+        .get("steps")
+        .and_then(Value::as_array)
+        .filter(|steps| !steps.is_empty())
+        .ok_or_else(|| anyhow!("preset `{name}` without `steps` in settings"))?
+        .iter()
+        .map(|step| {
+            step.as_str()
+                .map(|step| step.trim().to_string())
+                .filter(|step| !step.is_empty())
+                .ok_or_else(|| anyhow!("invalid step in preset `{name}`"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let key = match preset.get("key") {
+        None => None,
+        Some(Value::String(key)) if key.chars().count() == 1 => key.chars().next(),
+        Some(_) => {
+            return Err(anyhow!(
+                "invalid key in preset `{name}`: use a single character"
+            ))
+        }
+    };
+    Ok(Preset {
+        name: name.to_string(),
+        steps,
+        key,
+    })
+}
+
+/// Records the hub layout with worktrees under `worktrees_dir` (relative to
+/// the repo root unless absolute).
+/// Branches meant to stay without a worktree (`noWorktreeBranches`), e.g.
+/// the ones `gw init --branches` left out. Health checks don't count them as
+/// missing a worktree.
+pub fn load_no_worktree_branches(repo_root: &Path) -> Result<Vec<String>> {
+    let settings = load_settings(repo_root)?;
+    match settings.get("noWorktreeBranches") {
+        None => Ok(Vec::new()),
+        Some(Value::Array(branches)) => branches
+            .iter()
+            .map(|branch| {
+                branch
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("invalid noWorktreeBranches in settings"))
+            })
+            .collect(),
+        Some(_) => Err(anyhow!("invalid noWorktreeBranches in settings")),
+    }
+}
+
+/// Adds `branches` to `noWorktreeBranches`, keeping the existing entries.
+pub fn add_no_worktree_branches(repo_root: &Path, branches: &[String]) -> Result<()> {
+    let mut entries = load_no_worktree_branches(repo_root)?;
+    for branch in branches {
+        if !entries.contains(branch) {
+            entries.push(branch.clone());
+        }
+    }
+    entries.sort();
+    let mut settings = load_settings(repo_root)?;
+    let Some(settings_obj) = settings.as_object_mut() else {
+        return Err(anyhow!("invalid settings object"));
+    };
+    settings_obj.insert("noWorktreeBranches".to_string(), json!(entries));
+    save_settings(repo_root, &settings)
+}
+
+pub fn save_hub_layout(repo_root: &Path, worktrees_dir: &str) -> Result<()> {
+    let mut settings = load_settings(repo_root)?;
+    let Some(settings_obj) = settings.as_object_mut() else {
+        return Err(anyhow!("invalid settings object"));
+    };
+    settings_obj.insert("layout".to_string(), json!("hub"));
+    settings_obj.insert("worktreesDir".to_string(), json!(worktrees_dir));
+    save_settings(repo_root, &settings)
+}
+
+/// `../<repo name>.worktrees`, next to the main checkout.
+pub fn default_worktrees_dir(repo_root: &Path) -> String {
+    let name = repo_root
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "repo".to_string());
+    format!("../{name}.worktrees")
+}
+
+fn resolve_dir(repo_root: &Path, dir: &str) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in repo_root.join(dir).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    git_ops::canonicalize(&resolved)
+}
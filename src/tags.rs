@@ -0,0 +1,48 @@
+use crate::{git_ops, services};
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TAG_DIR: &str = "tags";
+/// Written to a tag worktree's admin dir in the git common dir; holds the tag
+/// name. Goes away with the worktree.
+const MARKER_FILE: &str = "gw-tag";
+
+/// `tags/<tag>` in the worktrees directory.
+pub fn tag_path(repo_root: &Path, tag: &str) -> PathBuf {
+    services::worktrees_dir(repo_root).join(TAG_DIR).join(tag)
+}
+
+/// The tag if the worktree at `path` was created by `gw new --tag`.
+pub fn tag_name(path: &Path) -> Option<String> {
+    let admin_dir = git_ops::worktree_admin_dir(path)?;
+    let tag = fs::read_to_string(admin_dir.join(MARKER_FILE)).ok()?;
+    Some(tag.trim().to_string()).filter(|tag| !tag.is_empty())
+}
+
+/// Checks out `tag` on a detached HEAD in `tags/<tag>`, fetching it from
+/// origin when it is not known locally. An existing worktree for the tag is
+/// reused. Returns its path.
+pub fn open_tag(repo_root: &Path, tag: &str) -> Result<PathBuf> {
+    let tag_ref = format!("refs/tags/{tag}");
+    if git_ops::tag_commit(repo_root, tag).is_none() {
+        // Release tags are often only on the remote.
+        let _ = git_ops::fetch_ref(repo_root, &tag_ref, &tag_ref);
+        if git_ops::tag_commit(repo_root, tag).is_none() {
+            return Err(anyhow!("no tag named {tag}"));
+        }
+    }
+
+    let path = tag_path(repo_root, tag);
+    if tag_name(&path).as_deref() == Some(tag) {
+        return Ok(path);
+    }
+    if !git_ops::is_path_available(&path) {
+        return Err(anyhow!("{} already exists", path.display()));
+    }
+    git_ops::worktree_add_detached(repo_root, &path, &tag_ref)?;
+    let admin_dir = git_ops::worktree_admin_dir(&path)
+        .ok_or_else(|| anyhow!("{} is not registered", path.display()))?;
+    fs::write(admin_dir.join(MARKER_FILE), format!("{tag}\n"))?;
+    Ok(path)
+}
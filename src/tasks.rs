@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+
+/// Latest progress message of a task, shared between the task and whoever
+/// shows it. Cloning gives another handle to the same message.
+#[derive(Debug, Clone, Default)]
+pub struct Progress(Arc<Mutex<Option<String>>>);
+
+impl Progress {
+    pub fn set(&self, message: impl Into<String>) {
+        if let Ok(mut slot) = self.0.lock() {
+            *slot = Some(message.into());
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut slot) = self.0.lock() {
+            *slot = None;
+        }
+    }
+
+    pub fn get(&self) -> Option<String> {
+        self.0.lock().ok().and_then(|slot| slot.clone())
+    }
+}
+
+/// What a running task sees of itself.
+pub struct TaskContext {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TaskContext {
+    /// Whether the task's result is no longer wanted; long tasks check this
+    /// between steps and stop early.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fails with "cancelled" once [`Self::is_cancelled`], for `?` between
+    /// the steps of a task.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(anyhow!("cancelled"));
+        }
+        Ok(())
+    }
+}
+
+/// State of a task as seen by [`Task::poll`].
+pub enum TaskPoll<T> {
+    Running,
+    Done(T),
+    /// The task panicked, was cancelled before it started, or its thread
+    /// could not be spawned.
+    Lost,
+}
+
+/// Handle to background work started with [`TaskPool::spawn`].
+pub struct Task<T> {
+    result: mpsc::Receiver<T>,
+    cancelled: Arc<AtomicBool>,
+    progress: Progress,
+}
+
+impl<T> Task<T> {
+    /// The task's result once it is there; never blocks.
+    pub fn poll(&self) -> TaskPoll<T> {
+        match self.result.try_recv() {
+            Ok(result) => TaskPoll::Done(result),
+            Err(mpsc::TryRecvError::Empty) => TaskPoll::Running,
+            Err(mpsc::TryRecvError::Disconnected) => TaskPoll::Lost,
+        }
+    }
+
+    /// Asks the task to stop. One still waiting for a slot never runs; a
+    /// running one stops at its next [`TaskContext::is_cancelled`] check.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn progress(&self) -> Option<String> {
+        self.progress.get()
+    }
+}
+
+/// Runs tasks on named threads, at most `limit` at a time; the rest wait for
+/// a slot.
+pub struct TaskPool {
+    limit: usize,
+    running: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl TaskPool {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            running: Arc::new((Mutex::new(0), Condvar::new())),
+        }
+    }
+
+    /// Starts `work` on a thread named `gw-<name>` (as panic messages show
+    /// it). The task reports progress through its clone of `progress`.
+    pub fn spawn<T, F>(&self, name: &str, progress: Progress, work: F) -> Task<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&TaskContext) -> T + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let context = TaskContext {
+            cancelled: Arc::clone(&cancelled),
+        };
+        let running = Arc::clone(&self.running);
+        let limit = self.limit;
+        // If the thread cannot be spawned, the sender is dropped with the
+        // closure and the task polls as lost.
+        let _ = thread::Builder::new()
+            .name(format!("gw-{name}"))
+            .spawn(move || {
+                let _slot = Slot::acquire(running, limit);
+                if context.is_cancelled() {
+                    return;
+                }
+                let _ = tx.send(work(&context));
+            });
+
+        Task {
+            result: rx,
+            cancelled,
+            progress,
+        }
+    }
+}
+
+/// One of the pool's running slots, given back when dropped, also when the
+/// task panics.
+struct Slot {
+    running: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl Slot {
+    /// Waits until fewer than `limit` tasks are running.
+    fn acquire(running: Arc<(Mutex<usize>, Condvar)>, limit: usize) -> Self {
+        {
+            let (count, freed) = &*running;
+            let mut count = count
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            while *count >= limit {
+                count = freed
+                    .wait(count)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+            *count += 1;
+        }
+        Self { running }
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let (count, freed) = &*self.running;
+        let mut count = count
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *count -= 1;
+        freed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait<T>(task: &Task<T>) -> TaskPoll<T> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match task.poll() {
+                TaskPoll::Running if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(5))
+                }
+                poll => return poll,
+            }
+        }
+    }
+
+    #[test]
+    fn running_tasks_stop_at_their_next_check() {
+        let pool = TaskPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let task = pool.spawn("steps", Progress::default(), move |context| {
+            started_tx.send(()).unwrap();
+            let mut steps = 0;
+            while context.check().is_ok() && steps < 500 {
+                steps += 1;
+                thread::sleep(Duration::from_millis(10));
+            }
+            steps
+        });
+        started_rx.recv().unwrap();
+        task.cancel();
+        match wait(&task) {
+            TaskPoll::Done(steps) => assert!(steps < 500),
+            _ => panic!("task did not finish"),
+        }
+    }
+
+    #[test]
+    fn tasks_cancelled_while_waiting_for_a_slot_never_run() {
+        let pool = TaskPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let blocker = pool.spawn("blocker", Progress::default(), move |_| {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        });
+        started_rx.recv().unwrap();
+        let waiting = pool.spawn("waiting", Progress::default(), |_| ());
+        waiting.cancel();
+        release_tx.send(()).unwrap();
+        assert!(matches!(wait(&blocker), TaskPoll::Done(())));
+        assert!(matches!(wait(&waiting), TaskPoll::Lost));
+    }
+}
@@ -0,0 +1,454 @@
+use crate::cache_db::now_ts;
+use crate::models::{DeletedBranch, TrashedWorktree};
+use crate::{git_ops, hooks, logging, services, settings};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const TRASH_DIR_NAME: &str = "gw-trash";
+const TRASH_REF_PREFIX: &str = "refs/gw/trash/";
+const ENTRY_FILE: &str = "entry.json";
+const SECONDS_PER_DAY: i64 = 86_400;
+const DELETED_BRANCHES_FILE: &str = "gw-deleted-branches.jsonl";
+/// Deletions the log keeps; older ones are dropped.
+const DELETED_BRANCHES_LIMIT: usize = 200;
+
+/// `gw-trash` in the git common dir, next to the worktree admin entries. Each
+/// entry is a directory holding the moved worktree, its index and
+/// `entry.json`.
+fn trash_root(repo_root: &Path) -> Result<PathBuf> {
+    Ok(git_ops::get_git_common_dir(repo_root)?.join(TRASH_DIR_NAME))
+}
+
+/// Deletes the worktree at `path` and its local `branch` by moving the
+/// directory into the trash, uncommitted and ignored files included. Entries
+/// older than the retention period are purged afterwards.
+pub fn trash_worktree(repo_root: &Path, path: &Path, branch: &str) -> Result<TrashedWorktree> {
+    let admin_dir = git_ops::worktree_admin_dir(path)
+        .ok_or_else(|| anyhow!("{} is not a linked worktree", path.display()))?;
+    // The directory is moved by hand, so git's own lock check doesn't apply.
+    if admin_dir.join("locked").exists() {
+        return Err(anyhow!(
+            "{} is locked; run `git worktree unlock` on it first",
+            path.display()
+        ));
+    }
+    let deleted_ts = now_ts();
+    let id = format!(
+        "{deleted_ts}-{}",
+        services::short_hash(&path.display().to_string())
+    );
+    let entry = TrashedWorktree {
+        id: id.clone(),
+        branch: branch.to_string(),
+        path: path.to_path_buf(),
+        head: git_ops::worktree_head_commit(path),
+        upstream: git_ops::get_upstream(repo_root, branch),
+        deleted_ts,
+    };
+    if git_ops::dry_run() {
+        logging::would_run(&format!(
+            "move {} to the trash and delete branch {branch}",
+            path.display()
+        ));
+        return Ok(entry);
+    }
+
+    let entry_dir = trash_root(repo_root)?.join(&id);
+    fs::create_dir_all(&entry_dir)?;
+    let _ = fs::copy(admin_dir.join("index"), entry_dir.join("index"));
+    if let Err(err) = move_dir(path, &entry_dir.join("worktree")) {
+        let _ = fs::remove_dir_all(&entry_dir);
+        return Err(anyhow!(
+            "cannot move {} to the trash in {}: {err}",
+            path.display(),
+            entry_dir.display()
+        ));
+    }
+    fs::write(
+        entry_dir.join(ENTRY_FILE),
+        serde_json::to_string_pretty(&entry)?,
+    )?;
+
+    git_ops::unregister_worktree(&admin_dir)?;
+    git_ops::remove_empty_parents(&services::worktrees_dir(repo_root), path);
+    if let Some(head) = &entry.head {
+        git_ops::update_ref(repo_root, &format!("{TRASH_REF_PREFIX}{id}"), head)?;
+    }
+    git_ops::branch_delete(repo_root, branch)?;
+    if let Some(head) = &entry.head {
+        record_deleted_branch(
+            repo_root,
+            &DeletedBranch {
+                branch: branch.to_string(),
+                head: head.clone(),
+                deleted_ts,
+                trash_id: Some(id),
+            },
+        )?;
+    }
+
+    purge_expired(repo_root)?;
+    Ok(entry)
+}
+
+/// Trashed worktrees, newest first.
+pub fn list_trash(repo_root: &Path) -> Result<Vec<TrashedWorktree>> {
+    let Ok(dirs) = fs::read_dir(trash_root(repo_root)?) else {
+        return Ok(Vec::new());
+    };
+    let mut entries: Vec<TrashedWorktree> = dirs
+        .flatten()
+        .filter_map(|dir| fs::read_to_string(dir.path().join(ENTRY_FILE)).ok())
+        .filter_map(|text| serde_json::from_str(&text).ok())
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_ts));
+    Ok(entries)
+}
+
+/// The newest trashed worktree whose id or branch is `name`.
+pub fn find_trashed(repo_root: &Path, name: &str) -> Result<Option<TrashedWorktree>> {
+    Ok(list_trash(repo_root)?
+        .into_iter()
+        .find(|entry| entry.id == name || entry.branch == name))
+}
+
+/// Puts a trashed worktree back on its branch (or `branch`, if given) at the
+/// branch's usual path, with its files and staged changes as they were.
+/// Returns the branch and the worktree path.
+pub fn restore_trashed(
+    repo_root: &Path,
+    entry: &TrashedWorktree,
+    branch: Option<&str>,
+) -> Result<(String, PathBuf)> {
+    let branch = branch.unwrap_or(&entry.branch).to_string();
+    if !git_ops::is_valid_branch_name(repo_root, &branch) {
+        return Err(anyhow!("gw restore: invalid branch name {branch}"));
+    }
+    if git_ops::branch_exists(repo_root, &branch) {
+        return Err(anyhow!(
+            "gw restore: branch {branch} already exists; pass --branch <name>"
+        ));
+    }
+    let entry_dir = entry_dir(repo_root, entry)?;
+    let head = entry.head.as_deref().ok_or_else(|| {
+        anyhow!(
+            "gw restore: {} was deleted without a commit; its files are in {}",
+            entry.branch,
+            entry_dir.join("worktree").display()
+        )
+    })?;
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let target = services::new_worktree_path(repo_root, &branch, &worktrees);
+    if !git_ops::is_path_available(&target) {
+        return Err(anyhow!(
+            "gw restore: target path already exists: {}",
+            target.display()
+        ));
+    }
+
+    git_ops::branch_create(repo_root, &branch, head)?;
+    if let Some(upstream) = &entry.upstream {
+        // The remote branch may be gone by now; the branch is fine without it.
+        let _ = git_ops::branch_set_upstream(repo_root, &branch, upstream);
+    }
+
+    // Register the worktree, then swap the trashed directory in under the
+    // fresh `.git` file.
+    git_ops::worktree_add_no_checkout(repo_root, &target, &branch, None)?;
+    services::record_worktree_path(repo_root, &branch, &target);
+    if git_ops::dry_run() {
+        logging::would_run(&format!(
+            "move the trashed files of {} to {}",
+            entry.branch,
+            target.display()
+        ));
+        return Ok((branch, target));
+    }
+    let dot_git = fs::read(target.join(".git"))?;
+    fs::remove_dir_all(&target)?;
+    move_dir(&entry_dir.join("worktree"), &target)
+        .with_context(|| format!("gw restore: cannot move files to {}", target.display()))?;
+    fs::write(target.join(".git"), dot_git)?;
+    let admin_dir = git_ops::worktree_admin_dir(&target)
+        .ok_or_else(|| anyhow!("gw restore: {} is not registered", target.display()))?;
+    if fs::copy(entry_dir.join("index"), admin_dir.join("index")).is_err() {
+        git_ops::reset_index(&target)?;
+    }
+
+    remove_entry(repo_root, entry)?;
+    Ok((branch, target))
+}
+
+/// Removes trash entries older than `trashRetentionDays`. Returns how many.
+pub fn purge_expired(repo_root: &Path) -> Result<usize> {
+    let days = settings::load_trash_retention_days(repo_root)?;
+    let cutoff = now_ts() - days as i64 * SECONDS_PER_DAY;
+    purge(repo_root, |entry| entry.deleted_ts < cutoff)
+}
+
+/// Empties the trash. Returns how many entries were removed.
+pub fn purge_all(repo_root: &Path) -> Result<usize> {
+    purge(repo_root, |_| true)
+}
+
+fn purge(repo_root: &Path, expired: impl Fn(&TrashedWorktree) -> bool) -> Result<usize> {
+    let mut purged = 0;
+    for entry in list_trash(repo_root)?.iter().filter(|entry| expired(entry)) {
+        remove_entry(repo_root, entry)?;
+        // Without the entry the branch can't come back, and neither can its
+        // memo, unless a branch of that name exists again.
+        if !git_ops::branch_exists(repo_root, &entry.branch) {
+            services::forget_worktree_note(repo_root, &entry.branch);
+        }
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+fn entry_dir(repo_root: &Path, entry: &TrashedWorktree) -> Result<PathBuf> {
+    Ok(trash_root(repo_root)?.join(&entry.id))
+}
+
+fn remove_entry(repo_root: &Path, entry: &TrashedWorktree) -> Result<()> {
+    if git_ops::dry_run() {
+        logging::would_run(&format!("remove trash entry {}", entry.id));
+        return Ok(());
+    }
+    if entry.head.is_some() {
+        git_ops::delete_ref(repo_root, &format!("{TRASH_REF_PREFIX}{}", entry.id))?;
+    }
+    fs::remove_dir_all(entry_dir(repo_root, entry)?)?;
+    Ok(())
+}
+
+/// `fs::rename`, falling back to copying `from` and removing it when `to` is
+/// on another filesystem, as the git common dir can be for a worktree on
+/// another mount. If the move fails, `from` is left as it was.
+fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => copy_then_remove(from, to),
+        result => result,
+    }
+}
+
+fn copy_then_remove(from: &Path, to: &Path) -> io::Result<()> {
+    if let Err(err) = copy_tree(from, to) {
+        let _ = fs::remove_dir_all(to);
+        return Err(err);
+    }
+    // The copy is complete, so the move stands even if leftovers remain.
+    if let Err(err) = fs::remove_dir_all(from) {
+        tracing::warn!(path = %from.display(), %err, "cannot remove moved directory");
+    }
+    Ok(())
+}
+
+/// Copies `from` to `to` recursively, keeping symlinks as links and
+/// permissions as they were. Fails on sockets, FIFOs and devices.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+    let kind = meta.file_type();
+    if kind.is_dir() {
+        fs::create_dir(to)?;
+        for child in fs::read_dir(from)? {
+            let child = child?;
+            copy_tree(&child.path(), &to.join(child.file_name()))?;
+        }
+        // Last, so a read-only directory can still be filled.
+        fs::set_permissions(to, meta.permissions())?;
+    } else if kind.is_symlink() {
+        let target = fs::read_link(from)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&target, to)?;
+        #[cfg(windows)]
+        if fs::metadata(from).is_ok_and(|meta| meta.is_dir()) {
+            std::os::windows::fs::symlink_dir(&target, to)?;
+        } else {
+            std::os::windows::fs::symlink_file(&target, to)?;
+        }
+    } else if kind.is_file() {
+        fs::copy(from, to)?;
+    } else {
+        return Err(io::Error::other(format!(
+            "cannot copy special file {}",
+            from.display()
+        )));
+    }
+    Ok(())
+}
+
+/// `gw-deleted-branches.jsonl` in the git common dir: one line per branch
+/// deleted through gw, oldest first. It outlives the trash, and the tips stay
+/// restorable until git garbage-collects them.
+fn deleted_branches_log(repo_root: &Path) -> Result<PathBuf> {
+    Ok(git_ops::get_git_common_dir(repo_root)?.join(DELETED_BRANCHES_FILE))
+}
+
+fn record_deleted_branch(repo_root: &Path, deleted: &DeletedBranch) -> Result<()> {
+    let path = deleted_branches_log(repo_root)?;
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    let mut lines: Vec<&str> = text.lines().collect();
+    let line = serde_json::to_string(deleted)?;
+    lines.push(&line);
+    let start = lines.len().saturating_sub(DELETED_BRANCHES_LIMIT);
+    fs::write(&path, lines[start..].join("\n") + "\n")?;
+    Ok(())
+}
+
+/// Branches deleted through gw, or that a worktree's HEAD reflog switched
+/// away from, which don't exist any more and whose tip is still around.
+/// Newest first, one per branch.
+pub fn list_deleted_branches(repo_root: &Path) -> Result<Vec<DeletedBranch>> {
+    let mut found: Vec<DeletedBranch> = fs::read_to_string(deleted_branches_log(repo_root)?)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    for worktree in git_ops::parse_worktrees(Some(repo_root))? {
+        for (branch, head, switched_ts) in git_ops::head_reflog_departures(&worktree.path) {
+            found.push(DeletedBranch {
+                branch,
+                head,
+                deleted_ts: switched_ts,
+                trash_id: None,
+            });
+        }
+    }
+    found.sort_by_key(|deleted| std::cmp::Reverse(deleted.deleted_ts));
+
+    let existing: HashSet<String> = git_ops::list_local_branches(repo_root)?
+        .into_iter()
+        .collect();
+    let trash_ids: HashSet<String> = list_trash(repo_root)?
+        .into_iter()
+        .map(|entry| entry.id)
+        .collect();
+    let mut seen = HashSet::new();
+    let mut deleted = Vec::new();
+    for mut branch in found {
+        if existing.contains(&branch.branch) || !seen.insert(branch.branch.clone()) {
+            continue;
+        }
+        if !git_ops::commit_exists(repo_root, &branch.head) {
+            continue;
+        }
+        branch.trash_id = branch.trash_id.filter(|id| trash_ids.contains(id));
+        deleted.push(branch);
+    }
+    Ok(deleted)
+}
+
+/// The most recently deleted branch named `name`.
+pub fn find_deleted_branch(repo_root: &Path, name: &str) -> Result<Option<DeletedBranch>> {
+    Ok(list_deleted_branches(repo_root)?
+        .into_iter()
+        .find(|deleted| deleted.branch == name))
+}
+
+/// Brings a deleted branch back with a worktree at the branch's usual path,
+/// as `branch` if given. A worktree still in the trash comes back from there
+/// with its files; otherwise the branch is recreated at its old tip and
+/// checked out like `gw new` would, hooks included. Returns the branch and
+/// the worktree path.
+pub fn restore_deleted_branch(
+    repo_root: &Path,
+    deleted: &DeletedBranch,
+    branch: Option<&str>,
+) -> Result<(String, PathBuf)> {
+    let trashed = match &deleted.trash_id {
+        Some(id) => find_trashed(repo_root, id)?,
+        None => None,
+    };
+    if let Some(entry) = trashed {
+        return restore_trashed(repo_root, &entry, branch);
+    }
+
+    let branch = branch.unwrap_or(&deleted.branch).to_string();
+    if !git_ops::is_valid_branch_name(repo_root, &branch) {
+        return Err(anyhow!("gw restore: invalid branch name {branch}"));
+    }
+    if git_ops::branch_exists(repo_root, &branch) {
+        return Err(anyhow!(
+            "gw restore: branch {branch} already exists; pass --branch <name>"
+        ));
+    }
+    let worktrees = git_ops::parse_worktrees(Some(repo_root))?;
+    let target = services::new_worktree_path(repo_root, &branch, &worktrees);
+    if !git_ops::is_path_available(&target) {
+        return Err(anyhow!(
+            "gw restore: target path already exists: {}",
+            target.display()
+        ));
+    }
+
+    git_ops::branch_create(repo_root, &branch, &deleted.head)?;
+    if let Err(err) = git_ops::worktree_add(repo_root, &target, &branch, None) {
+        let _ = git_ops::branch_delete(repo_root, &branch);
+        return Err(err);
+    }
+    services::record_worktree_path(repo_root, &branch, &target);
+    hooks::run_post_worktree_creation_hooks(repo_root, Some(&target))?;
+    Ok((branch, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gw-trash-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("worktree/src")).unwrap();
+        fs::write(
+            dir.join("worktree/.git"),
+            "gitdir: /repo/.git/worktrees/feat\n",
+        )
+        .unwrap();
+        fs::write(dir.join("worktree/src/main.rs"), "fn main() {}\n").unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copying_across_devices_keeps_files_and_links() {
+        let dir = scratch("copy");
+        std::os::unix::fs::symlink("src/main.rs", dir.join("worktree/main.rs")).unwrap();
+
+        copy_then_remove(&dir.join("worktree"), &dir.join("moved")).unwrap();
+
+        assert!(!dir.join("worktree").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("moved/src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert_eq!(
+            fs::read_link(dir.join("moved/main.rs")).unwrap(),
+            Path::new("src/main.rs")
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_failed_copy_leaves_the_worktree_intact() {
+        let dir = scratch("fail");
+        // A socket can't be copied, so the copy fails halfway.
+        let _socket =
+            std::os::unix::net::UnixListener::bind(dir.join("worktree/src/dev.sock")).unwrap();
+
+        let result = copy_then_remove(&dir.join("worktree"), &dir.join("moved"));
+
+        assert!(result.is_err());
+        assert!(!dir.join("moved").exists());
+        assert!(dir.join("worktree/.git").is_file());
+        assert_eq!(
+            fs::read_to_string(dir.join("worktree/src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert!(dir.join("worktree/src/dev.sock").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,18 @@
-use crate::models::WorktreeInfo;
-use crate::{git_ops, hooks, services};
+use crate::models::{
+    BranchComparison, CleanupPolicy, CommitSummary, DeletedBranch, HealthReport, NotificationEvent,
+    RepairFix, RepairPlan, TableExport, TimeFormat, UnpushedCommits, WorktreeDetails,
+    WorktreeFilter, WorktreeInfo,
+};
+use crate::{
+    errors, forge, git_ops, hooks, logging, policies, repo_lock, review, services, settings, tasks,
+    trash,
+};
 use anyhow::{anyhow, Result};
-use ratatui::backend::CrosstermBackend;
+use ratatui::backend::{Backend, CrosstermBackend};
 use ratatui::crossterm::event::{
-    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
-    MouseButton, MouseEvent, MouseEventKind,
+    self, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+    EnableFocusChange, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton,
+    MouseEvent, MouseEventKind,
 };
 use ratatui::crossterm::execute;
 use ratatui::crossterm::terminal::{
@@ -15,11 +23,10 @@ use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Text};
 use ratatui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState};
 use ratatui::Terminal;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::{self, Stderr};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -32,20 +39,73 @@ const HEADERS: [&str; 6] = [
     "CHANGES",
 ];
 
+const PR_TITLE_HEADER: &str = "PR TITLE";
+const DESCRIPTION_HEADER: &str = "DESCRIPTION";
+const DESCRIPTION_COLUMN_WIDTH: u16 = 32;
+const PATH_HEADER: &str = "PATH";
+const PATH_COLUMN_WIDTH: u16 = 32;
+
 const COMMAND_BAR: &str =
-    "Enter: open  |  o: open PR  |  click PR: open in browser  |  n: new from main  |  N: new from selected  |  D: delete  |  R: rename  |  p: pull  |  P: push  |  r: refresh  |  q/Esc: quit";
+    "Enter: open  |  o: open PR  |  click PR: open in browser  |  w: PR draft/ready  |  t: PR titles  |  b: descriptions  |  e: edit description  |  m: memo  |  n: new from main  |  N: new from selected  |  M: move changes to new branch  |  c: cherry-pick  |  D: delete  |  C: clean up merged  |  R: rename  |  a: adopt detached as branch  |  p: pull  |  P: push  |  u: reconcile diverged/deleted upstream  |  r: refresh  |  /: search  |  H: doctor  |  v: compare with a branch  |  U: recently deleted  |  :: run git in worktree  |  y/Y: copy table (Markdown/CSV)  |  X: dry run on/off  |  Esc: cancel running work  |  q/Esc: quit";
+/// Lines of `:` command output kept for the output popup.
+const OUTPUT_LINE_LIMIT: usize = 5000;
 const SPINNER: &[char] = &['|', '/', '-', '\\'];
+/// Background tasks running at once: the refresh, an operation, a detail
+/// lookup and one spare.
+const MAX_BACKGROUND_TASKS: usize = 4;
 const TABLE_COLUMN_WIDTHS: [u16; 6] = [36, 12, 18, 24, 14, 14];
+const BRANCH_COLUMN_INDEX: usize = 0;
+const LAST_COMMIT_COLUMN_INDEX: usize = 1;
+const PULL_PUSH_COLUMN_INDEX: usize = 2;
 const PR_COLUMN_INDEX: usize = 3;
+/// LAST COMMIT width when it holds `YYYY-MM-DD HH:MM` (`timeFormat: "datetime"`).
+const DATETIME_COLUMN_WIDTH: u16 = 17;
 const HIGHLIGHT_SYMBOL_WIDTH: u16 = 3;
 const TABLE_TOP_ROW: u16 = 4;
 const TABLE_FIRST_DATA_ROW: u16 = TABLE_TOP_ROW + 1;
+/// Rows reserved below the table for the selected worktree's PR details,
+/// HEAD and surroundings, data ages and full path.
+const DETAIL_PANE_HEIGHT: u16 = 5;
+/// Ending of the startup banner listing merged worktrees, so a refresh can
+/// tell it apart from other status messages and update it.
+const MERGED_BANNER_SUFFIX: &str = "press C to clean up.";
+/// Most commits offered by `c`.
+const CHERRY_PICK_LIMIT: usize = 50;
 
 enum ConfirmAction {
     Delete {
         branch: String,
         path: PathBuf,
         ref_name: String,
+        /// Uncommitted changes that `s` can snapshot before deleting.
+        dirty: bool,
+        snapshot: bool,
+    },
+    DeleteMerged {
+        worktrees: Vec<(PathBuf, String)>,
+    },
+    /// Take over a worktree outside gw that has `branch` checked out too.
+    ImportExternal {
+        branch: String,
+        path: PathBuf,
+    },
+    /// Remove a `gw new --tag` worktree; there is no branch to delete.
+    RemoveTag {
+        tag: String,
+        path: PathBuf,
+    },
+    /// Remove a detached worktree, and for a `gw review` one its review ref;
+    /// there is no branch to delete.
+    RemoveDetached {
+        label: String,
+        path: PathBuf,
+        review: Option<u64>,
+    },
+    /// Run a git command from the `:` prompt that may change the worktree.
+    RunGit {
+        branch: String,
+        path: PathBuf,
+        args: Vec<String>,
     },
 }
 
@@ -58,6 +118,37 @@ enum InputAction {
         base_branch: String,
         pull_before_create: Option<PathBuf>,
     },
+    /// New worktree from the default branch that takes over the uncommitted
+    /// changes of `source`.
+    MoveChanges {
+        source: PathBuf,
+        base_branch: String,
+        pull_before_create: Option<PathBuf>,
+    },
+    Compare {
+        branch: String,
+    },
+    /// Set or, when left empty, remove `branch`'s description.
+    EditDescription {
+        branch: String,
+        path: PathBuf,
+    },
+    /// Set or, when left empty, remove the memo of the worktree cached under
+    /// `cache_key`.
+    EditNote {
+        label: String,
+        cache_key: String,
+        path: PathBuf,
+    },
+    /// Put the detached worktree at `path` on a new branch.
+    Adopt {
+        path: PathBuf,
+    },
+    /// `:`: a git command to run in the worktree at `path`.
+    GitCommand {
+        branch: String,
+        path: PathBuf,
+    },
 }
 
 enum Mode {
@@ -71,6 +162,90 @@ enum Mode {
         value: String,
         action: InputAction,
     },
+    Doctor {
+        report: Box<HealthReport>,
+        plan: RepairPlan,
+        selected: usize,
+    },
+    /// `/`: typing narrows the table to branches fuzzy-matching `query`.
+    Search {
+        query: String,
+    },
+    /// `c`: mark commits of `source` to cherry-pick, then choose the
+    /// worktree to pick them into.
+    CherryPick {
+        source: String,
+        commits: Vec<CommitSummary>,
+        marked: Vec<bool>,
+        selected: usize,
+        /// The worktrees to choose from, once the commits are chosen.
+        targets: Option<Vec<WorktreeInfo>>,
+    },
+    /// `u`: `branch` and its upstream have diverged; rebase onto the
+    /// upstream or force-push over it.
+    Reconcile {
+        branch: String,
+        path: PathBuf,
+        pull: i64,
+        push: i64,
+    },
+    /// Output of a git command run from the `:` prompt, scrolled by
+    /// `scroll` lines.
+    Output {
+        title: String,
+        lines: Vec<String>,
+        scroll: usize,
+    },
+    /// `u`: the upstream of `branch` was deleted on the remote; delete the
+    /// worktree or push the branch again.
+    UpstreamGone {
+        branch: String,
+        path: PathBuf,
+    },
+    /// `v`: the commits only on either side of two branches and the diff
+    /// stat, scrolled by `scroll` lines.
+    Compare {
+        comparison: BranchComparison,
+        scroll: usize,
+    },
+    /// `U`: recently deleted branches, to bring one back with a worktree.
+    Deleted {
+        branches: Vec<DeletedBranch>,
+        selected: usize,
+    },
+}
+
+/// Everything that changes the TUI's state: terminal input, finished
+/// background tasks and the clock, applied one at a time by
+/// `TuiApp::update`.
+enum Message {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    Paste(String),
+    Focus(bool),
+    OperationDone(OpResult),
+    /// The operation's task ended without a result, e.g. it panicked.
+    OperationLost,
+    DetailsLoaded(PathBuf, WorktreeDetails),
+    DetailsLost,
+    /// The refresh finished, with its error if it failed.
+    RefreshDone(Option<String>),
+    RefreshLost,
+    /// After each event, or 100ms without one: spinner and polling.
+    Tick,
+}
+
+impl Message {
+    fn from_event(event: Event) -> Option<Self> {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => Some(Message::Key(key)),
+            Event::Mouse(mouse) => Some(Message::Mouse(mouse)),
+            Event::Paste(text) => Some(Message::Paste(text)),
+            Event::FocusGained => Some(Message::Focus(true)),
+            Event::FocusLost => Some(Message::Focus(false)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -80,11 +255,73 @@ enum PostSuccessAction {
     ReloadAndRefresh,
 }
 
+/// What an operation touches; two operations conflict when their scopes
+/// overlap.
+#[derive(Clone)]
+enum OpScope {
+    /// The whole repository, e.g. repairing the setup.
+    Repo,
+    Worktrees(Vec<PathBuf>),
+}
+
+impl OpScope {
+    fn worktree(path: PathBuf) -> Self {
+        OpScope::Worktrees(vec![path])
+    }
+
+    fn overlaps(&self, other: &OpScope) -> bool {
+        match (self, other) {
+            (OpScope::Worktrees(ours), OpScope::Worktrees(theirs)) => {
+                ours.iter().any(|path| theirs.contains(path))
+            }
+            _ => true,
+        }
+    }
+}
+
+/// An operation waiting for the running one to finish.
+struct QueuedOperation {
+    scope: OpScope,
+    spinner_message: String,
+    success_message: String,
+    failure_prefix: String,
+    selected_branch_after: Option<String>,
+    post_success_action: PostSuccessAction,
+    notification: Option<NotificationEvent>,
+    action: OperationAction,
+}
+
+/// The work of a queued operation, returning the note for its success
+/// message.
+type OperationAction = Box<dyn FnOnce(&tasks::TaskContext) -> Result<Option<String>> + Send>;
+
+/// What an operation can return on success: nothing, or a note appended to
+/// its success message.
+trait OperationNote {
+    fn into_note(self) -> Option<String>;
+}
+
+impl OperationNote for () {
+    fn into_note(self) -> Option<String> {
+        None
+    }
+}
+
+impl OperationNote for Option<String> {
+    fn into_note(self) -> Option<String> {
+        self
+    }
+}
+
+/// Title and output lines of a git command run from the `:` prompt.
+type GitOutput = (String, Vec<String>);
+
 struct OpResult {
     status: String,
     succeeded: bool,
     post_success_action: PostSuccessAction,
     selected_branch_after: Option<String>,
+    notification: Option<NotificationEvent>,
 }
 
 pub fn run_tui(
@@ -92,13 +329,25 @@ pub fn run_tui(
     items: Vec<WorktreeInfo>,
     default_branch: String,
     warning: Option<String>,
-    gh_available: bool,
+    forge_available: bool,
+    filter: WorktreeFilter,
 ) -> Result<Option<PathBuf>> {
+    // Dry-run output would garble the screen; operations show it instead.
+    logging::collect_would_run();
     let mut terminal = setup_terminal()?;
-    let mut app = TuiApp::new(repo_root, items, default_branch, warning, gh_available);
+    let mut app = TuiApp::new(
+        repo_root,
+        items,
+        default_branch,
+        warning,
+        forge_available,
+        filter,
+    );
     app.start_refresh(false);
+    app.prune_closed_reviews();
+    app.apply_startup_policies();
 
-    let run_result = app.run(&mut terminal);
+    let run_result = app.run(&mut terminal, &mut terminal_event);
     let restore_result = restore_terminal(&mut terminal);
 
     restore_result?;
@@ -110,7 +359,10 @@ struct TuiApp {
     repo_root: PathBuf,
     default_branch: String,
     warning: Option<String>,
-    gh_available: bool,
+    forge_available: bool,
+    /// Worktrees the table leaves out stay in `items`, so background work
+    /// and merged cleanup still see all of them.
+    filter: WorktreeFilter,
     items: Arc<Mutex<Vec<WorktreeInfo>>>,
     table_state: TableState,
     mode: Mode,
@@ -120,9 +372,36 @@ struct TuiApp {
     busy: bool,
     spinner_index: usize,
     spinner_message: Option<String>,
-    refresh_running: Arc<AtomicBool>,
-    refresh_rx: Option<mpsc::Receiver<Option<String>>>,
-    op_rx: Option<mpsc::Receiver<OpResult>>,
+    /// Runs the refresh, operations and detail lookups in the background.
+    tasks: tasks::TaskPool,
+    refresh_task: Option<tasks::Task<Option<String>>>,
+    op_task: Option<tasks::Task<OpResult>>,
+    /// Scope and spinner message of the running operation.
+    running_op: Option<(OpScope, String)>,
+    op_queue: VecDeque<QueuedOperation>,
+    show_pr_title: bool,
+    show_description: bool,
+    stale_after_days: u64,
+    /// Where worktrees named after their branch live, for the PATH column.
+    worktrees_dir: PathBuf,
+    table_area: Rect,
+    /// Detail pane extras of the worktrees selected so far, by path, until
+    /// the next reload.
+    details: HashMap<PathBuf, WorktreeDetails>,
+    details_task: Option<(PathBuf, tasks::Task<WorktreeDetails>)>,
+    /// Output of the last `:` command, until it is shown.
+    git_output: Arc<Mutex<Option<GitOutput>>>,
+    /// Step of the running operation, for operations made of several.
+    op_progress: tasks::Progress,
+    /// Events with desktop notifications, from the `notifications` setting.
+    notifications: Vec<NotificationEvent>,
+    /// Whether the terminal has focus, as far as it reports focus changes.
+    focused: bool,
+    /// Event the next `start_operation` notifies about when it finishes.
+    next_notification: Option<NotificationEvent>,
+    /// Branches whose PR checks were pending when the running refresh
+    /// started.
+    pending_checks: Vec<String>,
 }
 
 impl TuiApp {
@@ -131,9 +410,10 @@ impl TuiApp {
         mut items: Vec<WorktreeInfo>,
         default_branch: String,
         warning: Option<String>,
-        gh_available: bool,
+        forge_available: bool,
+        filter: WorktreeFilter,
     ) -> Self {
-        if !gh_available {
+        if !forge_available {
             for item in &mut items {
                 item.pr_validated = true;
                 item.checks_validated = true;
@@ -141,38 +421,68 @@ impl TuiApp {
         }
 
         let mut table_state = TableState::default();
-        if items.is_empty() {
+        if !items
+            .iter()
+            .any(|item| services::matches_filter(item, &filter))
+        {
             table_state.select(None);
         } else {
             table_state.select(Some(0));
         }
+        let status = merged_cleanup_banner(&items, &repo_root, &default_branch).unwrap_or_default();
+        let stale_after_days = settings::load_stale_after_days(&repo_root)
+            .unwrap_or(settings::DEFAULT_STALE_AFTER_DAYS);
+        let worktrees_dir = services::worktrees_dir(&repo_root);
+        let notifications = settings::load_notifications(&repo_root).unwrap_or_default();
 
         Self {
             repo_root,
             default_branch,
             warning,
-            gh_available,
+            forge_available,
+            filter,
             items: Arc::new(Mutex::new(items)),
             table_state,
             mode: Mode::Normal,
-            status: String::new(),
+            status,
             selected_path: None,
             should_quit: false,
             busy: false,
             spinner_index: 0,
             spinner_message: None,
-            refresh_running: Arc::new(AtomicBool::new(false)),
-            refresh_rx: None,
-            op_rx: None,
+            tasks: tasks::TaskPool::new(MAX_BACKGROUND_TASKS),
+            refresh_task: None,
+            op_task: None,
+            running_op: None,
+            op_queue: VecDeque::new(),
+            show_pr_title: false,
+            show_description: false,
+            stale_after_days,
+            worktrees_dir,
+            table_area: Rect::default(),
+            details: HashMap::new(),
+            details_task: None,
+            git_output: Arc::new(Mutex::new(None)),
+            op_progress: tasks::Progress::default(),
+            notifications,
+            focused: true,
+            next_notification: None,
+            pending_checks: Vec::new(),
         }
     }
 
-    fn run(
+    /// Draws to `terminal` and applies events from `next_event` until the
+    /// user quits. Neither has to be a real terminal: a `TestBackend` and a
+    /// scripted list of events drive the same loop headless.
+    fn run<B: Backend>(
         &mut self,
-        terminal: &mut Terminal<CrosstermBackend<Stderr>>,
+        terminal: &mut Terminal<B>,
+        next_event: &mut dyn FnMut(Duration) -> io::Result<Option<Event>>,
     ) -> Result<Option<PathBuf>> {
         loop {
-            self.handle_async_results();
+            for message in self.task_messages() {
+                self.update(message);
+            }
 
             terminal.draw(|frame| self.draw(frame))?;
 
@@ -180,76 +490,195 @@ impl TuiApp {
                 return Ok(self.selected_path.take());
             }
 
-            if event::poll(Duration::from_millis(100))? {
-                match event::read()? {
-                    Event::Key(key) => {
-                        if key.kind == KeyEventKind::Press {
-                            self.handle_key(key);
-                        }
-                    }
-                    Event::Mouse(mouse) => self.handle_mouse(mouse),
-                    _ => {}
-                }
+            if let Some(message) =
+                next_event(Duration::from_millis(100))?.and_then(Message::from_event)
+            {
+                self.update(message);
             }
 
-            self.on_tick();
+            self.update(Message::Tick);
         }
     }
 
     fn on_tick(&mut self) {
-        if self.busy || self.refresh_running.load(Ordering::SeqCst) {
+        if self.busy || self.refresh_task.is_some() {
             self.spinner_index = (self.spinner_index + 1) % SPINNER.len();
         }
+        self.poll_background_checkouts();
+        self.request_details();
     }
 
-    fn handle_async_results(&mut self) {
-        if let Some(rx) = &self.op_rx {
-            match rx.try_recv() {
-                Ok(result) => {
-                    self.finish_operation(result);
-                    self.op_rx = None;
-                }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    self.busy = false;
-                    self.spinner_message = None;
-                    self.op_rx = None;
-                    self.status = "Operation interrupted.".to_string();
-                }
-                Err(mpsc::TryRecvError::Empty) => {}
+    /// Gathers the detail pane extras of the selected worktree in the
+    /// background, one worktree at a time.
+    fn request_details(&mut self) {
+        let Some(item) = self.current_item() else {
+            return;
+        };
+        if let Some((path, task)) = &self.details_task {
+            // One lookup at a time; a stale one that hasn't started yet is
+            // dropped, a running one still fills the cache.
+            if *path != item.path {
+                task.cancel();
             }
+            return;
+        }
+        if self.details.contains_key(&item.path) {
+            return;
         }
+        let repo_root = self.repo_root.clone();
+        let path = item.path.clone();
+        let task = self
+            .tasks
+            .spawn("details", tasks::Progress::default(), move |_| {
+                services::worktree_details(&repo_root, &item).unwrap_or_default()
+            });
+        self.details_task = Some((path, task));
+    }
 
-        if let Some(rx) = &self.refresh_rx {
-            match rx.try_recv() {
-                Ok(maybe_err) => {
-                    if let Some(err) = maybe_err {
-                        self.status = format!("Refresh failed: {err}");
-                    } else if self.status.starts_with("Refreshing") {
-                        self.status = "Refreshed.".to_string();
+    /// Updates the progress of fast-created worktrees whose files are still
+    /// being checked out, and says when one is done or failed.
+    fn poll_background_checkouts(&mut self) {
+        let mut finished = Vec::new();
+        let mut failed = Vec::new();
+        {
+            let mut items = match self.items.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            for item in items
+                .iter_mut()
+                .filter(|item| item.checkout.is_some_and(|checkout| !checkout.failed))
+            {
+                item.checkout = services::background_checkout(&item.path);
+                match item.checkout {
+                    None => finished.push(item.branch.clone()),
+                    Some(checkout) if checkout.failed => {
+                        failed.push((item.branch.clone(), item.path.clone()))
                     }
-                    self.refresh_rx = None;
+                    Some(_) => {}
                 }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    self.refresh_rx = None;
+            }
+        }
+        if let Some((branch, path)) = failed.first() {
+            let error = services::background_checkout_error(path).unwrap_or_default();
+            self.status = format!("Checking out {branch} failed: {error}");
+        } else if !finished.is_empty() {
+            self.status = format!("Checked out {}.", finished.join(", "));
+        }
+    }
+
+    /// Results of the background tasks that finished since the last call.
+    fn task_messages(&self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        if let Some(task) = &self.op_task {
+            match task.poll() {
+                tasks::TaskPoll::Done(result) => messages.push(Message::OperationDone(result)),
+                tasks::TaskPoll::Lost => messages.push(Message::OperationLost),
+                tasks::TaskPoll::Running => {}
+            }
+        }
+        if let Some((path, task)) = &self.details_task {
+            match task.poll() {
+                tasks::TaskPoll::Done(details) => {
+                    messages.push(Message::DetailsLoaded(path.clone(), details))
                 }
-                Err(mpsc::TryRecvError::Empty) => {}
+                tasks::TaskPoll::Lost => messages.push(Message::DetailsLost),
+                tasks::TaskPoll::Running => {}
+            }
+        }
+        if let Some(task) = &self.refresh_task {
+            match task.poll() {
+                tasks::TaskPoll::Done(error) => messages.push(Message::RefreshDone(error)),
+                tasks::TaskPoll::Lost => messages.push(Message::RefreshLost),
+                tasks::TaskPoll::Running => {}
+            }
+        }
+        messages
+    }
+
+    /// Applies one message to the app state; the event loop changes state
+    /// only through here.
+    fn update(&mut self, message: Message) {
+        match message {
+            Message::Key(key) => self.handle_key(key),
+            Message::Mouse(mouse) => self.handle_mouse(mouse),
+            Message::Paste(text) => self.handle_paste(&text),
+            Message::Focus(focused) => self.focused = focused,
+            Message::OperationDone(result) => {
+                self.finish_operation(result);
+                self.show_git_output();
+                self.show_dry_run_output();
+                self.op_task = None;
+                self.start_next_operation();
+            }
+            Message::OperationLost => {
+                self.busy = false;
+                self.spinner_message = None;
+                self.running_op = None;
+                self.op_task = None;
+                self.status = "Operation interrupted.".to_string();
+                self.start_next_operation();
             }
+            Message::DetailsLoaded(path, details) => {
+                self.details.insert(path, details);
+                self.details_task = None;
+            }
+            Message::DetailsLost => self.details_task = None,
+            Message::RefreshDone(error) => self.finish_refresh(error),
+            Message::RefreshLost => self.refresh_task = None,
+            Message::Tick => self.on_tick(),
+        }
+    }
+
+    fn finish_refresh(&mut self, error: Option<String>) {
+        self.refresh_task = None;
+        if self.forge_available {
+            self.warning = services::cached_forge_warning(&self.repo_root);
         }
+        if let Some(err) = error {
+            self.status = format!("Refresh failed: {err}");
+        } else if self.status.is_empty()
+            || self.status.starts_with("Refreshing")
+            || self.status.ends_with(MERGED_BANNER_SUFFIX)
+        {
+            self.status = merged_cleanup_banner(
+                &self.snapshot_items(),
+                &self.repo_root,
+                &self.default_branch,
+            )
+            .unwrap_or_else(|| "Refreshed.".to_string());
+        }
+        self.notify_finished_checks();
     }
 
     fn finish_operation(&mut self, result: OpResult) {
         self.busy = false;
         self.spinner_message = None;
+        self.running_op = None;
         self.status = result.status;
+        if let Some(event) = result.notification {
+            let message = self.status.clone();
+            self.notify(event, &message);
+        }
 
-        if !result.succeeded {
+        // A cancelled operation reloads without a post action; other failures
+        // change nothing.
+        if !result.succeeded && matches!(result.post_success_action, PostSuccessAction::None) {
             return;
         }
 
+        // With more operations queued the user has likely moved on, so keep
+        // their selection instead of jumping to this operation's branch.
+        let selected_branch = if self.op_queue.is_empty() {
+            result.selected_branch_after
+        } else {
+            self.current_item().map(|item| item.branch)
+        };
+
         match result.post_success_action {
             PostSuccessAction::None => {}
             PostSuccessAction::ReloadOnly | PostSuccessAction::ReloadAndRefresh => {
-                if let Err(err) = self.reload_items(result.selected_branch_after.as_deref()) {
+                if let Err(err) = self.reload_items(selected_branch.as_deref()) {
                     self.status = format!("Reload failed: {err}");
                     return;
                 }
@@ -274,6 +703,14 @@ impl TuiApp {
             Mode::Normal => self.handle_key_normal(key),
             Mode::Confirm { .. } => self.handle_key_confirm(key),
             Mode::Input { .. } => self.handle_key_input(key),
+            Mode::Doctor { .. } => self.handle_key_doctor(key),
+            Mode::Search { .. } => self.handle_key_search(key),
+            Mode::CherryPick { .. } => self.handle_key_cherry_pick(key),
+            Mode::Reconcile { .. } => self.handle_key_reconcile(key),
+            Mode::UpstreamGone { .. } => self.handle_key_upstream_gone(key),
+            Mode::Output { .. } => self.handle_key_output(key),
+            Mode::Deleted { .. } => self.handle_key_deleted(key),
+            Mode::Compare { .. } => self.handle_key_compare(key),
         }
     }
 
@@ -282,21 +719,104 @@ impl TuiApp {
             KeyCode::Up => self.select_prev(),
             KeyCode::Down => self.select_next(),
             KeyCode::Enter => self.action_choose(),
-            KeyCode::Esc => self.should_quit = true,
+            KeyCode::Esc => self.action_cancel_or_quit(),
             KeyCode::Char('q') => self.should_quit = true,
             KeyCode::Char('r') => self.action_refresh(),
             KeyCode::Char('n') => self.action_new_worktree_from_main(),
             KeyCode::Char('N') => self.action_new_worktree_from_selected(),
             KeyCode::Char('d') => self.action_delete_worktree(),
             KeyCode::Char('D') => self.action_delete_worktree(),
+            KeyCode::Char('C') => self.action_cleanup_merged(),
             KeyCode::Char('R') => self.action_rename_worktree(),
+            KeyCode::Char('a') => self.action_adopt_detached(),
+            KeyCode::Char('M') => self.action_move_changes(),
+            KeyCode::Char('c') => self.action_cherry_pick(),
             KeyCode::Char('o') => self.action_open_pr(),
+            KeyCode::Char('t') => self.show_pr_title = !self.show_pr_title,
+            KeyCode::Char('b') => self.show_description = !self.show_description,
+            KeyCode::Char('e') => self.action_edit_description(),
+            KeyCode::Char('m') => self.action_edit_note(),
+            KeyCode::Char('w') => self.action_toggle_pr_draft(),
             KeyCode::Char('p') => self.action_pull_worktree(),
             KeyCode::Char('P') => self.action_push_worktree(),
+            KeyCode::Char('u') => self.action_reconcile(),
+            KeyCode::Char('H') => self.action_open_doctor(),
+            KeyCode::Char('U') => self.action_open_deleted(),
+            KeyCode::Char('v') => self.action_compare(),
+            KeyCode::Char(':') => self.action_git_command(),
+            KeyCode::Char('y') => self.action_copy_table(TableExport::Markdown),
+            KeyCode::Char('Y') => self.action_copy_table(TableExport::Csv),
+            KeyCode::Char('X') => self.action_toggle_dry_run(),
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search {
+                    query: String::new(),
+                }
+            }
+            KeyCode::Char(ch) => self.action_run_preset(ch),
+            _ => {}
+        }
+    }
+
+    fn handle_key_search(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                // Keep the row that was selected in the narrowed table.
+                let selected = self.current_item().map(|item| item.path);
+                self.mode = Mode::Normal;
+                self.select_visible(selected.as_deref());
+            }
+            KeyCode::Up => self.select_prev(),
+            KeyCode::Down => self.select_next(),
+            KeyCode::Enter => {
+                if self.current_item().is_some() {
+                    self.action_choose();
+                } else if let Mode::Search { query } =
+                    std::mem::replace(&mut self.mode, Mode::Normal)
+                {
+                    self.select_visible(None);
+                    if query.trim().is_empty() {
+                        return;
+                    }
+                    // Nothing matched: create the branch that was searched for.
+                    self.action_new_worktree_from_main();
+                    if let Mode::Input { value, .. } = &mut self.mode {
+                        *value = query.trim().to_string();
+                    }
+                }
+            }
+            KeyCode::Backspace => {
+                if let Mode::Search { query } = &mut self.mode {
+                    query.pop();
+                }
+                self.select_visible(None);
+            }
+            KeyCode::Char(ch) => {
+                if let Mode::Search { query } = &mut self.mode {
+                    query.push(ch);
+                }
+                self.select_visible(None);
+            }
             _ => {}
         }
     }
 
+    /// Pasted lines become space-separated words, so a pasted list of
+    /// branches at the `n`/`N` prompt creates all of them.
+    fn handle_paste(&mut self, text: &str) {
+        let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        match &mut self.mode {
+            Mode::Input { value, .. } => {
+                if !value.is_empty() && !value.ends_with(' ') && !words.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(&words);
+            }
+            Mode::Search { query } => query.push_str(&words),
+            _ => return,
+        }
+        self.select_visible(None);
+    }
+
     fn handle_mouse(&mut self, mouse: MouseEvent) {
         if !matches!(self.mode, Mode::Normal) {
             return;
@@ -306,11 +826,12 @@ impl TuiApp {
             return;
         }
 
-        if mouse.row < TABLE_FIRST_DATA_ROW {
+        let table_bottom = self.table_area.y + self.table_area.height;
+        if mouse.row < TABLE_FIRST_DATA_ROW || mouse.row >= table_bottom {
             return;
         }
 
-        let items = self.snapshot_items();
+        let items = self.visible_items();
         let row_index = self.table_state.offset() + (mouse.row - TABLE_FIRST_DATA_ROW) as usize;
         let Some(item) = items.get(row_index) else {
             return;
@@ -339,8 +860,21 @@ impl TuiApp {
     fn handle_key_confirm(&mut self, key: KeyEvent) {
         match key.code {
             KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {
-                self.mode = Mode::Normal;
-                self.status = "Delete cancelled.".to_string();
+                let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                self.status = match mode {
+                    Mode::Confirm {
+                        action: ConfirmAction::ImportExternal { path, .. },
+                        ..
+                    } => format!(
+                        "Left {} alone.",
+                        services::display_path(&self.repo_root, &path)
+                    ),
+                    Mode::Confirm {
+                        action: ConfirmAction::RunGit { .. },
+                        ..
+                    } => "Git command cancelled.".to_string(),
+                    _ => "Delete cancelled.".to_string(),
+                };
             }
             KeyCode::Char('y') | KeyCode::Char('Y') => {
                 let mode = std::mem::replace(&mut self.mode, Mode::Normal);
@@ -348,6 +882,29 @@ impl TuiApp {
                     self.run_confirm_action(action);
                 }
             }
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                match mode {
+                    Mode::Confirm {
+                        action:
+                            ConfirmAction::Delete {
+                                branch,
+                                path,
+                                ref_name,
+                                dirty: true,
+                                ..
+                            },
+                        ..
+                    } => self.run_confirm_action(ConfirmAction::Delete {
+                        branch,
+                        path,
+                        ref_name,
+                        dirty: true,
+                        snapshot: true,
+                    }),
+                    other => self.mode = other,
+                }
+            }
             _ => {}
         }
     }
@@ -360,6 +917,12 @@ impl TuiApp {
                     self.status = match action {
                         InputAction::Rename { .. } => "Rename cancelled.".to_string(),
                         InputAction::NewWorktree { .. } => "Create cancelled.".to_string(),
+                        InputAction::MoveChanges { .. } => "Move cancelled.".to_string(),
+                        InputAction::Compare { .. } => "Compare cancelled.".to_string(),
+                        InputAction::Adopt { .. } => "Adopt cancelled.".to_string(),
+                        InputAction::EditDescription { .. } => "Description unchanged.".to_string(),
+                        InputAction::EditNote { .. } => "Memo unchanged.".to_string(),
+                        InputAction::GitCommand { .. } => "Git command cancelled.".to_string(),
                     };
                 }
             }
@@ -383,91 +946,466 @@ impl TuiApp {
         }
     }
 
-    fn run_confirm_action(&mut self, action: ConfirmAction) {
-        match action {
-            ConfirmAction::Delete {
-                branch,
-                path,
-                ref_name,
-            } => {
-                let repo_root = self.repo_root.clone();
-                self.start_operation(
-                    format!("Deleting {branch}"),
-                    format!("Deleted {branch}."),
-                    "Delete failed".to_string(),
-                    None,
-                    PostSuccessAction::ReloadOnly,
-                    move || {
-                        git_ops::worktree_remove(&repo_root, &path)?;
-                        git_ops::branch_delete(&repo_root, &ref_name)?;
-                        Ok(())
-                    },
-                );
+    fn handle_key_doctor(&mut self, key: KeyEvent) {
+        let Mode::Doctor {
+            report,
+            plan,
+            selected,
+        } = &mut self.mode
+        else {
+            return;
+        };
+        let fixes = report.fixes();
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = Mode::Normal;
+                self.status = "Doctor closed.".to_string();
+            }
+            KeyCode::Up => *selected = selected.saturating_sub(1),
+            KeyCode::Down => *selected = (*selected + 1).min(fixes.len().saturating_sub(1)),
+            KeyCode::Char(' ') => {
+                if let Some(fix) = fixes.get(*selected) {
+                    plan.toggle(fix);
+                }
+            }
+            KeyCode::Char('a') => {
+                if let Some(RepairFix::Orphan(path)) = fixes.get(*selected) {
+                    if let Some(orphan) = report.orphan(path) {
+                        plan.toggle_adopt(orphan);
+                    }
+                }
+            }
+            KeyCode::Char('i') => {
+                if let Some(RepairFix::CreateWorktree(branch)) = fixes.get(*selected) {
+                    plan.toggle_ignore(branch);
+                }
+            }
+            KeyCode::Enter => {
+                if !report.is_recoverable() {
+                    self.status = "Setup has unrecoverable issues; run `gw init` outside the TUI."
+                        .to_string();
+                    return;
+                }
+                let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                if let Mode::Doctor { report, plan, .. } = mode {
+                    let repo_root = self.repo_root.clone();
+                    let selected_branch = self.current_item().map(|item| item.branch);
+                    let deletes = report
+                        .orphaned_worktrees
+                        .iter()
+                        .any(|orphan| plan.deletes_orphan(orphan));
+                    let success = if deletes {
+                        "Setup repaired. Deleted worktrees can be recovered with `gw restore`."
+                    } else {
+                        "Setup repaired."
+                    };
+                    self.start_operation(
+                        OpScope::Repo,
+                        "Repairing setup".to_string(),
+                        success.to_string(),
+                        "Repair failed".to_string(),
+                        selected_branch,
+                        PostSuccessAction::ReloadAndRefresh,
+                        move || services::doctor_repo(&repo_root, &report, &plan).map(|_| ()),
+                    );
+                }
             }
+            _ => {}
         }
     }
 
-    fn run_input_action(&mut self, value: String, action: InputAction) {
-        let normalized = value.trim().to_string();
+    fn handle_key_cherry_pick(&mut self, key: KeyEvent) {
+        let Mode::CherryPick {
+            commits,
+            marked,
+            selected,
+            targets,
+            ..
+        } = &mut self.mode
+        else {
+            return;
+        };
+        let len = targets.as_ref().map_or(commits.len(), Vec::len);
 
-        match action {
-            InputAction::Rename {
-                old_ref_name,
-                old_path,
-            } => {
-                if normalized.is_empty() {
-                    self.status = "Rename cancelled.".to_string();
-                    return;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = Mode::Normal;
+                self.status = "Cherry-pick cancelled.".to_string();
+            }
+            KeyCode::Up => *selected = selected.saturating_sub(1),
+            KeyCode::Down => *selected = (*selected + 1).min(len.saturating_sub(1)),
+            KeyCode::Char(' ') if targets.is_none() => {
+                if let Some(mark) = marked.get_mut(*selected) {
+                    *mark = !*mark;
                 }
-
-                if !git_ops::is_valid_branch_name(&self.repo_root, &normalized) {
-                    self.status = "Invalid branch name.".to_string();
+            }
+            KeyCode::Enter if targets.is_none() => {
+                // Enter without marks picks the highlighted commit.
+                if !marked.contains(&true) {
+                    if let Some(mark) = marked.get_mut(*selected) {
+                        *mark = true;
+                    }
+                }
+                let source = match &self.mode {
+                    Mode::CherryPick { source, .. } => source.clone(),
+                    _ => return,
+                };
+                let candidates: Vec<WorktreeInfo> = self
+                    .snapshot_items()
+                    .into_iter()
+                    .filter(|item| item.ref_name.is_some() && item.branch != source)
+                    .collect();
+                if candidates.is_empty() {
+                    self.mode = Mode::Normal;
+                    self.status = "No other worktree to cherry-pick into.".to_string();
                     return;
                 }
-
-                if git_ops::branch_exists(&self.repo_root, &normalized) {
-                    self.status = "Branch already exists.".to_string();
+                if let Mode::CherryPick {
+                    selected, targets, ..
+                } = &mut self.mode
+                {
+                    *selected = 0;
+                    *targets = Some(candidates);
+                }
+            }
+            KeyCode::Enter => {
+                let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                let Mode::CherryPick {
+                    commits,
+                    marked,
+                    selected,
+                    targets: Some(targets),
+                    ..
+                } = mode
+                else {
+                    return;
+                };
+                let Some(target) = targets.into_iter().nth(selected) else {
+                    return;
+                };
+                let picked: Vec<CommitSummary> = commits
+                    .into_iter()
+                    .zip(marked)
+                    .filter_map(|(commit, mark)| mark.then_some(commit))
+                    .collect();
+                let scope = OpScope::worktree(target.path.clone());
+                if self.reject_if_conflicting(&scope) {
                     return;
                 }
-
                 let repo_root = self.repo_root.clone();
-                let new_branch = normalized.clone();
-                let new_path = repo_root.join(&new_branch);
-
+                let count = commit_count(picked.len() as i64);
                 self.start_operation(
-                    format!("Renaming to {new_branch}"),
-                    format!("Renamed to {new_branch}."),
-                    "Rename failed".to_string(),
-                    Some(new_branch.clone()),
+                    scope,
+                    format!("Cherry-picking {count} into {}", target.branch),
+                    format!("Cherry-picked {count} into {}.", target.branch),
+                    "Cherry-pick failed".to_string(),
+                    self.current_item().map(|item| item.branch),
                     PostSuccessAction::ReloadOnly,
-                    move || {
-                        git_ops::branch_rename(&repo_root, &old_ref_name, &new_branch)?;
-                        git_ops::worktree_move(&repo_root, &old_path, &new_path)?;
-                        Ok(())
-                    },
+                    move || services::cherry_pick_commits(&repo_root, &target.path, &picked),
                 );
             }
-            InputAction::NewWorktree {
-                base_branch,
-                pull_before_create,
-            } => {
-                if normalized.is_empty() {
-                    self.status = "Create cancelled.".to_string();
-                    return;
-                }
+            _ => {}
+        }
+    }
 
-                if !git_ops::is_valid_branch_name(&self.repo_root, &normalized) {
-                    self.status = "Invalid branch name.".to_string();
-                    return;
-                }
+    fn handle_key_output(&mut self, key: KeyEvent) {
+        let Mode::Output { lines, scroll, .. } = &mut self.mode else {
+            return;
+        };
+        let last = lines.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => self.mode = Mode::Normal,
+            KeyCode::Up => *scroll = scroll.saturating_sub(1),
+            KeyCode::Down => *scroll = (*scroll + 1).min(last),
+            KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+            KeyCode::PageDown => *scroll = (*scroll + 10).min(last),
+            _ => {}
+        }
+    }
 
-                if git_ops::branch_exists(&self.repo_root, &normalized) {
-                    self.status = "Branch already exists locally.".to_string();
-                    return;
+    fn handle_key_compare(&mut self, key: KeyEvent) {
+        let Mode::Compare { comparison, scroll } = &mut self.mode else {
+            return;
+        };
+        let last = compare_lines(comparison).len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => self.mode = Mode::Normal,
+            KeyCode::Up => *scroll = scroll.saturating_sub(1),
+            KeyCode::Down => *scroll = (*scroll + 1).min(last),
+            KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+            KeyCode::PageDown => *scroll = (*scroll + 10).min(last),
+            _ => {}
+        }
+    }
+
+    fn handle_key_deleted(&mut self, key: KeyEvent) {
+        let Mode::Deleted { branches, selected } = &mut self.mode else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = Mode::Normal;
+                self.status = "Restore cancelled.".to_string();
+            }
+            KeyCode::Up => *selected = selected.saturating_sub(1),
+            KeyCode::Down => *selected = (*selected + 1).min(branches.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                let Mode::Deleted { branches, selected } =
+                    std::mem::replace(&mut self.mode, Mode::Normal)
+                else {
+                    return;
+                };
+                let Some(deleted) = branches.into_iter().nth(selected) else {
+                    return;
+                };
+                let branch = deleted.branch.clone();
+                let path = services::worktree_path_for_branch(&self.repo_root, &branch);
+                let repo_root = self.repo_root.clone();
+                self.next_notification = self.hooks_notification();
+                self.start_operation(
+                    OpScope::worktree(path),
+                    format!("Restoring {branch}"),
+                    format!("Restored {branch}."),
+                    "Restore failed".to_string(),
+                    Some(branch),
+                    PostSuccessAction::ReloadAndRefresh,
+                    move || {
+                        trash::restore_deleted_branch(&repo_root, &deleted, None)?;
+                        Ok(())
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_reconcile(&mut self, key: KeyEvent) {
+        let force = match key.code {
+            KeyCode::Char('r') => false,
+            KeyCode::Char('f') => true,
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('n') => {
+                self.mode = Mode::Normal;
+                self.status = "Reconcile cancelled.".to_string();
+                return;
+            }
+            _ => return,
+        };
+        let Mode::Reconcile { branch, path, .. } = std::mem::replace(&mut self.mode, Mode::Normal)
+        else {
+            return;
+        };
+        let repo_root = self.repo_root.clone();
+        if force {
+            self.next_notification = Some(NotificationEvent::Push);
+            self.start_operation(
+                OpScope::worktree(path.clone()),
+                format!("Force-pushing {branch}"),
+                format!("Force-pushed {branch}."),
+                "Force-push failed".to_string(),
+                Some(branch),
+                PostSuccessAction::ReloadAndRefresh,
+                move || git_ops::push_force_with_lease(&path),
+            );
+        } else {
+            self.next_notification = Some(NotificationEvent::Pull);
+            self.start_operation(
+                OpScope::worktree(path.clone()),
+                format!("Rebasing {branch} onto its upstream"),
+                format!("Rebased {branch} onto its upstream; push to publish it."),
+                "Rebase failed".to_string(),
+                Some(branch),
+                PostSuccessAction::ReloadAndRefresh,
+                move || services::rebase_onto_upstream(&repo_root, &path),
+            );
+        }
+    }
+
+    fn handle_key_upstream_gone(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('d') => {
+                // The delete dialog takes it from here, including its checks
+                // for uncommitted changes.
+                self.mode = Mode::Normal;
+                self.action_delete_worktree();
+            }
+            KeyCode::Char('p') => {
+                let Mode::UpstreamGone { branch, path } =
+                    std::mem::replace(&mut self.mode, Mode::Normal)
+                else {
+                    return;
+                };
+                let ref_name = branch.clone();
+                self.next_notification = Some(NotificationEvent::Push);
+                self.start_operation(
+                    OpScope::worktree(path.clone()),
+                    format!("Pushing {branch}"),
+                    format!("Pushed {branch}, recreating its upstream."),
+                    "Push failed".to_string(),
+                    Some(branch),
+                    PostSuccessAction::ReloadAndRefresh,
+                    move || git_ops::push_set_upstream(&path, &ref_name),
+                );
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('n') => {
+                self.mode = Mode::Normal;
+                self.status = "Cancelled.".to_string();
+            }
+            _ => {}
+        }
+    }
+
+    fn run_confirm_action(&mut self, action: ConfirmAction) {
+        match action {
+            ConfirmAction::Delete {
+                branch,
+                path,
+                ref_name,
+                snapshot: false,
+                ..
+            } => {
+                let repo_root = self.repo_root.clone();
+                self.start_operation(
+                    OpScope::worktree(path.clone()),
+                    format!("Deleting {branch}"),
+                    format!("Deleted {branch} (`gw restore {branch}` brings it back)."),
+                    "Delete failed".to_string(),
+                    None,
+                    PostSuccessAction::ReloadOnly,
+                    move || delete_worktree_and_branch(&repo_root, &path, &ref_name),
+                );
+            }
+            ConfirmAction::Delete {
+                branch,
+                path,
+                ref_name,
+                snapshot: true,
+                ..
+            } => {
+                let repo_root = self.repo_root.clone();
+                let backup_branch = branch.clone();
+                self.start_operation(
+                    OpScope::worktree(path.clone()),
+                    format!("Snapshotting and deleting {branch}"),
+                    format!(
+                        "Deleted {branch}; its uncommitted changes are in a backup (`gw restore` lists it)."
+                    ),
+                    "Delete failed".to_string(),
+                    None,
+                    PostSuccessAction::ReloadOnly,
+                    move || {
+                        let head = git_ops::worktree_head_commit(&path);
+                        services::backup_worktree(
+                            &repo_root,
+                            &path,
+                            head.as_deref(),
+                            Some(&backup_branch),
+                        )?;
+                        delete_worktree_and_branch(&repo_root, &path, &ref_name)
+                    },
+                );
+            }
+            ConfirmAction::ImportExternal { branch, path } => {
+                let repo_root = self.repo_root.clone();
+                self.start_operation(
+                    OpScope::worktree(path.clone()),
+                    format!("Importing {}", services::display_path(&repo_root, &path)),
+                    format!("Imported the other checkout of {branch}."),
+                    "Import failed".to_string(),
+                    Some(branch.clone()),
+                    PostSuccessAction::ReloadOnly,
+                    move || {
+                        services::import_external_worktree(&repo_root, &path, &branch).map(Some)
+                    },
+                );
+            }
+            ConfirmAction::RemoveTag { tag, path } => {
+                let repo_root = self.repo_root.clone();
+                self.start_operation(
+                    OpScope::worktree(path.clone()),
+                    format!("Removing tag {tag}"),
+                    format!("Removed the worktree of tag {tag}."),
+                    "Remove failed".to_string(),
+                    None,
+                    PostSuccessAction::ReloadOnly,
+                    move || services::remove_worktree(&repo_root, &path),
+                );
+            }
+            ConfirmAction::RemoveDetached {
+                label,
+                path,
+                review,
+            } => {
+                let repo_root = self.repo_root.clone();
+                self.start_operation(
+                    OpScope::worktree(path.clone()),
+                    format!("Removing {label}"),
+                    format!("Removed the worktree of {label}."),
+                    "Remove failed".to_string(),
+                    None,
+                    PostSuccessAction::ReloadOnly,
+                    move || match review {
+                        Some(number) => review::remove_review(&repo_root, number, &path),
+                        None => services::remove_worktree(&repo_root, &path),
+                    },
+                );
+            }
+            ConfirmAction::RunGit { branch, path, args } => {
+                self.run_git_command(branch, path, args, true);
+            }
+            ConfirmAction::DeleteMerged { worktrees } => {
+                let repo_root = self.repo_root.clone();
+                let count = worktrees.len();
+                let scope =
+                    OpScope::Worktrees(worktrees.iter().map(|(path, _)| path.clone()).collect());
+                self.start_cancellable_operation(
+                    scope,
+                    format!("Cleaning up {count} merged worktree(s)"),
+                    format!("Deleted {count} merged worktree(s)."),
+                    "Cleanup failed".to_string(),
+                    None,
+                    PostSuccessAction::ReloadOnly,
+                    move |context| {
+                        for (path, ref_name) in &worktrees {
+                            context.check()?;
+                            delete_worktree_and_branch(&repo_root, path, ref_name)?;
+                        }
+                        Ok(())
+                    },
+                );
+            }
+        }
+    }
+
+    fn run_input_action(&mut self, value: String, action: InputAction) {
+        let normalized = value.trim().to_string();
+
+        match action {
+            InputAction::Rename {
+                old_ref_name,
+                old_path,
+            } => {
+                if normalized.is_empty() {
+                    self.status = "Rename cancelled.".to_string();
+                    return;
+                }
+
+                if !git_ops::is_valid_branch_name(&self.repo_root, &normalized) {
+                    self.status = "Invalid branch name.".to_string();
+                    return;
                 }
 
-                let new_path = self.repo_root.join(&normalized);
-                if new_path.exists() {
+                if git_ops::branch_exists(&self.repo_root, &normalized) {
+                    self.status = "Branch already exists.".to_string();
+                    return;
+                }
+
+                // The checkout at the root of a hub layout stays where it is.
+                let is_root = old_path == self.repo_root;
+                let worktrees = git_ops::parse_worktrees(Some(&self.repo_root)).unwrap_or_default();
+                let new_path =
+                    services::new_worktree_path(&self.repo_root, &normalized, &worktrees);
+                if !is_root && !git_ops::is_path_available(&new_path) {
                     self.status = "Target worktree path already exists.".to_string();
                     return;
                 }
@@ -476,41 +1414,298 @@ impl TuiApp {
                 let new_branch = normalized.clone();
 
                 self.start_operation(
-                    format!("Creating {new_branch}"),
-                    format!("Created {new_branch}."),
-                    "Create failed".to_string(),
+                    OpScope::Worktrees(vec![old_path.clone(), new_path.clone()]),
+                    format!("Renaming to {new_branch}"),
+                    format!("Renamed to {new_branch}."),
+                    "Rename failed".to_string(),
                     Some(new_branch.clone()),
                     PostSuccessAction::ReloadOnly,
                     move || {
-                        if let Some(base_path) = pull_before_create {
-                            git_ops::pull(&base_path)?;
+                        git_ops::branch_rename(&repo_root, &old_ref_name, &new_branch)?;
+                        if !is_root {
+                            services::move_worktree(&repo_root, &old_path, &new_path)?;
+                            services::record_worktree_path(&repo_root, &new_branch, &new_path);
                         }
+                        services::forget_worktree_path(&repo_root, &old_ref_name);
+                        services::carry_worktree_note(&repo_root, &old_ref_name, &new_branch);
+                        Ok(())
+                    },
+                );
+            }
+            InputAction::NewWorktree {
+                base_branch,
+                pull_before_create,
+            } => {
+                if normalized.is_empty() {
+                    self.status = "Create cancelled.".to_string();
+                    return;
+                }
+                let branches: Vec<String> =
+                    normalized.split_whitespace().map(str::to_string).collect();
+                if branches.len() > 1 {
+                    self.create_worktrees(branches, base_branch, pull_before_create);
+                    return;
+                }
 
-                        let target = repo_root.join(&new_branch);
-                        if git_ops::remote_branch_exists(&repo_root, &new_branch) {
-                            git_ops::fetch_branch(&repo_root, &new_branch)?;
-                            git_ops::branch_set_upstream(
-                                &repo_root,
-                                &new_branch,
-                                &format!("origin/{new_branch}"),
-                            )?;
-                            git_ops::worktree_add(&repo_root, &target, &new_branch, None)?;
-                        } else {
-                            git_ops::worktree_add(
+                let new_path = match services::new_worktree_target(&self.repo_root, &normalized) {
+                    Ok(path) => path,
+                    Err(err) => {
+                        self.status = format!("Cannot create: {}", describe_error(&err));
+                        return;
+                    }
+                };
+
+                let fast = match settings::load_fast_create(&self.repo_root) {
+                    Ok(fast) => fast,
+                    Err(err) => {
+                        self.status = format!("Cannot create: {}", describe_error(&err));
+                        return;
+                    }
+                };
+
+                let repo_root = self.repo_root.clone();
+                let new_branch = normalized.clone();
+                let mut scope = vec![new_path.clone()];
+                scope.extend(pull_before_create.clone());
+                let success = if fast {
+                    format!("Created {new_branch}; checking out its files in the background.")
+                } else {
+                    format!("Created {new_branch}.")
+                };
+
+                self.next_notification = self.hooks_notification();
+                self.start_operation(
+                    OpScope::Worktrees(scope),
+                    format!("Creating {new_branch}"),
+                    success,
+                    "Create failed".to_string(),
+                    Some(new_branch.clone()),
+                    PostSuccessAction::ReloadOnly,
+                    move || {
+                        if fast {
+                            services::create_worktree_fast(
                                 &repo_root,
-                                &target,
+                                &new_path,
                                 &new_branch,
-                                Some(&base_branch),
+                                &base_branch,
+                                pull_before_create.as_deref(),
+                                false,
                             )?;
+                            return Ok(None);
                         }
-                        hooks::run_post_worktree_creation_hooks(&repo_root, Some(&target))?;
-                        Ok(())
+                        services::create_worktree(
+                            &repo_root,
+                            &new_path,
+                            &new_branch,
+                            &base_branch,
+                            pull_before_create.as_deref(),
+                            false,
+                        )
+                    },
+                );
+            }
+            InputAction::EditDescription { branch, path } => {
+                let repo_root = self.repo_root.clone();
+                let success = if normalized.is_empty() {
+                    format!("Removed the description of {branch}.")
+                } else {
+                    format!("Described {branch}.")
+                };
+                self.start_operation(
+                    OpScope::worktree(path),
+                    format!("Describing {branch}"),
+                    success,
+                    "Describe failed".to_string(),
+                    Some(branch.clone()),
+                    PostSuccessAction::ReloadOnly,
+                    move || git_ops::set_branch_description(&repo_root, &branch, &normalized),
+                );
+            }
+            InputAction::EditNote {
+                label,
+                cache_key,
+                path,
+            } => {
+                let repo_root = self.repo_root.clone();
+                let success = if normalized.is_empty() {
+                    format!("Removed the memo of {label}.")
+                } else {
+                    format!("Saved the memo of {label}.")
+                };
+                self.start_operation(
+                    OpScope::worktree(path),
+                    format!("Saving the memo of {label}"),
+                    success,
+                    "Saving the memo failed".to_string(),
+                    None,
+                    PostSuccessAction::ReloadOnly,
+                    move || services::set_worktree_note(&repo_root, &cache_key, &normalized),
+                );
+            }
+            InputAction::Adopt { path } => {
+                if normalized.is_empty() {
+                    self.status = "Adopt cancelled.".to_string();
+                    return;
+                }
+
+                if !git_ops::is_valid_branch_name(&self.repo_root, &normalized) {
+                    self.status = "Invalid branch name.".to_string();
+                    return;
+                }
+
+                if git_ops::branch_exists(&self.repo_root, &normalized) {
+                    self.status = "Branch already exists.".to_string();
+                    return;
+                }
+
+                let repo_root = self.repo_root.clone();
+                let new_path = services::worktree_path_for_branch(&repo_root, &normalized);
+                let branch = normalized.clone();
+                self.start_operation(
+                    OpScope::Worktrees(vec![path.clone(), new_path]),
+                    format!("Adopting as {branch}"),
+                    format!("Adopted as {branch}."),
+                    "Adopt failed".to_string(),
+                    Some(branch.clone()),
+                    PostSuccessAction::ReloadOnly,
+                    move || services::adopt_detached_worktree(&repo_root, &path, &branch),
+                );
+            }
+            InputAction::Compare { branch } => {
+                if normalized.is_empty() {
+                    self.status = "Compare cancelled.".to_string();
+                    return;
+                }
+                match services::compare_branches(&self.repo_root, &branch, &normalized) {
+                    Ok(comparison) => {
+                        self.mode = Mode::Compare {
+                            comparison,
+                            scroll: 0,
+                        }
+                    }
+                    Err(err) => {
+                        self.status = format!("Cannot compare: {}", describe_error(&err));
+                    }
+                }
+            }
+            InputAction::GitCommand { branch, path } => {
+                let args = match services::split_command_line(&normalized) {
+                    Ok(args) => args,
+                    Err(err) => {
+                        self.status = format!("Cannot run git: {err}");
+                        return;
+                    }
+                };
+                // Typing the `git` is optional.
+                let args = match args.split_first() {
+                    Some((first, rest)) if first == "git" => rest.to_vec(),
+                    _ => args,
+                };
+                if args.is_empty() {
+                    self.status = "Git command cancelled.".to_string();
+                } else if git_ops::is_read_only_command(&args) {
+                    self.run_git_command(branch, path, args, false);
+                } else {
+                    self.mode = Mode::Confirm {
+                        prompt: format!("Run `git {}` in {branch}?", join_command_line(&args)),
+                        action: ConfirmAction::RunGit { branch, path, args },
+                    };
+                }
+            }
+            InputAction::MoveChanges {
+                source,
+                base_branch,
+                pull_before_create,
+            } => {
+                if normalized.is_empty() {
+                    self.status = "Move cancelled.".to_string();
+                    return;
+                }
+                let new_path = match services::new_worktree_target(&self.repo_root, &normalized) {
+                    Ok(path) => path,
+                    Err(err) => {
+                        self.status = format!("Cannot create: {}", describe_error(&err));
+                        return;
+                    }
+                };
+
+                let repo_root = self.repo_root.clone();
+                let new_branch = normalized;
+                let mut scope = vec![new_path.clone(), source.clone()];
+                scope.extend(pull_before_create.clone());
+                self.start_operation(
+                    OpScope::Worktrees(scope),
+                    format!("Moving changes to {new_branch}"),
+                    format!("Created {new_branch} with the uncommitted changes."),
+                    "Move failed".to_string(),
+                    Some(new_branch.clone()),
+                    PostSuccessAction::ReloadAndRefresh,
+                    move || {
+                        services::create_worktree_with_changes(
+                            &repo_root,
+                            &source,
+                            &new_path,
+                            &new_branch,
+                            &base_branch,
+                            pull_before_create.as_deref(),
+                            false,
+                        )
                     },
                 );
             }
         }
     }
 
+    /// Several names at the new-branch prompt: one operation creating them
+    /// in order, pulling the base only once.
+    fn create_worktrees(
+        &mut self,
+        branches: Vec<String>,
+        base_branch: String,
+        pull_before_create: Option<PathBuf>,
+    ) {
+        let targets = match services::new_worktree_targets(&self.repo_root, &branches) {
+            Ok(targets) => targets,
+            Err(err) => {
+                self.status = format!("Cannot create: {}", describe_error(&err));
+                return;
+            }
+        };
+        let fast = match settings::load_fast_create(&self.repo_root) {
+            Ok(fast) => fast,
+            Err(err) => {
+                self.status = format!("Cannot create: {}", describe_error(&err));
+                return;
+            }
+        };
+
+        let repo_root = self.repo_root.clone();
+        let mut scope: Vec<PathBuf> = targets.iter().map(|(_, path)| path.clone()).collect();
+        scope.extend(pull_before_create.clone());
+
+        self.next_notification = self.hooks_notification();
+        self.start_operation(
+            OpScope::Worktrees(scope),
+            format!("Creating {} worktrees", targets.len()),
+            format!("Created {}.", branches.join(", ")),
+            "Create failed".to_string(),
+            branches.first().cloned(),
+            PostSuccessAction::ReloadOnly,
+            move || {
+                let reminders = services::create_worktrees(
+                    &repo_root,
+                    &targets,
+                    &base_branch,
+                    pull_before_create.as_deref(),
+                    fast,
+                    false,
+                    &mut |_, _| {},
+                )?;
+                Ok((!reminders.is_empty()).then(|| reminders.join(" ")))
+            },
+        );
+    }
+
     fn action_choose(&mut self) {
         let Some(current) = self.current_item() else {
             self.should_quit = true;
@@ -521,20 +1716,53 @@ impl TuiApp {
         self.should_quit = true;
     }
 
-    fn action_refresh(&mut self) {
-        if self.busy {
-            self.status = "Another operation is in progress.".to_string();
+    /// Esc: stops the running refresh and operation at their next step and
+    /// drops queued operations, or quits when nothing is running.
+    fn action_cancel_or_quit(&mut self) {
+        if self.refresh_task.is_none() && self.op_task.is_none() {
+            self.should_quit = true;
             return;
         }
-        self.start_refresh(true);
+        if let Some(task) = &self.refresh_task {
+            task.cancel();
+        }
+        if let Some(task) = &self.op_task {
+            task.cancel();
+        }
+        self.op_queue.clear();
+        self.status = "Cancelling...".to_string();
     }
 
-    fn action_pull_worktree(&mut self) {
+    fn action_refresh(&mut self) {
         if self.busy {
             self.status = "Another operation is in progress.".to_string();
             return;
         }
+        self.start_refresh(true);
+    }
 
+    /// Asks whether to import the worktree outside gw that also has
+    /// `item`'s branch checked out, which would make operations on the
+    /// branch fail. Returns whether there is one.
+    fn offer_import_if_checked_out_elsewhere(&mut self, item: &WorktreeInfo) -> bool {
+        let Some(other) = services::checked_out_elsewhere(&self.repo_root, &item.branch) else {
+            return false;
+        };
+        self.mode = Mode::Confirm {
+            prompt: format!(
+                "{} is also checked out at {}, a worktree gw doesn't manage. Import it?",
+                item.branch,
+                services::display_path(&self.repo_root, &other)
+            ),
+            action: ConfirmAction::ImportExternal {
+                branch: item.branch.clone(),
+                path: other,
+            },
+        };
+        true
+    }
+
+    fn action_pull_worktree(&mut self) {
         let Some(current) = self.current_item() else {
             self.status = "No worktrees available.".to_string();
             return;
@@ -545,22 +1773,76 @@ impl TuiApp {
             return;
         }
 
+        if let Err(err) = services::ensure_no_operation_in_progress(&current.branch, &current.path)
+        {
+            self.status = format!("Cannot pull: {}", describe_error(&err));
+            return;
+        }
+
+        if self.offer_import_if_checked_out_elsewhere(&current) {
+            return;
+        }
+
+        let strategy = match settings::load_pull_strategy(&self.repo_root) {
+            Ok(strategy) => strategy,
+            Err(err) => {
+                self.status = format!("Pull failed: {err}");
+                return;
+            }
+        };
         let branch = current.branch.clone();
         let path = current.path.clone();
 
+        self.next_notification = Some(NotificationEvent::Pull);
         self.start_operation(
+            OpScope::worktree(path.clone()),
             format!("Pulling {branch}"),
             format!("Pulled {branch}."),
             "Pull failed".to_string(),
             Some(branch),
             PostSuccessAction::ReloadAndRefresh,
             move || {
-                git_ops::pull(&path)?;
+                git_ops::pull(&path, strategy)?;
                 Ok(())
             },
         );
     }
 
+    fn action_reconcile(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+        if current.upstream_gone && !current.is_detached() {
+            if self.reject_if_conflicting(&OpScope::worktree(current.path.clone())) {
+                return;
+            }
+            self.mode = Mode::UpstreamGone {
+                branch: current.branch,
+                path: current.path,
+            };
+            return;
+        }
+        if !current.is_diverged() {
+            self.status = format!("{} has not diverged from its upstream.", current.branch);
+            return;
+        }
+        if let Err(err) = services::ensure_no_operation_in_progress(&current.branch, &current.path)
+        {
+            self.status = format!("Cannot reconcile: {}", describe_error(&err));
+            return;
+        }
+        if self.reject_if_conflicting(&OpScope::worktree(current.path.clone())) {
+            return;
+        }
+        self.mode = Mode::Reconcile {
+            branch: current.branch,
+            path: current.path,
+            pull: current.pull,
+            push: current.push,
+        };
+    }
+
     fn action_open_pr(&mut self) {
         let Some(current) = self.current_item() else {
             self.status = "No worktrees available.".to_string();
@@ -580,17 +1862,149 @@ impl TuiApp {
                 );
             }
             Err(err) => {
-                self.status = format!("Failed to open PR: {err}");
+                self.status = format!("Failed to open PR: {err}");
+            }
+        }
+    }
+
+    fn action_toggle_pr_draft(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+
+        let Some(number) = current.pr_number else {
+            self.status = "No pull request for selected worktree.".to_string();
+            return;
+        };
+        if current
+            .pr_state
+            .as_deref()
+            .is_some_and(|state| state != "OPEN")
+        {
+            self.status = format!("PR #{number} is not open.");
+            return;
+        }
+
+        let draft = !current.pr_draft;
+        let (spinner, success) = if draft {
+            (
+                format!("Converting PR #{number} to draft"),
+                format!("PR #{number} is now a draft."),
+            )
+        } else {
+            (
+                format!("Marking PR #{number} ready for review"),
+                format!("PR #{number} is ready for review."),
+            )
+        };
+        let repo_root = self.repo_root.clone();
+        let cache_key = current.cache_key.clone();
+
+        self.start_operation(
+            OpScope::worktree(current.path.clone()),
+            spinner,
+            success,
+            "Draft change failed".to_string(),
+            current.ref_name.clone(),
+            PostSuccessAction::ReloadOnly,
+            move || services::set_pr_draft(&repo_root, &cache_key, number, draft),
+        );
+    }
+
+    /// Removes `gw review` worktrees whose pull request has closed since the
+    /// last run.
+    fn prune_closed_reviews(&mut self) {
+        if !self.forge_available {
+            return;
+        }
+        let reviews: Vec<PathBuf> = self
+            .snapshot_items()
+            .into_iter()
+            .filter(|item| item.review.is_some())
+            .map(|item| item.path)
+            .collect();
+        if reviews.is_empty() {
+            return;
+        }
+        let repo_root = self.repo_root.clone();
+
+        self.start_operation(
+            OpScope::Worktrees(reviews),
+            "Checking review worktrees".to_string(),
+            "Checked review worktrees.".to_string(),
+            "Review cleanup failed".to_string(),
+            None,
+            PostSuccessAction::ReloadOnly,
+            move || {
+                let forge = forge::detect_forge(&repo_root);
+                let removed = review::prune_closed(&repo_root, forge.as_ref())?;
+                Ok((!removed.is_empty()).then(|| {
+                    let numbers: Vec<String> =
+                        removed.iter().map(|number| format!("#{number}")).collect();
+                    format!("removed closed reviews {}.", numbers.join(", "))
+                }))
+            },
+        );
+    }
+
+    /// Applies the cleanup policies marked `onStartup`: deletions run as an
+    /// operation, warnings go to the status line.
+    fn apply_startup_policies(&mut self) {
+        let policies: Vec<CleanupPolicy> = match settings::load_cleanup_policies(&self.repo_root) {
+            Ok(policies) => policies
+                .into_iter()
+                .filter(|policy| policy.on_startup)
+                .collect(),
+            Err(err) => {
+                self.status = format!("Cleanup policies not applied: {err}");
+                return;
             }
+        };
+        if policies.is_empty() {
+            return;
         }
-    }
+        let matches = policies::evaluate(
+            &self.repo_root,
+            &self.default_branch,
+            &self.snapshot_items(),
+            &policies,
+        );
 
-    fn action_push_worktree(&mut self) {
-        if self.busy {
-            self.status = "Another operation is in progress.".to_string();
+        let warnings: Vec<String> = matches
+            .iter()
+            .filter(|found| !found.deletes())
+            .map(policies::describe)
+            .collect();
+        if !warnings.is_empty() {
+            self.status = format!("Cleanup policies: {}", warnings.join("; "));
+        }
+
+        let deleting: Vec<PathBuf> = matches
+            .iter()
+            .filter(|found| found.deletes())
+            .map(|found| found.path.clone())
+            .collect();
+        if deleting.is_empty() {
             return;
         }
+        let count = deleting.len();
+        let repo_root = self.repo_root.clone();
+        self.start_operation(
+            OpScope::Worktrees(deleting),
+            format!("Applying cleanup policies to {count} worktree(s)"),
+            format!("Cleanup policies deleted {count} worktree(s)."),
+            "Cleanup policies failed".to_string(),
+            None,
+            PostSuccessAction::ReloadOnly,
+            move || {
+                let deleted = policies::apply(&repo_root, &matches)?;
+                Ok(Some(format!("{} moved to the trash.", deleted.join(", "))))
+            },
+        );
+    }
 
+    fn action_push_worktree(&mut self) {
         let Some(current) = self.current_item() else {
             self.status = "No worktrees available.".to_string();
             return;
@@ -601,12 +2015,20 @@ impl TuiApp {
             return;
         }
 
+        if let Err(err) = services::ensure_no_operation_in_progress(&current.branch, &current.path)
+        {
+            self.status = format!("Cannot push: {}", describe_error(&err));
+            return;
+        }
+
         let branch = current.branch.clone();
         let path = current.path.clone();
         let ref_name = current.ref_name.clone().unwrap_or_default();
         let has_upstream = current.has_upstream;
 
+        self.next_notification = Some(NotificationEvent::Push);
         self.start_operation(
+            OpScope::worktree(path.clone()),
             format!("Pushing {branch}"),
             format!("Pushed {branch}."),
             "Push failed".to_string(),
@@ -624,18 +2046,54 @@ impl TuiApp {
     }
 
     fn action_delete_worktree(&mut self) {
-        if self.busy {
-            self.status = "Another operation is in progress.".to_string();
-            return;
-        }
-
         let Some(current) = self.current_item() else {
             self.status = "No worktrees available.".to_string();
             return;
         };
 
+        if let Some(tag) = current.tag.clone() {
+            if self.reject_if_conflicting(&OpScope::worktree(current.path.clone())) {
+                return;
+            }
+            let mut prompt = format!("Remove the worktree of tag {tag}?");
+            if current.dirty {
+                prompt = format!(
+                    "Remove the worktree of tag {tag} (uncommitted changes are discarded)?"
+                );
+            }
+            self.mode = Mode::Confirm {
+                prompt,
+                action: ConfirmAction::RemoveTag {
+                    tag,
+                    path: current.path,
+                },
+            };
+            return;
+        }
+
         if current.is_detached() {
-            self.status = "Cannot delete a detached worktree.".to_string();
+            self.confirm_remove_detached(current);
+            return;
+        }
+
+        if current.locked.is_some() {
+            self.status = format!(
+                "Cannot delete {}: it is locked; run `git worktree unlock` on it first.",
+                current.branch
+            );
+            return;
+        }
+
+        if self.reject_if_conflicting(&OpScope::worktree(current.path.clone())) {
+            return;
+        }
+
+        if self.offer_import_if_checked_out_elsewhere(&current) {
+            return;
+        }
+
+        if current.path == self.repo_root {
+            self.status = "Cannot delete the checkout at the repo root.".to_string();
             return;
         }
 
@@ -644,8 +2102,19 @@ impl TuiApp {
         if current.dirty {
             warn_parts.push("working tree has uncommitted changes".to_string());
         }
-        if git_ops::has_unpushed_commits(&self.repo_root, &ref_name) {
-            warn_parts.push("branch has unpushed commits".to_string());
+        let base = services::ahead_behind_base(&self.repo_root, &self.default_branch)
+            .unwrap_or_else(|_| self.default_branch.clone());
+        match git_ops::unpushed_commits(&self.repo_root, &ref_name, &base) {
+            UnpushedCommits::None => {}
+            UnpushedCommits::Ahead(count) => {
+                warn_parts.push(format!("{} not pushed", commit_count(count)));
+            }
+            UnpushedCommits::NeverPushed(count) => {
+                warn_parts.push(format!(
+                    "never pushed, {} not on {base}",
+                    commit_count(count)
+                ));
+            }
         }
 
         let mut prompt = format!("Delete {}?", current.branch);
@@ -659,67 +2128,510 @@ impl TuiApp {
                 branch: current.branch,
                 path: current.path,
                 ref_name,
+                dirty: current.dirty,
+                snapshot: false,
+            },
+        };
+    }
+
+    /// Asks to remove a detached worktree, warning about uncommitted changes
+    /// and a HEAD that no branch or tag contains.
+    fn confirm_remove_detached(&mut self, current: WorktreeInfo) {
+        if current.locked.is_some() {
+            self.status = format!(
+                "Cannot remove {}: it is locked; run `git worktree unlock` on it first.",
+                branch_label(&current)
+            );
+            return;
+        }
+        if current.path == self.repo_root {
+            self.status = "Cannot delete the checkout at the repo root.".to_string();
+            return;
+        }
+        if self.reject_if_conflicting(&OpScope::worktree(current.path.clone())) {
+            return;
+        }
+
+        let label = branch_label(&current);
+        let mut warn_parts = Vec::new();
+        if current.dirty {
+            warn_parts.push("uncommitted changes are discarded".to_string());
+        }
+        if current.review.is_none() && !git_ops::commit_on_any_ref(&self.repo_root, &current.head) {
+            warn_parts.push(format!("{} is on no branch or tag", short_head(&current)));
+        }
+        let mut prompt = format!("Remove the worktree of {label}?");
+        if !warn_parts.is_empty() {
+            prompt = format!(
+                "Remove the worktree of {label} ({})?",
+                warn_parts.join("; ")
+            );
+        }
+        self.mode = Mode::Confirm {
+            prompt,
+            action: ConfirmAction::RemoveDetached {
+                label,
+                path: current.path,
+                review: current.review,
             },
         };
     }
 
+    fn action_cleanup_merged(&mut self) {
+        let items = self.snapshot_items();
+        let merged =
+            services::merged_cleanup_candidates(&items, &self.repo_root, &self.default_branch);
+        if merged.is_empty() {
+            self.status = "No merged worktrees to clean up.".to_string();
+            return;
+        }
+
+        let scope = OpScope::Worktrees(merged.iter().map(|item| item.path.clone()).collect());
+        if self.reject_if_conflicting(&scope) {
+            return;
+        }
+
+        let branches: Vec<&str> = merged.iter().map(|item| item.branch.as_str()).collect();
+        let prompt = format!(
+            "Delete {} merged worktree(s) and their local branches ({})?",
+            merged.len(),
+            branches.join(", ")
+        );
+        let worktrees = merged
+            .iter()
+            .map(|item| (item.path.clone(), item.ref_name.clone().unwrap_or_default()))
+            .collect();
+
+        self.mode = Mode::Confirm {
+            prompt,
+            action: ConfirmAction::DeleteMerged { worktrees },
+        };
+    }
+
     fn action_rename_worktree(&mut self) {
-        if self.busy {
-            self.status = "Another operation is in progress.".to_string();
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+
+        if current.is_detached() {
+            self.status = "Cannot rename a detached worktree.".to_string();
+            return;
+        }
+
+        if self.reject_if_conflicting(&OpScope::worktree(current.path.clone())) {
+            return;
+        }
+
+        if self.offer_import_if_checked_out_elsewhere(&current) {
+            return;
+        }
+
+        self.mode = Mode::Input {
+            prompt: format!("Rename {} to:", current.branch),
+            value: String::new(),
+            action: InputAction::Rename {
+                old_ref_name: current.ref_name.unwrap_or_default(),
+                old_path: current.path,
+            },
+        };
+    }
+
+    fn action_edit_description(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+
+        let Some(branch) = current.ref_name.clone() else {
+            self.status = "Cannot describe a detached worktree.".to_string();
+            return;
+        };
+
+        // Multi-line descriptions are edited as one line.
+        let value = current
+            .description
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.mode = Mode::Input {
+            prompt: format!("Description of {branch} (empty to remove):"),
+            value,
+            action: InputAction::EditDescription {
+                branch,
+                path: current.path,
+            },
+        };
+    }
+
+    fn action_edit_note(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+
+        let label = branch_label(&current);
+        self.mode = Mode::Input {
+            prompt: format!("Memo for {label} (empty to remove):"),
+            value: current.note.unwrap_or_default(),
+            action: InputAction::EditNote {
+                label,
+                cache_key: current.cache_key,
+                path: current.path,
+            },
+        };
+    }
+
+    fn action_adopt_detached(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+
+        if !current.is_detached() {
+            self.status = format!("{} is already on a branch.", current.branch);
+            return;
+        }
+
+        if self.reject_if_conflicting(&OpScope::worktree(current.path.clone())) {
+            return;
+        }
+
+        self.mode = Mode::Input {
+            prompt: format!("Adopt {} as branch:", branch_label(&current)),
+            value: String::new(),
+            action: InputAction::Adopt { path: current.path },
+        };
+    }
+
+    fn action_new_worktree_from_main(&mut self) {
+        let Some(main_item) = self
+            .snapshot_items()
+            .into_iter()
+            .find(|item| item.branch == "main")
+        else {
+            self.status = "Cannot create from main: no 'main' worktree is available.".to_string();
+            return;
+        };
+
+        if self.reject_if_conflicting(&OpScope::worktree(main_item.path.clone())) {
+            return;
+        }
+
+        self.mode = Mode::Input {
+            prompt: "New branch name:".to_string(),
+            value: String::new(),
+            action: InputAction::NewWorktree {
+                base_branch: "main".to_string(),
+                pull_before_create: Some(main_item.path),
+            },
+        };
+    }
+
+    fn action_move_changes(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+
+        let dirty = match git_ops::has_uncommitted_changes(&current.path) {
+            Ok(dirty) => dirty,
+            Err(err) => {
+                self.status = format!("Cannot move changes: {}", describe_error(&err));
+                return;
+            }
+        };
+        if !dirty {
+            self.status = format!("{} has no uncommitted changes to move.", current.branch);
+            return;
+        }
+
+        let (base_branch, pull_before_create) = match services::default_base(&self.repo_root) {
+            Ok(base) => base,
+            Err(err) => {
+                self.status = format!("Cannot move changes: {}", describe_error(&err));
+                return;
+            }
+        };
+        let mut scope = vec![current.path.clone()];
+        scope.extend(pull_before_create.clone());
+        if self.reject_if_conflicting(&OpScope::Worktrees(scope)) {
+            return;
+        }
+
+        self.mode = Mode::Input {
+            prompt: format!(
+                "Move the uncommitted changes of {} to a new branch from {base_branch}:",
+                current.branch
+            ),
+            value: String::new(),
+            action: InputAction::MoveChanges {
+                source: current.path,
+                base_branch,
+                pull_before_create,
+            },
+        };
+    }
+
+    fn action_compare(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+        let base = match services::compare_base(&self.repo_root, &current, &self.default_branch) {
+            Ok(base) => base,
+            Err(err) => {
+                self.status = format!("Cannot compare: {}", describe_error(&err));
+                return;
+            }
+        };
+        self.mode = Mode::Input {
+            prompt: format!("Compare {} with:", current.branch),
+            value: base,
+            action: InputAction::Compare {
+                branch: current.branch,
+            },
+        };
+    }
+
+    /// Copies the rows shown, filters applied, to the clipboard.
+    fn action_copy_table(&mut self, format: TableExport) {
+        let items = self.visible_items();
+        if items.is_empty() {
+            self.status = "No worktrees to copy.".to_string();
+            return;
+        }
+        let label = match format {
+            TableExport::Markdown => "Markdown",
+            TableExport::Csv => "CSV",
+        };
+        let text = export_table(&items, &self.default_branch, format);
+        self.status = match copy_to_clipboard(&text) {
+            Ok(()) => format!("Copied the table as {label}."),
+            Err(err) => format!("Cannot copy: {err}"),
+        };
+    }
+
+    fn action_git_command(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+        self.mode = Mode::Input {
+            prompt: format!("git (in {}):", current.branch),
+            value: String::new(),
+            action: InputAction::GitCommand {
+                branch: current.branch,
+                path: current.path,
+            },
+        };
+    }
+
+    /// Runs `git <args>` in the worktree as an operation and shows its
+    /// output once it finishes. Commands that may change something reload
+    /// and refresh afterwards.
+    fn run_git_command(
+        &mut self,
+        branch: String,
+        path: PathBuf,
+        args: Vec<String>,
+        mutating: bool,
+    ) {
+        let command = join_command_line(&args);
+        let title = format!("git {command} ({branch})");
+        let output = Arc::clone(&self.git_output);
+        self.start_operation(
+            OpScope::worktree(path.clone()),
+            format!("Running git {command}"),
+            format!("git {command} finished."),
+            format!("git {command} failed"),
+            Some(branch),
+            if mutating {
+                PostSuccessAction::ReloadAndRefresh
+            } else {
+                PostSuccessAction::None
+            },
+            move || {
+                let (succeeded, text) = git_ops::run_user_command(&path, &args)?;
+                let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+                if lines.len() > OUTPUT_LINE_LIMIT {
+                    let more = lines.len() - OUTPUT_LINE_LIMIT;
+                    lines.truncate(OUTPUT_LINE_LIMIT);
+                    lines.push(format!("… {more} more lines"));
+                }
+                if lines.is_empty() {
+                    lines.push("(no output)".to_string());
+                }
+                if let Ok(mut slot) = output.lock() {
+                    *slot = Some((title, lines));
+                }
+                if succeeded {
+                    Ok(())
+                } else {
+                    Err(anyhow!("it exited with an error, see its output"))
+                }
+            },
+        );
+    }
+
+    /// Runs the preset bound to `key`, if any, in the selected worktree.
+    /// Keys of built-in actions never reach here.
+    fn action_run_preset(&mut self, key: char) {
+        let presets = match settings::load_presets(&self.repo_root) {
+            Ok(presets) => presets,
+            Err(err) => {
+                self.status = format!("Cannot load presets: {err}");
+                return;
+            }
+        };
+        let Some(preset) = presets.into_iter().find(|preset| preset.key == Some(key)) else {
             return;
-        }
-
+        };
         let Some(current) = self.current_item() else {
             self.status = "No worktrees available.".to_string();
             return;
         };
-
         if current.is_detached() {
-            self.status = "Cannot rename a detached worktree.".to_string();
+            self.status = format!("Cannot run {} in a detached worktree.", preset.name);
+            return;
+        }
+        if let Err(err) = services::ensure_no_operation_in_progress(&current.branch, &current.path)
+        {
+            self.status = format!("Cannot run {}: {}", preset.name, describe_error(&err));
             return;
         }
 
-        self.mode = Mode::Input {
-            prompt: format!("Rename {} to:", current.branch),
-            value: String::new(),
-            action: InputAction::Rename {
-                old_ref_name: current.ref_name.unwrap_or_default(),
-                old_path: current.path,
+        let repo_root = self.repo_root.clone();
+        let branch = current.branch.clone();
+        let path = current.path.clone();
+        let progress = self.op_progress.clone();
+        let name = preset.name.clone();
+        self.start_operation(
+            OpScope::worktree(current.path),
+            format!("Running {name} on {branch}"),
+            format!("{name} finished on {branch}."),
+            format!("{name} failed on {branch}"),
+            Some(branch.clone()),
+            PostSuccessAction::ReloadAndRefresh,
+            move || {
+                let total = preset.steps.len();
+                services::run_preset(&repo_root, &path, &branch, &preset, &mut |index, line| {
+                    progress.set(format!("[{}/{total}] git {line}", index + 1));
+                })
             },
+        );
+    }
+
+    fn action_toggle_dry_run(&mut self) {
+        let on = !git_ops::dry_run();
+        git_ops::set_dry_run(on);
+        // Leftovers from before the toggle don't belong to the next operation.
+        logging::take_would_run();
+        self.status = if on {
+            "Dry run: operations list the commands that would change something.".to_string()
+        } else {
+            "Dry run off.".to_string()
         };
     }
 
-    fn action_new_worktree_from_main(&mut self) {
-        if self.busy {
-            self.status = "Another operation is in progress.".to_string();
+    /// Lists what the finished operation would have run, during a dry run.
+    fn show_dry_run_output(&mut self) {
+        if git_ops::dry_run() {
+            self.status = format!("[dry run] {}", self.status);
+        }
+        let lines = logging::take_would_run();
+        if lines.is_empty() || !matches!(self.mode, Mode::Normal) {
             return;
         }
+        self.mode = Mode::Output {
+            title: "Dry run".to_string(),
+            lines,
+            scroll: 0,
+        };
+    }
 
-        let Some(main_item) = self
-            .snapshot_items()
-            .into_iter()
-            .find(|item| item.branch == "main")
+    /// Opens the output of a finished `:` command, unless another popup
+    /// took over meanwhile.
+    fn show_git_output(&mut self) {
+        let Some((title, lines)) = self.git_output.lock().ok().and_then(|mut slot| slot.take())
         else {
-            self.status = "Cannot create from main: no 'main' worktree is available.".to_string();
             return;
         };
+        if matches!(self.mode, Mode::Normal) {
+            self.mode = Mode::Output {
+                title,
+                lines,
+                scroll: 0,
+            };
+        }
+    }
 
-        self.mode = Mode::Input {
-            prompt: "New branch name:".to_string(),
-            value: String::new(),
-            action: InputAction::NewWorktree {
-                base_branch: "main".to_string(),
-                pull_before_create: Some(main_item.path),
-            },
+    fn action_open_deleted(&mut self) {
+        let branches = match trash::list_deleted_branches(&self.repo_root) {
+            Ok(branches) => branches,
+            Err(err) => {
+                self.status = format!("Cannot list deleted branches: {}", describe_error(&err));
+                return;
+            }
+        };
+        if branches.is_empty() {
+            self.status = "No recently deleted branches.".to_string();
+            return;
+        }
+        self.mode = Mode::Deleted {
+            branches,
+            selected: 0,
         };
     }
 
-    fn action_new_worktree_from_selected(&mut self) {
-        if self.busy {
-            self.status = "Another operation is in progress.".to_string();
+    fn action_cherry_pick(&mut self) {
+        let Some(current) = self.current_item() else {
+            self.status = "No worktrees available.".to_string();
+            return;
+        };
+        let Some(ref_name) = current.ref_name.clone() else {
+            self.status = "Cannot cherry-pick from a detached worktree.".to_string();
+            return;
+        };
+
+        // Commits not on the default branch; on the default branch itself,
+        // its latest commits.
+        let range = if current.branch == self.default_branch {
+            ref_name
+        } else {
+            let base = services::ahead_behind_base(&self.repo_root, &self.default_branch)
+                .unwrap_or_else(|_| self.default_branch.clone());
+            format!("{base}..{ref_name}")
+        };
+        let commits = match git_ops::log_commits(&self.repo_root, &range, CHERRY_PICK_LIMIT) {
+            Ok(commits) => commits,
+            Err(err) => {
+                self.status = format!("Cannot list commits: {}", describe_error(&err));
+                return;
+            }
+        };
+        if commits.is_empty() {
+            self.status = format!(
+                "{} has no commits that are not on {}.",
+                current.branch, self.default_branch
+            );
             return;
         }
 
+        self.mode = Mode::CherryPick {
+            source: current.branch,
+            marked: vec![false; commits.len()],
+            commits,
+            selected: 0,
+            targets: None,
+        };
+    }
+
+    fn action_new_worktree_from_selected(&mut self) {
         let Some(current) = self.current_item() else {
             self.status = "No worktrees available.".to_string();
             return;
@@ -740,13 +2652,31 @@ impl TuiApp {
         };
     }
 
+    fn action_open_doctor(&mut self) {
+        if self.reject_if_conflicting(&OpScope::Repo) {
+            return;
+        }
+
+        match services::health_check(&self.repo_root) {
+            Ok(report) if !report.has_issues() => {
+                self.status = "No setup issues found.".to_string();
+            }
+            Ok(report) => {
+                self.mode = Mode::Doctor {
+                    report: Box::new(report),
+                    plan: RepairPlan::default(),
+                    selected: 0,
+                };
+            }
+            Err(err) => {
+                self.status = format!("Health check failed: {err}");
+            }
+        }
+    }
+
     fn current_item(&self) -> Option<WorktreeInfo> {
         let selected = self.table_state.selected()?;
-        let guard = match self.items.lock() {
-            Ok(guard) => guard,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-        guard.get(selected).cloned()
+        self.visible_items().into_iter().nth(selected)
     }
 
     fn snapshot_items(&self) -> Vec<WorktreeInfo> {
@@ -757,8 +2687,33 @@ impl TuiApp {
         guard.clone()
     }
 
+    /// The worktrees shown in the table, in table order.
+    fn visible_items(&self) -> Vec<WorktreeInfo> {
+        let mut items = self.snapshot_items();
+        items.retain(|item| services::matches_filter(item, &self.filter));
+        if let Mode::Search { query } = &self.mode {
+            items.retain(|item| {
+                services::fuzzy_match(query, &item.branch)
+                    || item
+                        .note
+                        .as_deref()
+                        .is_some_and(|note| services::fuzzy_match(query, note))
+            });
+        }
+        items
+    }
+
+    /// Selects the row of `path`, or the first row if it isn't visible.
+    fn select_visible(&mut self, path: Option<&Path>) {
+        let items = self.visible_items();
+        let index = path
+            .and_then(|path| items.iter().position(|item| item.path == path))
+            .or((!items.is_empty()).then_some(0));
+        self.table_state.select(index);
+    }
+
     fn select_prev(&mut self) {
-        let len = self.snapshot_items().len();
+        let len = self.visible_items().len();
         if len == 0 {
             self.table_state.select(None);
             return;
@@ -770,7 +2725,7 @@ impl TuiApp {
     }
 
     fn select_next(&mut self) {
-        let len = self.snapshot_items().len();
+        let len = self.visible_items().len();
         if len == 0 {
             self.table_state.select(None);
             return;
@@ -784,7 +2739,8 @@ impl TuiApp {
     fn reload_items(&mut self, selected_branch: Option<&str>) -> Result<()> {
         self.default_branch = git_ops::get_default_branch(&self.repo_root);
         let mut new_items = services::load_worktrees(&self.repo_root)?;
-        if !self.gh_available {
+        self.details.clear();
+        if !self.forge_available {
             for item in &mut new_items {
                 item.pr_validated = true;
                 item.checks_validated = true;
@@ -798,6 +2754,7 @@ impl TuiApp {
             };
             *guard = new_items.clone();
         }
+        new_items.retain(|item| services::matches_filter(item, &self.filter));
 
         if new_items.is_empty() {
             self.table_state.select(None);
@@ -816,7 +2773,7 @@ impl TuiApp {
     }
 
     fn start_refresh(&mut self, manual: bool) {
-        if self.refresh_running.swap(true, Ordering::SeqCst) {
+        if self.refresh_task.is_some() {
             if manual {
                 self.status = "Refresh already in progress...".to_string();
             }
@@ -829,38 +2786,134 @@ impl TuiApp {
 
         let repo_root = self.repo_root.clone();
         let items = Arc::clone(&self.items);
-        let gh_available = self.gh_available;
-        let refresh_running = Arc::clone(&self.refresh_running);
-        let (tx, rx) = mpsc::channel();
-        self.refresh_rx = Some(rx);
-
-        thread::spawn(move || {
-            let snapshot = {
-                let guard = match items.lock() {
+        let forge_available = self.forge_available;
+
+        // Every column this refresh recomputes is pending until its row is
+        // merged back, so the table can mark it.
+        let snapshot = {
+            let mut guard = match self.items.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            mark_refresh_columns_pending(&mut guard, forge_available);
+            guard.clone()
+        };
+        self.pending_checks = snapshot
+            .iter()
+            .filter(|item| item.checks_state.as_deref() == Some("pend"))
+            .map(|item| item.branch.clone())
+            .collect();
+
+        let task = self
+            .tasks
+            .spawn("refresh", tasks::Progress::default(), move |context| {
+                let mut refreshed = snapshot;
+                let mut merge_row = |item: &WorktreeInfo| {
+                    let mut guard = match items.lock() {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    merge_refreshed_items(&mut guard, std::slice::from_ref(item));
+                };
+                let result = services::refresh_from_upstream(
+                    &repo_root,
+                    &mut refreshed,
+                    forge_available,
+                    &|| context.is_cancelled(),
+                    &mut merge_row,
+                )
+                .err()
+                .map(|err| err.to_string());
+
+                let mut guard = match items.lock() {
                     Ok(guard) => guard,
                     Err(poisoned) => poisoned.into_inner(),
                 };
-                guard.clone()
-            };
+                merge_refreshed_items(&mut guard, &refreshed);
+                result
+            });
+        self.refresh_task = Some(task);
+    }
 
-            let mut refreshed = snapshot;
-            let result = services::refresh_from_upstream(&repo_root, &mut refreshed, gh_available)
-                .err()
-                .map(|err| err.to_string());
+    /// Notifies about a creation or restore only when it runs hooks, which
+    /// is what can make it slow.
+    fn hooks_notification(&self) -> Option<NotificationEvent> {
+        hooks::get_post_worktree_creation_hooks(&self.repo_root)
+            .is_ok_and(|hooks| !hooks.is_empty())
+            .then_some(NotificationEvent::Hooks)
+    }
 
-            let mut guard = match items.lock() {
-                Ok(guard) => guard,
-                Err(poisoned) => poisoned.into_inner(),
-            };
-            merge_refreshed_items(&mut guard, &refreshed);
+    /// Sends a desktop notification with `message` if `event` has them
+    /// enabled and the terminal is unfocused.
+    fn notify(&mut self, event: NotificationEvent, message: &str) {
+        if self.focused || !self.notifications.contains(&event) {
+            return;
+        }
+        if let Err(err) = send_notification(message) {
+            self.status = format!("{}  ·  Notification failed: {err}", self.status);
+        }
+    }
 
-            let _ = tx.send(result);
-            refresh_running.store(false, Ordering::SeqCst);
-        });
+    /// Notifies about PRs whose checks were pending before the refresh that
+    /// just finished and are done now.
+    fn notify_finished_checks(&mut self) {
+        let pending = std::mem::take(&mut self.pending_checks);
+        if pending.is_empty() {
+            return;
+        }
+        let finished: Vec<String> = self
+            .snapshot_items()
+            .iter()
+            .filter(|item| pending.contains(&item.branch))
+            .filter_map(|item| {
+                let outcome = match item.checks_state.as_deref()? {
+                    "ok" => "passed",
+                    "fail" => "failed",
+                    _ => return None,
+                };
+                let number = item.pr_number?;
+                Some(format!("Checks {outcome} on {} (#{number}).", item.branch))
+            })
+            .collect();
+        for message in finished {
+            self.notify(NotificationEvent::Checks, &message);
+        }
+    }
+
+    /// Runs `action` in the background, or queues it behind the running
+    /// operation. Operations whose scope overlaps a running or queued one are
+    /// rejected.
+    #[allow(clippy::too_many_arguments)]
+    fn start_operation<F, N>(
+        &mut self,
+        scope: OpScope,
+        spinner_message: String,
+        success_message: String,
+        failure_prefix: String,
+        selected_branch_after: Option<String>,
+        post_success_action: PostSuccessAction,
+        action: F,
+    ) where
+        F: FnOnce() -> Result<N> + Send + 'static,
+        N: OperationNote,
+    {
+        self.start_cancellable_operation(
+            scope,
+            spinner_message,
+            success_message,
+            failure_prefix,
+            selected_branch_after,
+            post_success_action,
+            move |_| action(),
+        );
     }
 
-    fn start_operation<F>(
+    /// [`Self::start_operation`] for work of several steps, which checks its
+    /// context between them so Esc can stop it.
+    #[allow(clippy::too_many_arguments)]
+    fn start_cancellable_operation<F, N>(
         &mut self,
+        scope: OpScope,
         spinner_message: String,
         success_message: String,
         failure_prefix: String,
@@ -868,56 +2921,173 @@ impl TuiApp {
         post_success_action: PostSuccessAction,
         action: F,
     ) where
-        F: FnOnce() -> Result<()> + Send + 'static,
+        F: FnOnce(&tasks::TaskContext) -> Result<N> + Send + 'static,
+        N: OperationNote,
     {
+        let notification = self.next_notification.take();
+        if self.reject_if_conflicting(&scope) {
+            return;
+        }
+
+        let operation = QueuedOperation {
+            scope,
+            spinner_message,
+            success_message,
+            failure_prefix,
+            selected_branch_after,
+            post_success_action,
+            notification,
+            action: Box::new(move |context| action(context).map(OperationNote::into_note)),
+        };
+        if self.busy {
+            self.status = format!("Queued: {}.", operation.spinner_message);
+            self.op_queue.push_back(operation);
+            return;
+        }
+
+        self.status.clear();
+        self.run_operation(operation);
+    }
+
+    /// The spinner message of a running or queued operation overlapping
+    /// `scope`, if any.
+    fn conflicting_operation(&self, scope: &OpScope) -> Option<&str> {
+        self.running_op
+            .iter()
+            .map(|(running, message)| (running, message.as_str()))
+            .chain(
+                self.op_queue
+                    .iter()
+                    .map(|queued| (&queued.scope, queued.spinner_message.as_str())),
+            )
+            .find(|(pending, _)| pending.overlaps(scope))
+            .map(|(_, message)| message)
+    }
+
+    /// Sets the status and returns true when an operation on `scope` would
+    /// conflict with a pending one.
+    fn reject_if_conflicting(&mut self, scope: &OpScope) -> bool {
+        let Some(message) = self.conflicting_operation(scope) else {
+            return false;
+        };
+        self.status = format!("Conflicts with a pending operation ({message}).");
+        true
+    }
+
+    fn start_next_operation(&mut self) {
         if self.busy {
-            self.status = "Another operation is in progress.".to_string();
             return;
         }
+        if let Some(operation) = self.op_queue.pop_front() {
+            self.run_operation(operation);
+        }
+    }
+
+    fn run_operation(&mut self, operation: QueuedOperation) {
+        let QueuedOperation {
+            scope,
+            spinner_message,
+            success_message,
+            failure_prefix,
+            selected_branch_after,
+            post_success_action,
+            notification,
+            action,
+        } = operation;
 
         self.busy = true;
         self.spinner_index = 0;
-        self.spinner_message = Some(spinner_message);
-
-        let (tx, rx) = mpsc::channel();
-        self.op_rx = Some(rx);
-
-        thread::spawn(move || {
-            let result = match action() {
-                Ok(()) => OpResult {
-                    status: success_message,
-                    succeeded: true,
-                    post_success_action,
-                    selected_branch_after,
-                },
-                Err(err) => OpResult {
-                    status: format!("{failure_prefix}: {err}"),
-                    succeeded: false,
-                    post_success_action: PostSuccessAction::None,
-                    selected_branch_after: None,
-                },
-            };
+        self.spinner_message = Some(spinner_message.clone());
+        self.running_op = Some((scope, spinner_message.clone()));
+        self.op_progress.clear();
 
-            let _ = tx.send(result);
-        });
+        let repo_root = self.repo_root.clone();
+        let task = self
+            .tasks
+            .spawn("operation", self.op_progress.clone(), move |context| {
+                let locked = repo_lock::acquire(&repo_root);
+                match locked.and_then(|_lock| {
+                    context.check()?;
+                    action(context)
+                }) {
+                    Ok(note) => OpResult {
+                        status: match note {
+                            Some(note) => format!("{success_message} Note: {note}"),
+                            None => success_message,
+                        },
+                        succeeded: true,
+                        post_success_action,
+                        selected_branch_after,
+                        notification,
+                    },
+                    // Steps done before the cancellation stay done, so the
+                    // table is reloaded to show them.
+                    Err(_) if context.is_cancelled() => OpResult {
+                        status: format!("Cancelled: {spinner_message}."),
+                        succeeded: false,
+                        post_success_action: match post_success_action {
+                            PostSuccessAction::None => PostSuccessAction::None,
+                            _ => PostSuccessAction::ReloadOnly,
+                        },
+                        selected_branch_after: None,
+                        notification: None,
+                    },
+                    Err(err) => OpResult {
+                        status: format!("{failure_prefix}: {}", describe_error(&err)),
+                        succeeded: false,
+                        post_success_action: PostSuccessAction::None,
+                        selected_branch_after: None,
+                        notification,
+                    },
+                }
+            });
+        self.op_task = Some(task);
     }
 
     fn status_line(&self) -> String {
         let spinner = SPINNER[self.spinner_index % SPINNER.len()];
 
         if let Some(message) = &self.spinner_message {
-            return format!("{message} {spinner}");
+            let mut line = format!("{message} {spinner}");
+            if let Some(progress) = self.op_task.as_ref().and_then(tasks::Task::progress) {
+                line.push_str(&format!("  {progress}"));
+            }
+            if !self.op_queue.is_empty() {
+                line.push_str(&format!("  [{} queued]", self.op_queue.len()));
+            }
+            // Results of earlier operations and rejected keys stay visible.
+            if !self.status.is_empty() {
+                line.push_str(&format!("  ·  {}", self.status));
+            }
+            return line;
+        }
+
+        if let Mode::Search { query } = &self.mode {
+            if self.visible_items().is_empty() && !query.trim().is_empty() {
+                return format!(
+                    "/{query}  ·  no match; Enter: create worktree '{}' from main",
+                    query.trim()
+                );
+            }
+            return format!("/{query}  ·  Enter: open  ·  Esc: cancel");
         }
 
-        if self.refresh_running.load(Ordering::SeqCst) {
-            return format!("Refreshing {spinner}");
+        if self.refresh_task.is_some() {
+            return "Refreshing...".to_string();
         }
 
         self.status.clone()
     }
 
     fn repo_line(&self) -> String {
-        format!("Repo: {}", self.repo_root.display())
+        let mut line = format!("Repo: {}", services::tilde_path(&self.repo_root));
+        if !self.filter.is_empty() {
+            line.push_str(&format!("  ·  filter: {}", self.filter.describe()));
+        }
+        if git_ops::dry_run() {
+            line.push_str("  ·  DRY RUN");
+        }
+        line
     }
 
     fn draw(&mut self, frame: &mut ratatui::Frame<'_>) {
@@ -930,8 +3100,10 @@ impl TuiApp {
                 Constraint::Length(1),
                 Constraint::Length(1),
                 Constraint::Min(1),
+                Constraint::Length(DETAIL_PANE_HEIGHT),
             ])
             .split(area);
+        self.table_area = chunks[4];
 
         frame.render_widget(Paragraph::new(self.repo_line()), chunks[0]);
         frame.render_widget(Paragraph::new(COMMAND_BAR), chunks[1]);
@@ -942,13 +3114,54 @@ impl TuiApp {
             chunks[3],
         );
 
-        let items = self.snapshot_items();
+        let items = self.visible_items();
+        // A refresh can filter out the selected row.
+        if let Some(selected) = self.table_state.selected() {
+            if selected >= items.len() {
+                self.table_state.select(items.len().checked_sub(1));
+            }
+        }
+        // Cells still waiting on the running refresh get a spinner.
+        let pending_marker = self
+            .refresh_task
+            .is_some()
+            .then(|| SPINNER[self.spinner_index % SPINNER.len()]);
+        // Only shown when some worktree isn't at the path its branch implies.
+        let deviates = |item: &WorktreeInfo| {
+            services::path_deviates(&self.repo_root, &self.worktrees_dir, item)
+        };
+        let show_path = items.iter().any(deviates);
         let rows = items.iter().map(|item| {
-            let values = format_row(item, &self.default_branch);
+            let stale = services::is_stale(item, self.stale_after_days);
+            let mut values = format_row(item, &self.default_branch);
+            if show_path {
+                let path = if deviates(item) {
+                    services::display_path(&self.repo_root, &item.path)
+                } else {
+                    String::new()
+                };
+                values.push((path, false));
+            }
+            if self.show_description {
+                let description = item.description.as_deref().unwrap_or_default();
+                values.push((first_line(description).to_string(), false));
+            }
+            if self.show_pr_title {
+                values.push((
+                    item.pr_title.clone().unwrap_or_default(),
+                    !item.pr_validated,
+                ));
+            }
             let cells: Vec<Cell<'_>> = values
                 .into_iter()
                 .enumerate()
-                .map(|(column_index, (text, cached))| {
+                .map(|(column_index, (mut text, cached))| {
+                    if let Some(marker) = pending_marker.filter(|_| cached) {
+                        if !text.is_empty() {
+                            text.push(' ');
+                        }
+                        text.push(marker);
+                    }
                     let clickable_pr = column_index == PR_COLUMN_INDEX
                         && item.pr_url.is_some()
                         && !text.is_empty();
@@ -957,6 +3170,14 @@ impl TuiApp {
                         style = style.fg(Color::DarkGray);
                     } else if clickable_pr {
                         style = style.fg(Color::Cyan);
+                    } else if (item.in_progress.is_some() || item.checkout.is_some())
+                        && column_index == BRANCH_COLUMN_INDEX
+                    {
+                        style = style.fg(Color::Magenta);
+                    } else if stale && column_index == LAST_COMMIT_COLUMN_INDEX {
+                        style = style.fg(Color::Yellow);
+                    } else if item.is_diverged() && column_index == PULL_PUSH_COLUMN_INDEX {
+                        style = style.fg(Color::LightRed);
                     }
                     if clickable_pr {
                         style = style.add_modifier(Modifier::UNDERLINED);
@@ -968,37 +3189,73 @@ impl TuiApp {
             Row::new(cells)
         });
 
-        let table = Table::new(
-            rows,
-            [
-                Constraint::Length(TABLE_COLUMN_WIDTHS[0]),
-                Constraint::Length(TABLE_COLUMN_WIDTHS[1]),
-                Constraint::Length(TABLE_COLUMN_WIDTHS[2]),
-                Constraint::Length(TABLE_COLUMN_WIDTHS[3]),
-                Constraint::Length(TABLE_COLUMN_WIDTHS[4]),
-                Constraint::Length(TABLE_COLUMN_WIDTHS[5]),
-            ],
-        )
-        .header(
-            Row::new(HEADERS)
-                .style(Style::default().add_modifier(Modifier::BOLD))
-                .bottom_margin(0),
-        )
-        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-        .highlight_symbol(" > ")
-        .block(Block::default().borders(Borders::TOP));
+        let mut widths: Vec<Constraint> = table_column_widths()
+            .iter()
+            .map(|width| Constraint::Length(*width))
+            .collect();
+        let mut headers = HEADERS.to_vec();
+        if show_path {
+            widths.push(Constraint::Length(PATH_COLUMN_WIDTH));
+            headers.push(PATH_HEADER);
+        }
+        if self.show_description {
+            widths.push(Constraint::Length(DESCRIPTION_COLUMN_WIDTH));
+            headers.push(DESCRIPTION_HEADER);
+        }
+        if self.show_pr_title {
+            widths.push(Constraint::Min(20));
+            headers.push(PR_TITLE_HEADER);
+        }
+
+        let table = Table::new(rows, widths)
+            .header(
+                Row::new(headers)
+                    .style(Style::default().add_modifier(Modifier::BOLD))
+                    .bottom_margin(0),
+            )
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol(" > ")
+            .block(Block::default().borders(Borders::TOP));
 
         frame.render_stateful_widget(table, chunks[4], &mut self.table_state);
 
+        let detail = self
+            .table_state
+            .selected()
+            .and_then(|index| items.get(index))
+            .map(|item| {
+                Text::from(vec![
+                    Line::from(format_pr_detail(item)),
+                    Line::from(format_worktree_detail(item, self.details.get(&item.path))),
+                    Line::styled(format_data_ages(item), Style::default().fg(Color::DarkGray)),
+                    Line::styled(
+                        format_path_detail(item),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(detail).block(Block::default().borders(Borders::TOP)),
+            chunks[5],
+        );
+
         match &self.mode {
-            Mode::Normal => {}
-            Mode::Confirm { prompt, .. } => {
+            Mode::Normal | Mode::Search { .. } => {}
+            Mode::Confirm { prompt, action } => {
                 let popup = centered_rect(70, 22, area);
                 frame.render_widget(Clear, popup);
+                let hint = match action {
+                    ConfirmAction::Delete { dirty: true, .. } => {
+                        "Press y to confirm, s to snapshot the changes to a backup ref first, n or Esc to cancel."
+                    }
+                    ConfirmAction::ImportExternal { .. } => "Press y to import it, n or Esc to skip.",
+                    _ => "Press y to confirm, n or Esc to cancel.",
+                };
                 let content = vec![
                     Line::from(prompt.as_str()),
                     Line::from(""),
-                    Line::from("Press y to confirm, n or Esc to cancel."),
+                    Line::from(hint),
                 ];
                 let widget = Paragraph::new(Text::from(content))
                     .block(Block::default().borders(Borders::ALL).title("Confirm"));
@@ -1022,14 +3279,268 @@ impl TuiApp {
                 let cursor_y = popup.y + 3;
                 frame.set_cursor_position((cursor_x, cursor_y));
             }
+            Mode::Doctor {
+                report,
+                plan,
+                selected,
+            } => {
+                let popup = centered_rect(80, 60, area);
+                frame.render_widget(Clear, popup);
+                let mut content = Vec::new();
+                for (idx, fix) in report.fixes().iter().enumerate() {
+                    let mark = if plan.is_enabled(fix) { "x" } else { " " };
+                    let text = format!(
+                        "[{mark}] {}",
+                        services::describe_repair_fix(&self.repo_root, report, plan, fix)
+                    );
+                    let mut line = Line::from(text);
+                    if idx == *selected {
+                        line = line.style(Style::default().add_modifier(Modifier::REVERSED));
+                    }
+                    content.push(line);
+                }
+                if !report.unrecoverable_reasons.is_empty() {
+                    content.push(Line::from(""));
+                    content.push(
+                        Line::from("Unrecoverable issues:").style(Style::default().fg(Color::Red)),
+                    );
+                    for reason in &report.unrecoverable_reasons {
+                        content.push(Line::from(format!("  {reason}")));
+                    }
+                }
+                content.push(Line::from(""));
+                content.push(Line::from(
+                    "Space: toggle  |  a: adopt/delete orphan  |  i: never create worktree  |  Enter: apply  |  Esc: close",
+                ));
+                let widget = Paragraph::new(Text::from(content))
+                    .block(Block::default().borders(Borders::ALL).title("Doctor"));
+                frame.render_widget(widget, popup);
+            }
+            Mode::Reconcile {
+                branch, pull, push, ..
+            } => {
+                let popup = centered_rect(70, 30, area);
+                frame.render_widget(Clear, popup);
+                let content = vec![
+                    Line::from(format!(
+                        "{branch} and its upstream have diverged: {} only upstream, {} only here.",
+                        commit_count(*pull),
+                        commit_count(*push)
+                    )),
+                    Line::from(""),
+                    Line::from("r: rebase onto the upstream, keeping both"),
+                    Line::from("f: force-push, replacing the upstream commits"),
+                    Line::from(""),
+                    Line::from("Esc: cancel"),
+                ];
+                let widget = Paragraph::new(Text::from(content))
+                    .block(Block::default().borders(Borders::ALL).title("Reconcile"));
+                frame.render_widget(widget, popup);
+            }
+            Mode::Output {
+                title,
+                lines,
+                scroll,
+            } => {
+                let popup = centered_rect(80, 70, area);
+                frame.render_widget(Clear, popup);
+                let height = popup.height.saturating_sub(4) as usize;
+                let skip = (*scroll).min(lines.len().saturating_sub(height));
+                let mut content: Vec<Line> = lines
+                    .iter()
+                    .skip(skip)
+                    .take(height)
+                    .map(|line| Line::from(line.as_str()))
+                    .collect();
+                content.push(Line::from(""));
+                content.push(Line::from("Up/Down/PgUp/PgDn: scroll  |  Esc: close"));
+                let widget = Paragraph::new(Text::from(content))
+                    .block(Block::default().borders(Borders::ALL).title(title.as_str()));
+                frame.render_widget(widget, popup);
+            }
+            Mode::UpstreamGone { branch, .. } => {
+                let popup = centered_rect(70, 30, area);
+                frame.render_widget(Clear, popup);
+                let content = vec![
+                    Line::from(format!(
+                        "The upstream of {branch} was deleted on the remote."
+                    )),
+                    Line::from(""),
+                    Line::from("d: delete the worktree and branch"),
+                    Line::from("p: push the branch again, recreating the upstream"),
+                    Line::from(""),
+                    Line::from("Esc: cancel"),
+                ];
+                let widget = Paragraph::new(Text::from(content)).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Remote deleted"),
+                );
+                frame.render_widget(widget, popup);
+            }
+            Mode::Compare { comparison, scroll } => {
+                let popup = centered_rect(80, 70, area);
+                frame.render_widget(Clear, popup);
+                let rows = compare_lines(comparison);
+                let height = popup.height.saturating_sub(4) as usize;
+                // Stop once the last line is at the bottom.
+                let skip = (*scroll).min(rows.len().saturating_sub(height));
+                let mut content: Vec<Line> = rows
+                    .into_iter()
+                    .skip(skip)
+                    .take(height)
+                    .map(Line::from)
+                    .collect();
+                content.push(Line::from(""));
+                content.push(Line::from("Up/Down/PgUp/PgDn: scroll  |  Esc: close"));
+                let widget = Paragraph::new(Text::from(content)).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("{} vs {}", comparison.branch, comparison.other)),
+                );
+                frame.render_widget(widget, popup);
+            }
+            Mode::Deleted { branches, selected } => {
+                let popup = centered_rect(80, 60, area);
+                frame.render_widget(Clear, popup);
+                // Keep the selected row in view.
+                let height = popup.height.saturating_sub(4) as usize;
+                let skip = (*selected + 1).saturating_sub(height);
+                let mut content = Vec::new();
+                for (idx, deleted) in branches.iter().enumerate().skip(skip).take(height) {
+                    let trashed = if deleted.trash_id.is_some() {
+                        "  (in the trash)"
+                    } else {
+                        ""
+                    };
+                    let mut line = Line::from(format!(
+                        "{}  {}  deleted {}{trashed}",
+                        deleted.branch,
+                        &deleted.head[..deleted.head.len().min(7)],
+                        format_time(deleted.deleted_ts)
+                    ));
+                    if idx == *selected {
+                        line = line.style(Style::default().add_modifier(Modifier::REVERSED));
+                    }
+                    content.push(line);
+                }
+                content.push(Line::from(""));
+                content.push(Line::from(
+                    "Enter: restore the branch with a worktree  |  Esc: cancel",
+                ));
+                let widget = Paragraph::new(Text::from(content)).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Recently deleted branches"),
+                );
+                frame.render_widget(widget, popup);
+            }
+            Mode::CherryPick {
+                source,
+                commits,
+                marked,
+                selected,
+                targets,
+            } => {
+                let popup = centered_rect(80, 60, area);
+                frame.render_widget(Clear, popup);
+                let (title, rows, hint) = match targets {
+                    None => (
+                        format!("Cherry-pick from {source}"),
+                        commits
+                            .iter()
+                            .zip(marked)
+                            .map(|(commit, mark)| {
+                                let mark = if *mark { "x" } else { " " };
+                                format!("[{mark}] {} {}", &commit.sha[..7], commit.subject)
+                            })
+                            .collect::<Vec<_>>(),
+                        "Space: mark  |  Enter: choose target  |  Esc: cancel",
+                    ),
+                    Some(targets) => (
+                        format!(
+                            "Cherry-pick {} into",
+                            commit_count(marked.iter().filter(|mark| **mark).count() as i64)
+                        ),
+                        targets.iter().map(|item| item.branch.clone()).collect(),
+                        "Enter: cherry-pick  |  Esc: cancel",
+                    ),
+                };
+                // Keep the selected row in view.
+                let height = popup.height.saturating_sub(4) as usize;
+                let skip = (*selected + 1).saturating_sub(height);
+                let mut content = Vec::new();
+                for (idx, row) in rows.into_iter().enumerate().skip(skip).take(height) {
+                    let mut line = Line::from(row);
+                    if idx == *selected {
+                        line = line.style(Style::default().add_modifier(Modifier::REVERSED));
+                    }
+                    content.push(line);
+                }
+                content.push(Line::from(""));
+                content.push(Line::from(hint));
+                let widget = Paragraph::new(Text::from(content))
+                    .block(Block::default().borders(Borders::ALL).title(title));
+                frame.render_widget(widget, popup);
+            }
         }
     }
 }
 
+/// The lines of the `v` popup: each side's own commits, then the diff stat.
+fn compare_lines(comparison: &BranchComparison) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (name, commits) in [
+        (&comparison.branch, &comparison.only_branch),
+        (&comparison.other, &comparison.only_other),
+    ] {
+        let count = if commits.len() == services::COMPARE_LIMIT {
+            format!("{}+ commits", commits.len())
+        } else {
+            commit_count(commits.len() as i64)
+        };
+        lines.push(format!("{count} only on {name}:"));
+        lines.extend(
+            commits
+                .iter()
+                .map(|commit| format!("  {} {}", &commit.sha[..7], commit.subject)),
+        );
+        lines.push(String::new());
+    }
+    if comparison.stat.is_empty() {
+        lines.push(format!(
+            "No file changes since {} forked from {}.",
+            comparison.branch, comparison.other
+        ));
+    } else {
+        lines.push(format!(
+            "Changed since {} forked from {}:",
+            comparison.branch, comparison.other
+        ));
+        lines.extend(comparison.stat.iter().map(|line| format!("  {line}")));
+    }
+    lines
+}
+
+/// The next terminal event, or `None` when there is none within `timeout`.
+fn terminal_event(timeout: Duration) -> io::Result<Option<Event>> {
+    if event::poll(timeout)? {
+        event::read().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
 fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stderr>>> {
     enable_raw_mode()?;
     let mut stderr = io::stderr();
-    execute!(stderr, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stderr,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste,
+        EnableFocusChange
+    )?;
     let backend = CrosstermBackend::new(stderr);
     let terminal = Terminal::new(backend)?;
     Ok(terminal)
@@ -1039,6 +3550,8 @@ fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stderr>>) -> Result
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
+        DisableFocusChange,
+        DisableBracketedPaste,
         DisableMouseCapture,
         LeaveAlternateScreen
     )?;
@@ -1046,14 +3559,86 @@ fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stderr>>) -> Result
     Ok(())
 }
 
+/// `err` followed by the next step to take, for errors with a known cause.
+/// One line for the status bar. Known failures keep the first line of git's
+/// stderr and get their next step appended; other errors are joined into one
+/// line.
+fn describe_error(err: &anyhow::Error) -> String {
+    let message = err.to_string();
+    let mut lines = message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    match errors::classify(err) {
+        Some(known) => format!(
+            "{} → {}",
+            lines.next().unwrap_or_default(),
+            known.suggestion()
+        ),
+        None => lines.collect::<Vec<_>>().join(" "),
+    }
+}
+
+/// The reverse of `services::split_command_line` for display, quoting arguments with
+/// whitespace.
+fn join_command_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("\"{}\"", arg.replace('"', "\\\""))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn commit_count(count: i64) -> String {
+    if count == 1 {
+        "1 commit".to_string()
+    } else {
+        format!("{count} commits")
+    }
+}
+
+/// Moves the worktree to the trash (see `gw restore`) and deletes its branch.
+fn delete_worktree_and_branch(repo_root: &Path, path: &Path, ref_name: &str) -> Result<()> {
+    trash::trash_worktree(repo_root, path, ref_name)?;
+    services::forget_worktree_path(repo_root, ref_name);
+    Ok(())
+}
+
+fn merged_cleanup_banner(
+    items: &[WorktreeInfo],
+    repo_root: &Path,
+    default_branch: &str,
+) -> Option<String> {
+    let merged = services::merged_cleanup_candidates(items, repo_root, default_branch);
+    if merged.is_empty() {
+        return None;
+    }
+    let branches: Vec<&str> = merged.iter().map(|item| item.branch.as_str()).collect();
+    Some(format!(
+        "{} merged branch(es) ({}): {MERGED_BANNER_SUFFIX}",
+        merged.len(),
+        branches.join(", ")
+    ))
+}
+
+fn table_column_widths() -> [u16; 6] {
+    let mut widths = TABLE_COLUMN_WIDTHS;
+    if time_format() == TimeFormat::DateTime {
+        widths[LAST_COMMIT_COLUMN_INDEX] = DATETIME_COLUMN_WIDTH;
+    }
+    widths
+}
+
 fn is_pr_column(column: u16) -> bool {
+    let widths = table_column_widths();
     let left_offset = HIGHLIGHT_SYMBOL_WIDTH;
-    let pr_start = left_offset
-        + TABLE_COLUMN_WIDTHS
-            .iter()
-            .take(PR_COLUMN_INDEX)
-            .sum::<u16>();
-    let pr_end = pr_start + TABLE_COLUMN_WIDTHS[PR_COLUMN_INDEX];
+    let pr_start = left_offset + widths.iter().take(PR_COLUMN_INDEX).sum::<u16>();
+    let pr_end = pr_start + widths[PR_COLUMN_INDEX];
     column >= pr_start && column < pr_end
 }
 
@@ -1077,6 +3662,28 @@ fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
+/// How [`format_time`] shows timestamps for the rest of the process, from
+/// the `timeFormat` setting.
+static TIME_FORMAT: OnceLock<TimeFormat> = OnceLock::new();
+
+pub fn set_time_format(format: TimeFormat) {
+    let _ = TIME_FORMAT.set(format);
+}
+
+fn time_format() -> TimeFormat {
+    TIME_FORMAT.get().copied().unwrap_or_default()
+}
+
+/// `ts` as the `timeFormat` setting asks: relative to now, or as a local
+/// date with or without the time of day.
+pub fn format_time(ts: i64) -> String {
+    match time_format() {
+        TimeFormat::Relative => relative_time(ts),
+        TimeFormat::Date => calendar_time(ts, false),
+        TimeFormat::DateTime => calendar_time(ts, true),
+    }
+}
+
 fn relative_time(ts: i64) -> String {
     if ts <= 0 {
         return "unknown".to_string();
@@ -1089,24 +3696,83 @@ fn relative_time(ts: i64) -> String {
     let delta = (now - ts).max(0);
 
     if delta < 60 {
-        format!("{delta}s ago")
+        return format!("{delta}s ago");
     } else if delta < 3600 {
-        format!("{}m ago", delta / 60)
+        return format!("{}m ago", delta / 60);
     } else if delta < 86_400 {
-        format!("{}h ago", delta / 3600)
+        return format!("{}h ago", delta / 3600);
     } else if delta < 604_800 {
-        format!("{}d ago", delta / 86_400)
-    } else if delta < 2_629_800 {
+        return format!("{}d ago", delta / 86_400);
+    }
+
+    // Whole calendar months between the two local dates, so that e.g.
+    // Jan 31 to Mar 1 is one month and not a 30.4-day approximation.
+    let offset = local_utc_offset();
+    let (then_year, then_month, then_day) = civil_from_days((ts + offset).div_euclid(86_400));
+    let (now_year, now_month, now_day) = civil_from_days((now + offset).div_euclid(86_400));
+    let mut months = (now_year - then_year) * 12 + now_month - then_month;
+    if now_day < then_day {
+        months -= 1;
+    }
+    if months < 1 {
         format!("{}w ago", delta / 604_800)
+    } else if months < 12 {
+        format!("{months}mo ago")
     } else {
-        format!("{}mo ago", delta / 2_629_800)
+        format!("{}y ago", months / 12)
+    }
+}
+
+/// `ts` as a local `YYYY-MM-DD`, with ` HH:MM` when `with_clock` is set.
+fn calendar_time(ts: i64, with_clock: bool) -> String {
+    if ts <= 0 {
+        return "unknown".to_string();
+    }
+
+    let local = ts + local_utc_offset();
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    if !with_clock {
+        return format!("{year:04}-{month:02}-{day:02}");
     }
+    let seconds = local.rem_euclid(86_400);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// Looked up once per process; see [`git_ops::local_utc_offset`].
+fn local_utc_offset() -> i64 {
+    static OFFSET: OnceLock<i64> = OnceLock::new();
+    *OFFSET.get_or_init(git_ops::local_utc_offset)
+}
+
+/// Year, month and day of `days` since 1970-01-01 in the proleptic Gregorian
+/// calendar; the inverse of the day count in [`forge::parse_timestamp`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let m = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * m + 2) / 5 + 1;
+    let month = if m < 10 { m + 3 } else { m - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
 }
 
-fn format_pull_push(item: &WorktreeInfo) -> (String, bool) {
+pub fn format_pull_push(item: &WorktreeInfo) -> (String, bool) {
     let mut pull_push = String::new();
     if item.pr_state.as_deref() == Some("MERGED") {
         pull_push = "merged (remote deleted)".to_string();
+    } else if item.upstream_gone {
+        pull_push = "remote deleted".to_string();
+    } else if item.is_diverged() {
+        // Can't fast-forward; pulling merges or rebases.
+        pull_push = format!("⇕ {}↓ {}↑ diverged", item.pull, item.push);
     } else if item.has_upstream && (item.pull != 0 || item.push != 0) {
         pull_push = format!("{}↓ {}↑", item.pull, item.push);
     }
@@ -1122,7 +3788,7 @@ fn format_pull_push(item: &WorktreeInfo) -> (String, bool) {
     (pull_push, !item.pull_push_validated)
 }
 
-fn format_pr(item: &WorktreeInfo, default_branch: &str) -> (String, bool) {
+pub fn format_pr(item: &WorktreeInfo, default_branch: &str) -> (String, bool) {
     let mut pr = String::new();
     if let Some(number) = item.pr_number {
         let state = item.pr_state.as_deref().unwrap_or("OPEN");
@@ -1130,6 +3796,8 @@ fn format_pr(item: &WorktreeInfo, default_branch: &str) -> (String, bool) {
             pr = format!("#{number} merged (remote deleted)");
         } else if state == "CLOSED" {
             pr = format!("#{number} closed");
+        } else if item.pr_draft {
+            pr = format!("#{number} draft");
         } else {
             pr = format!("#{number}");
         }
@@ -1139,11 +3807,121 @@ fn format_pr(item: &WorktreeInfo, default_branch: &str) -> (String, bool) {
                 pr.push_str(&format!(" -> {base}"));
             }
         }
+
+        if let Some(threads) = item.pr_unresolved_threads.filter(|count| *count > 0) {
+            pr.push_str(&format!(" {threads}💬"));
+        }
     }
 
     (pr, !item.pr_validated)
 }
 
+/// One-line summary of the selected worktree's issue and PR for the detail
+/// pane.
+fn format_pr_detail(item: &WorktreeInfo) -> String {
+    let issue = item
+        .issue
+        .map(|issue| format!("issue #{issue}"))
+        .unwrap_or_default();
+    let Some(number) = item.pr_number else {
+        return issue;
+    };
+
+    let mut detail = if issue.is_empty() {
+        format!("#{number}")
+    } else {
+        format!("{issue}  ·  PR #{number}")
+    };
+    if let Some(title) = &item.pr_title {
+        detail.push_str(&format!(" {title}"));
+    }
+    if let Some(author) = &item.pr_author {
+        detail.push_str(&format!("  ·  @{author}"));
+    }
+    if let Some(created) = item.pr_created_ts {
+        detail.push_str(&format!("  ·  opened {}", format_time(created)));
+    }
+    match item.pr_unresolved_threads {
+        Some(1) => detail.push_str("  ·  1 unresolved thread"),
+        Some(threads) if threads > 1 => {
+            detail.push_str(&format!("  ·  {threads} unresolved threads"));
+        }
+        _ => {}
+    }
+    detail
+}
+
+/// HEAD, upstream, stashes, unfinished operation, last hook run and disk
+/// usage of the selected worktree, e.g. `HEAD 1a2b3c4  ·  upstream
+/// origin/feat  ·  2 stashes  ·  hooks ran 3h ago  ·  12.5 MiB on disk`.
+/// Only the HEAD until `details` are gathered.
+fn format_worktree_detail(item: &WorktreeInfo, details: Option<&WorktreeDetails>) -> String {
+    let mut parts = vec![format!("HEAD {}", short_head(item))];
+    if let Some(description) = &item.description {
+        parts.push(format!("“{}”", first_line(description)));
+    }
+    if let Some(note) = &item.note {
+        parts.push(format!("memo: {note}"));
+    }
+    if let Some(tag) = &item.nearest_tag {
+        parts.push(format!("detached, nearest tag {tag}"));
+    }
+    let Some(details) = details else {
+        parts.push("…".to_string());
+        return parts.join("  ·  ");
+    };
+    match &details.upstream {
+        Some(upstream) => parts.push(format!("upstream {upstream}")),
+        None if item.ref_name.is_some() => parts.push("no upstream".to_string()),
+        None => {}
+    }
+    match details.stashes {
+        0 => {}
+        1 => parts.push("1 stash".to_string()),
+        count => parts.push(format!("{count} stashes")),
+    }
+    if let Some(operation) = item.in_progress {
+        parts.push(format!("{} in progress", operation.name()));
+    }
+    match details.hooks_ran_ts {
+        Some(ts) => parts.push(format!("hooks ran {}", format_time(ts))),
+        None if details.hooks_configured => parts.push("hooks never ran".to_string()),
+        None => {}
+    }
+    parts.push(format!("{} on disk", format_size(details.disk_usage)));
+    parts.join("  ·  ")
+}
+
+/// `12.5 MiB`, `830 B`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// How old each cached data class of the selected worktree is, e.g.
+/// `upstream: 12s old  ·  changes: 12s old  ·  PR data: 4m old`.
+fn format_data_ages(item: &WorktreeInfo) -> String {
+    let age = |ts: Option<i64>| match ts {
+        Some(ts) => relative_time(ts).replace(" ago", " old"),
+        None => "never fetched".to_string(),
+    };
+    format!(
+        "upstream: {}  ·  changes: {}  ·  PR data: {}",
+        age(item.pull_push_updated_ts),
+        age(item.changes_updated_ts),
+        age(item.pr_updated_ts)
+    )
+}
+
 fn format_changes(item: &WorktreeInfo) -> (String, bool) {
     (
         format!("+{} -{}", item.additions, item.deletions),
@@ -1151,16 +3929,64 @@ fn format_changes(item: &WorktreeInfo) -> (String, bool) {
     )
 }
 
+/// The worktree's path, and why it is locked or prunable.
+fn format_path_detail(item: &WorktreeInfo) -> String {
+    let mut detail = format!("path: {}", item.path.display());
+    for (label, reason) in [("locked", &item.locked), ("prunable", &item.prunable)] {
+        match reason.as_deref() {
+            Some("") => detail.push_str(&format!("  ·  {label}")),
+            Some(reason) => detail.push_str(&format!("  ·  {label}: {reason}")),
+            None => {}
+        }
+    }
+    detail
+}
+
+/// What the BRANCH NAME column calls a worktree: its branch, or for a
+/// detached HEAD the review, the tag, or the short SHA and nearest tag, e.g.
+/// `detached 1a2b3c4 (v1.2.0)`.
+fn branch_label(item: &WorktreeInfo) -> String {
+    match (&item.review, &item.tag) {
+        (Some(number), _) => format!("review #{number}"),
+        (None, Some(tag)) => format!("tag {tag}"),
+        (None, None) if item.is_detached() => match &item.nearest_tag {
+            Some(tag) => format!("detached {} ({tag})", short_head(item)),
+            None => format!("detached {}", short_head(item)),
+        },
+        (None, None) => item.branch.clone(),
+    }
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+fn short_head(item: &WorktreeInfo) -> &str {
+    &item.head[..item.head.len().min(7)]
+}
+
 fn format_row(item: &WorktreeInfo, default_branch: &str) -> Vec<(String, bool)> {
     let (pr, pr_cached) = format_pr(item, default_branch);
     let (pull_push, pull_push_cached) = format_pull_push(item);
     let (changes, changes_cached) = format_changes(item);
     let behind = item.behind;
     let ahead = item.ahead;
+    let mut branch = branch_label(item);
+    if let Some(operation) = item.in_progress {
+        branch = format!("{branch} [{}]", operation.badge());
+    }
+    if let Some(checkout) = item.checkout {
+        branch = format!("{branch} [{}]", checkout.badge());
+    }
+    if item.locked.is_some() {
+        branch.push_str(" [locked]");
+    } else if item.prunable.is_some() {
+        branch.push_str(" [prunable]");
+    }
 
     vec![
-        (item.branch.clone(), false),
-        (relative_time(item.last_commit_ts), false),
+        (branch, false),
+        (format_time(item.last_commit_ts), false),
         (pull_push, pull_push_cached),
         (pr, pr_cached),
         (format!("{behind:>6}|{ahead}"), false),
@@ -1168,6 +3994,58 @@ fn format_row(item: &WorktreeInfo, default_branch: &str) -> Vec<(String, bool)>
     ]
 }
 
+/// The table's columns for `items` as Markdown (PR numbers linked) or CSV,
+/// with a header row.
+pub fn export_table(items: &[WorktreeInfo], default_branch: &str, format: TableExport) -> String {
+    let rows = items.iter().map(|item| {
+        let mut cells: Vec<String> = format_row(item, default_branch)
+            .into_iter()
+            .map(|(cell, _)| cell.trim().to_string())
+            .collect();
+        if format == TableExport::Markdown {
+            cells = cells.iter().map(|cell| markdown_cell(cell)).collect();
+            if let (Some(url), Some(number)) = (&item.pr_url, item.pr_number) {
+                cells[3] =
+                    cells[3].replacen(&format!("#{number}"), &format!("[#{number}]({url})"), 1);
+            }
+        }
+        cells
+    });
+
+    let mut out = String::new();
+    match format {
+        TableExport::Markdown => {
+            let headers: Vec<String> = HEADERS.iter().map(|header| markdown_cell(header)).collect();
+            out.push_str(&format!("| {} |\n", headers.join(" | ")));
+            out.push_str(&format!("|{}\n", " --- |".repeat(HEADERS.len())));
+            for cells in rows {
+                out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            }
+        }
+        TableExport::Csv => {
+            let headers: Vec<String> = HEADERS.iter().map(|header| csv_field(header)).collect();
+            out.push_str(&format!("{}\n", headers.join(",")));
+            for cells in rows {
+                let fields: Vec<String> = cells.iter().map(|cell| csv_field(cell)).collect();
+                out.push_str(&format!("{}\n", fields.join(",")));
+            }
+        }
+    }
+    out
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
 fn merge_refreshed_items(current: &mut [WorktreeInfo], refreshed: &[WorktreeInfo]) {
     let refreshed_by_key: HashMap<&str, &WorktreeInfo> = refreshed
         .iter()
@@ -1182,20 +4060,46 @@ fn merge_refreshed_items(current: &mut [WorktreeInfo], refreshed: &[WorktreeInfo
         item.pull = new_item.pull;
         item.push = new_item.push;
         item.pull_push_validated = new_item.pull_push_validated;
+        item.pull_push_updated_ts = new_item.pull_push_updated_ts;
         item.has_upstream = new_item.has_upstream;
+        item.upstream_gone = new_item.upstream_gone;
+        item.behind = new_item.behind;
+        item.ahead = new_item.ahead;
         item.additions = new_item.additions;
         item.deletions = new_item.deletions;
         item.dirty = new_item.dirty;
+        item.in_progress = new_item.in_progress;
+        item.checkout = new_item.checkout;
         item.pr_number = new_item.pr_number;
         item.pr_state = new_item.pr_state.clone();
         item.pr_base = new_item.pr_base.clone();
         item.pr_url = new_item.pr_url.clone();
+        item.pr_draft = new_item.pr_draft;
+        item.pr_review_decision = new_item.pr_review_decision.clone();
+        item.pr_title = new_item.pr_title.clone();
+        item.pr_author = new_item.pr_author.clone();
+        item.pr_created_ts = new_item.pr_created_ts;
+        item.pr_merged_ts = new_item.pr_merged_ts;
+        item.pr_unresolved_threads = new_item.pr_unresolved_threads;
         item.pr_validated = new_item.pr_validated;
+        item.pr_updated_ts = new_item.pr_updated_ts;
         item.checks_passed = new_item.checks_passed;
         item.checks_total = new_item.checks_total;
         item.checks_state = new_item.checks_state.clone();
         item.checks_validated = new_item.checks_validated;
         item.changes_validated = new_item.changes_validated;
+        item.changes_updated_ts = new_item.changes_updated_ts;
+    }
+}
+
+fn mark_refresh_columns_pending(items: &mut [WorktreeInfo], forge_available: bool) {
+    for item in items {
+        item.pull_push_validated = false;
+        item.changes_validated = false;
+        if forge_available {
+            item.pr_validated = false;
+            item.checks_validated = false;
+        }
     }
 }
 
@@ -1208,14 +4112,96 @@ fn mark_refresh_columns_validated(items: &mut [WorktreeInfo]) {
     }
 }
 
+/// Puts `text` on the system clipboard with `pbcopy`, `clip`, or on other
+/// unixes `wl-copy`, `xclip` or `xsel`, whichever is installed.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    use std::io::Write;
+
+    #[cfg(target_os = "macos")]
+    let commands: &[&[&str]] = &[&["pbcopy"]];
+    #[cfg(target_os = "windows")]
+    let commands: &[&[&str]] = &[&["clip"]];
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let commands: &[&[&str]] = &[
+        &["wl-copy"],
+        &["xclip", "-selection", "clipboard"],
+        &["xsel", "--clipboard", "--input"],
+    ];
+
+    let mut failure = None;
+    for command in commands {
+        let child = std::process::Command::new(command[0])
+            .args(&command[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(anyhow!("{}: {err}", command[0])),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        // e.g. wl-copy outside a Wayland session; another one may work.
+        failure = Some(anyhow!("{} exited with status {status}", command[0]));
+    }
+    let names: Vec<&str> = commands.iter().map(|command| command[0]).collect();
+    Err(failure.unwrap_or_else(|| anyhow!("no clipboard command found ({})", names.join(", "))))
+}
+
+/// Shows `message` as a desktop notification titled "gw", through
+/// `osascript` on macOS and `notify-send` on other unixes.
+fn send_notification(message: &str) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let child = {
+        let quote = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        std::process::Command::new("osascript")
+            .arg("-e")
+            .arg(format!(
+                "display notification \"{}\" with title \"gw\"",
+                quote(message)
+            ))
+            .spawn()
+    };
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let child = std::process::Command::new("notify-send")
+        .args(["--app-name=gw", "gw", message])
+        .spawn();
+
+    #[cfg(target_os = "windows")]
+    let child: io::Result<std::process::Child> = Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "not supported on Windows",
+    ));
+
+    let mut child = child.map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => anyhow!("notification command not found"),
+        _ => anyhow!("{err}"),
+    })?;
+    // Reap it without holding up the UI.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
 fn open_url(url: &str) -> Result<()> {
     #[cfg(target_os = "macos")]
     let status = std::process::Command::new("open").arg(url).status()?;
 
     #[cfg(target_os = "windows")]
-    let status = std::process::Command::new("cmd")
-        .args(["/C", "start", "", url])
-        .status()?;
+    let status = {
+        use std::os::windows::process::CommandExt;
+        std::process::Command::new("cmd")
+            .args(["/C", "start", "", url])
+            .creation_flags(crate::logging::CREATE_NO_WINDOW)
+            .status()?
+    };
 
     #[cfg(all(unix, not(target_os = "macos")))]
     let status = std::process::Command::new("xdg-open").arg(url).status()?;
@@ -1227,7 +4213,565 @@ fn open_url(url: &str) -> Result<()> {
     }
 }
 
-pub fn write_selected_path(selected_path: &Path) -> Result<()> {
-    println!("{}", selected_path.display());
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_ops::GitOps;
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::KeyModifiers;
+    use std::cell::RefCell;
+    use std::ffi::OsStr;
+    use std::process::Output;
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    /// Fails every git command, so the app runs without a repository.
+    struct NoGit;
+
+    impl GitOps for NoGit {
+        fn output(
+            &self,
+            _args: &[&str],
+            _cwd: Option<&Path>,
+            _envs: &[(&str, &OsStr)],
+            _limit: Option<Duration>,
+        ) -> io::Result<Option<Output>> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "no git in tests"))
+        }
+    }
+
+    fn item(branch: &str) -> WorktreeInfo {
+        WorktreeInfo {
+            path: PathBuf::from("/repo").join(branch),
+            branch: branch.to_string(),
+            ref_name: Some(branch.to_string()),
+            cache_key: branch.to_string(),
+            pr_validated: true,
+            checks_validated: true,
+            changes_validated: true,
+            pull_push_validated: true,
+            ..WorktreeInfo::default()
+        }
+    }
+
+    fn app(branches: &[&str]) -> TuiApp {
+        TuiApp::new(
+            PathBuf::from("/repo"),
+            branches.iter().map(|branch| item(branch)).collect(),
+            "main".to_string(),
+            None,
+            false,
+            WorktreeFilter::default(),
+        )
+    }
+
+    fn key(code: KeyCode) -> Message {
+        Message::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn done(status: &str, succeeded: bool) -> Message {
+        Message::OperationDone(OpResult {
+            status: status.to_string(),
+            succeeded,
+            post_success_action: PostSuccessAction::None,
+            selected_branch_after: None,
+            notification: None,
+        })
+    }
+
+    fn queued(spinner_message: &str) -> QueuedOperation {
+        QueuedOperation {
+            scope: OpScope::Repo,
+            spinner_message: spinner_message.to_string(),
+            success_message: String::new(),
+            failure_prefix: String::new(),
+            selected_branch_after: None,
+            post_success_action: PostSuccessAction::None,
+            notification: None,
+            action: Box::new(|_| Ok(None)),
+        }
+    }
+
+    #[test]
+    fn finished_operation_shows_its_status_and_starts_the_next_one() {
+        let _fakes = git_ops::lock_fakes();
+        git_ops::set_git_ops(Some(Arc::new(NoGit)));
+        let mut app = app(&["main", "feat"]);
+        app.busy = true;
+        app.running_op = Some((OpScope::Repo, "Pulling".to_string()));
+        app.op_queue.push_back(queued("Pushing"));
+
+        app.update(done("Pulled.", true));
+
+        assert_eq!(app.status, "Pulled.");
+        assert!(matches!(app.mode, Mode::Normal));
+        assert!(app.op_queue.is_empty());
+        assert!(app.busy);
+        assert_eq!(app.spinner_message.as_deref(), Some("Pushing"));
+    }
+
+    #[test]
+    fn failed_or_lost_operations_leave_the_app_idle() {
+        let _fakes = git_ops::lock_fakes();
+        git_ops::set_git_ops(Some(Arc::new(NoGit)));
+        let mut app = app(&["main"]);
+        app.busy = true;
+        app.spinner_message = Some("Pulling".to_string());
+
+        app.update(done("Pull failed: conflict", false));
+        assert_eq!(app.status, "Pull failed: conflict");
+        assert!(!app.busy);
+        assert_eq!(app.spinner_message, None);
+
+        app.busy = true;
+        app.running_op = Some((OpScope::Repo, "Pushing".to_string()));
+        app.update(Message::OperationLost);
+        assert_eq!(app.status, "Operation interrupted.");
+        assert!(!app.busy);
+        assert!(app.running_op.is_none());
+    }
+
+    #[test]
+    fn refresh_results_update_the_status() {
+        let _fakes = git_ops::lock_fakes();
+        git_ops::set_git_ops(Some(Arc::new(NoGit)));
+        let mut app = app(&["main"]);
+        app.refresh_task = Some(
+            app.tasks
+                .spawn("refresh", tasks::Progress::default(), |_| None),
+        );
+
+        app.update(Message::RefreshDone(Some("offline".to_string())));
+        assert_eq!(app.status, "Refresh failed: offline");
+        assert!(app.refresh_task.is_none());
+
+        app.status = "Refreshing...".to_string();
+        app.update(Message::RefreshDone(None));
+        assert_eq!(app.status, "Refreshed.");
+
+        app.refresh_task = Some(
+            app.tasks
+                .spawn("refresh", tasks::Progress::default(), |_| None),
+        );
+        app.update(Message::RefreshLost);
+        assert!(app.refresh_task.is_none());
+    }
+
+    #[test]
+    fn ticks_spin_only_while_something_runs() {
+        let _fakes = git_ops::lock_fakes();
+        git_ops::set_git_ops(Some(Arc::new(NoGit)));
+        let mut app = app(&["main"]);
+        app.details
+            .insert(PathBuf::from("/repo/main"), WorktreeDetails::default());
+
+        app.update(Message::Tick);
+        assert_eq!(app.spinner_index, 0);
+
+        app.busy = true;
+        app.update(Message::Tick);
+        app.update(Message::Tick);
+        assert_eq!(app.spinner_index, 2);
+    }
+
+    #[test]
+    fn keys_search_the_table_and_quit() {
+        let _fakes = git_ops::lock_fakes();
+        git_ops::set_git_ops(Some(Arc::new(NoGit)));
+        let mut app = app(&["main", "feat/login", "fix/typo"]);
+
+        app.update(key(KeyCode::Char('/')));
+        for ch in "fix".chars() {
+            app.update(key(KeyCode::Char(ch)));
+        }
+        assert!(matches!(&app.mode, Mode::Search { query } if query == "fix"));
+        let visible: Vec<String> = app
+            .visible_items()
+            .into_iter()
+            .map(|item| item.branch)
+            .collect();
+        assert_eq!(visible, ["fix/typo"]);
+
+        app.update(key(KeyCode::Esc));
+        assert!(matches!(app.mode, Mode::Normal));
+        assert_eq!(
+            app.current_item().map(|item| item.branch).as_deref(),
+            Some("fix/typo")
+        );
+        assert!(!app.should_quit);
+
+        app.update(key(KeyCode::Esc));
+        assert!(app.should_quit);
+    }
+
+    const HEAD_SHA: &str = "c09ca9526d9f30a4e6f83e713be5603fe9de57af";
+
+    fn exit_status(code: i32) -> std::process::ExitStatus {
+        #[cfg(unix)]
+        let status = std::os::unix::process::ExitStatusExt::from_raw(code << 8);
+        #[cfg(windows)]
+        let status = std::os::windows::process::ExitStatusExt::from_raw(code as u32);
+        status
+    }
+
+    fn answer(code: i32, stdout: &str) -> io::Result<Option<Output>> {
+        Ok(Some(Output {
+            status: exit_status(code),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        }))
+    }
+
+    /// Answers git with canned output for a bare-layout repo at `root` that
+    /// has `main` checked out, and records every command. The worktree list
+    /// follows `worktree add`/`move` and `branch -m`/`-D`; worktrees are
+    /// plain directories with a `.git` file, as the trash expects.
+    struct CannedGit {
+        root: PathBuf,
+        commands: Mutex<Vec<String>>,
+        /// Worktree paths and their branches.
+        worktrees: Mutex<Vec<(PathBuf, String)>>,
+    }
+
+    impl CannedGit {
+        fn new(root: &Path) -> Self {
+            let fake = CannedGit {
+                root: root.to_path_buf(),
+                commands: Mutex::new(Vec::new()),
+                worktrees: Mutex::new(Vec::new()),
+            };
+            fake.add_worktree(&root.join("main"), "main");
+            fake
+        }
+
+        fn add_worktree(&self, path: &Path, branch: &str) {
+            let admin_dir = self.root.join(".git").join("worktrees").join(branch);
+            std::fs::create_dir_all(&admin_dir).unwrap();
+            std::fs::create_dir_all(path).unwrap();
+            std::fs::write(
+                path.join(".git"),
+                format!("gitdir: {}\n", admin_dir.display()),
+            )
+            .unwrap();
+            self.worktrees
+                .lock()
+                .unwrap()
+                .push((path.to_path_buf(), branch.to_string()));
+        }
+
+        fn has_branch(&self, branch: &str) -> bool {
+            self.worktrees
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|(_, name)| name == branch)
+        }
+
+        fn worktree_list(&self) -> String {
+            let mut list = format!("worktree {}\nbare\n\n", self.root.join(".git").display());
+            for (path, branch) in self.worktrees.lock().unwrap().iter() {
+                if path.is_dir() {
+                    list.push_str(&format!(
+                        "worktree {}\nHEAD {HEAD_SHA}\nbranch refs/heads/{branch}\n\n",
+                        path.display()
+                    ));
+                }
+            }
+            list
+        }
+    }
+
+    impl GitOps for CannedGit {
+        fn output(
+            &self,
+            args: &[&str],
+            _cwd: Option<&Path>,
+            _envs: &[(&str, &OsStr)],
+            _limit: Option<Duration>,
+        ) -> io::Result<Option<Output>> {
+            self.commands.lock().unwrap().push(args.join(" "));
+            match args {
+                ["symbolic-ref", "--quiet", "--short", "refs/remotes/origin/HEAD"] => {
+                    answer(0, "origin/main\n")
+                }
+                ["rev-parse", "--git-common-dir"] => {
+                    answer(0, &format!("{}\n", self.root.join(".git").display()))
+                }
+                ["worktree", "list", "--porcelain"] => answer(0, &self.worktree_list()),
+                ["check-ref-format", "--branch", branch] => answer(0, &format!("{branch}\n")),
+                ["show-ref", "--verify", name] => {
+                    let known = *name == "refs/remotes/origin/main"
+                        || name
+                            .strip_prefix("refs/heads/")
+                            .is_some_and(|branch| self.has_branch(branch));
+                    answer(if known { 0 } else { 1 }, "")
+                }
+                ["rev-parse", "--verify", ..] => answer(0, &format!("{HEAD_SHA}\n")),
+                ["log", "-1", "--format=%ct", _] => answer(0, "1700000000\n"),
+                ["rev-list", "--left-right", "--count", _] => answer(0, "0\t0\n"),
+                ["worktree", "add", "-b", branch, path, _] => {
+                    self.add_worktree(Path::new(path), branch);
+                    answer(0, "")
+                }
+                ["worktree", "move", from, to] => {
+                    std::fs::rename(from, to)?;
+                    for (path, _) in self.worktrees.lock().unwrap().iter_mut() {
+                        if path == Path::new(from) {
+                            *path = PathBuf::from(to);
+                        }
+                    }
+                    answer(0, "")
+                }
+                ["branch", "-m", old, new] => {
+                    for (_, branch) in self.worktrees.lock().unwrap().iter_mut() {
+                        if branch == old {
+                            *branch = new.to_string();
+                        }
+                    }
+                    answer(0, "")
+                }
+                ["branch", "-D", doomed] => {
+                    self.worktrees
+                        .lock()
+                        .unwrap()
+                        .retain(|(_, branch)| branch != doomed);
+                    answer(0, "")
+                }
+                // Unset, like git answers for keys nobody configured.
+                ["config", "--get" | "--get-regexp" | "-z", ..] => answer(1, ""),
+                ["rev-parse", "--abbrev-ref", _] => answer(128, ""),
+                _ => answer(0, ""),
+            }
+        }
+    }
+
+    /// A forge without any requests.
+    struct NoRequests;
+
+    impl forge::Forge for NoRequests {
+        fn request_label(&self) -> &'static str {
+            "PR"
+        }
+
+        fn availability(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn requests_for_branches(
+            &self,
+            _repo_root: &Path,
+            _branches: &[String],
+        ) -> Result<forge::BranchRequests, forge::ForgeError> {
+            Ok(forge::BranchRequests::default())
+        }
+
+        fn issue_title(
+            &self,
+            _repo_root: &Path,
+            _number: u64,
+        ) -> Result<String, forge::ForgeError> {
+            Err(forge::ForgeError::Failed)
+        }
+
+        fn request_state(
+            &self,
+            _repo_root: &Path,
+            _number: u64,
+        ) -> Result<String, forge::ForgeError> {
+            Err(forge::ForgeError::Failed)
+        }
+    }
+
+    /// A `TestBackend` the script can look at between frames.
+    struct Screen(Rc<RefCell<TestBackend>>);
+
+    impl Screen {
+        fn text(&self) -> String {
+            let backend = self.0.borrow();
+            let buffer = backend.buffer();
+            let width = usize::from(buffer.area.width);
+            buffer
+                .content
+                .chunks(width)
+                .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+
+    impl Backend for Screen {
+        fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+        where
+            I: Iterator<Item = (u16, u16, &'a ratatui::buffer::Cell)>,
+        {
+            self.0.borrow_mut().draw(content)
+        }
+
+        fn hide_cursor(&mut self) -> io::Result<()> {
+            self.0.borrow_mut().hide_cursor()
+        }
+
+        fn show_cursor(&mut self) -> io::Result<()> {
+            self.0.borrow_mut().show_cursor()
+        }
+
+        fn get_cursor_position(&mut self) -> io::Result<ratatui::layout::Position> {
+            self.0.borrow_mut().get_cursor_position()
+        }
+
+        fn set_cursor_position<P: Into<ratatui::layout::Position>>(
+            &mut self,
+            position: P,
+        ) -> io::Result<()> {
+            self.0.borrow_mut().set_cursor_position(position)
+        }
+
+        fn clear(&mut self) -> io::Result<()> {
+            self.0.borrow_mut().clear()
+        }
+
+        fn size(&self) -> io::Result<ratatui::layout::Size> {
+            self.0.borrow().size()
+        }
+
+        fn window_size(&mut self) -> io::Result<ratatui::backend::WindowSize> {
+            self.0.borrow_mut().window_size()
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.borrow_mut().flush()
+        }
+    }
+
+    enum Step {
+        Keys(&'static str),
+        Key(KeyCode),
+        /// Waits until the screen shows the text.
+        Shows(&'static str),
+    }
+
+    /// Runs the TUI over `root` on a test terminal, feeding it `script`, and
+    /// returns the last screen and the git commands that ran.
+    fn drive(root: &Path, script: Vec<Step>) -> (String, Vec<String>) {
+        let fake = Arc::new(CannedGit::new(root));
+        git_ops::set_git_ops(Some(fake.clone()));
+        forge::set_forge_factory(Some(Arc::new(|_: &Path| {
+            Box::new(NoRequests) as Box<dyn forge::Forge>
+        })));
+
+        let items = services::load_worktrees(root).unwrap();
+        let mut app = TuiApp::new(
+            root.to_path_buf(),
+            items,
+            "main".to_string(),
+            None,
+            true,
+            WorktreeFilter::default(),
+        );
+        let backend = Rc::new(RefCell::new(TestBackend::new(160, 30)));
+        let screen = Screen(Rc::clone(&backend));
+        let mut terminal = Terminal::new(Screen(Rc::clone(&backend))).unwrap();
+
+        let mut steps: VecDeque<Step> = script.into();
+        let mut pending: VecDeque<KeyCode> = VecDeque::new();
+        let mut waiting_since = Instant::now();
+        let mut next_event = |_timeout: Duration| -> io::Result<Option<Event>> {
+            loop {
+                if let Some(code) = pending.pop_front() {
+                    return Ok(Some(Event::Key(KeyEvent::new(code, KeyModifiers::NONE))));
+                }
+                match steps.pop_front() {
+                    None => {
+                        return Ok(Some(Event::Key(KeyEvent::new(
+                            KeyCode::Char('q'),
+                            KeyModifiers::NONE,
+                        ))))
+                    }
+                    Some(Step::Keys(text)) => {
+                        pending.extend(text.chars().map(KeyCode::Char));
+                        waiting_since = Instant::now();
+                    }
+                    Some(Step::Key(code)) => {
+                        pending.push_back(code);
+                        waiting_since = Instant::now();
+                    }
+                    Some(Step::Shows(text)) => {
+                        if screen.text().contains(text) {
+                            continue;
+                        }
+                        assert!(
+                            waiting_since.elapsed() < Duration::from_secs(10),
+                            "screen never showed {text:?}:\n{}",
+                            screen.text()
+                        );
+                        steps.push_front(Step::Shows(text));
+                        thread::sleep(Duration::from_millis(10));
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+        app.run(&mut terminal, &mut next_event).unwrap();
+
+        let commands = fake.commands.lock().unwrap().clone();
+        let text = screen.text();
+        (text, commands)
+    }
+
+    fn ran(commands: &[String], command: &str) -> bool {
+        commands.iter().any(|ran| ran == command)
+    }
+
+    #[test]
+    fn create_rename_delete_and_refresh_through_the_event_loop() {
+        let _fakes = git_ops::lock_fakes();
+        let dir = std::env::temp_dir().join(format!("gw-tui-harness-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let root = git_ops::canonicalize(&dir);
+        let path = |branch: &str| root.join(branch).to_string_lossy().to_string();
+
+        let (screen, commands) = drive(
+            &root,
+            vec![
+                Step::Shows("> main"),
+                Step::Keys("n"),
+                Step::Shows("New branch"),
+                Step::Keys("feat"),
+                Step::Key(KeyCode::Enter),
+                Step::Shows("Created feat"),
+                Step::Keys("R"),
+                Step::Shows("Rename feat to:"),
+                Step::Keys("login"),
+                Step::Key(KeyCode::Enter),
+                Step::Shows("Renamed to login."),
+                Step::Keys("d"),
+                Step::Shows("Delete"),
+                Step::Keys("y"),
+                Step::Shows("Deleted"),
+                Step::Keys("r"),
+                Step::Shows("Refreshed."),
+            ],
+        );
+        let login_left = root.join("login").exists();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(screen.contains("> main"), "{screen}");
+        assert!(!screen.contains("login"), "{screen}");
+        assert!(!login_left);
+        assert!(ran(
+            &commands,
+            &format!("worktree add -b feat {} main", path("feat"))
+        ));
+        assert!(ran(&commands, "branch -m feat login"));
+        assert!(ran(
+            &commands,
+            &format!("worktree move {} {}", path("feat"), path("login"))
+        ));
+        assert!(commands
+            .iter()
+            .any(|ran| ran.starts_with("update-ref refs/gw/trash/")));
+        assert!(ran(&commands, "branch -D login"));
+        assert!(ran(&commands, "fetch --prune"));
+    }
 }